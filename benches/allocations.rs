@@ -0,0 +1,35 @@
+//! Reports the allocations per iteration of a few routines, alongside their time.
+//!
+//! Run with `cargo bench --features criterion --bench allocations`.
+
+use criterion::measurement::Measurement;
+use criterion::{BenchmarkGroup, Criterion, criterion_group, criterion_main};
+use leaktracer::LeaktracerAllocator;
+use leaktracer::bench::Allocations;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+// criterion rejects the routines which never allocate, since their measurements are all zero
+fn routines<M: Measurement>(group: &mut BenchmarkGroup<'_, M>) {
+    group.bench_function("vec", |b| b.iter(|| vec![0u8; 64]));
+    group.bench_function("format", |b| b.iter(|| format!("{}-{}", 4, 2)));
+}
+
+fn time(c: &mut Criterion) {
+    routines(&mut c.benchmark_group("time"));
+}
+
+fn allocations(c: &mut Criterion<Allocations>) {
+    routines(&mut c.benchmark_group("allocations"));
+}
+
+criterion_group!(time_benches, time);
+
+criterion_group! {
+    name = allocation_benches;
+    config = Criterion::default().with_measurement(Allocations);
+    targets = allocations
+}
+
+criterion_main!(time_benches, allocation_benches);
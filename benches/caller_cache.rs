@@ -0,0 +1,49 @@
+//! Compares the cost of a traced allocation with the full call stack scan and with the caller cache.
+//!
+//! Run with `cargo bench --bench caller_cache`.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use leaktracer::LeaktracerAllocator;
+
+const ITERATIONS: u32 = 10_000;
+
+static FULL_SCAN: LeaktracerAllocator = LeaktracerAllocator::init();
+static CALLER_CACHE: LeaktracerAllocator = LeaktracerAllocator::init().with_caller_cache(true);
+
+/// Allocates and frees a block `ITERATIONS` times with `allocator`, returning the average time per iteration.
+#[inline(never)]
+fn allocate(allocator: &LeaktracerAllocator) -> Duration {
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        unsafe {
+            let ptr = black_box(allocator.alloc(layout));
+            allocator.dealloc(ptr, layout);
+        }
+    }
+
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    leaktracer::init_symbol_table(&["caller_cache"]);
+    // load the debug info before measuring
+    allocate(&FULL_SCAN);
+
+    let full_scan = allocate(&FULL_SCAN);
+    let cached = allocate(&CALLER_CACHE);
+    println!("full scan:    {full_scan:?} per allocation");
+    println!("caller cache: {cached:?} per allocation");
+
+    let symbol = leaktracer::with_symbol_table(|table| {
+        table
+            .iter()
+            .find(|(name, _)| name.ends_with("::allocate"))
+            .map(|(_, symbol)| symbol.total_count())
+    })
+    .expect("failed to access symbol table");
+    assert_eq!(symbol, Some(3 * ITERATIONS as usize));
+}
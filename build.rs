@@ -0,0 +1,12 @@
+//! Enables the `stack_capture` cfg when the call stacks can be captured,
+//! i.e. with the `symbols` feature on a target supported by `backtrace` (any but WebAssembly).
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(stack_capture)");
+
+    let wasm = std::env::var("CARGO_CFG_TARGET_FAMILY")
+        .is_ok_and(|families| families.split(',').any(|family| family == "wasm"));
+    if std::env::var_os("CARGO_FEATURE_SYMBOLS").is_some() && !wasm {
+        println!("cargo::rustc-cfg=stack_capture");
+    }
+}
@@ -41,7 +41,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let _result = task_1.await?;
     let _another_result = task_2.await?;
 
-    let _buff = function_which_allocates();
+    let _buff = tracing::info_span!("allocating_function").in_scope(function_which_allocates);
 
     leaktracer::with_symbol_table(|table| {
         for (name, symbol) in table.iter() {
@@ -53,6 +53,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     })?;
 
+    for tag in leaktracer::tag_stats()? {
+        tracing::info!(
+            "Tag: {}, Allocated: {}, Count: {}",
+            tag.name,
+            tag.allocated,
+            tag.count
+        );
+    }
+
     // Log completion
     tracing::info!(
         "Application finished successfully. Allocated {} bytes",
@@ -70,7 +79,9 @@ fn init_log() -> Result<(), Box<dyn std::error::Error>> {
         .with_line_number(true)
         .with_writer(std::io::stdout);
 
+    // group the allocations by the active span
     let registry = tracing_subscriber::registry()
+        .with(leaktracer::tracing_layer())
         .with(stdout_logger.with_filter(LevelFilter::from(Level::TRACE)));
 
     tracing::subscriber::set_global_default(registry)?;
@@ -79,6 +90,8 @@ fn init_log() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 async fn task(sz: usize) -> Vec<u8> {
+    // group the allocations of each task under its own tag
+    let _tag = leaktracer::TagGuard::new(&format!("task:{sz}"));
     let vec: Vec<u8> = vec![0; sz]; // Allocate 1kb
     tracing::info!("Allocated {} bytes in the task", vec.len());
     vec
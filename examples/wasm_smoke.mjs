@@ -0,0 +1,11 @@
+// Runs the `wasm_smoke` example built for `wasm32-unknown-unknown`: a panic inside the allocator traps,
+// which fails the run.
+import { readFileSync } from "node:fs";
+
+const path = process.argv[2];
+const { instance } = await WebAssembly.instantiate(readFileSync(path), {});
+const traced = instance.exports.run();
+if (traced === 0) {
+  throw new Error("no allocation was traced");
+}
+console.log(`traced ${traced} allocations`);
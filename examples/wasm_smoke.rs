@@ -0,0 +1,29 @@
+//! Traces a few allocations on `wasm32-unknown-unknown`, where [`std::time::Instant`] is not available
+//! and any panic inside the allocator aborts the module.
+//!
+//! It's a `cdylib` exporting `run`, driven by `examples/wasm_smoke.mjs` under node:
+//!
+//! ```sh
+//! cargo build --example wasm_smoke --target wasm32-unknown-unknown
+//! node examples/wasm_smoke.mjs target/wasm32-unknown-unknown/debug/examples/wasm_smoke.wasm
+//! ```
+
+use leaktracer::{LeaktracerAllocator, Sampling};
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator =
+    LeaktracerAllocator::init().with_sampling(Sampling::Adaptive { budget: 0.05 });
+
+/// Traces a few allocations and a report, returning the number of traced allocations.
+#[unsafe(no_mangle)]
+pub extern "C" fn run() -> usize {
+    leaktracer::init_symbol_table(&["wasm_smoke"]);
+
+    let buffers: Vec<Vec<u8>> = (1..=8).map(|i| vec![0; 64 * i]).collect();
+    let mut report = Vec::new();
+    leaktracer::write_report(&mut report).expect("failed to write the report");
+    assert!(report.starts_with(b"leaktracer report (uptime "));
+    drop(buffers);
+
+    leaktracer::quick_stats().total_allocations
+}
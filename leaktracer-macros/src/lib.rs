@@ -0,0 +1,131 @@
+//! # leaktracer-macros
+//!
+//! Procedural macros of [leaktracer](https://docs.rs/leaktracer), re-exported by it with the `macros` feature:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! leaktracer = { version = "0.1", features = ["macros"] }
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::Parser;
+use syn::{Attribute, Expr, ItemFn, ReturnType, parse_macro_input, parse_quote};
+
+/// Turns a function into a test failing if the live bytes attributed to the traced modules grew while it ran.
+///
+/// The test is wrapped with `leaktracer::LeakCheck`: a snapshot of the symbol table is taken before running the body
+/// and another one after it, and the test panics with the symbols which grew if the live bytes grew by more than
+/// the tolerance, `0` by default. The symbol table is initialized with the crate of the test if needed,
+/// and `leaktracer::LeaktracerAllocator` must be the global allocator of the test binary.
+///
+/// ```rust,ignore
+/// #[leaktracer::leak_test]
+/// fn test_should_not_leak() {
+///     let cache = Cache::default();
+///     cache.insert("key", "value");
+/// }
+///
+/// #[leaktracer::leak_test(tolerance = 1024)]
+/// fn test_should_not_leak_more_than_a_kib() {
+///     // ...
+/// }
+/// ```
+///
+/// The function gets a `#[test]` attribute, unless it already has one: any attribute whose path ends with `test`,
+/// such as `#[tokio::test]`, counts. The other attributes are kept as they are, in the same order.
+/// Put `#[leak_test]` above `#[tokio::test]`, so that the check runs inside the runtime and the allocations of
+/// the runtime itself don't count:
+///
+/// ```rust,ignore
+/// #[leaktracer::leak_test]
+/// #[tokio::test]
+/// async fn test_should_not_leak_connections() {
+///     // ...
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn leak_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let tolerance = match parse_tolerance(args.into()) {
+        Ok(tolerance) => tolerance,
+        Err(err) => return err.into_compile_error().into(),
+    };
+    let item = parse_macro_input!(item as ItemFn);
+
+    match expand(tolerance, item) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
+/// Parses the arguments of [`leak_test`], returning the tolerance expression.
+fn parse_tolerance(args: TokenStream2) -> syn::Result<Expr> {
+    let mut tolerance: Expr = parse_quote!(0);
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("tolerance") {
+            tolerance = meta.value()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported leak_test argument, expected `tolerance = N`"))
+        }
+    });
+    parser.parse2(args)?;
+
+    Ok(tolerance)
+}
+
+/// Wraps the body of `item` with a leak check allowing `tolerance` bytes of growth.
+fn expand(tolerance: Expr, item: ItemFn) -> syn::Result<TokenStream2> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+    let has_test_attr = attrs.iter().any(is_test_attr);
+    if sig.asyncness.is_some() && !has_test_attr {
+        return Err(syn::Error::new_spanned(
+            sig.fn_token,
+            "async leak tests need a runtime attribute, e.g. `#[tokio::test]`, below `#[leak_test]`",
+        ));
+    }
+    let test_attr = (!has_test_attr).then(|| quote!(#[::core::prelude::v1::test]));
+    let output = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    // the body runs in its own closure or future, so its locals and temporaries are dropped before the check
+    let run = if sig.asyncness.is_some() {
+        quote! {
+            let __leak_test_body: ::core::pin::Pin<&mut dyn ::core::future::Future<Output = #output>> =
+                ::core::pin::pin!(async move #block);
+            let __leak_test_result = __leak_test_body.await;
+        }
+    } else {
+        quote! {
+            let __leak_test_result = (move || -> #output #block)();
+        }
+    };
+
+    Ok(quote! {
+        #test_attr
+        #(#attrs)*
+        #vis #sig {
+            let __leak_test_check =
+                ::leaktracer::LeakCheck::start(&[::core::env!("CARGO_CRATE_NAME")], #tolerance);
+            #run
+            __leak_test_check.finish();
+            __leak_test_result
+        }
+    })
+}
+
+/// Returns whether `attr` marks a test, e.g. `#[test]` or `#[tokio::test]`.
+fn is_test_attr(attr: &Attribute) -> bool {
+    attr.path()
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "test")
+}
@@ -0,0 +1,12 @@
+//! The symbol table is global, so each leak test runs in its own process, as the other tests would count.
+
+use leaktracer::{LeaktracerAllocator, leak_test};
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+#[leak_test(tolerance = 1024)]
+#[should_panic(expected = "leak check failed: live bytes grew by 4.0 KiB (4096 bytes)")]
+fn test_should_fail_on_leak() {
+    std::mem::forget(vec![0u8; 4096]);
+}
@@ -0,0 +1,16 @@
+//! The symbol table is global, so each leak test runs in its own process, as the other tests would count.
+
+use std::num::ParseIntError;
+
+use leaktracer::{LeaktracerAllocator, leak_test};
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+#[leak_test]
+fn test_should_pass_without_leaks() -> Result<(), ParseIntError> {
+    let len: usize = "4096".parse()?;
+    let buffer = vec![0u8; len];
+    assert_eq!(buffer.len(), 4096);
+    Ok(())
+}
@@ -0,0 +1,18 @@
+//! The symbol table is global, so each leak test runs in its own process, as the other tests would count.
+
+use std::num::ParseIntError;
+
+use leaktracer::{LeaktracerAllocator, leak_test};
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+#[leak_test(tolerance = 1024)]
+#[tokio::test]
+async fn test_should_tolerate_growth_in_runtime() -> Result<(), ParseIntError> {
+    let len: usize = "4096".parse()?;
+    let buffer = tokio::spawn(async move { vec![0u8; len] }).await.unwrap();
+    assert_eq!(buffer.len(), 4096);
+    std::mem::forget(vec![0u8; 512]);
+    Ok(())
+}
@@ -0,0 +1,9 @@
+//! Expansion tests of the macros: the `pass` cases must compile, while the `fail` ones must fail with the
+//! errors in their `.stderr` file (regenerate them with `TRYBUILD=overwrite`).
+
+#[test]
+fn test_should_expand_leak_test() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+    t.compile_fail("tests/ui/fail/*.rs");
+}
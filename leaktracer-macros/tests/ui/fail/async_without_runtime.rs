@@ -0,0 +1,6 @@
+use leaktracer::leak_test;
+
+#[leak_test]
+async fn test_without_runtime() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use leaktracer::leak_test;
+
+#[leak_test]
+struct NotATest;
+
+fn main() {}
@@ -0,0 +1,6 @@
+use leaktracer::leak_test;
+
+#[leak_test(threshold = 1024)]
+fn test_with_unknown_argument() {}
+
+fn main() {}
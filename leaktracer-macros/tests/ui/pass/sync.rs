@@ -0,0 +1,32 @@
+use leaktracer::leak_test;
+
+#[leak_test]
+fn test_without_arguments() {
+    let _ = vec![0u8; 16];
+}
+
+#[leak_test(tolerance = 1024)]
+fn test_with_tolerance() {}
+
+const TOLERANCE: usize = 4096;
+
+#[leak_test(tolerance = TOLERANCE)]
+fn test_with_const_tolerance() {}
+
+#[leak_test]
+fn test_with_early_return() -> Result<(), std::num::ParseIntError> {
+    let value: u32 = "42".parse()?;
+    if value == 42 {
+        return Ok(());
+    }
+    Ok(())
+}
+
+#[test]
+#[leak_test]
+#[should_panic]
+fn test_with_test_attribute_above() {
+    panic!();
+}
+
+fn main() {}
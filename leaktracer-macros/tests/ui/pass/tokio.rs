@@ -0,0 +1,22 @@
+use leaktracer::leak_test;
+
+#[leak_test]
+#[tokio::test]
+async fn test_inside_runtime() {
+    tokio::task::yield_now().await;
+}
+
+#[tokio::test]
+#[leak_test(tolerance = 1024)]
+async fn test_around_runtime() {
+    tokio::task::yield_now().await;
+}
+
+#[leak_test]
+#[tokio::test(flavor = "current_thread")]
+async fn test_with_question_mark() -> Result<(), std::num::ParseIntError> {
+    let _: u32 = "42".parse()?;
+    Ok(())
+}
+
+fn main() {}
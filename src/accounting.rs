@@ -0,0 +1,140 @@
+//! Strict accounting checks, which detect deallocations driving the counters of a symbol below zero.
+
+use std::sync::Mutex;
+
+/// Maximum number of [`AccountingViolation`]s kept, the following ones are dropped.
+const MAX_ACCOUNTING_VIOLATIONS: usize = 1024;
+
+/// A deallocation which would have driven the counters of a symbol below zero,
+/// detected with [`crate::LeaktracerAllocator::with_strict_accounting`].
+///
+/// The counters are saturated to zero anyway, so it always points to a bug in the attribution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountingViolation {
+    /// The freed pointer.
+    pub ptr: usize,
+    /// The symbol the allocation is attributed to.
+    pub symbol: &'static str,
+    /// The bytes released from the symbol, i.e. the allocation size scaled by its sampling weight.
+    pub size: usize,
+    /// The live bytes of the symbol before the deallocation.
+    pub allocated: usize,
+    /// The symbol which freed the pointer, if the call stack can be resolved on deallocation.
+    pub freeing_symbol: Option<&'static str>,
+}
+
+/// The recorded [`AccountingViolation`]s, with the pending panic.
+#[derive(Debug)]
+pub(crate) struct AccountingLog {
+    violations: Vec<AccountingViolation>,
+    /// The first violation recorded since the last panic.
+    pending: Option<AccountingViolation>,
+}
+
+impl AccountingLog {
+    /// Creates an empty log.
+    const fn new() -> Self {
+        Self {
+            violations: Vec::new(),
+            pending: None,
+        }
+    }
+
+    /// Records `violation`, dropping it if the log is full, and arms the pending panic.
+    fn record(&mut self, violation: AccountingViolation) {
+        if self.violations.len() < MAX_ACCOUNTING_VIOLATIONS {
+            self.violations.push(violation);
+        }
+        self.pending.get_or_insert(violation);
+    }
+
+    /// Returns the first violation recorded since the last call, disarming the pending panic.
+    fn take_pending(&mut self) -> Option<AccountingViolation> {
+        self.pending.take()
+    }
+}
+
+/// The violations recorded by the allocator.
+///
+/// Taken with the symbol table locked, so it must never be held while acquiring the symbol table lock.
+pub(crate) static ACCOUNTING_LOG: Mutex<AccountingLog> = Mutex::new(AccountingLog::new());
+
+/// Returns the [`AccountingViolation`]s detected with [`crate::LeaktracerAllocator::with_strict_accounting`],
+/// in the order they were detected, up to the first 1024.
+pub fn accounting_violations() -> Vec<AccountingViolation> {
+    crate::alloc::untraced(|| {
+        ACCOUNTING_LOG
+            .lock()
+            .map(|log| log.violations.clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Records a violation detected inside the allocator.
+pub(crate) fn record_violation(violation: AccountingViolation) {
+    if let Ok(mut log) = ACCOUNTING_LOG.lock() {
+        log.record(violation);
+    }
+}
+
+/// Panics if a violation has been recorded since the last call, with `debug_assertions` on.
+///
+/// The allocator can't panic, so the panic is deferred to the next access to the symbol table.
+pub(crate) fn raise_pending() {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let pending = crate::alloc::untraced(|| {
+        ACCOUNTING_LOG
+            .lock()
+            .ok()
+            .and_then(|mut log| log.take_pending())
+    });
+    if let Some(violation) = pending {
+        panic!("leaktracer accounting violation: {violation:?}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn violation(ptr: usize) -> AccountingViolation {
+        AccountingViolation {
+            ptr,
+            symbol: "app::alloc",
+            size: 64,
+            allocated: 0,
+            freeing_symbol: Some("app::free"),
+        }
+    }
+
+    #[test]
+    fn test_should_defer_accounting_panic_once() {
+        let mut log = AccountingLog::new();
+        assert_eq!(log.take_pending(), None);
+
+        log.record(violation(0x1000));
+        log.record(violation(0x2000));
+        assert_eq!(log.take_pending(), Some(violation(0x1000)));
+        assert_eq!(log.take_pending(), None);
+        assert_eq!(log.violations.len(), 2);
+
+        log.record(violation(0x3000));
+        assert_eq!(log.take_pending(), Some(violation(0x3000)));
+    }
+
+    #[test]
+    fn test_should_bound_accounting_violations() {
+        let mut log = AccountingLog::new();
+        for ptr in 0..MAX_ACCOUNTING_VIOLATIONS + 10 {
+            log.record(violation(ptr));
+        }
+        assert_eq!(log.violations.len(), MAX_ACCOUNTING_VIOLATIONS);
+        assert_eq!(
+            log.violations.last(),
+            Some(&violation(MAX_ACCOUNTING_VIOLATIONS - 1))
+        );
+    }
+}
@@ -0,0 +1,103 @@
+/// Number of alignment classes counted by [`AlignmentStats`].
+pub(crate) const ALIGN_CLASSES: usize = 5;
+
+/// Number of allocations in each alignment class, from [`std::alloc::Layout::align`].
+///
+/// The over-aligned allocations, e.g. SIMD or page-aligned buffers, take a different path in most system allocators,
+/// which may waste the padding up to the alignment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlignmentStats {
+    /// Allocations aligned to 16 bytes or less, the alignment the system allocators provide anyway.
+    pub natural: usize,
+    /// Allocations aligned to 32 bytes.
+    pub align_32: usize,
+    /// Allocations aligned to 64 bytes, typically a cache line.
+    pub align_64: usize,
+    /// Allocations aligned to 4096 bytes, typically a page.
+    pub page: usize,
+    /// Allocations with any other alignment, i.e. from 128 to 2048 bytes, or more than 4096 bytes.
+    pub other: usize,
+}
+
+impl AlignmentStats {
+    /// Builds the stats from the counts of each class, in the order of [`align_class`].
+    pub(crate) fn from_counts(counts: [usize; ALIGN_CLASSES]) -> Self {
+        let [natural, align_32, align_64, page, other] = counts;
+        Self {
+            natural,
+            align_32,
+            align_64,
+            page,
+            other,
+        }
+    }
+
+    /// Returns the counts of each class, in the order of [`align_class`].
+    pub(crate) fn counts(&self) -> [usize; ALIGN_CLASSES] {
+        [
+            self.natural,
+            self.align_32,
+            self.align_64,
+            self.page,
+            self.other,
+        ]
+    }
+
+    /// Returns the number of allocations aligned to 64 bytes or more.
+    pub fn over_aligned(&self) -> usize {
+        self.align_64 + self.page + self.other
+    }
+
+    /// Adds the counts of `other` to these stats.
+    pub(crate) fn add(&mut self, other: &AlignmentStats) {
+        let mut counts = self.counts();
+        for (count, other) in counts.iter_mut().zip(other.counts()) {
+            *count += other;
+        }
+        *self = Self::from_counts(counts);
+    }
+}
+
+/// Returns the index of the class of an allocation aligned to `align` bytes, in the order of the fields
+/// of [`AlignmentStats`].
+pub(crate) fn align_class(align: usize) -> usize {
+    match align {
+        0..=16 => 0,
+        32 => 1,
+        64 => 2,
+        4096 => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_classify_alignments() {
+        assert_eq!(align_class(1), 0);
+        assert_eq!(align_class(8), 0);
+        assert_eq!(align_class(16), 0);
+        assert_eq!(align_class(32), 1);
+        assert_eq!(align_class(64), 2);
+        assert_eq!(align_class(128), 4);
+        assert_eq!(align_class(2048), 4);
+        assert_eq!(align_class(4096), 3);
+        assert_eq!(align_class(8192), 4);
+    }
+
+    #[test]
+    fn test_should_sum_alignment_stats() {
+        let mut stats = AlignmentStats::from_counts([1, 2, 3, 4, 5]);
+        assert_eq!(stats.counts(), [1, 2, 3, 4, 5]);
+        assert_eq!(stats.over_aligned(), 12);
+
+        stats.add(&AlignmentStats {
+            page: 1,
+            ..Default::default()
+        });
+        assert_eq!(stats.page, 5);
+    }
+}
@@ -1,19 +1,94 @@
 use std::alloc::{GlobalAlloc, Layout, System};
 use std::cell::Cell;
-use std::sync::atomic::AtomicUsize;
-use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread::LocalKey;
+use std::time::Duration;
 
-use crate::symbols::SymbolTable;
+use crate::accounting;
+use crate::atomic_ref::AtomicRef;
+use crate::hooks::{AllocCallbacks, AllocHook};
+use crate::sampling::{self, ADAPTIVE_WINDOW, Sampling};
+use crate::size_class::{DEFAULT_SIZE_CLASS_BOUNDS, SizeClass, SizeClassCounters, SizeClassStats};
+use crate::strict::{self, StrictViolation, StrictViolationKind};
+use crate::symbols::{Dealloc, SymbolTable, intern};
+use crate::time::Clock;
+use crate::{hooks, largest, report, stats, symbols, time};
 
 thread_local! {
+    /// Whether the current thread is inside the tracer, so its allocations must not be traced.
     static IN_ALLOC: Cell<bool> = const { Cell::new(false) };
+    /// Whether the current thread is running the tracer bookkeeping (see [`bookkeeping`]).
+    static IN_BOOKKEEPING: Cell<bool> = const { Cell::new(false) };
+    /// Whether the current thread holds the symbol table lock, so locking it again would deadlock.
+    static HOLDS_TABLE: Cell<bool> = const { Cell::new(false) };
 }
 
 /// Initial size of the symbol table.
 /// This is used to preallocate the symbol table to avoid reallocations.
 const DEFAULT_SYMBOL_TABLE_SIZE: usize = 1024;
 
-static SYMBOL_TABLE: OnceLock<Mutex<SymbolTable>> = OnceLock::new();
+pub(crate) static SYMBOL_TABLE: OnceLock<Mutex<SymbolTable>> = OnceLock::new();
+
+/// Whether allocations are traced (see [`set_enabled`]).
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Size in bytes below which the allocations are not attributed to any symbol
+/// (see [`set_attribution_size_threshold`]).
+static ATTRIBUTION_SIZE_THRESHOLD: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the startup phase is over (see [`mark_startup_complete`]).
+static STARTUP_COMPLETE: AtomicBool = AtomicBool::new(false);
+
+/// The symbol every allocation is attributed to, if any (see [`set_fixed_symbol`]).
+static FIXED_SYMBOL: AtomicRef<str> = AtomicRef::new();
+
+/// Result of an operation on the symbol table, which fails if the lock can't be taken.
+pub(crate) type LockResult<T> = Result<T, TableError>;
+
+/// The error returned when the symbol table lock can't be taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableError {
+    /// The lock is poisoned, by a thread which panicked while holding it.
+    Poisoned,
+    /// The current thread already holds the lock, so taking it again would deadlock.
+    ///
+    /// This happens when the symbol table is accessed from an allocator callback (e.g. [`crate::on_threshold_exceeded`]
+    /// or [`LeaktracerAllocator::on_alloc`]), which runs with the table locked, or from the closure given to
+    /// [`with_symbol_table`].
+    WouldDeadlock,
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poisoned => write!(f, "symbol table lock poisoned"),
+            Self::WouldDeadlock => write!(
+                f,
+                "symbol table already locked by the current thread, e.g. from an allocator callback"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+impl<T> From<PoisonError<T>> for TableError {
+    fn from(_: PoisonError<T>) -> Self {
+        Self::Poisoned
+    }
+}
+
+impl From<TableError> for std::io::Error {
+    fn from(err: TableError) -> Self {
+        let kind = match err {
+            TableError::Poisoned => std::io::ErrorKind::Other,
+            TableError::WouldDeadlock => std::io::ErrorKind::Deadlock,
+        };
+        Self::new(kind, err)
+    }
+}
 
 /// This module provides a custom Allocator ([`GlobalAlloc`]) that tracks to log the memory allocations and stores the
 /// allocation information for each module in the program.
@@ -28,53 +103,398 @@ static SYMBOL_TABLE: OnceLock<Mutex<SymbolTable>> = OnceLock::new();
 /// ```
 pub struct LeaktracerAllocator {
     allocated: AtomicUsize,
+    /// Upper bounds (exclusive) of the first three [`SizeClass`]es.
+    size_class_bounds: [usize; 3],
+    /// Counters for each [`SizeClass`].
+    size_classes: [SizeClassCounters; 4],
+    /// Whether to account the layout size padded to its alignment.
+    padded_size: bool,
+    /// Whether strict mode checks are enabled.
+    strict: bool,
+    /// Whether the deallocations driving the counters of a symbol below zero are recorded.
+    strict_accounting: bool,
+    /// Number of freed pointers held back from the system allocator, `0` to release them right away.
+    quarantine: usize,
+    /// Whether the allocations made before [`mark_startup_complete`] are attributed to `<startup>`.
+    startup_bucket: bool,
+    /// Whether the symbols of the call sites are cached by each thread.
+    #[cfg_attr(any(windows, not(stack_capture)), allow(dead_code))]
+    caller_cache: bool,
+    /// Double frees detected in strict mode.
+    double_frees: AtomicUsize,
+    /// Mismatched layout sizes detected in strict mode.
+    size_mismatches: AtomicUsize,
+    /// Deallocations of pointers missing from the registry.
+    untracked_frees: AtomicUsize,
+    /// Bytes freed by deallocations of pointers missing from the registry.
+    untracked_freed_bytes: AtomicUsize,
+    /// The reset epoch the counters belong to; the counters are zeroed when it falls behind.
+    reset_epoch: AtomicU64,
+    /// How the allocations are sampled.
+    sampling: Sampling,
+    /// The current sampling divisor: 1 out of `divisor` allocations is traced.
+    sampling_divisor: AtomicUsize,
+    /// Sampling divisor of each [`SizeClass`], multiplied by the sampling divisor.
+    size_class_sampling: [usize; 4],
+    /// Number of allocations seen by the sampling, for each [`SizeClass`].
+    sampling_counters: [AtomicUsize; 4],
+    /// Nanoseconds spent capturing call stacks in the current adaptive sampling window.
+    capture_nanos: AtomicU64,
+    /// Start of the current adaptive sampling window, as nanoseconds of [`crate::uptime`].
+    window_start_nanos: AtomicU64,
+    /// The clock installed on the first traced allocation, `None` to keep the global one.
+    clock: Option<&'static dyn Clock>,
+    /// The callbacks invoked on each traced allocation and deallocation.
+    callbacks: AllocCallbacks,
 }
 
 /// Initializes the leak tracer with a symbol table of the given size.
 ///
 /// Provide the modules to be traced as a slice of static strings.
 /// Providing modules is necessary to filter out allocations that are not relevant to the user (such as from [`std`], [`tokio`], etc.).
-pub fn init_symbol_table(modules: &'static [&'static str]) {
-    SYMBOL_TABLE.get_or_init(|| Mutex::new(SymbolTable::new(DEFAULT_SYMBOL_TABLE_SIZE, modules)));
+///
+/// Returns whether this call initialized the symbol table: if it was already initialized, e.g. by a library,
+/// the call does nothing and `modules` are ignored. Use [`reconfigure`] to replace the modules anyway.
+pub fn init_symbol_table(modules: &'static [&'static str]) -> bool {
+    let mut initialized = false;
+    SYMBOL_TABLE.get_or_init(|| {
+        initialized = true;
+        bookkeeping(|| Mutex::new(SymbolTable::new(DEFAULT_SYMBOL_TABLE_SIZE, modules)))
+    });
+    #[cfg(unix)]
+    if initialized {
+        crate::fork::register_atfork();
+    }
+    initialized
+}
+
+/// Replaces the modules being traced with `modules`, initializing the symbol table if needed.
+///
+/// Libraries should call [`init_symbol_table`], which leaves the modules of an already initialized table alone,
+/// while the binary, which knows the whole set of modules to trace, can call this to win over them.
+/// The bytes already attributed are not reattributed retroactively, unless `clear` is set,
+/// in which case the stats are reset as with [`reset`].
+pub fn reconfigure(modules: &'static [&'static str], clear: bool) -> LockResult<()> {
+    if init_symbol_table(modules) {
+        return Ok(());
+    }
+    with_symbol_table_mut(|table| {
+        table.set_modules(modules);
+        if clear {
+            clear_stats(table);
+        }
+    })
 }
 
 /// Provides a way to access the symbol table in a thread-safe manner.
 ///
 /// Takes a closure `f` that receives a reference to the symbol table and returns a result.
-pub fn with_symbol_table<F, R>(
-    f: F,
-) -> Result<R, PoisonError<std::sync::MutexGuard<'static, SymbolTable>>>
+///
+/// Fails with [`TableError::WouldDeadlock`] instead of blocking if the current thread already holds the lock,
+/// e.g. when called from an allocator callback.
+pub fn with_symbol_table<F, R>(f: F) -> LockResult<R>
 where
     F: FnOnce(&SymbolTable) -> R,
 {
+    accounting::raise_pending();
+
     // prevent allocations DURING lock acquisition
-    IN_ALLOC.with(|cell| cell.set(true));
+    let _in_alloc = FlagGuard::set(&IN_ALLOC, true);
+    let lock = lock_table()?;
+    let _holds_table = FlagGuard::set(&HOLDS_TABLE, true);
+
+    Ok(f(&lock))
+}
 
-    let lock = match SYMBOL_TABLE
+/// Sets a flag of the current thread, restoring its previous value when dropped,
+/// so a panicking closure doesn't leave the flag set on the thread.
+struct FlagGuard {
+    flag: &'static LocalKey<Cell<bool>>,
+    prev: bool,
+}
+
+impl FlagGuard {
+    /// Sets `flag` to `value` until the guard is dropped.
+    fn set(flag: &'static LocalKey<Cell<bool>>, value: bool) -> Self {
+        // during the thread teardown the flag is gone, so there's nothing to restore
+        let prev = flag.try_with(|cell| cell.replace(value)).unwrap_or(value);
+        Self { flag, prev }
+    }
+}
+
+impl Drop for FlagGuard {
+    fn drop(&mut self) {
+        let _ = self.flag.try_with(|cell| cell.set(self.prev));
+    }
+}
+
+/// Locks the symbol table, unless the current thread already holds the lock.
+fn lock_table() -> LockResult<MutexGuard<'static, SymbolTable>> {
+    if HOLDS_TABLE.try_with(Cell::get).unwrap_or_default() {
+        return Err(TableError::WouldDeadlock);
+    }
+
+    Ok(SYMBOL_TABLE
         .get()
         .expect("Symbol table not initialized")
-        .lock()
-    {
-        Ok(lock) => lock,
-        Err(poisoned) => {
-            // free alloc
-            IN_ALLOC.with(|cell| cell.set(false));
-            // If the lock is poisoned, we return the poisoned error
-            return Err(poisoned);
-        }
-    };
+        .lock()?)
+}
+
+/// Sets whether the current thread holds the symbol table lock.
+fn set_holds_table(holds: bool) {
+    let _ = HOLDS_TABLE.try_with(|cell| cell.set(holds));
+}
+
+/// Runs `f` with tracing suppressed on the current thread, so its allocations are not traced.
+///
+/// The previous state is restored afterwards, so calls can be nested.
+pub(crate) fn untraced<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _in_alloc = FlagGuard::set(&IN_ALLOC, true);
+    f()
+}
+
+/// Runs `f` as tracer bookkeeping, with tracing suppressed on the current thread.
+///
+/// The allocations made by `f` belong to the tracer internal structures (symbol table, pointer registry,
+/// interned names, symbolization caches), so they are accounted in [`crate::self_overhead_bytes`].
+/// The previous state is restored afterwards, so calls can be nested.
+pub(crate) fn bookkeeping<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _in_bookkeeping = FlagGuard::set(&IN_BOOKKEEPING, true);
+    untraced(f)
+}
+
+/// Sets whether the current thread is inside the tracer, returning the previous state.
+///
+/// During the thread teardown, once the thread-local is destroyed, the current thread is always inside the tracer.
+fn replace_in_alloc(in_alloc: bool) -> bool {
+    IN_ALLOC
+        .try_with(|cell| cell.replace(in_alloc))
+        .unwrap_or(true)
+}
+
+/// Returns whether the current thread is running the tracer bookkeeping.
+fn in_bookkeeping() -> bool {
+    IN_BOOKKEEPING.try_with(Cell::get).unwrap_or_default()
+}
+
+/// Returns the modules being traced, if the symbol table has been initialized.
+pub(crate) fn configured_modules() -> Option<Arc<[&'static str]>> {
+    SYMBOL_TABLE.get()?;
+    untraced(|| lock_table().ok().map(|table| table.modules()))
+}
+
+/// Provides mutable access to the symbol table in a thread-safe manner.
+///
+/// Allocations made by `f` are not traced.
+pub(crate) fn with_symbol_table_mut<F, R>(f: F) -> LockResult<R>
+where
+    F: FnOnce(&mut SymbolTable) -> R,
+{
+    let _in_alloc = FlagGuard::set(&IN_ALLOC, true);
+    let mut lock = lock_table()?;
+    let _holds_table = FlagGuard::set(&HOLDS_TABLE, true);
+
+    Ok(bookkeeping(|| f(&mut lock)))
+}
+
+/// Adds `module` to the modules being traced, e.g. the crate prefix of a plugin loaded at runtime.
+///
+/// The allocations made by `module` after the call get their own symbols. The bytes already attributed
+/// to `<unknown>` are not reattributed retroactively.
+/// The module list is swapped as a whole, so the allocation path never sees it partially updated.
+pub fn add_traced_module(module: impl Into<String>) -> LockResult<()> {
+    let module = intern(&module.into());
+    with_symbol_table_mut(|table| table.add_module(module))
+}
 
-    let res = Ok(f(&lock));
+/// Returns a copy of the modules being traced, e.g. to check the ones loaded from a configuration file.
+///
+/// The list includes the modules added with [`add_traced_module`], and it's empty if the symbol table
+/// has not been initialized or its lock is poisoned.
+pub fn traced_modules() -> Vec<&'static str> {
+    configured_modules()
+        .map(|modules| modules.to_vec())
+        .unwrap_or_default()
+}
 
-    IN_ALLOC.with(|cell| cell.set(false));
+/// Returns all the allocations which are still live, as `(pointer, size, symbol)`.
+///
+/// The symbol is the one which made the allocation, so this tells exactly what's leaked and who made it.
+pub fn live_allocations() -> LockResult<Vec<(usize, usize, &'static str)>> {
+    with_symbol_table(|table| table.live_allocations().collect())
+}
 
-    res
+/// Returns the sum of the allocated bytes of all the symbols, computed under the lock.
+///
+/// Comparing it with [`crate::global_allocated`] reveals the bytes which are not attributed to any symbol.
+/// The two values only match exactly when read under the same lock (see [`SymbolTable::total_allocated`]),
+/// since allocations may happen in between.
+/// Allocations made before the symbol table was initialized and the allocations skipped by sampling are in neither of them.
+pub fn sum_symbol_allocated() -> LockResult<usize> {
+    with_symbol_table(SymbolTable::total_allocated)
+}
+
+/// Returns the fraction of the live bytes attributed to a function of the traced modules, from `0.0` to `1.0`.
+///
+/// The bytes of the `<unknown>` symbol, allocated outside of the traced modules, and the bytes not attributed
+/// to any symbol (see [`sum_symbol_allocated`]) are not covered. A low coverage means that more modules should be traced
+/// (see [`add_traced_module`]). Returns `0.0` if the symbol table is not initialized or its lock is poisoned.
+pub fn attribution_coverage() -> f64 {
+    if SYMBOL_TABLE.get().is_none() {
+        return 0.0;
+    }
+    // the global counters are updated under the lock too
+    with_symbol_table(|table| {
+        let attributed = table.attributed_allocated();
+        (attributed as f64 / stats::global_allocated().max(1) as f64).min(1.0)
+    })
+    .unwrap_or_default()
+}
+
+/// Returns the live bytes and count of the allocations of each traced module, as `(module, allocated, count)`,
+/// sorted by allocated bytes in descending order.
+///
+/// Each allocation is accounted to the longest traced module its symbol belongs to, e.g. `app::net::connect`
+/// to `app::net` rather than to `app`. The gauges are updated on each allocation and deallocation,
+/// so this costs O(modules) regardless of the number of symbols, which makes it cheap enough for dashboards.
+/// Returns an empty list if the symbol table is not initialized or its lock is poisoned.
+pub fn module_stats() -> Vec<(&'static str, usize, usize)> {
+    if SYMBOL_TABLE.get().is_none() {
+        return Vec::new();
+    }
+    with_symbol_table(|table| {
+        let mut stats = table.module_stats();
+        stats.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        stats
+    })
+    .unwrap_or_default()
+}
+
+/// Sets the boundaries of the allocation size histogram buckets of each [`crate::Symbol`].
+///
+/// The boundaries must be sorted in ascending order; by default [`crate::DEFAULT_HISTOGRAM_BUCKETS`] is used.
+/// The histograms of the existing symbols are reset.
+pub fn set_histogram_buckets(buckets: &'static [usize]) -> LockResult<()> {
+    with_symbol_table_mut(|table| table.set_histogram_buckets(buckets))
+}
+
+/// Sets the smoothing factor (`alpha`) of the allocation size moving average of each [`crate::Symbol`]
+/// (see [`crate::Symbol::ema_size`]), clamped to `0.0..=1.0`.
+///
+/// By default [`crate::DEFAULT_EMA_ALPHA`] is used. The averages of the existing symbols are kept.
+pub fn set_ema_alpha(alpha: f64) -> LockResult<()> {
+    with_symbol_table_mut(|table| table.set_ema_alpha(alpha))
+}
+
+/// Sets whether each allocation is also credited to every function of the traced modules on its call stack,
+/// besides the caller it's attributed to (see [`crate::Symbol::inclusive_allocated`]).
+///
+/// With inclusive accounting, `main` accounts for everything allocated beneath it, at the cost of keeping the list
+/// of the credited functions of each live allocation in the pointer registry.
+/// Only the allocations made after the call are credited. It has no effect on Windows, where the call stacks
+/// are resolved lazily.
+#[cfg(stack_capture)]
+pub fn set_inclusive_accounting(enabled: bool) -> LockResult<()> {
+    with_symbol_table_mut(|table| table.set_inclusive(enabled))
+}
+
+/// Reserves capacity for at least `additional` more symbols in the symbol table.
+///
+/// Growing the table while tracing causes a rehash inside the allocation path;
+/// calling this at a quiet moment pre-grows the table without losing any data.
+/// The rehash allocation is not attributed to any symbol.
+pub fn reserve(additional: usize) -> LockResult<()> {
+    with_symbol_table_mut(|table| table.reserve(additional))
+}
+
+/// Enables or disables tracing of new allocations, e.g. to pause tracing around a noisy phase.
+///
+/// While disabled, deallocations of the pointers traced before are still accounted, so the stats stay consistent
+/// when tracing is enabled again.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Sets the size in bytes below which the allocations are only counted globally, without capturing their call stack,
+/// so the expensive attribution is spent on the allocations that matter, e.g. `1024 * 1024` to attribute
+/// only the allocations of 1 MiB or more. `0`, the default, attributes all of them.
+///
+/// The allocations below the threshold still count in the global live bytes (e.g. [`crate::global_allocated`])
+/// and are reported as `<unattributed>` by [`crate::live_allocations`], but they never create a symbol.
+/// Only the allocations made after the call are affected.
+pub fn set_attribution_size_threshold(bytes: usize) {
+    ATTRIBUTION_SIZE_THRESHOLD.store(bytes, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Marks the end of the startup phase, e.g. once the lazy statics and the `OnceLock`s are initialized.
+///
+/// With [`LeaktracerAllocator::with_startup_bucket`], the allocations made before this call are attributed
+/// to the `<startup>` symbol, which separates the one-time setup from the steady state.
+pub fn mark_startup_complete() {
+    STARTUP_COMPLETE.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Attributes every allocation to the `symbol`, without capturing the call stack, until called again with `None`.
+///
+/// The call stack resolution depends on the build (inlining, debug info, the platform), which makes the tests
+/// asserting on the symbols fragile: with a fixed symbol, each allocation lands on a known key, e.g. `test::sym`.
+/// It's also the cheapest attribution there is, so it's a baseline to benchmark the tracer overhead against.
+/// The symbol is global, so the tests using it should run in their own test binary, or with `--test-threads=1`.
+/// Only the allocations made after the call are affected. Since it needs no call stack, it works
+/// in the [counter-only mode](crate#counter-only-mode) and on WebAssembly too.
+///
+/// ```rust
+/// leaktracer::set_fixed_symbol(Some("test::sym"));
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+/// ```
+pub fn set_fixed_symbol(symbol: Option<&'static str>) {
+    FIXED_SYMBOL.store(symbol);
+}
+
+/// Returns the symbol set with [`set_fixed_symbol`], if any.
+fn fixed_symbol() -> Option<&'static str> {
+    FIXED_SYMBOL.load()
+}
+
+/// Returns whether tracing of new allocations is enabled (see [`set_enabled`]).
+pub fn is_enabled() -> bool {
+    ENABLED.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Resets the stats, by clearing the symbol table, the largest allocations and zeroing the counters.
+///
+/// Pointers allocated before the reset are forgotten, so their deallocations are counted as untracked frees.
+/// The counters of the [`LeaktracerAllocator`] are zeroed on its next traced operation.
+pub fn reset() -> LockResult<()> {
+    with_symbol_table_mut(clear_stats)
+}
+
+/// Clears the symbol table, the largest allocations and zeroes the counters.
+fn clear_stats(table: &mut SymbolTable) {
+    table.clear();
+    #[cfg(unix)]
+    crate::emergency::refresh(table);
+    largest::clear();
+    stats::reset();
 }
 
 /// An enumeration representing the type of allocation operation being traced.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum AllocOp {
+pub(crate) enum AllocOp {
     Alloc,
+    AllocZeroed,
+    Realloc {
+        /// Whether the block moved to another address.
+        moved: bool,
+        /// The traced size of the block before the reallocation.
+        old_size: usize,
+    },
     Dealloc,
 }
 
@@ -83,84 +503,817 @@ impl LeaktracerAllocator {
     pub const fn init() -> Self {
         LeaktracerAllocator {
             allocated: AtomicUsize::new(0),
+            size_class_bounds: DEFAULT_SIZE_CLASS_BOUNDS,
+            size_classes: [const { SizeClassCounters::new() }; 4],
+            padded_size: false,
+            strict: false,
+            strict_accounting: false,
+            quarantine: 0,
+            startup_bucket: false,
+            caller_cache: false,
+            double_frees: AtomicUsize::new(0),
+            size_mismatches: AtomicUsize::new(0),
+            untracked_frees: AtomicUsize::new(0),
+            untracked_freed_bytes: AtomicUsize::new(0),
+            reset_epoch: AtomicU64::new(0),
+            sampling: Sampling::Fixed(1),
+            sampling_divisor: AtomicUsize::new(1),
+            size_class_sampling: [1; 4],
+            sampling_counters: [const { AtomicUsize::new(0) }; 4],
+            capture_nanos: AtomicU64::new(0),
+            window_start_nanos: AtomicU64::new(0),
+            clock: None,
+            callbacks: AllocCallbacks::new(),
         }
     }
 
+    /// Sets how the allocations are sampled, to bound the cost of the call stack capture.
+    ///
+    /// Only 1 out of every `divisor` allocations is attributed to a symbol, with its bytes and count scaled
+    /// by the divisor, so the aggregates stay approximately correct. The other allocations skip the call stack capture,
+    /// but they're still registered, so their deallocations are not counted as untracked frees.
+    ///
+    /// With [`Sampling::Adaptive`], the divisor is raised when the time spent capturing call stacks exceeds the budget,
+    /// and lowered when idle. The effective divisor is reported in [`crate::SymbolTableSnapshot::sampling_divisor`].
+    ///
+    /// ```rust
+    /// use leaktracer::{LeaktracerAllocator, Sampling};
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator =
+    ///     LeaktracerAllocator::init().with_sampling(Sampling::Adaptive { budget: 0.05 });
+    /// ```
+    pub const fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self.sampling_divisor = AtomicUsize::new(sampling.initial_divisor());
+        self
+    }
+
+    /// Enables the strict mode checks on deallocation.
+    ///
+    /// In strict mode, each deallocation is checked against the pointer registry:
+    ///
+    /// - freeing a pointer which has recently been freed increments [`LeaktracerAllocator::double_frees`]
+    /// - freeing a pointer with a layout size different from the allocation increments [`LeaktracerAllocator::size_mismatches`]
+    ///
+    /// Violations are also reported to the callback set with [`crate::on_strict_violation`], rather than aborting.
+    /// Pointers allocated before the symbol table was initialized are not in the registry, so they are never checked.
+    pub const fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Enables the strict accounting checks on deallocation, intended for tests.
+    ///
+    /// A deallocation which would drive the counters of the symbol it's attributed to below zero always
+    /// points to a bug in the attribution. In strict accounting it's recorded as an [`crate::AccountingViolation`],
+    /// with the symbol freeing the pointer, retrievable with [`crate::accounting_violations`].
+    /// With `debug_assertions` on, the next call to [`with_symbol_table`] panics, since the allocator itself can't.
+    /// The counters are saturated to zero either way.
+    ///
+    /// ```rust
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_strict_accounting(true);
+    /// ```
+    pub const fn with_strict_accounting(mut self, strict_accounting: bool) -> Self {
+        self.strict_accounting = strict_accounting;
+        self
+    }
+
+    /// Holds the memory of the last `capacity` freed pointers back from the system allocator, `0` to disable it.
+    ///
+    /// Otherwise a freed address can be reused right away by the next allocation, and a late or duplicate free
+    /// of the old pointer would release the new allocation, corrupting the attribution (and the heap).
+    /// While a pointer is quarantined its address can't be reused, so freeing it again is always detected:
+    /// it increments [`LeaktracerAllocator::double_frees`] and it's reported to the callback set with
+    /// [`crate::on_strict_violation`], without touching the stats nor releasing the memory twice.
+    ///
+    /// The quarantined memory is released to the system allocator once `capacity` more pointers are freed.
+    /// Pointers freed before the symbol table is initialized are not quarantined.
+    ///
+    /// ```rust
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_quarantine(256);
+    /// ```
+    pub const fn with_quarantine(mut self, capacity: usize) -> Self {
+        self.quarantine = capacity;
+        self
+    }
+
+    /// Sets whether to account the layout size padded to its alignment (`layout.pad_to_align().size()`)
+    /// instead of the requested size.
+    ///
+    /// This matters for high-alignment allocations (e.g. SIMD buffers, page-aligned regions),
+    /// where the allocator may hand back more memory than requested.
+    ///
+    /// ```rust
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_padded_size(true);
+    /// ```
+    pub const fn with_padded_size(mut self, padded_size: bool) -> Self {
+        self.padded_size = padded_size;
+        self
+    }
+
+    /// Attributes the allocations made before [`mark_startup_complete`] to the `<startup>` symbol,
+    /// instead of the calling symbol.
+    ///
+    /// Allocations made during the startup phase (e.g. lazy statics and `OnceLock` initialization) are usually
+    /// one-time setup rather than leaks, so this keeps them from cluttering the symbol table.
+    /// The startup allocations skip the call stack capture, so they are attributed
+    /// in the [counter-only mode](crate#counter-only-mode) and on WebAssembly too.
+    ///
+    /// ```rust
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_startup_bucket(true);
+    ///
+    /// fn main() {
+    ///     leaktracer::init_symbol_table(&["my_crate_name"]);
+    ///     // ... initialize the application
+    ///     leaktracer::mark_startup_complete();
+    /// }
+    /// ```
+    pub const fn with_startup_bucket(mut self, startup_bucket: bool) -> Self {
+        self.startup_bucket = startup_bucket;
+        self
+    }
+
+    /// Caches the symbol of each call site on the thread which resolved it, so the following allocations made
+    /// from the same call site skip the call stack resolution, which is the most expensive part of the tracing.
+    ///
+    /// The call site is identified by the return addresses of the innermost 32 frames of the call stack,
+    /// including the allocator ones, which are walked without being resolved. This changes the attribution
+    /// granularity slightly: two call stacks which differ only beyond those frames share the symbol resolved
+    /// for the first one, even if a full scan would attribute them to different functions, e.g. a deep
+    /// helper outside of the traced modules called by two traced functions. Each thread caches up to 256
+    /// call sites, evicting the older ones on collision.
+    ///
+    /// The symbol table lock is still taken, since the pointer registry is needed to attribute the deallocations.
+    /// It has no effect in inclusive accounting (see `set_inclusive_accounting`), on Windows, where the
+    /// call stacks are already resolved by a helper thread, nor without the `symbols` feature.
+    ///
+    /// ```rust
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_caller_cache(true);
+    /// ```
+    pub const fn with_caller_cache(mut self, enabled: bool) -> Self {
+        self.caller_cache = enabled;
+        self
+    }
+
+    /// Sets the sampling divisor of each [`SizeClass`], from the smallest to the biggest,
+    /// e.g. to sample 1 out of 1000 small allocations, but every huge allocation.
+    ///
+    /// Within each class, only 1 out of `divisor` allocations is attributed to a symbol, with its bytes and count
+    /// scaled by the divisor (see [`LeaktracerAllocator::with_sampling`]). The class divisor is multiplied by
+    /// the divisor set with [`LeaktracerAllocator::with_sampling`], if any.
+    ///
+    /// ```rust
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator =
+    ///     LeaktracerAllocator::init().with_size_class_sampling([1000, 10, 1, 1]);
+    /// ```
+    pub const fn with_size_class_sampling(mut self, divisors: [usize; 4]) -> Self {
+        self.size_class_sampling = divisors;
+        self
+    }
+
+    /// Sets the [`Clock`] read by all the timestamps of the tracer, as [`crate::set_clock`] does with a function.
+    ///
+    /// The clock is installed on the first traced allocation, which captures the tracer start from it,
+    /// so the [`crate::uptime`] starts from zero. Calling [`crate::set_clock`] afterwards replaces it.
+    /// With the `test-util` feature, a [`crate::ManualClock`] makes the time-dependent features deterministic in tests.
+    ///
+    /// ```rust
+    /// use leaktracer::{LeaktracerAllocator, MonotonicClock};
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_clock(&MonotonicClock);
+    /// ```
+    pub const fn with_clock(mut self, clock: &'static dyn Clock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Sets the upper bounds (exclusive) of the [`SizeClass::Small`], [`SizeClass::Medium`] and [`SizeClass::Large`] classes.
+    ///
+    /// Allocations greater or equal than the last bound are classified as [`SizeClass::Huge`].
+    /// The bounds must be sorted in ascending order.
+    ///
+    /// ```rust
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator =
+    ///     LeaktracerAllocator::init().with_size_classes([256, 4096, 1024 * 1024]);
+    /// ```
+    pub const fn with_size_classes(mut self, bounds: [usize; 3]) -> Self {
+        self.size_class_bounds = bounds;
+        self
+    }
+
+    /// Registers a callback invoked with the size and the symbol of each traced allocation,
+    /// e.g. to feed an application metric.
+    ///
+    /// Up to [`crate::MAX_ALLOC_CALLBACKS`] callbacks can be registered, with this method, [`LeaktracerAllocator::on_dealloc`]
+    /// and [`LeaktracerAllocator::on_large_alloc`] together; registering more panics, at compile time for a static.
+    /// They are invoked in the order they were registered.
+    ///
+    /// The callbacks are invoked from inside the allocator, with tracing suppressed and the symbol table locked,
+    /// so they can't access the symbol table ([`with_symbol_table`] fails with [`TableError::WouldDeadlock`]).
+    /// Their panics are caught and ignored. The allocations made before
+    /// the symbol table is initialized, and the ones skipped by sampling, don't invoke them.
+    ///
+    /// ```rust
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// use leaktracer::LeaktracerAllocator;
+    ///
+    /// static CACHE_BYTES: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// #[global_allocator]
+    /// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().on_alloc(|size, symbol| {
+    ///     if symbol.starts_with("my_crate::cache") {
+    ///         CACHE_BYTES.fetch_add(size, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// ```
+    pub const fn on_alloc(mut self, hook: AllocHook) -> Self {
+        self.callbacks = self.callbacks.with_alloc(hook);
+        self
+    }
+
+    /// Registers a callback invoked with the size of each traced deallocation and the symbol which allocated it.
+    ///
+    /// See [`LeaktracerAllocator::on_alloc`] for the limits and the context the callbacks run in.
+    pub const fn on_dealloc(mut self, hook: AllocHook) -> Self {
+        self.callbacks = self.callbacks.with_dealloc(hook);
+        self
+    }
+
+    /// Registers a callback invoked with the size and the symbol of each traced allocation of at least
+    /// `threshold` bytes, e.g. to abort a test on a giant allocation.
+    ///
+    /// See [`LeaktracerAllocator::on_alloc`] for the limits and the context the callbacks run in.
+    pub const fn on_large_alloc(mut self, threshold: usize, hook: AllocHook) -> Self {
+        self.callbacks = self.callbacks.with_large_alloc(threshold, hook);
+        self
+    }
+
     /// Returns the total number of bytes allocated by the allocator up to this point.
+    ///
+    /// Only allocations made after the symbol table was initialized are accounted.
     pub fn allocated(&self) -> usize {
         self.allocated.load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    /// Returns the traced allocations per second since the tracer started (see [`crate::uptime`]),
+    /// `0.0` if no time elapsed yet.
+    ///
+    /// Like the other global counters, it includes the allocations traced by any [`LeaktracerAllocator`].
+    pub fn alloc_rate(&self) -> f64 {
+        time::rate(stats::quick_stats().total_allocations, time::uptime())
+    }
+
+    /// Returns the number of deallocations of pointers which were never traced,
+    /// such as pointers allocated before the symbol table was initialized.
+    ///
+    /// These deallocations don't touch the symbol table nor [`LeaktracerAllocator::allocated`].
+    pub fn untracked_frees(&self) -> usize {
+        self.untracked_frees
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the bytes freed by deallocations of pointers which were never traced.
+    pub fn untracked_freed_bytes(&self) -> usize {
+        self.untracked_freed_bytes
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of double frees detected in strict mode.
+    pub fn double_frees(&self) -> usize {
+        self.double_frees.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of deallocations with a mismatched layout size detected in strict mode.
+    pub fn size_mismatches(&self) -> usize {
+        self.size_mismatches
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the [`SizeClassStats`] for each [`SizeClass`], from the smallest to the biggest.
+    ///
+    /// Deallocations are classified by recomputing the class from the layout size,
+    /// which is the same size the allocation was classified with.
+    pub fn size_class_stats(&self) -> [SizeClassStats; 4] {
+        SizeClass::ALL.map(|class| self.size_classes[class.index()].stats(class))
+    }
+
+    /// Zeroes the counters if a [`reset`] happened since the last traced operation.
+    ///
+    /// Must be called with the symbol table locked.
+    fn sync_reset(&self) {
+        let epoch = stats::reset_epoch();
+        if self
+            .reset_epoch
+            .swap(epoch, std::sync::atomic::Ordering::Relaxed)
+            == epoch
+        {
+            return;
+        }
+
+        for counter in [
+            &self.allocated,
+            &self.double_frees,
+            &self.size_mismatches,
+            &self.untracked_frees,
+            &self.untracked_freed_bytes,
+        ] {
+            counter.store(0, std::sync::atomic::Ordering::Relaxed);
+        }
+        for class in &self.size_classes {
+            class.reset();
+        }
+    }
+
+    /// Returns the counters of the [`SizeClass`] for an allocation of `size` bytes.
+    fn size_class(&self, size: usize) -> &SizeClassCounters {
+        &self.size_classes[SizeClass::classify(size, &self.size_class_bounds).index()]
+    }
+
     /// Returns whether the allocation is an external allocation.
     ///
     /// With **external allocation**, we mean that the allocation is not requested by the allocator itself,
     /// but rather by the user of the allocator.
     ///
     /// This is determined by checking if the `IN_ALLOC` thread-local variable is set to `false`.
+    /// During the thread teardown, once `IN_ALLOC` is destroyed, the allocation is treated as an internal one,
+    /// and counted in [`stats::tls_fallbacks`].
     fn is_external_allocation(&self) -> bool {
-        !IN_ALLOC.get()
+        match IN_ALLOC.try_with(Cell::get) {
+            Ok(in_alloc) => !in_alloc,
+            Err(_) => {
+                stats::record_tls_fallback();
+                false
+            }
+        }
     }
 
     /// Enters the allocation context, marking that an allocation is being made.
     fn enter_alloc(&self) {
-        IN_ALLOC.with(|cell| cell.set(true));
+        replace_in_alloc(true);
     }
 
     /// Exits the allocation context, marking that the allocation is done.
     fn exit_alloc(&self) {
-        IN_ALLOC.with(|cell| cell.set(false));
+        replace_in_alloc(false);
     }
 
-    /// Traces the allocation, logging the layout of the allocation.
-    fn trace_allocation(&self, layout: Layout, table: Option<&mut MutexGuard<SymbolTable>>) {
-        // first increment the allocated bytes
+    /// Traces the allocation of `size` bytes at `ptr`, aligned to `align` bytes, made by `op`.
+    fn trace_allocation(
+        &self,
+        ptr: *mut u8,
+        size: usize,
+        align: usize,
+        op: AllocOp,
+        table: Option<&mut MutexGuard<SymbolTable>>,
+    ) {
+        stats::record_thread_alloc(size);
+        // allocations made before the symbol table is initialized are not registered, so they are not traced at all
+        let Some(table) = table else {
+            return;
+        };
+
+        let weight = self.sample(size);
+        if weight == 0 {
+            table.skip(ptr as usize, size);
+            stats::advance_generation();
+            return;
+        }
+
+        let recorded = size.saturating_mul(weight);
         self.allocated
-            .fetch_add(layout.size(), std::sync::atomic::Ordering::Relaxed);
-        if let Some(table) = table {
-            table.alloc(layout.size());
+            .fetch_add(recorded, std::sync::atomic::Ordering::Relaxed);
+        let total_allocations = stats::record_alloc(recorded, weight);
+        stats::record_op(op, recorded, weight);
+        self.size_class(size).alloc(recorded, weight);
+        let capture = time::Stopwatch::start();
+        #[cfg(feature = "tokio")]
+        let task = crate::task::current_task();
+        #[cfg(not(feature = "tokio"))]
+        let task: Option<&'static str> = None;
+        let symbol = if let Some(symbol) = fixed_symbol() {
+            table.alloc_fixed(ptr as usize, size, weight, symbol)
+        } else if size < ATTRIBUTION_SIZE_THRESHOLD.load(std::sync::atomic::Ordering::Relaxed) {
+            table.alloc_unattributed(ptr as usize, size, weight)
+        } else if self.startup_bucket
+            && !STARTUP_COMPLETE.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            table.alloc_startup(ptr as usize, size, weight)
+        } else if let Some(task) = task {
+            // the task id replaces the call stack, which is dominated by the executor frames
+            table.alloc_to(ptr as usize, size, weight, task)
+        } else {
+            #[cfg(all(windows, stack_capture))]
+            {
+                table.alloc_deferred(ptr as usize, size, weight)
+            }
+            #[cfg(all(not(windows), stack_capture))]
+            if self.caller_cache {
+                table.alloc_cached(ptr as usize, size, weight)
+            } else {
+                table.alloc(ptr as usize, size, weight)
+            }
+            #[cfg(not(stack_capture))]
+            {
+                table.alloc(ptr as usize, size, weight)
+            }
+        };
+        self.adapt_sampling(capture.elapsed());
+        if let AllocOp::Realloc { moved, old_size } = op {
+            table.record_realloc(ptr as usize, moved, old_size);
         }
+        table.record_alignment(ptr as usize, align);
+        table.record_fingerprint(size, align, symbol, weight);
+        largest::record(size, symbol);
+        self.callbacks.alloc(size, symbol);
+        #[cfg(feature = "heaptrack")]
+        crate::heaptrack::record_alloc(ptr as usize, size, symbol);
+        stats::set_symbol_count(table.len());
+        stats::advance_generation();
+        #[cfg(feature = "log")]
+        crate::logging::log_alloc(size, symbol);
+        hooks::periodic(total_allocations, total_allocations + weight, table);
+        #[cfg(unix)]
+        crate::emergency::refresh_every(total_allocations, total_allocations + weight, table);
     }
 
-    /// Traces the deallocation, logging the layout of the deallocation.
-    fn trace_deallocation(&self, layout: Layout, table: Option<&mut MutexGuard<SymbolTable>>) {
-        // first decrement the allocated bytes
+    /// Traces the deallocation of `size` bytes at `ptr`, allocated with `layout`.
+    ///
+    /// Returns the pointer whose memory must be released to the system allocator, with its layout.
+    fn trace_deallocation(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        size: usize,
+        table: Option<&mut MutexGuard<SymbolTable>>,
+    ) -> Option<(*mut u8, Layout)> {
+        let Some(table) = table else {
+            return Some((ptr, layout));
+        };
+
+        let release = if self.quarantine > 0 {
+            // the address can't have been reused, so it's freed twice
+            if let Some(symbol) = table.quarantined(ptr as usize) {
+                self.record_violation(StrictViolation {
+                    kind: StrictViolationKind::DoubleFree,
+                    ptr: ptr as usize,
+                    symbol,
+                });
+                return None;
+            }
+            let symbol = table
+                .allocation_symbol(ptr as usize)
+                .unwrap_or(symbols::UNTRACKED);
+            table
+                .quarantine(ptr as usize, layout, symbol, self.quarantine)
+                .map(|(ptr, layout)| (ptr as *mut u8, layout))
+        } else {
+            Some((ptr, layout))
+        };
+        self.record_deallocation(ptr, size, table);
+
+        release
+    }
+
+    /// Records the deallocation of `size` bytes at `ptr` into the symbol table and the counters.
+    fn record_deallocation(&self, ptr: *mut u8, size: usize, table: &mut SymbolTable) {
+        let symbol = if self.callbacks.has_dealloc() {
+            table.allocation_symbol(ptr as usize)
+        } else {
+            None
+        };
+        if self.strict_accounting {
+            if let Some(violation) = table.accounting_underflow(ptr as usize) {
+                accounting::record_violation(violation);
+            }
+        }
+        let (res, violation) = table.dealloc(ptr as usize, size, self.strict);
+        stats::advance_generation();
+        if let Some(violation) = violation {
+            self.record_violation(violation);
+            // a double free doesn't release any live memory
+            if violation.kind == StrictViolationKind::DoubleFree {
+                return;
+            }
+        }
+        // release the size recorded at allocation, so the counters always match the symbols
+        let (size, weight) = match res {
+            Dealloc::Tracked { size, weight } => {
+                #[cfg(feature = "heaptrack")]
+                crate::heaptrack::record_dealloc(ptr as usize);
+                if let Some(symbol) = symbol {
+                    self.callbacks.dealloc(size, symbol);
+                }
+                (size, weight)
+            }
+            // the allocation was never recorded, so there's nothing to release
+            Dealloc::Skipped => return,
+            Dealloc::Untracked => {
+                // the pointer was never traced, so it must not touch the live counters
+                self.untracked_frees
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                self.untracked_freed_bytes
+                    .fetch_add(size, std::sync::atomic::Ordering::Relaxed);
+                stats::record_untracked_free(size);
+                hooks::untracked_dealloc(size);
+                return;
+            }
+        };
+        let recorded = size.saturating_mul(weight);
         self.allocated
-            .fetch_sub(layout.size(), std::sync::atomic::Ordering::Relaxed);
-        if let Some(table) = table {
-            table.dealloc(layout.size());
+            .fetch_sub(recorded, std::sync::atomic::Ordering::Relaxed);
+        stats::record_dealloc(recorded, weight);
+        self.size_class(size).dealloc(recorded, weight);
+    }
+
+    /// Returns the sampling weight of the next allocation of `size` bytes:
+    /// the divisor of its [`SizeClass`] if it's sampled, `0` otherwise.
+    fn sample(&self, size: usize) -> usize {
+        if let Some(divisor) = sampling::divisor_override() {
+            if divisor <= 1 {
+                return 1;
+            }
+        }
+
+        let class = SizeClass::classify(size, &self.size_class_bounds).index();
+        let divisor = self
+            .sampling_divisor
+            .load(std::sync::atomic::Ordering::Relaxed)
+            .saturating_mul(self.size_class_sampling[class].max(1));
+        if divisor <= 1 {
+            return 1;
+        }
+
+        let n = self.sampling_counters[class].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if n % divisor == 0 { divisor } else { 0 }
+    }
+
+    /// Accounts the time spent capturing a call stack and, in adaptive sampling, updates the divisor
+    /// at the end of each window.
+    ///
+    /// Must be called with the symbol table locked.
+    fn adapt_sampling(&self, capture: Duration) {
+        let Sampling::Adaptive { budget } = self.sampling else {
+            return;
+        };
+
+        let busy = self.capture_nanos.fetch_add(
+            capture.as_nanos() as u64,
+            std::sync::atomic::Ordering::Relaxed,
+        ) + capture.as_nanos() as u64;
+        let now = time::uptime().as_nanos() as u64;
+        let start = self
+            .window_start_nanos
+            .load(std::sync::atomic::Ordering::Relaxed);
+        let elapsed = Duration::from_nanos(now.saturating_sub(start));
+        if elapsed < ADAPTIVE_WINDOW {
+            return;
+        }
+
+        let divisor = sampling::next_divisor(
+            self.sampling_divisor
+                .load(std::sync::atomic::Ordering::Relaxed),
+            budget,
+            Duration::from_nanos(busy),
+            elapsed,
+        );
+        self.sampling_divisor
+            .store(divisor, std::sync::atomic::Ordering::Relaxed);
+        self.capture_nanos
+            .store(0, std::sync::atomic::Ordering::Relaxed);
+        self.window_start_nanos
+            .store(now, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Records a [`StrictViolation`] into the counters and reports it to the violation hook.
+    fn record_violation(&self, violation: StrictViolation) {
+        let counter = match violation.kind {
+            StrictViolationKind::DoubleFree => &self.double_frees,
+            StrictViolationKind::SizeMismatch { .. } => &self.size_mismatches,
+        };
+        counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        strict::report_violation(violation);
+    }
+
+    /// Returns the size accounted for the given [`Layout`].
+    ///
+    /// This is the layout size padded to its alignment if [`LeaktracerAllocator::with_padded_size`] is enabled.
+    fn traced_size(&self, layout: Layout) -> usize {
+        if self.padded_size {
+            layout.pad_to_align().size()
+        } else {
+            layout.size()
         }
     }
 
-    /// Traces the allocation or deallocation operation using the [`Layout`], depending on the [`AllocOp`] type.
-    fn trace(&self, layout: Layout, op: AllocOp) {
+    /// Traces the allocation or deallocation operation of `ptr` using the [`Layout`], depending on the [`AllocOp`] type.
+    ///
+    /// On deallocation, returns the pointer whose memory must be released to the system allocator with its layout:
+    /// `ptr` itself, or the pointer evicted from the quarantine (see [`LeaktracerAllocator::with_quarantine`]).
+    pub(crate) fn trace(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        op: AllocOp,
+    ) -> Option<(*mut u8, Layout)> {
+        // deallocations are traced even while paused, to release the pointers traced before
+        if op != AllocOp::Dealloc && !is_enabled() {
+            return None;
+        }
+        let mut lock = self.lock_for_trace();
+        let release = self.trace_locked(ptr, layout, op, lock.as_mut());
+        drop(lock);
+        report::check_auto_report(stats::allocated());
+
+        release
+    }
+
+    /// Installs the clock, captures the tracer start and locks the symbol table, if initialized, to trace an op.
+    fn lock_for_trace(&self) -> Option<MutexGuard<'static, SymbolTable>> {
+        // install the clock and capture the tracer start on the first traced allocation
+        if let Some(clock) = self.clock {
+            time::inject_clock(clock);
+        }
+        time::mark_start();
         // lock symbol table to avoid deadlocks
-        let mut lock = SYMBOL_TABLE.get().and_then(|table| table.lock().ok());
+        SYMBOL_TABLE.get().and_then(|table| table.lock().ok())
+    }
 
+    /// Like [`LeaktracerAllocator::trace`], with the symbol table locked by [`LeaktracerAllocator::lock_for_trace`].
+    fn trace_locked(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        op: AllocOp,
+        table: Option<&mut MutexGuard<SymbolTable>>,
+    ) -> Option<(*mut u8, Layout)> {
         self.enter_alloc();
-        match op {
-            AllocOp::Alloc => self.trace_allocation(layout, lock.as_mut()),
-            AllocOp::Dealloc => self.trace_deallocation(layout, lock.as_mut()),
+        // the callbacks invoked meanwhile must not lock the table again
+        set_holds_table(table.is_some());
+        if table.is_some() {
+            self.sync_reset();
         }
+        stats::set_padded_size(self.padded_size);
+        stats::set_sampling_divisor(
+            self.sampling_divisor
+                .load(std::sync::atomic::Ordering::Relaxed),
+        );
+        let size = self.traced_size(layout);
+        let release = bookkeeping(|| match op {
+            AllocOp::Dealloc => self.trace_deallocation(ptr, layout, size, table),
+            op => {
+                self.trace_allocation(ptr, size, layout.align(), op, table);
+                None
+            }
+        });
+        set_holds_table(false);
         self.exit_alloc();
-        drop(lock);
+
+        release
+    }
+
+    /// Traces `ptr`, returned by the system allocator for `op`, or accounts it as overhead while bookkeeping.
+    fn trace_system_allocation(&self, ptr: *mut u8, layout: Layout, op: AllocOp) {
+        if layout.size() == 0 {
+            self.record_zero_size_event();
+            return;
+        }
+        // if the allocation is not null AND the allocation is external, trace the allocation
+        if !ptr.is_null() && self.is_external_allocation() {
+            self.trace(ptr, layout, op);
+        } else if !ptr.is_null() && in_bookkeeping() {
+            stats::record_overhead_alloc(layout.size());
+        }
+    }
+
+    /// Counts an external allocator call with a zero-sized layout, which is never traced:
+    /// there are no bytes to attribute, so it isn't worth a backtrace nor a registry entry.
+    fn record_zero_size_event(&self) {
+        if self.is_external_allocation() {
+            stats::record_zero_size_event();
+        }
     }
 }
 
 unsafe impl GlobalAlloc for LeaktracerAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let ptr = unsafe { System.alloc(layout) };
-        // if the allocation is not null AND the allocation is external, trace the allocation
-        if !ptr.is_null() && self.is_external_allocation() {
-            self.trace(layout, AllocOp::Alloc);
-        }
+        self.trace_system_allocation(ptr, layout, AllocOp::Alloc);
         ptr
     }
 
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        self.trace_system_allocation(ptr, layout, AllocOp::AllocZeroed);
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size == 0 {
+            // the caller must not shrink a block to zero bytes, and the system allocator must not be asked to:
+            // the block is left untouched, and stays traced as it was
+            debug_assert!(new_size != 0, "realloc called with a zero new size");
+            self.record_zero_size_event();
+            return ptr;
+        }
+        // SAFETY: the caller guarantees `new_size`, rounded up to the alignment, doesn't overflow
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        if layout.size() == 0 {
+            // a zero-sized block is never traced, so this is an untraced deallocation of it,
+            // and a traced allocation of the new block
+            let new_ptr = unsafe { self.alloc(new_layout) };
+            if !new_ptr.is_null() {
+                unsafe { self.dealloc(ptr, layout) };
+            }
+            return new_ptr;
+        }
+        if self.quarantine > 0 && self.is_external_allocation() {
+            // the old block must go through the quarantine, so it can't be resized in place
+            let new_ptr = unsafe { System.alloc(new_layout) };
+            if !new_ptr.is_null() {
+                unsafe { std::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size)) };
+                let old_size = self.traced_size(layout);
+                self.trace_system_allocation(
+                    new_ptr,
+                    new_layout,
+                    AllocOp::Realloc {
+                        moved: true,
+                        old_size,
+                    },
+                );
+                unsafe { self.dealloc(ptr, layout) };
+            }
+            return new_ptr;
+        }
+
+        if !self.is_external_allocation() {
+            let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+            if !new_ptr.is_null() && in_bookkeeping() {
+                stats::record_overhead_dealloc(layout.size());
+                stats::record_overhead_alloc(new_size);
+            }
+            return new_ptr;
+        }
+
+        // the table stays locked across the reallocation: once the old block is freed, another thread may get
+        // and trace the same address, while if it fails, the old block is still live and stays traced as it was
+        let mut lock = self.lock_for_trace();
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            self.trace_locked(ptr, layout, AllocOp::Dealloc, lock.as_mut());
+            if is_enabled() {
+                let op = AllocOp::Realloc {
+                    moved: new_ptr != ptr,
+                    old_size: self.traced_size(layout),
+                };
+                self.trace_locked(new_ptr, new_layout, op, lock.as_mut());
+            }
+        }
+        drop(lock);
+        report::check_auto_report(stats::allocated());
+
+        new_ptr
+    }
+
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        if !ptr.is_null() && self.is_external_allocation() {
-            self.trace(layout, AllocOp::Dealloc);
+        let release = if layout.size() == 0 {
+            self.record_zero_size_event();
+            Some((ptr, layout))
+        } else if !ptr.is_null() && self.is_external_allocation() {
+            // the memory may be held back in the quarantine, releasing an older pointer instead
+            self.trace(ptr, layout, AllocOp::Dealloc)
+        } else {
+            if !ptr.is_null() && in_bookkeeping() {
+                stats::record_overhead_dealloc(layout.size());
+            }
+            Some((ptr, layout))
+        };
+        if let Some((ptr, layout)) = release {
+            unsafe { System.dealloc(ptr, layout) };
         }
-        unsafe { System.dealloc(ptr, layout) };
     }
 }
 
@@ -168,6 +1321,7 @@ unsafe impl GlobalAlloc for LeaktracerAllocator {
 mod test {
 
     use super::*;
+    use crate::test_utils::fake_ptr;
 
     #[test]
     fn test_should_tell_if_external_allocation() {
@@ -189,19 +1343,688 @@ mod test {
 
         let allocator = LeaktracerAllocator::init();
         let layout = Layout::from_size_align(1024, 8).unwrap();
-        allocator.trace(layout, AllocOp::Alloc);
+        allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
         assert_eq!(allocator.allocated(), 1024);
     }
 
+    #[test]
+    fn test_should_compute_alloc_rate() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(1024, 8).unwrap();
+        allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(allocator.alloc_rate() > 0.0);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_count_realloc_moves() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(16, 8).unwrap();
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            // a neighbour block, so the first one can't grow in place
+            let neighbour = allocator.alloc(layout);
+            let grown = allocator.realloc(ptr, layout, 1 << 20);
+            assert!(!grown.is_null());
+
+            // assert once the lock is released, so a failure doesn't poison the table
+            let (moves, inplace) = with_symbol_table(|table| {
+                table
+                    .allocation_symbol(grown as usize)
+                    .and_then(|name| table.get(name))
+                    .map(|symbol| (symbol.realloc_moves(), symbol.realloc_inplace()))
+            })
+            .unwrap()
+            .expect("reallocated block should be traced");
+            if grown != ptr {
+                assert_eq!((moves, inplace), (1, 0));
+            } else {
+                assert_eq!((moves, inplace), (0, 1));
+            }
+
+            allocator.dealloc(grown, Layout::from_size_align(1 << 20, 8).unwrap());
+            allocator.dealloc(neighbour, layout);
+        }
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_count_alignment_classes() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let ptrs: Vec<_> = [8, 16, 32, 64, 128, 4096, 4096, 8192]
+            .into_iter()
+            .map(|align| {
+                let ptr = fake_ptr();
+                let layout = Layout::from_size_align(64, align).unwrap();
+                allocator.trace(ptr, layout, AllocOp::Alloc);
+                ptr
+            })
+            .collect();
+
+        let stats = with_symbol_table(|table| {
+            let name = table.allocation_symbol(ptrs[0] as usize).unwrap();
+            assert!(
+                ptrs.iter()
+                    .all(|ptr| table.allocation_symbol(*ptr as usize) == Some(name))
+            );
+            table.get(name).unwrap().alignment_stats()
+        })
+        .unwrap();
+        assert_eq!(
+            stats,
+            crate::AlignmentStats {
+                natural: 2,
+                align_32: 1,
+                align_64: 1,
+                page: 2,
+                other: 2,
+            }
+        );
+        assert_eq!(stats.over_aligned(), 5);
+    }
+
+    #[test]
+    fn test_should_invoke_alloc_callbacks() {
+        thread_local! {
+            static CALLS: std::cell::RefCell<Vec<(&'static str, usize, &'static str)>> =
+                const { std::cell::RefCell::new(Vec::new()) };
+        }
+        fn record(kind: &'static str, size: usize, symbol: &'static str) {
+            CALLS.with_borrow_mut(|calls| calls.push((kind, size, symbol)));
+        }
+
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init()
+            .on_alloc(|size, symbol| record("alloc", size, symbol))
+            .on_large_alloc(4096, |size, symbol| record("large", size, symbol))
+            .on_dealloc(|size, symbol| record("dealloc", size, symbol));
+        let small = fake_ptr();
+        let large = fake_ptr();
+        allocator.trace(
+            small,
+            Layout::from_size_align(64, 8).unwrap(),
+            AllocOp::Alloc,
+        );
+        allocator.trace(
+            large,
+            Layout::from_size_align(8192, 8).unwrap(),
+            AllocOp::Alloc,
+        );
+        let symbol = with_symbol_table(|table| table.allocation_symbol(small as usize))
+            .unwrap()
+            .unwrap();
+        allocator.trace(
+            small,
+            Layout::from_size_align(64, 8).unwrap(),
+            AllocOp::Dealloc,
+        );
+        allocator.trace(
+            large,
+            Layout::from_size_align(8192, 8).unwrap(),
+            AllocOp::Dealloc,
+        );
+
+        assert_eq!(
+            CALLS.take(),
+            [
+                ("alloc", 64, symbol),
+                ("alloc", 8192, symbol),
+                ("large", 8192, symbol),
+                ("dealloc", 64, symbol),
+                ("dealloc", 8192, symbol),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_restore_thread_flags_after_panic() {
+        init_symbol_table(&["leaktracer"]);
+        let result = std::panic::catch_unwind(|| {
+            let _holds_table = FlagGuard::set(&HOLDS_TABLE, true);
+            bookkeeping(|| panic!("test panic"));
+        });
+        assert!(result.is_err());
+
+        assert!(!HOLDS_TABLE.with(Cell::get));
+        assert!(!IN_ALLOC.with(Cell::get));
+        assert!(!in_bookkeeping());
+        assert!(with_symbol_table(|_| ()).is_ok());
+    }
+
+    #[test]
+    fn test_should_fail_to_lock_table_from_callback() {
+        thread_local! {
+            static LOCKED: Cell<Option<LockResult<()>>> = const { Cell::new(None) };
+        }
+
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init()
+            .on_alloc(|_, _| LOCKED.set(Some(with_symbol_table(|_| ()))));
+        let ptr = fake_ptr();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        assert_eq!(LOCKED.take(), Some(Err(TableError::WouldDeadlock)));
+        assert_eq!(
+            with_symbol_table(|_| with_symbol_table(|_| ())).unwrap(),
+            Err(TableError::WouldDeadlock)
+        );
+        // the reporters tell it apart from a poisoned lock
+        let err = with_symbol_table(|_| crate::write_report(&mut Vec::new()))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Deadlock);
+        assert_eq!(err.to_string(), TableError::WouldDeadlock.to_string());
+        assert_eq!(
+            with_symbol_table(|_| crate::snapshot().map(|_| ())).unwrap(),
+            Err(TableError::WouldDeadlock)
+        );
+        // the lock is released afterwards
+        assert!(with_symbol_table(|_| ()).is_ok());
+        assert!(reserve(0).is_ok());
+    }
+
+    #[test]
+    fn test_should_not_trace_zero_size_allocations() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let zero = Layout::from_size_align(0, 1).unwrap();
+        let events = stats::zero_size_events();
+        let thread_allocations = stats::thread_allocations();
+        unsafe {
+            let ptr = allocator.alloc(zero);
+            let zeroed = allocator.alloc_zeroed(zero);
+            for ptr in [ptr, zeroed] {
+                let symbol =
+                    with_symbol_table(|table| table.allocation_symbol(ptr as usize)).unwrap();
+                assert_eq!(symbol, None);
+            }
+            allocator.dealloc(ptr, zero);
+            allocator.dealloc(zeroed, zero);
+        }
+
+        assert_eq!(stats::thread_allocations(), thread_allocations);
+        assert!(stats::zero_size_events() >= events + 4);
+    }
+
+    #[test]
+    fn test_should_not_trace_realloc_from_zero() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let zero = Layout::from_size_align(0, 8).unwrap();
+        let events = stats::zero_size_events();
+        unsafe {
+            let traced = |ptr: *mut u8| {
+                with_symbol_table(|table| table.allocation_symbol(ptr as usize).is_some()).unwrap()
+            };
+            // the zero-sized block is not traced
+            let empty = allocator.alloc(zero);
+            assert!(!empty.is_null());
+            assert!(!traced(empty));
+            let thread_allocations = stats::thread_allocations();
+
+            // growing it is a plain allocation
+            let grown = allocator.realloc(empty, zero, 64);
+            assert!(!grown.is_null());
+            assert!(traced(grown));
+            assert_eq!(stats::thread_allocations(), thread_allocations + 1);
+
+            allocator.dealloc(grown, layout);
+        }
+
+        assert!(stats::zero_size_events() >= events + 2);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn test_should_keep_block_of_realloc_to_zero() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            // the block is left untouched, and stays traced
+            assert_eq!(allocator.realloc(ptr, layout, 0), ptr);
+            assert!(
+                with_symbol_table(|table| table.allocation_symbol(ptr as usize).is_some()).unwrap()
+            );
+
+            allocator.dealloc(ptr, layout);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "realloc called with a zero new size")]
+    fn test_should_reject_realloc_to_zero() {
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            allocator.realloc(ptr, layout, 0);
+        }
+    }
+
     #[test]
     fn test_should_trace_deallocations() {
         init_symbol_table(&["leaktracer"]);
 
         let allocator = LeaktracerAllocator::init();
         let layout = Layout::from_size_align(1024, 8).unwrap();
-        allocator.trace(layout, AllocOp::Alloc);
+        let ptr = fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
         assert_eq!(allocator.allocated(), 1024);
-        allocator.trace(layout, AllocOp::Dealloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+        assert_eq!(allocator.allocated(), 0);
+    }
+
+    #[test]
+    fn test_should_trace_padded_size() {
+        init_symbol_table(&["leaktracer"]);
+
+        let layout = Layout::from_size_align(100, 64).unwrap();
+
+        let allocator = LeaktracerAllocator::init();
+        allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
+        assert_eq!(allocator.allocated(), 100);
+
+        let allocator = LeaktracerAllocator::init().with_padded_size(true);
+        let ptr = fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        assert_eq!(allocator.allocated(), 128);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
         assert_eq!(allocator.allocated(), 0);
     }
+
+    #[test]
+    fn test_should_reserve_symbol_table() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        allocator.trace(
+            fake_ptr(),
+            Layout::from_size_align(8, 8).unwrap(),
+            AllocOp::Alloc,
+        );
+        let symbols = with_symbol_table(|table| table.iter().count()).unwrap();
+
+        reserve(4096).expect("failed to reserve");
+        assert!(with_symbol_table(|table| table.capacity()).unwrap() >= symbols + 4096);
+        assert!(with_symbol_table(|table| table.iter().count()).unwrap() >= symbols);
+    }
+
+    #[test]
+    fn test_should_trace_size_classes() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_size_classes([16, 32, 64]);
+        for size in [15, 16, 31, 32, 64] {
+            allocator.trace(
+                fake_ptr(),
+                Layout::from_size_align(size, 1).unwrap(),
+                AllocOp::Alloc,
+            );
+        }
+        let layout = Layout::from_size_align(128, 1).unwrap();
+        let ptr = fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        let [small, medium, large, huge] = allocator.size_class_stats();
+        assert_eq!(small.class, SizeClass::Small);
+        assert_eq!(small.live_bytes, 15);
+        assert_eq!(small.allocations, 1);
+        assert_eq!(medium.live_bytes, 16 + 31);
+        assert_eq!(medium.allocations, 2);
+        assert_eq!(large.live_bytes, 32);
+        assert_eq!(large.allocations, 1);
+        assert_eq!(huge.live_bytes, 64);
+        assert_eq!(huge.allocations, 2);
+        assert_eq!(huge.deallocations, 1);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_list_live_allocations() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let freed = fake_ptr();
+        let leaked = fake_ptr();
+        allocator.trace(freed, layout, AllocOp::Alloc);
+        allocator.trace(leaked, layout, AllocOp::Alloc);
+        allocator.trace(freed, layout, AllocOp::Dealloc);
+
+        // on Windows the call stacks are resolved by a helper thread
+        #[cfg(windows)]
+        assert!(crate::symbols::wait_resolved(
+            std::time::Duration::from_secs(10)
+        ));
+        let live = live_allocations().expect("failed to list live allocations");
+        assert!(!live.iter().any(|(ptr, _, _)| *ptr == freed as usize));
+        let (_, size, symbol) = live
+            .iter()
+            .find(|(ptr, _, _)| *ptr == leaked as usize)
+            .expect("allocation should be live");
+        assert_eq!(*size, 64);
+        assert!(symbol.starts_with("leaktracer::alloc::test"));
+    }
+
+    #[test]
+    fn test_should_detect_size_mismatch_in_strict_mode() {
+        static VIOLATIONS: AtomicUsize = AtomicUsize::new(0);
+
+        init_symbol_table(&["leaktracer"]);
+        crate::on_strict_violation(|_| {
+            VIOLATIONS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        });
+
+        let allocator = LeaktracerAllocator::init().with_strict(true);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let mismatched = Layout::from_size_align(32, 8).unwrap();
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            allocator.dealloc(ptr, mismatched);
+        }
+
+        assert_eq!(allocator.size_mismatches(), 1);
+        assert_eq!(allocator.double_frees(), 0);
+        assert!(VIOLATIONS.load(std::sync::atomic::Ordering::Relaxed) >= 1);
+    }
+
+    #[test]
+    fn test_should_detect_double_free_in_strict_mode() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_strict(true);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        assert_eq!(allocator.double_frees(), 1);
+        assert_eq!(allocator.size_mismatches(), 0);
+        assert_eq!(allocator.allocated(), 0);
+    }
+
+    #[test]
+    fn test_should_detect_double_free_in_quarantine() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_quarantine(2);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let (ptr, other) = (fake_ptr(), fake_ptr());
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        allocator.trace(other, layout, AllocOp::Alloc);
+        let symbol = with_symbol_table(|table| table.allocation_symbol(ptr as usize))
+            .unwrap()
+            .expect("pointer should be registered");
+        let allocated =
+            || with_symbol_table(|table| table.get(symbol).map(|s| s.allocated())).unwrap();
+
+        // the memory is held back, so the freed address can't be reused
+        assert_eq!(allocator.trace(ptr, layout, AllocOp::Dealloc), None);
+        let after_free = allocated();
+        assert_eq!(allocator.trace(ptr, layout, AllocOp::Dealloc), None);
+
+        assert_eq!(allocator.double_frees(), 1);
+        assert_eq!(allocator.allocated(), 64);
+        assert_eq!(allocated(), after_free);
+        #[cfg(stack_capture)]
+        assert_eq!(after_free, Some(64));
+
+        // the oldest quarantined pointer is released once the quarantine is full
+        allocator.trace(other, layout, AllocOp::Dealloc);
+        let third = fake_ptr();
+        allocator.trace(third, layout, AllocOp::Alloc);
+        assert_eq!(
+            allocator.trace(third, layout, AllocOp::Dealloc),
+            Some((ptr, layout))
+        );
+        assert_eq!(allocator.allocated(), 0);
+    }
+
+    #[test]
+    fn test_should_not_check_untracked_pointers_in_strict_mode() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_strict(true);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        allocator.trace(fake_ptr(), layout, AllocOp::Dealloc);
+
+        assert_eq!(allocator.double_frees(), 0);
+        assert_eq!(allocator.size_mismatches(), 0);
+    }
+
+    #[test]
+    fn test_should_scale_sampled_allocations() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_sampling(Sampling::Fixed(4));
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptrs: Vec<_> = (0..8).map(|_| fake_ptr()).collect();
+        for ptr in &ptrs {
+            allocator.trace(*ptr, layout, AllocOp::Alloc);
+        }
+        // 2 sampled allocations, each one scaled by 4
+        assert_eq!(allocator.allocated(), 800);
+        let [small, ..] = allocator.size_class_stats();
+        assert_eq!(small.allocations, 8);
+
+        for ptr in &ptrs {
+            allocator.trace(*ptr, layout, AllocOp::Dealloc);
+        }
+        assert_eq!(allocator.allocated(), 0);
+        // the skipped allocations are not untracked
+        assert_eq!(allocator.untracked_frees(), 0);
+    }
+
+    #[test]
+    fn test_should_trace_every_allocation_with_full_fidelity() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_sampling(Sampling::Fixed(4));
+        let layout = Layout::from_size_align(100, 8).unwrap();
+        let ptrs: Vec<_> = (0..8).map(|_| fake_ptr()).collect();
+        sampling::with_full_fidelity(|| {
+            for ptr in &ptrs[..4] {
+                allocator.trace(*ptr, layout, AllocOp::Alloc);
+            }
+        });
+        // every allocation traced, unscaled
+        assert_eq!(allocator.allocated(), 400);
+
+        // sampled again afterwards
+        for ptr in &ptrs[4..] {
+            allocator.trace(*ptr, layout, AllocOp::Alloc);
+        }
+        assert_eq!(allocator.allocated(), 800);
+
+        for ptr in &ptrs {
+            allocator.trace(*ptr, layout, AllocOp::Dealloc);
+        }
+        assert_eq!(allocator.allocated(), 0);
+    }
+
+    #[test]
+    fn test_should_scale_size_class_sampling() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init()
+            .with_size_classes([1024, 64 * 1024, 1024 * 1024])
+            .with_size_class_sampling([1000, 10, 1, 1]);
+        let mut expected = 0;
+        for (count, size) in [(10_000, 100), (100, 2048), (3, 128 * 1024)] {
+            for _ in 0..count {
+                allocator.trace(
+                    fake_ptr(),
+                    Layout::from_size_align(size, 8).unwrap(),
+                    AllocOp::Alloc,
+                );
+            }
+            expected += count * size;
+        }
+
+        assert_eq!(allocator.allocated(), expected);
+        let [small, medium, large, _] = allocator.size_class_stats();
+        assert_eq!(small.allocations, 10_000);
+        assert_eq!(medium.allocations, 100);
+        assert_eq!(large.allocations, 3);
+    }
+
+    #[test]
+    fn test_should_converge_sampled_totals() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_size_class_sampling([100, 1, 1, 1]);
+        // deterministic pseudo-random sizes
+        let mut seed: u64 = 42;
+        let mut actual = 0;
+        let mut ptrs = Vec::new();
+        for _ in 0..20_000 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let size = 8 + (seed >> 33) as usize % 1000;
+            let ptr = fake_ptr();
+            allocator.trace(
+                ptr,
+                Layout::from_size_align(size, 8).unwrap(),
+                AllocOp::Alloc,
+            );
+            ptrs.push((ptr, size));
+            actual += size;
+        }
+
+        let estimated = allocator.allocated();
+        assert!(
+            estimated.abs_diff(actual) < actual / 10,
+            "{estimated} vs {actual}"
+        );
+
+        // the deallocations are scaled with the weight they were recorded with
+        for (ptr, size) in ptrs {
+            allocator.trace(
+                ptr,
+                Layout::from_size_align(size, 8).unwrap(),
+                AllocOp::Dealloc,
+            );
+        }
+        assert_eq!(allocator.allocated(), 0);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_match_global_allocated() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        for size in [16, 100, 4096] {
+            allocator.trace(
+                fake_ptr(),
+                Layout::from_size_align(size, 8).unwrap(),
+                AllocOp::Alloc,
+            );
+        }
+        assert!(sum_symbol_allocated().unwrap() >= 16 + 100 + 4096);
+
+        // the counters are updated under the lock, so they match exactly when read under the lock
+        let (sum, global) =
+            with_symbol_table(|table| (table.total_allocated(), stats::global_allocated()))
+                .unwrap();
+        assert_eq!(sum, global);
+    }
+
+    #[test]
+    fn test_should_count_untracked_frees() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(256, 8).unwrap();
+        let tracked = fake_ptr();
+        allocator.trace(tracked, layout, AllocOp::Alloc);
+        // free a pointer which was allocated before tracing started
+        allocator.trace(fake_ptr(), layout, AllocOp::Dealloc);
+
+        assert_eq!(allocator.untracked_frees(), 1);
+        assert_eq!(allocator.untracked_freed_bytes(), 256);
+        assert_eq!(allocator.allocated(), 256);
+
+        allocator.trace(tracked, layout, AllocOp::Dealloc);
+        assert_eq!(allocator.untracked_frees(), 1);
+        assert_eq!(allocator.allocated(), 0);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_attribute_allocations_before_startup_complete() {
+        init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init().with_startup_bucket(true);
+        let startup_allocated = || {
+            with_symbol_table(|table| {
+                table
+                    .get("<startup>")
+                    .map_or(0, |symbol| symbol.allocated())
+            })
+            .unwrap()
+        };
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+
+        allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
+        assert_eq!(startup_allocated(), 4096);
+
+        mark_startup_complete();
+        allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
+        assert_eq!(startup_allocated(), 4096);
+        assert_eq!(allocator.allocated(), 8192);
+    }
+
+    #[test]
+    fn test_should_not_init_twice() {
+        init_symbol_table(&["leaktracer"]);
+        assert!(!init_symbol_table(&["other"]));
+        assert!(
+            !with_symbol_table(SymbolTable::modules)
+                .unwrap()
+                .contains(&"other")
+        );
+    }
+
+    #[test]
+    fn test_should_add_traced_module() {
+        init_symbol_table(&["leaktracer"]);
+
+        // the module list is global, so the reconfiguration is tested here, before adding the module
+        reconfigure(&["leaktracer", "leaktracer_test_app"], false).unwrap();
+        assert_eq!(traced_modules(), ["leaktracer", "leaktracer_test_app"]);
+        reconfigure(&["leaktracer"], false).unwrap();
+        assert_eq!(
+            &*with_symbol_table(SymbolTable::modules).unwrap(),
+            &["leaktracer"]
+        );
+
+        add_traced_module(String::from("leaktracer_test_plugin")).unwrap();
+        let modules = with_symbol_table(SymbolTable::modules).unwrap();
+        assert!(modules.contains(&"leaktracer"));
+        assert!(modules.contains(&"leaktracer_test_plugin"));
+        assert_eq!(traced_modules(), &*modules);
+    }
 }
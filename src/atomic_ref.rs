@@ -0,0 +1,74 @@
+//! Lock-free storage of the `'static` settings read on the allocation path.
+
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+/// A `&'static T`, or none, which can be replaced and read without taking a lock.
+///
+/// The settings read by the allocator on each traced operation can't be behind a lock: it would be one more lock
+/// to take before forking (see [`crate::before_fork`]), and one more point of contention.
+/// A reference to an unsized `T` (e.g. `&'static str`) is a fat pointer, too wide for an atomic,
+/// so each [`AtomicRef::store`] leaks a box holding it: it's meant for settings replaced a handful of times.
+pub(crate) struct AtomicRef<T: ?Sized + 'static> {
+    ptr: AtomicPtr<&'static T>,
+}
+
+impl<T: ?Sized + 'static> AtomicRef<T> {
+    /// Creates a new empty [`AtomicRef`].
+    pub(crate) const fn new() -> Self {
+        Self {
+            ptr: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+
+    /// Replaces the reference with `value`, or clears it with `None`.
+    ///
+    /// The box holding the reference is allocated outside of the tracing.
+    pub(crate) fn store(&self, value: Option<&'static T>) {
+        let ptr = match value {
+            Some(value) => crate::alloc::bookkeeping(|| Box::into_raw(Box::new(value))),
+            None => std::ptr::null_mut(),
+        };
+        // the previous box is leaked, since another thread may still be reading it
+        self.ptr.store(ptr, Ordering::Release);
+    }
+
+    /// Returns the reference, if any.
+    pub(crate) fn load(&self) -> Option<&'static T> {
+        let ptr = self.ptr.load(Ordering::Acquire);
+        // SAFETY: the pointer is either null or a leaked box, which is never freed
+        unsafe { ptr.as_ref() }.copied()
+    }
+}
+
+impl<T: 'static> AtomicRef<T> {
+    /// Replaces the reference with one to `value`, leaked outside of the tracing, or clears it with `None`.
+    pub(crate) fn store_value(&self, value: Option<T>) {
+        let value = value.map(|value| &*crate::alloc::bookkeeping(|| Box::leak(Box::new(value))));
+        self.store(value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_store_and_load_reference() {
+        static NAME: AtomicRef<str> = AtomicRef::new();
+        assert_eq!(NAME.load(), None);
+
+        NAME.store(Some("test::sym"));
+        assert_eq!(NAME.load(), Some("test::sym"));
+        NAME.store(Some("test::other"));
+        assert_eq!(NAME.load(), Some("test::other"));
+        NAME.store(None);
+        assert_eq!(NAME.load(), None);
+
+        static LIMIT: AtomicRef<usize> = AtomicRef::new();
+        LIMIT.store_value(Some(64));
+        assert_eq!(LIMIT.load(), Some(&64));
+        LIMIT.store_value(None);
+        assert_eq!(LIMIT.load(), None);
+    }
+}
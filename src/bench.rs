@@ -0,0 +1,233 @@
+//! Allocation counts as a benchmark metric, next to the time.
+//!
+//! The allocations are counted exactly by each thread, regardless of the sampling (see [`crate::Sampling`]),
+//! so [`crate::LeaktracerAllocator`] must be the global allocator. The allocations made while tracing is paused
+//! (see [`crate::set_enabled`]) are not counted.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::stats;
+
+/// The allocations made by a benchmarked routine, see [`measured`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    /// Number of allocations, including the reallocations.
+    pub allocations: usize,
+    /// Allocated bytes, including the ones freed meanwhile.
+    pub bytes: usize,
+}
+
+impl AllocStats {
+    /// Returns the average number of allocations of each of the `iters` iterations, `0.0` without iterations.
+    pub fn allocations_per_iter(&self, iters: u64) -> f64 {
+        if iters == 0 {
+            return 0.0;
+        }
+        self.allocations as f64 / iters as f64
+    }
+
+    /// Returns the average allocated bytes of each of the `iters` iterations, `0.0` without iterations.
+    pub fn bytes_per_iter(&self, iters: u64) -> f64 {
+        if iters == 0 {
+            return 0.0;
+        }
+        self.bytes as f64 / iters as f64
+    }
+}
+
+/// Runs `f` `iters` times on the current thread, returning the elapsed time and the allocations it made.
+///
+/// Only the allocations made by the current thread are counted, so the ones made by the other threads meanwhile,
+/// or by the threads spawned by `f`, are not.
+///
+/// ```rust
+/// #[global_allocator]
+/// static ALLOCATOR: leaktracer::LeaktracerAllocator = leaktracer::LeaktracerAllocator::init();
+///
+/// let (elapsed, allocs) = leaktracer::bench::measured(100, || vec![0u8; 64]);
+/// println!("{:?} and {} allocations per iteration", elapsed / 100, allocs.allocations_per_iter(100));
+/// ```
+pub fn measured<F, R>(iters: u64, mut f: F) -> (Duration, AllocStats)
+where
+    F: FnMut() -> R,
+{
+    let allocations = stats::thread_allocations();
+    let bytes = stats::thread_allocated_bytes();
+    let start = Instant::now();
+    for _ in 0..iters {
+        black_box(f());
+    }
+    let elapsed = start.elapsed();
+
+    (
+        elapsed,
+        AllocStats {
+            allocations: stats::thread_allocations() - allocations,
+            bytes: stats::thread_allocated_bytes().wrapping_sub(bytes),
+        },
+    )
+}
+
+/// A [criterion](https://docs.rs/criterion) measurement of the allocations made by each iteration,
+/// in place of the wall time.
+///
+/// Criterion measures a single quantity per group, so the time and the allocations are measured by two groups
+/// (or two [`criterion::Criterion`] instances) running the same routines. Criterion fails to analyze a routine
+/// which never allocates, since all its measurements are zero: check those with [`measured`] or
+/// [`crate::assert_no_alloc`] instead.
+///
+///
+/// ```rust,no_run
+/// use criterion::{Criterion, criterion_group, criterion_main};
+/// use leaktracer::bench::Allocations;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: leaktracer::LeaktracerAllocator = leaktracer::LeaktracerAllocator::init();
+///
+/// fn bench_parse(c: &mut Criterion<Allocations>) {
+///     c.bench_function("parse", |b| b.iter(|| "42".parse::<u32>()));
+/// }
+///
+/// criterion_group! {
+///     name = allocations;
+///     config = Criterion::default().with_measurement(Allocations);
+///     targets = bench_parse
+/// }
+/// criterion_main!(allocations);
+/// ```
+#[cfg(feature = "criterion")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Allocations;
+
+#[cfg(feature = "criterion")]
+impl criterion::measurement::Measurement for Allocations {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> Self::Intermediate {
+        stats::thread_allocations()
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        stats::thread_allocations() - start
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn criterion::measurement::ValueFormatter {
+        &AllocationsFormatter
+    }
+}
+
+/// Formats the values measured by [`Allocations`].
+#[cfg(feature = "criterion")]
+struct AllocationsFormatter;
+
+#[cfg(feature = "criterion")]
+impl criterion::measurement::ValueFormatter for AllocationsFormatter {
+    fn scale_values(&self, _typical_value: f64, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        throughput: &criterion::Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        let (per, unit) = match throughput {
+            criterion::Throughput::Bytes(bytes) | criterion::Throughput::BytesDecimal(bytes) => {
+                (*bytes, "allocs/byte")
+            }
+            criterion::Throughput::Elements(elements) => (*elements, "allocs/elem"),
+        };
+        for value in values {
+            *value /= per.max(1) as f64;
+        }
+        unit
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "allocs"
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+
+    #[test]
+    fn test_should_measure_zero_allocations() {
+        let (_, allocs) = measured(100, || (0..10u64).sum::<u64>());
+        assert_eq!(allocs, AllocStats::default());
+        assert_eq!(allocs.allocations_per_iter(100), 0.0);
+    }
+
+    #[test]
+    fn test_should_measure_allocations() {
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+
+        let (_, allocs) = measured(10, || {
+            let ptr = fake_ptr();
+            allocator.trace(ptr, layout, AllocOp::Alloc);
+            allocator.trace(ptr, layout, AllocOp::Dealloc);
+        });
+        assert_eq!(
+            allocs,
+            AllocStats {
+                allocations: 10,
+                bytes: 640
+            }
+        );
+        assert_eq!(allocs.allocations_per_iter(10), 1.0);
+        assert_eq!(allocs.bytes_per_iter(10), 64.0);
+        assert_eq!(allocs.bytes_per_iter(0), 0.0);
+    }
+
+    #[test]
+    #[cfg(feature = "criterion")]
+    fn test_should_measure_allocations_with_criterion() {
+        use criterion::measurement::Measurement;
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+
+        let start = Allocations.start();
+        assert_eq!(Allocations.end(start), 0);
+
+        let start = Allocations.start();
+        let ptr = fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+        let value = Allocations.end(start);
+        assert_eq!(value, 1);
+        assert_eq!(Allocations.add(&value, &Allocations.zero()), 1);
+
+        let mut values = [10.0, 20.0];
+        let unit = Allocations.formatter().scale_throughputs(
+            15.0,
+            &criterion::Throughput::Elements(10),
+            &mut values,
+        );
+        assert_eq!(unit, "allocs/elem");
+        assert_eq!(values, [1.0, 2.0]);
+    }
+}
@@ -0,0 +1,536 @@
+//! Memory budgets of the symbols, read from a file, to gate the live bytes in CI.
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use toml::de::DeValue;
+
+/// Name of the default entry of a budget file, applying to the symbols not matched by any other entry.
+const WILDCARD: &str = "*";
+
+/// The error of an entry of a budget file which is not a table of limits.
+const EXPECTED_TABLE: &str = "expected a table of limits, e.g. { max_allocated = 1024 }";
+
+/// The error of a limit of a budget file which is not a number of bytes.
+const EXPECTED_BYTES: &str = "expected a number of bytes";
+
+/// The limit of a [`BudgetViolation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetLimit {
+    /// The live bytes of the symbol ([`crate::Symbol::allocated`]).
+    Allocated,
+    /// The highest live bytes ever reached by the symbol ([`crate::Symbol::peak`]).
+    Peak,
+}
+
+/// A symbol exceeding a limit of its budget entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetViolation {
+    /// The symbol name.
+    pub symbol: &'static str,
+    /// The prefix of the budget entry the symbol matched, `*` for the default entry.
+    pub entry: String,
+    /// The exceeded limit.
+    pub limit: BudgetLimit,
+    /// The maximum bytes allowed by the entry.
+    pub max: usize,
+    /// The bytes of the symbol.
+    pub actual: usize,
+}
+
+/// The outcome of [`enforce_budget_file`] when the budget is not met.
+#[derive(Debug)]
+pub enum BudgetReport {
+    /// The budget file couldn't be read, or the symbol table lock couldn't be taken (see [`crate::TableError`]).
+    Io(io::Error),
+    /// The budget file is malformed.
+    Malformed {
+        /// The line of the error, starting from 1. In a JSON file, the errors of an entry are reported
+        /// at the line of its key.
+        line: usize,
+        /// What's wrong with the line.
+        message: String,
+    },
+    /// Some symbols exceed their budget.
+    Exceeded(Vec<BudgetViolation>),
+}
+
+impl fmt::Display for BudgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to check the memory budget: {err}"),
+            Self::Malformed { line, message } => {
+                write!(f, "malformed budget file at line {line}: {message}")
+            }
+            Self::Exceeded(violations) => {
+                write!(f, "{} symbols exceed their memory budget", violations.len())?;
+                for violation in violations {
+                    let limit = match violation.limit {
+                        BudgetLimit::Allocated => "max_allocated",
+                        BudgetLimit::Peak => "max_peak",
+                    };
+                    write!(
+                        f,
+                        "\n  {}: {limit} {} (\"{}\"), actual {}",
+                        violation.symbol, violation.max, violation.entry, violation.actual
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BudgetReport {}
+
+/// An entry of a budget file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BudgetEntry {
+    prefix: String,
+    max_allocated: Option<usize>,
+    max_peak: Option<usize>,
+}
+
+impl BudgetEntry {
+    /// Creates a new [`BudgetEntry`] for the symbols starting with `prefix`, without limits.
+    fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            max_allocated: None,
+            max_peak: None,
+        }
+    }
+
+    /// Sets the limit named `key` to `bytes`.
+    fn set(&mut self, key: &str, bytes: usize) -> Result<(), &'static str> {
+        match key {
+            "max_allocated" => self.max_allocated = Some(bytes),
+            "max_peak" => self.max_peak = Some(bytes),
+            _ => return Err("unknown limit, expected max_allocated or max_peak"),
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks the symbols of the global table against the budget file at `path`, e.g. at the end of an integration test.
+///
+/// The file is a TOML document whose top-level keys are the symbol prefixes, each with a table limiting
+/// the live bytes (`max_allocated`) and/or the peak (`max_peak`) of each symbol whose name starts with the prefix.
+/// A symbol is checked against the entry with the longest matching prefix, or against the `"*"` entry
+/// if no prefix matches. Prefixes containing `::` must be quoted, so the TOML parser doesn't split them:
+///
+/// ```toml
+/// # the cache may not exceed 64 MiB
+/// ["app::cache"]
+/// max_allocated = 67_108_864
+///
+/// "app::config" = { max_peak = 65_536 }
+///
+/// ["*"]
+/// max_allocated = 1_048_576
+/// max_peak = 4_194_304
+/// ```
+///
+/// A file with the `.json` extension is read as a JSON object instead, with the same entries:
+///
+/// ```json
+/// { "app::cache": { "max_allocated": 67108864 }, "*": { "max_allocated": 1048576 } }
+/// ```
+///
+/// Returns [`BudgetReport::Exceeded`] with all the violations if any symbol exceeds a limit, which can be printed
+/// before exiting with a nonzero code.
+pub fn enforce_budget_file(path: impl AsRef<Path>) -> Result<(), BudgetReport> {
+    let path = path.as_ref();
+    let budget = std::fs::read_to_string(path).map_err(BudgetReport::Io)?;
+    let entries = parse(path, &budget)?;
+    let violations = crate::with_symbol_table(|table| {
+        evaluate(
+            &entries,
+            table
+                .iter_sorted()
+                .into_iter()
+                .map(|(name, symbol)| (name, symbol.allocated(), symbol.peak())),
+        )
+    })
+    .map_err(|err| BudgetReport::Io(err.into()))?;
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(BudgetReport::Exceeded(violations))
+    }
+}
+
+/// Parses the entries of a budget file, in JSON if its extension is `.json`, in TOML otherwise.
+fn parse(path: &Path, budget: &str) -> Result<Vec<BudgetEntry>, BudgetReport> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        parse_json(budget)
+    } else {
+        parse_toml(budget)
+    }
+}
+
+/// Parses the entries of a TOML budget file, whose top-level keys are the symbol prefixes.
+fn parse_toml(budget: &str) -> Result<Vec<BudgetEntry>, BudgetReport> {
+    let malformed = |offset: usize, message: &str| BudgetReport::Malformed {
+        line: line_at(budget, offset),
+        message: message.to_string(),
+    };
+    let document = toml::de::DeTable::parse(budget)
+        .map_err(|err| malformed(err.span().map_or(0, |span| span.start), err.message()))?;
+
+    let mut entries = Vec::new();
+    for (prefix, limits) in document.get_ref() {
+        let Some(limits) = limits.get_ref().as_table() else {
+            return Err(malformed(limits.span().start, EXPECTED_TABLE));
+        };
+        let mut entry = BudgetEntry::new(prefix.get_ref());
+        for (key, value) in limits {
+            let bytes = match value.get_ref() {
+                DeValue::Integer(int) => usize::from_str_radix(int.as_str(), int.radix()).ok(),
+                _ => None,
+            }
+            .ok_or_else(|| malformed(value.span().start, EXPECTED_BYTES))?;
+            entry
+                .set(key.get_ref(), bytes)
+                .map_err(|message| malformed(key.span().start, message))?;
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Parses the entries of a JSON budget file, an object whose keys are the symbol prefixes.
+fn parse_json(budget: &str) -> Result<Vec<BudgetEntry>, BudgetReport> {
+    let malformed = |line: usize, message: &str| BudgetReport::Malformed {
+        line,
+        message: message.to_string(),
+    };
+    let document = serde_json::from_str::<serde_json::Value>(budget).map_err(|err| {
+        // the message of `serde_json` ends with the position, which is already reported
+        let message = err.to_string();
+        let message = message
+            .rsplit_once(" at line ")
+            .map_or(message.as_str(), |(message, _)| message);
+        malformed(err.line(), message)
+    })?;
+    let Some(document) = document.as_object() else {
+        return Err(malformed(
+            1,
+            "expected an object with an entry for each symbol prefix",
+        ));
+    };
+
+    let mut entries = Vec::new();
+    for (prefix, limits) in document {
+        // the values of `serde_json` have no position, so the errors are reported at the key of the entry
+        let key = serde_json::Value::from(prefix.as_str()).to_string();
+        let line = budget
+            .find(&key)
+            .map_or(1, |offset| line_at(budget, offset));
+        let Some(limits) = limits.as_object() else {
+            return Err(malformed(line, EXPECTED_TABLE));
+        };
+        let mut entry = BudgetEntry::new(prefix);
+        for (key, value) in limits {
+            let bytes = value
+                .as_u64()
+                .and_then(|bytes| usize::try_from(bytes).ok())
+                .ok_or_else(|| malformed(line, EXPECTED_BYTES))?;
+            entry
+                .set(key, bytes)
+                .map_err(|message| malformed(line, message))?;
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Returns the line of the byte `offset` of `text`, starting from 1.
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Returns the violations of the symbols, as `(name, allocated, peak)`, against the budget `entries`.
+fn evaluate<I>(entries: &[BudgetEntry], symbols: I) -> Vec<BudgetViolation>
+where
+    I: Iterator<Item = (&'static str, usize, usize)>,
+{
+    let wildcard = entries.iter().find(|entry| entry.prefix == WILDCARD);
+    let mut violations = Vec::new();
+    for (symbol, allocated, peak) in symbols {
+        let Some(entry) = entries
+            .iter()
+            .filter(|entry| entry.prefix != WILDCARD && symbol.starts_with(&entry.prefix))
+            .max_by_key(|entry| entry.prefix.len())
+            .or(wildcard)
+        else {
+            continue;
+        };
+
+        let limits = [
+            (BudgetLimit::Allocated, entry.max_allocated, allocated),
+            (BudgetLimit::Peak, entry.max_peak, peak),
+        ];
+        for (limit, max, actual) in limits {
+            if let Some(max) = max.filter(|max| actual > *max) {
+                violations.push(BudgetViolation {
+                    symbol,
+                    entry: entry.prefix.clone(),
+                    limit,
+                    max,
+                    actual,
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    const BUDGET: &str = r#"
+# the cache may not exceed 64 MiB
+["app::cache"]
+max_allocated = 67_108_864
+
+["app::cache::index"] # tighter
+max_allocated = 1024
+max_peak = 4096
+
+["*"]
+max_allocated = 1_048_576
+"#;
+
+    const BUDGET_JSON: &str = r#"{
+    "app::cache": { "max_allocated": 67108864 },
+    "app::cache::index": { "max_allocated": 1024, "max_peak": 4096 },
+    "*": { "max_allocated": 1048576 }
+}"#;
+
+    fn malformed(path: &str, budget: &str) -> Option<(usize, String)> {
+        match parse(Path::new(path), budget) {
+            Err(BudgetReport::Malformed { line, message }) => Some((line, message)),
+            _ => None,
+        }
+    }
+
+    fn malformed_line(budget: &str) -> Option<usize> {
+        malformed("budget.toml", budget).map(|(line, _)| line)
+    }
+
+    fn malformed_json_line(budget: &str) -> Option<usize> {
+        malformed("budget.json", budget).map(|(line, _)| line)
+    }
+
+    fn entry(prefix: &str, max_allocated: Option<usize>, max_peak: Option<usize>) -> BudgetEntry {
+        BudgetEntry {
+            prefix: prefix.to_string(),
+            max_allocated,
+            max_peak,
+        }
+    }
+
+    #[test]
+    fn test_should_parse_budget() {
+        let expected = vec![
+            entry("*", Some(1 << 20), None),
+            entry("app::cache", Some(64 << 20), None),
+            entry("app::cache::index", Some(1024), Some(4096)),
+        ];
+
+        assert_eq!(parse_toml(BUDGET).unwrap(), expected);
+        assert_eq!(parse_json(BUDGET_JSON).unwrap(), expected);
+        assert_eq!(
+            parse(Path::new("budget.json"), BUDGET_JSON).unwrap(),
+            expected
+        );
+        assert_eq!(parse(Path::new("budget.toml"), BUDGET).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_should_parse_any_toml_syntax() {
+        let budget = r#"
+"app::config" = { max_allocated = 512, max_peak = 1_024 }
+"app::main".max_allocated = 4096
+
+[cache]
+max_allocated = 0x400
+
+['app::index']
+max_peak = 2048
+"#;
+
+        assert_eq!(
+            parse_toml(budget).unwrap(),
+            vec![
+                entry("app::config", Some(512), Some(1024)),
+                entry("app::index", None, Some(2048)),
+                entry("app::main", Some(4096), None),
+                entry("cache", Some(1024), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_pass_budget() {
+        let entries = parse_toml(BUDGET).unwrap();
+        let symbols = [
+            ("app::cache::insert", 32 << 20, 64 << 20),
+            ("app::cache::index::build", 1024, 4096),
+            ("app::main", 1024, 2 << 20),
+        ];
+
+        assert!(evaluate(&entries, symbols.into_iter()).is_empty());
+    }
+
+    #[test]
+    fn test_should_report_budget_violations() {
+        let entries = parse_toml(BUDGET).unwrap();
+        let symbols = [
+            ("app::cache::insert", 65 << 20, 65 << 20),
+            ("app::cache::index::build", 2048, 8192),
+            ("app::main", 2 << 20, 2 << 20),
+        ];
+
+        let violations = evaluate(&entries, symbols.into_iter());
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| (violation.symbol, violation.entry.as_str(), violation.limit))
+                .collect::<Vec<_>>(),
+            [
+                ("app::cache::insert", "app::cache", BudgetLimit::Allocated),
+                (
+                    "app::cache::index::build",
+                    "app::cache::index",
+                    BudgetLimit::Allocated
+                ),
+                (
+                    "app::cache::index::build",
+                    "app::cache::index",
+                    BudgetLimit::Peak
+                ),
+                ("app::main", "*", BudgetLimit::Allocated),
+            ]
+        );
+        assert_eq!(violations[0].max, 64 << 20);
+        assert_eq!(violations[0].actual, 65 << 20);
+
+        let report = BudgetReport::Exceeded(violations).to_string();
+        assert!(report.starts_with("4 symbols exceed their memory budget\n"));
+        assert!(report.contains("\n  app::main: max_allocated 1048576 (\"*\"), actual 2097152"));
+    }
+
+    #[test]
+    fn test_should_not_check_symbols_without_entry() {
+        let entries = parse_toml("[\"app::cache\"]\nmax_allocated = 0").unwrap();
+
+        assert!(evaluate(&entries, [("app::main", 1 << 30, 1 << 30)].into_iter()).is_empty());
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_enforce_budget_file() {
+        use std::alloc::Layout;
+
+        use crate::LeaktracerAllocator;
+        use crate::alloc::AllocOp;
+        use crate::test_utils::fake_ptr;
+
+        crate::init_symbol_table(&["leaktracer"]);
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+        let ptr = fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("budget.toml");
+        let name = crate::caller_symbol(&["leaktracer"]);
+        std::fs::write(&path, format!("[\"{name}\"]\nmax_allocated = 4096\n")).unwrap();
+        let within = enforce_budget_file(&path);
+        std::fs::write(&path, format!("[\"{name}\"]\nmax_allocated = 4095\n")).unwrap();
+        let exceeded = enforce_budget_file(&path);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        assert!(within.is_ok());
+        let Err(BudgetReport::Exceeded(violations)) = exceeded else {
+            panic!("the budget should be exceeded");
+        };
+        assert_eq!(
+            violations,
+            [BudgetViolation {
+                symbol: name,
+                entry: name.to_string(),
+                limit: BudgetLimit::Allocated,
+                max: 4095,
+                actual: 4096,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_should_reject_malformed_budget() {
+        // a limit outside of an entry
+        assert_eq!(malformed_line("max_allocated = 1"), Some(1));
+        // an unquoted path, which is not a valid key
+        assert_eq!(malformed_line("[app::cache]"), Some(1));
+        // a dotted key, which makes a nested table instead of a prefix
+        assert_eq!(malformed_line("[app.cache]\nmax_allocated = 1"), Some(1));
+        assert_eq!(malformed_line("[\"app\"]\nmax_allocated = 1MiB"), Some(2));
+        assert_eq!(malformed_line("[\"app\"]\nmax_allocated = -1"), Some(2));
+        assert_eq!(malformed_line("[\"app\"]\nmax_allocated = \"1\""), Some(2));
+        assert_eq!(malformed_line("[\"app\"]\n\nmax_live = 1"), Some(3));
+        assert_eq!(malformed_line("[\"app\"]\nmax_allocated"), Some(2));
+        assert_eq!(malformed_line("[\"app\"]\n[\"app\"]"), Some(2));
+        assert_eq!(malformed_line("\"app\" = { max_allocated = 1,"), Some(1));
+        assert_eq!(
+            malformed("budget.toml", "[\"app\"]\nmax_live = 1"),
+            Some((
+                2,
+                "unknown limit, expected max_allocated or max_peak".to_string()
+            ))
+        );
+
+        assert_eq!(malformed_json_line("[\"app\"]"), Some(1));
+        assert_eq!(
+            malformed_json_line("{\n  \"app\": {\"max_allocated\": 1,}\n}"),
+            Some(2)
+        );
+        assert_eq!(malformed_json_line("{\n  \"app\": 1\n}"), Some(2));
+        assert_eq!(
+            malformed_json_line("{\n  \"*\": {},\n  \"app\": {\"max_allocated\": -1}\n}"),
+            Some(3)
+        );
+        assert_eq!(
+            malformed_json_line("{\n  \"app\": {\"max_allocated\": \"1MiB\"}\n}"),
+            Some(2)
+        );
+        assert_eq!(
+            malformed("budget.json", "{\"app\": {\"max_live\": 1}}"),
+            Some((
+                1,
+                "unknown limit, expected max_allocated or max_peak".to_string()
+            ))
+        );
+        assert_eq!(
+            malformed("budget.json", "{\"app\": {\"max_allocated\": 1}"),
+            Some((1, "EOF while parsing an object".to_string()))
+        );
+        assert!(matches!(
+            enforce_budget_file("/nonexistent/budget.toml"),
+            Err(BudgetReport::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+//! Named checkpoints of the live bytes, to measure simple global deltas without keeping snapshots around.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::alloc::bookkeeping;
+
+/// The live bytes at each checkpoint, by name.
+///
+/// This lock is independent from the symbol table lock, and it's never held while acquiring another lock.
+static CHECKPOINTS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+/// Stores the current live bytes (see [`crate::global_allocated`]) under the checkpoint `name`,
+/// replacing the previous checkpoint with the same name.
+///
+/// ```rust
+/// leaktracer::checkpoint("after_load");
+/// let config = vec![0u8; 1024];
+/// if let Some(delta) = leaktracer::since_checkpoint("after_load") {
+///     println!("{delta} bytes allocated since loading");
+/// }
+/// ```
+pub fn checkpoint(name: &str) {
+    store(name, crate::global_allocated());
+}
+
+/// Returns the signed difference between the current live bytes and the ones stored at the checkpoint `name`,
+/// or [`None`] if there's no such checkpoint.
+///
+/// The difference is negative if more bytes have been freed than allocated since the checkpoint.
+pub fn since_checkpoint(name: &str) -> Option<i64> {
+    delta(name, crate::global_allocated())
+}
+
+/// Stores `allocated` under the checkpoint `name`.
+fn store(name: &str, allocated: usize) {
+    // the checkpoints are tracer bookkeeping, so they don't move the live bytes they measure
+    bookkeeping(|| {
+        if let Ok(mut checkpoints) = CHECKPOINTS.lock() {
+            checkpoints
+                .get_or_insert_with(HashMap::new)
+                .insert(name.to_string(), allocated);
+        }
+    });
+}
+
+/// Returns the difference between `allocated` and the live bytes at the checkpoint `name`.
+fn delta(name: &str, allocated: usize) -> Option<i64> {
+    let checkpoint = CHECKPOINTS
+        .lock()
+        .ok()?
+        .as_ref()
+        .and_then(|checkpoints| checkpoints.get(name).copied())?;
+
+    Some(allocated as i64 - checkpoint as i64)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+
+    #[test]
+    fn test_should_return_delta_since_checkpoint() {
+        store("test:after_load", 1024);
+        assert_eq!(delta("test:after_load", 4096), Some(3072));
+        assert_eq!(delta("test:after_load", 0), Some(-1024));
+        assert_eq!(delta("test:missing", 4096), None);
+
+        // replaced by the latest checkpoint
+        store("test:after_load", 4096);
+        assert_eq!(delta("test:after_load", 4096), Some(0));
+    }
+
+    #[test]
+    fn test_should_measure_allocations_since_checkpoint() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(1 << 20, 8).unwrap();
+        let ptr = fake_ptr();
+        checkpoint("test:before_alloc");
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        let since = since_checkpoint("test:before_alloc");
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        // the other tests allocate concurrently, but far less than this
+        assert!(since.is_some_and(|since| since >= (1 << 19)));
+    }
+}
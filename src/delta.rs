@@ -0,0 +1,183 @@
+//! Per-symbol deltas since a generation marker, to measure a section of code without diffing snapshots by hand.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::alloc::LockResult;
+use crate::snapshot::{self, SymbolSnapshot, SymbolTableSnapshot};
+
+/// A marker of the symbol table at a given [`crate::generation`], taken by [`record_generation`].
+///
+/// It keeps a [`SymbolTableSnapshot`] of the table, which [`delta_since`] compares to the current one.
+#[derive(Debug, Clone)]
+pub struct Generation {
+    snapshot: SymbolTableSnapshot,
+}
+
+impl Generation {
+    /// Returns the [`crate::generation`] the marker was recorded at.
+    pub fn generation(&self) -> u64 {
+        self.snapshot.generation
+    }
+
+    /// Returns the snapshot of the symbol table taken with the marker.
+    pub fn snapshot(&self) -> &SymbolTableSnapshot {
+        &self.snapshot
+    }
+}
+
+/// The change of the stats of a symbol between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolDiff {
+    /// The symbol name.
+    pub name: &'static str,
+    /// The signed difference of the live bytes, negative if more bytes were freed than allocated.
+    pub allocated: i64,
+    /// The signed difference of the live allocations.
+    pub count: i64,
+    /// The bytes allocated in between, including the ones already freed.
+    pub total_allocated: usize,
+    /// The allocations made in between, including the ones already freed.
+    pub total_count: usize,
+}
+
+/// Records a [`Generation`] marker, to compute the per-symbol deltas since then with [`delta_since`].
+///
+/// ```rust
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// let marker = leaktracer::record_generation().expect("Failed to record generation");
+/// let cache = vec![0u8; 1024];
+/// for diff in leaktracer::delta_since(&marker).expect("Failed to compute delta") {
+///     println!("{}: {:+} bytes", diff.name, diff.allocated);
+/// }
+/// ```
+pub fn record_generation() -> LockResult<Generation> {
+    snapshot::snapshot().map(|snapshot| Generation { snapshot })
+}
+
+/// Returns the per-symbol deltas from the `marker` to now, sorted by name.
+///
+/// The symbols which didn't change since the marker are omitted. The ones which disappeared, e.g. after
+/// a [`crate::reset`], are reported with their live bytes and count as negative deltas.
+pub fn delta_since(marker: &Generation) -> LockResult<Vec<SymbolDiff>> {
+    snapshot::snapshot().map(|now| diff(&marker.snapshot, &now))
+}
+
+/// Returns the per-symbol deltas from `before` to `after`, sorted by name, omitting the unchanged symbols.
+pub(crate) fn diff(before: &SymbolTableSnapshot, after: &SymbolTableSnapshot) -> Vec<SymbolDiff> {
+    let previous: HashMap<&'static str, &SymbolSnapshot> = before
+        .symbols
+        .iter()
+        .map(|symbol| (symbol.name, symbol))
+        .collect();
+
+    let mut diffs: Vec<SymbolDiff> = after
+        .symbols
+        .iter()
+        .map(|symbol| match previous.get(symbol.name) {
+            Some(before) => SymbolDiff {
+                name: symbol.name,
+                allocated: symbol.allocated as i64 - before.allocated as i64,
+                count: symbol.count as i64 - before.count as i64,
+                total_allocated: symbol
+                    .total_allocated
+                    .saturating_sub(before.total_allocated),
+                total_count: symbol.total_count.saturating_sub(before.total_count),
+            },
+            None => SymbolDiff {
+                name: symbol.name,
+                allocated: symbol.allocated as i64,
+                count: symbol.count as i64,
+                total_allocated: symbol.total_allocated,
+                total_count: symbol.total_count,
+            },
+        })
+        .collect();
+    let current: HashSet<&'static str> = after.symbols.iter().map(|symbol| symbol.name).collect();
+    diffs.extend(
+        before
+            .symbols
+            .iter()
+            .filter(|symbol| !current.contains(symbol.name))
+            .map(|symbol| SymbolDiff {
+                name: symbol.name,
+                allocated: -(symbol.allocated as i64),
+                count: -(symbol.count as i64),
+                total_allocated: 0,
+                total_count: 0,
+            }),
+    );
+    diffs.retain(|diff| diff.allocated != 0 || diff.count != 0 || diff.total_count != 0);
+    diffs.sort_unstable_by_key(|diff| diff.name);
+
+    diffs
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_diff_snapshots() {
+        let before = synthetic_snapshot();
+        let mut after = before.clone();
+        // app::main allocated 512 more bytes, app::config::load freed everything, app::new appeared
+        after.symbols[0].allocated += 512;
+        after.symbols[0].count += 1;
+        after.symbols[0].total_allocated += 512;
+        after.symbols[0].total_count += 1;
+        after.symbols.remove(2);
+        let mut new = before.symbols[1].clone();
+        new.name = "app::new";
+        after.symbols.push(new.clone());
+
+        let diffs = diff(&before, &after);
+        let names: Vec<_> = diffs.iter().map(|diff| diff.name).collect();
+        assert_eq!(names, ["app::config::load", "app::main", "app::new"]);
+        assert_eq!(
+            diffs[1],
+            SymbolDiff {
+                name: "app::main",
+                allocated: 512,
+                count: 1,
+                total_allocated: 512,
+                total_count: 1,
+            }
+        );
+        assert_eq!(diffs[0].allocated, -(before.symbols[2].allocated as i64));
+        assert_eq!(diffs[2].allocated, new.allocated as i64);
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_compute_delta_since_generation() {
+        use std::alloc::Layout;
+
+        use crate::LeaktracerAllocator;
+        use crate::alloc::AllocOp;
+        use crate::test_utils::fake_ptr;
+
+        crate::init_symbol_table(&["leaktracer"]);
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(4096, 8).unwrap();
+        let ptr = fake_ptr();
+
+        let marker = record_generation().unwrap();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        let delta = delta_since(&marker).unwrap();
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        assert!(marker.generation() < crate::generation());
+        let name = crate::caller_symbol(&["leaktracer"]);
+        let diff = delta
+            .iter()
+            .find(|diff| diff.name == name)
+            .expect("the allocation should be in the delta");
+        assert_eq!(diff.allocated, 4096);
+        assert_eq!(diff.count, 1);
+        assert_eq!(diff.total_count, 1);
+    }
+}
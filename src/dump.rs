@@ -0,0 +1,500 @@
+//! Compact binary dumps of the tracer state, to aggregate the stats of several processes offline.
+//!
+//! Each process writes a dump with [`dump_to`]; the dumps are then loaded with [`read`], by any process,
+//! even one without the tracer installed, and aggregated with [`Dump::merge`].
+//!
+//! A dump starts with a header made of the [`MAGIC`] bytes and the format version, followed by a sequence of
+//! sections, each one made of a tag, its length and its payload. All the integers are little endian.
+//! Readers skip the sections with an unknown tag and the trailing bytes of the records they don't know about,
+//! so newer writers can add sections and fields without breaking older readers. The version is only bumped on
+//! incompatible changes, and dumps with a newer version are rejected.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::alignment::AlignmentStats;
+use crate::snapshot::{self, SymbolSnapshot, SymbolTableSnapshot};
+use crate::symbols::SizeCardinality;
+
+/// The bytes a dump starts with.
+pub const MAGIC: &[u8; 8] = b"LKTRDUMP";
+
+/// The version of the dump format written by [`dump_to`], and the newest one [`read`] supports.
+pub const VERSION: u16 = 1;
+
+/// Tag of the section holding the [`SymbolTableSnapshot`].
+const SNAPSHOT_SECTION: u16 = 1;
+
+/// Tag of the section holding the [`SymbolTableSnapshot::self_overhead_bytes`], added after the first release
+/// of the format: the dumps without it read a zero overhead.
+const OVERHEAD_SECTION: u16 = 2;
+
+/// The contents of a dump, see [`read`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dump {
+    /// The format version the dump was written with.
+    pub version: u16,
+    /// The snapshot of the symbol table when the dump was written.
+    ///
+    /// The symbol names are interned when the dump is read.
+    pub snapshot: SymbolTableSnapshot,
+}
+
+impl Dump {
+    /// Merges several dumps, e.g. written by the instances of a service, into one.
+    ///
+    /// The symbols with the same name are summed (see [`crate::merge_snapshots`]), as are the live bytes,
+    /// the untracked frees, the tracer overhead and the RSS of the processes which reported it. The generation, the uptime and
+    /// the sampling divisor are the highest ones, and the sizes are padded if they are padded in any dump.
+    pub fn merge(dumps: &[Dump]) -> Dump {
+        let symbols: Vec<_> = dumps
+            .iter()
+            .map(|dump| dump.snapshot.symbols.clone())
+            .collect();
+        let snapshots = || dumps.iter().map(|dump| &dump.snapshot);
+
+        Dump {
+            version: VERSION,
+            snapshot: SymbolTableSnapshot {
+                generation: snapshots().map(|s| s.generation).max().unwrap_or_default(),
+                uptime: snapshots().map(|s| s.uptime).max().unwrap_or_default(),
+                padded_size: snapshots().any(|s| s.padded_size),
+                allocated: snapshots().map(|s| s.allocated).sum(),
+                sampling_divisor: snapshots().map(|s| s.sampling_divisor).max().unwrap_or(1),
+                process_rss: snapshots()
+                    .filter_map(|s| s.process_rss)
+                    .reduce(|a, b| a + b),
+                untracked_frees: snapshots().map(|s| s.untracked_frees).sum(),
+                untracked_freed_bytes: snapshots().map(|s| s.untracked_freed_bytes).sum(),
+                self_overhead_bytes: snapshots().map(|s| s.self_overhead_bytes).sum(),
+                symbols: snapshot::merge_snapshots(&symbols),
+            },
+        }
+    }
+}
+
+/// Writes a dump of the tracer state to `path`, see [`read`] to load it back.
+///
+/// The dump is written to a temporary file which is then renamed, so a crash never leaves a truncated dump.
+pub fn dump_to(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    let snapshot = snapshot::snapshot()?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "dump path is not a file"))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+
+    let mut file = BufWriter::new(File::create(&tmp_path)?);
+    write_dump(&snapshot, &mut file)?;
+    file.into_inner()
+        .map_err(|err| err.into_error())?
+        .sync_all()?;
+
+    fs::rename(&tmp_path, path)
+}
+
+/// Reads the dump at `path`, written by [`dump_to`].
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the file is not a dump, is truncated or corrupted,
+/// or was written with a newer version of the format.
+pub fn read(path: impl AsRef<Path>) -> io::Result<Dump> {
+    parse_dump(&fs::read(path)?)
+}
+
+/// Writes the dump of `snapshot` to `w`.
+fn write_dump(snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+
+    w.write_all(&OVERHEAD_SECTION.to_le_bytes())?;
+    w.write_all(&8u64.to_le_bytes())?;
+    w.write_all(&(snapshot.self_overhead_bytes as u64).to_le_bytes())?;
+
+    let mut section = Vec::new();
+    put_u64(&mut section, snapshot.generation);
+    put_u64(&mut section, snapshot.uptime.as_nanos() as u64);
+    section.push(snapshot.padded_size as u8);
+    put_u64(&mut section, snapshot.allocated as u64);
+    put_u64(&mut section, snapshot.sampling_divisor as u64);
+    section.push(snapshot.process_rss.is_some() as u8);
+    put_u64(
+        &mut section,
+        snapshot.process_rss.unwrap_or_default() as u64,
+    );
+    put_u64(&mut section, snapshot.untracked_frees as u64);
+    put_u64(&mut section, snapshot.untracked_freed_bytes as u64);
+    put_u64(&mut section, snapshot.symbols.len() as u64);
+    for symbol in &snapshot.symbols {
+        let mut record = Vec::new();
+        put_u32(&mut record, symbol.name.len() as u32);
+        record.extend_from_slice(symbol.name.as_bytes());
+        for value in [
+            symbol.allocated,
+            symbol.count,
+            symbol.total_allocated,
+            symbol.total_count,
+            symbol.turnover,
+            symbol.inclusive_allocated,
+            symbol.recent_allocated,
+            symbol.recent_count,
+            symbol.reallocs,
+            symbol.realloc_moves,
+            symbol.bytes_grown,
+            symbol.bytes_shrunk,
+        ]
+        .into_iter()
+        .chain(symbol.alignment.counts())
+        .chain([
+            symbol.size_cardinality.to_raw(),
+            symbol.peak,
+            symbol.peak_at.as_millis() as usize,
+        ]) {
+            put_u64(&mut record, value as u64);
+        }
+        put_u32(&mut section, record.len() as u32);
+        section.extend_from_slice(&record);
+    }
+
+    w.write_all(&SNAPSHOT_SECTION.to_le_bytes())?;
+    w.write_all(&(section.len() as u64).to_le_bytes())?;
+    w.write_all(&section)
+}
+
+/// Parses a dump from its bytes.
+fn parse_dump(bytes: &[u8]) -> io::Result<Dump> {
+    let mut reader = Reader(bytes);
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err(invalid_data("not a leaktracer dump"));
+    }
+    let version = reader.u16()?;
+    if version == 0 || version > VERSION {
+        return Err(invalid_data(format!("unsupported dump version {version}")));
+    }
+
+    let mut snapshot = None;
+    let mut self_overhead_bytes = 0;
+    while !reader.0.is_empty() {
+        let tag = reader.u16()?;
+        let len = reader.u64_len()?;
+        let mut section = Reader(reader.bytes(len)?);
+        // unknown sections are skipped, for forward compatibility
+        match tag {
+            SNAPSHOT_SECTION => snapshot = Some(parse_snapshot(&mut section)?),
+            OVERHEAD_SECTION => self_overhead_bytes = section.usize()?,
+            _ => {}
+        }
+    }
+    let mut snapshot = snapshot.ok_or_else(|| invalid_data("missing snapshot section"))?;
+    snapshot.self_overhead_bytes = self_overhead_bytes;
+
+    Ok(Dump { version, snapshot })
+}
+
+/// Parses the snapshot section.
+fn parse_snapshot(reader: &mut Reader<'_>) -> io::Result<SymbolTableSnapshot> {
+    let mut snapshot = SymbolTableSnapshot {
+        generation: reader.u64()?,
+        uptime: Duration::from_nanos(reader.u64()?),
+        padded_size: reader.u8()? != 0,
+        allocated: reader.usize()?,
+        sampling_divisor: reader.usize()?,
+        ..Default::default()
+    };
+    let has_rss = reader.u8()? != 0;
+    let rss = reader.usize()?;
+    snapshot.process_rss = has_rss.then_some(rss);
+    snapshot.untracked_frees = reader.usize()?;
+    snapshot.untracked_freed_bytes = reader.usize()?;
+
+    let symbols = reader.u64()?;
+    for _ in 0..symbols {
+        let len = reader.u32_len()?;
+        // the trailing fields added by newer versions are skipped
+        let mut record = Reader(reader.bytes(len)?);
+        let name_len = record.u32_len()?;
+        let name = std::str::from_utf8(record.bytes(name_len)?)
+            .map_err(|_| invalid_data("symbol name is not valid UTF-8"))?;
+        snapshot.symbols.push(SymbolSnapshot {
+            name: crate::symbols::intern(name),
+            allocated: record.usize()?,
+            count: record.usize()?,
+            total_allocated: record.usize()?,
+            total_count: record.usize()?,
+            turnover: record.usize()?,
+            inclusive_allocated: record.usize()?,
+            recent_allocated: record.usize()?,
+            recent_count: record.usize()?,
+            reallocs: record.trailing_usize()?,
+            realloc_moves: record.trailing_usize()?,
+            bytes_grown: record.trailing_usize()?,
+            bytes_shrunk: record.trailing_usize()?,
+            alignment: AlignmentStats::from_counts([
+                record.trailing_usize()?,
+                record.trailing_usize()?,
+                record.trailing_usize()?,
+                record.trailing_usize()?,
+                record.trailing_usize()?,
+            ]),
+            size_cardinality: SizeCardinality::from_raw(record.trailing_usize()?),
+            peak: record.trailing_usize()?,
+            peak_at: Duration::from_millis(record.trailing_usize()? as u64),
+        });
+    }
+
+    Ok(snapshot)
+}
+
+/// A cursor over the bytes of a dump, failing on truncated data.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    /// Reads the next `len` bytes.
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid_data("truncated dump"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        Ok(bytes)
+    }
+
+    /// Reads the next `N` bytes as an array.
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn usize(&mut self) -> io::Result<usize> {
+        let value = self.u64()?;
+        usize::try_from(value).map_err(|_| invalid_data("value out of range"))
+    }
+
+    /// Reads a field appended to a record after the first release of the format, `0` if the record ends before it.
+    fn trailing_usize(&mut self) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Ok(0);
+        }
+
+        self.usize()
+    }
+
+    /// Reads a 32-bit length, checking that it fits in the remaining bytes.
+    fn u32_len(&mut self) -> io::Result<usize> {
+        let len = self.u32()?;
+        self.check_len(len.into())
+    }
+
+    /// Reads a 64-bit length, checking that it fits in the remaining bytes.
+    fn u64_len(&mut self) -> io::Result<usize> {
+        let len = self.u64()?;
+        self.check_len(len)
+    }
+
+    /// Checks that a length read from the dump fits in the remaining bytes, before allocating anything for it.
+    fn check_len(&self, len: u64) -> io::Result<usize> {
+        match usize::try_from(len) {
+            Ok(len) if len <= self.0.len() => Ok(len),
+            _ => Err(invalid_data("truncated dump")),
+        }
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    fn encode(snapshot: &SymbolTableSnapshot) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_dump(snapshot, &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_should_round_trip_dump() {
+        let mut snapshot = synthetic_snapshot();
+        snapshot.symbols[0].recent_allocated = 128;
+        snapshot.process_rss = None;
+
+        let dump = parse_dump(&encode(&snapshot)).unwrap();
+        assert_eq!(dump.version, VERSION);
+        assert_eq!(dump.snapshot, snapshot);
+
+        // a dump written before the overhead section was added
+        let mut bytes = encode(&snapshot);
+        bytes.drain(MAGIC.len() + 2..MAGIC.len() + 2 + 18);
+        let dump = parse_dump(&bytes).unwrap();
+        assert_eq!(dump.snapshot.self_overhead_bytes, 0);
+        assert_eq!(dump.snapshot.symbols, snapshot.symbols);
+    }
+
+    #[test]
+    fn test_should_write_and_read_dump_file() {
+        crate::init_symbol_table(&["leaktracer"]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leaktracer.dump");
+
+        dump_to(&path).unwrap();
+        let dump = read(&path).unwrap();
+        assert_eq!(dump.version, VERSION);
+        assert!(dump.snapshot.sampling_divisor >= 1);
+        assert!(read(dir.path().join("missing.dump")).is_err());
+    }
+
+    #[test]
+    fn test_should_merge_dumps() {
+        let a = parse_dump(&encode(&synthetic_snapshot())).unwrap();
+        let mut other = synthetic_snapshot();
+        other.generation = 100;
+        other.process_rss = None;
+        other.symbols.truncate(1);
+        let b = parse_dump(&encode(&other)).unwrap();
+
+        let merged = Dump::merge(&[a.clone(), b]);
+        assert_eq!(merged.snapshot.generation, 100);
+        assert_eq!(merged.snapshot.allocated, a.snapshot.allocated * 2);
+        assert_eq!(merged.snapshot.process_rss, a.snapshot.process_rss);
+        assert_eq!(merged.snapshot.self_overhead_bytes, 2048);
+        assert_eq!(merged.snapshot.symbols.len(), 3);
+        let main = &merged.snapshot.symbols[0];
+        assert_eq!(main.name, "app::main");
+        assert_eq!(main.allocated, 2048);
+        assert_eq!(main.count, 20);
+        assert_eq!(merged.snapshot.symbols[1], a.snapshot.symbols[1]);
+
+        assert_eq!(Dump::merge(&[]).snapshot.symbols, vec![]);
+    }
+
+    #[test]
+    fn test_should_skip_unknown_sections_and_fields() {
+        let snapshot = synthetic_snapshot();
+        let mut bytes = encode(&snapshot);
+        // a section from a newer version
+        bytes.extend_from_slice(&42u16.to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(b"new");
+
+        let dump = parse_dump(&bytes).unwrap();
+        assert_eq!(dump.snapshot, snapshot);
+
+        // a symbol with a field from a newer version
+        let single = SymbolTableSnapshot {
+            symbols: snapshot.symbols[..1].to_vec(),
+            ..snapshot.clone()
+        };
+        let mut bytes = encode(&single);
+        resize_last_record(&mut bytes, "app::main", 8);
+        let len = bytes.len();
+        bytes[len - 8..].copy_from_slice(&7u64.to_le_bytes());
+
+        assert_eq!(parse_dump(&bytes).unwrap().snapshot, single);
+    }
+
+    #[test]
+    fn test_should_read_records_without_trailing_fields() {
+        let snapshot = synthetic_snapshot();
+        let single = SymbolTableSnapshot {
+            symbols: snapshot.symbols[..1].to_vec(),
+            ..snapshot
+        };
+        assert_ne!(single.symbols[0].reallocs, 0);
+        // the records written before the realloc, alignment, size cardinality and peak fields were added
+        let mut bytes = encode(&single);
+        resize_last_record(&mut bytes, "app::main", -12 * 8);
+
+        let symbol = &parse_dump(&bytes).unwrap().snapshot.symbols[0];
+        assert_eq!(symbol.allocated, single.symbols[0].allocated);
+        assert_eq!(symbol.recent_count, single.symbols[0].recent_count);
+        assert_eq!(
+            (
+                symbol.reallocs,
+                symbol.realloc_moves,
+                symbol.bytes_grown,
+                symbol.bytes_shrunk
+            ),
+            (0, 0, 0, 0)
+        );
+        assert_eq!(symbol.alignment, AlignmentStats::default());
+        assert_eq!(symbol.size_cardinality, SizeCardinality::default());
+        assert_eq!((symbol.peak, symbol.peak_at), (0, Duration::ZERO));
+    }
+
+    /// Grows or shrinks by `delta` bytes the last record of a dump with a single section, named `name`,
+    /// fixing up the lengths. The new bytes are zeroed.
+    fn resize_last_record(bytes: &mut Vec<u8>, name: &str, delta: i64) {
+        let record_len = 4 + name.len() + 20 * 8;
+        let record_start = bytes.len() - record_len - 4;
+        let new_len = (record_len as i64 + delta) as usize;
+        bytes[record_start..record_start + 4].copy_from_slice(&(new_len as u32).to_le_bytes());
+        bytes.resize((bytes.len() as i64 + delta) as usize, 0);
+        // the snapshot section follows the overhead one
+        let section_len_at = MAGIC.len() + 2 + (2 + 8 + 8) + 2;
+        let section_len = u64::from_le_bytes(
+            bytes[section_len_at..section_len_at + 8]
+                .try_into()
+                .unwrap(),
+        );
+        bytes[section_len_at..section_len_at + 8]
+            .copy_from_slice(&((section_len as i64 + delta) as u64).to_le_bytes());
+    }
+
+    #[test]
+    fn test_should_reject_corrupted_dump() {
+        let bytes = encode(&synthetic_snapshot());
+
+        let kind = |bytes: &[u8]| parse_dump(bytes).unwrap_err().kind();
+        assert_eq!(kind(b""), io::ErrorKind::InvalidData);
+        assert_eq!(kind(b"NOTADUMP\x01\x00"), io::ErrorKind::InvalidData);
+        // truncated at every length
+        for len in 0..bytes.len() {
+            assert_eq!(kind(&bytes[..len]), io::ErrorKind::InvalidData, "{len}");
+        }
+
+        // newer version
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(kind(&newer), io::ErrorKind::InvalidData);
+
+        // huge section length
+        let mut huge = bytes.clone();
+        huge[MAGIC.len() + 4..MAGIC.len() + 12].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(kind(&huge), io::ErrorKind::InvalidData);
+
+        // a dump without any snapshot
+        assert_eq!(kind(&bytes[..MAGIC.len() + 2]), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,368 @@
+//! Best-effort dump of the top symbols, safe to write from a signal handler.
+//!
+//! The symbol table can't be read from a signal handler, since its lock may be held by the interrupted code,
+//! so the allocator keeps a lock-free mirror of the top symbols in a static array, refreshed from the symbol table
+//! every [`REFRESH_EVERY`] traced allocations. The dump only reads the mirror and writes it with `write(2)`.
+
+use std::ffi::{c_int, c_void};
+use std::io;
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering, fence};
+
+use crate::symbols::SymbolTable;
+
+/// Number of symbols kept in the mirror.
+const EMERGENCY_DUMP_SYMBOLS: usize = 16;
+
+/// Number of traced allocations between two refreshes of the mirror.
+const REFRESH_EVERY: usize = 1024;
+
+/// Maximum length of a dumped line, including the newline.
+const LINE_CAPACITY: usize = 512;
+
+/// Number of attempts to read a slot being refreshed, before skipping it.
+const READ_ATTEMPTS: usize = 4;
+
+/// The top symbols by allocated bytes, from the largest.
+static MIRROR: [Slot; EMERGENCY_DUMP_SYMBOLS] = [const { Slot::new() }; EMERGENCY_DUMP_SYMBOLS];
+
+unsafe extern "C" {
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+}
+
+/// A symbol in the mirror, guarded by a sequence lock.
+///
+/// The sequence is odd while the slot is being refreshed, so readers never use a name pointer
+/// with the length of another name.
+struct Slot {
+    seq: AtomicUsize,
+    name: AtomicPtr<u8>,
+    name_len: AtomicUsize,
+    allocated: AtomicUsize,
+    count: AtomicUsize,
+}
+
+/// A consistent copy of a [`Slot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    name: &'static str,
+    allocated: usize,
+    count: usize,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            name: AtomicPtr::new(std::ptr::null_mut()),
+            name_len: AtomicUsize::new(0),
+            allocated: AtomicUsize::new(0),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Stores `entry` into the slot, or empties it.
+    ///
+    /// There must be a single writer at a time, i.e. it must be called with the symbol table locked.
+    fn store(&self, entry: Option<Entry>) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        let (name, allocated, count) = entry.map_or(("", 0, 0), |e| (e.name, e.allocated, e.count));
+        self.name.store(name.as_ptr().cast_mut(), Ordering::Relaxed);
+        self.name_len.store(name.len(), Ordering::Relaxed);
+        self.allocated.store(allocated, Ordering::Relaxed);
+        self.count.store(count, Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Reads the slot, without blocking.
+    ///
+    /// Returns [`None`] if the slot is empty, or if it's still being refreshed after a few attempts,
+    /// e.g. because the signal interrupted the refresh on the current thread.
+    fn load(&self) -> Option<Entry> {
+        for _ in 0..READ_ATTEMPTS {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let name = self.name.load(Ordering::Relaxed);
+            let name_len = self.name_len.load(Ordering::Relaxed);
+            let allocated = self.allocated.load(Ordering::Relaxed);
+            let count = self.count.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) != seq {
+                continue;
+            }
+            if name.is_null() || name_len == 0 {
+                return None;
+            }
+            // SAFETY: the name is an interned `&'static str`, and the sequence guarantees the length is its own
+            let name = unsafe {
+                std::str::from_utf8_unchecked(std::slice::from_raw_parts(name, name_len))
+            };
+            return Some(Entry {
+                name,
+                allocated,
+                count,
+            });
+        }
+
+        None
+    }
+}
+
+/// Refreshes the mirror from `table`, if the total allocation count going from `before` to `after`
+/// crossed a multiple of [`REFRESH_EVERY`].
+pub(crate) fn refresh_every(before: usize, after: usize, table: &SymbolTable) {
+    if before / REFRESH_EVERY != after / REFRESH_EVERY {
+        refresh(table);
+    }
+}
+
+/// Refreshes the mirror with the top symbols of `table`, without allocating.
+///
+/// Must be called with the symbol table locked.
+pub(crate) fn refresh(table: &SymbolTable) {
+    let mut top = [None::<Entry>; EMERGENCY_DUMP_SYMBOLS];
+    for (name, symbol) in table.iter() {
+        let allocated = symbol.allocated();
+        if allocated == 0 {
+            continue;
+        }
+        let Some(pos) = top
+            .iter()
+            .position(|entry| entry.is_none_or(|entry| entry.allocated < allocated))
+        else {
+            continue;
+        };
+        top.copy_within(pos..EMERGENCY_DUMP_SYMBOLS - 1, pos + 1);
+        top[pos] = Some(Entry {
+            name,
+            allocated,
+            count: symbol.count(),
+        });
+    }
+
+    for (slot, entry) in MIRROR.iter().zip(top) {
+        slot.store(entry);
+    }
+}
+
+/// Writes a best-effort dump of the top symbols to the file descriptor `fd`, using only `write(2)`.
+///
+/// It's meant to be called when the process is about to die, e.g. from a `SIGSEGV` handler or when
+/// the process is about to be OOM-killed, where the symbol table and the reporters can't be used.
+///
+/// The dump is a header line, the live bytes traced by the allocator (see [`crate::global_allocated`]),
+/// then a line for each of the top 16 symbols by allocated bytes:
+///
+/// ```text
+/// leaktracer emergency dump
+/// allocated 1048576 bytes
+/// 524288 bytes in 12 allocations @ app::cache::insert
+/// ```
+///
+/// # Guarantees
+///
+/// - it's async-signal-safe: it never allocates, never takes a lock, never panics and only calls `write(2)`;
+/// - the symbol stats come from a mirror of the top symbols of the global table, refreshed every 1024
+///   traced allocations and on [`crate::reset`], so they may lag behind the symbol table;
+/// - the named tables (see [`crate::scoped_table`]) are not in the mirror;
+/// - a symbol being refreshed while the dump runs, e.g. because the signal interrupted the refresh
+///   on the current thread, is skipped;
+/// - lines longer than 512 bytes are truncated, cutting the symbol name.
+///
+/// Returns the error of the first `write(2)` failing, except for `EINTR`, which is retried.
+/// The file descriptor is neither flushed nor closed.
+pub fn emergency_dump(fd: RawFd) -> io::Result<()> {
+    let mut line = Line::new();
+    line.push(b"leaktracer emergency dump\n");
+    line.write_to(fd)?;
+    line.push(b"allocated ");
+    line.push_usize(crate::global_allocated());
+    line.push(b" bytes\n");
+    line.write_to(fd)?;
+
+    for entry in MIRROR.iter().filter_map(Slot::load) {
+        line.push_usize(entry.allocated);
+        line.push(b" bytes in ");
+        line.push_usize(entry.count);
+        line.push(b" allocations @ ");
+        // keep room for the newline
+        let room = LINE_CAPACITY - 1 - line.len;
+        line.push(&entry.name.as_bytes()[..entry.name.len().min(room)]);
+        line.push(b"\n");
+        line.write_to(fd)?;
+    }
+
+    Ok(())
+}
+
+/// A fixed size line buffer on the stack.
+struct Line {
+    buf: [u8; LINE_CAPACITY],
+    len: usize,
+}
+
+impl Line {
+    fn new() -> Self {
+        Self {
+            buf: [0; LINE_CAPACITY],
+            len: 0,
+        }
+    }
+
+    /// Appends `bytes`, truncating them if the line is full.
+    fn push(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(LINE_CAPACITY - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+    }
+
+    /// Appends `n` in decimal.
+    fn push_usize(&mut self, mut n: usize) {
+        let mut digits = [0u8; 20];
+        let mut pos = digits.len();
+        loop {
+            pos -= 1;
+            digits[pos] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        self.push(&digits[pos..]);
+    }
+
+    /// Writes the line to `fd` and clears it.
+    fn write_to(&mut self, fd: RawFd) -> io::Result<()> {
+        let mut written = 0;
+        while written < self.len {
+            let rest = &self.buf[written..self.len];
+            // SAFETY: `rest` is valid for reads of `rest.len()` bytes
+            let n = unsafe { write(fd, rest.as_ptr().cast(), rest.len()) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            written += n as usize;
+        }
+        self.len = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::{Read as _, Seek as _};
+    use std::os::fd::AsRawFd as _;
+
+    use super::*;
+
+    #[test]
+    fn test_should_keep_top_symbols_in_mirror() {
+        let slot = Slot::new();
+        assert_eq!(slot.load(), None);
+        let entry = Entry {
+            name: "app::main",
+            allocated: 4096,
+            count: 2,
+        };
+        slot.store(Some(entry));
+        assert_eq!(slot.load(), Some(entry));
+
+        // a slot being refreshed is skipped
+        slot.seq.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(slot.load(), None);
+    }
+
+    #[test]
+    fn test_should_format_lines() {
+        let mut line = Line::new();
+        line.push_usize(0);
+        line.push(b" ");
+        line.push_usize(usize::MAX);
+        assert_eq!(
+            &line.buf[..line.len],
+            format!("0 {}", usize::MAX).as_bytes()
+        );
+
+        line.push(&[b'x'; LINE_CAPACITY]);
+        assert_eq!(line.len, LINE_CAPACITY);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_write_emergency_dump() {
+        use std::alloc::Layout;
+
+        use crate::LeaktracerAllocator;
+        use crate::alloc::AllocOp;
+
+        crate::init_symbol_table(&["leaktracer"]);
+        let allocator = LeaktracerAllocator::init();
+        // bigger than any allocation traced by the other tests, so it's in the top symbols
+        let layout = Layout::from_size_align(isize::MAX as usize >> 4, 8).unwrap();
+        let ptr = crate::test_utils::fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        crate::with_symbol_table(refresh).unwrap();
+        let mut file = tempfile::tempfile().unwrap();
+        emergency_dump(file.as_raw_fd()).unwrap();
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        file.rewind().unwrap();
+        let mut dump = String::new();
+        file.read_to_string(&mut dump).unwrap();
+
+        let mut lines = dump.lines();
+        assert_eq!(lines.next(), Some("leaktracer emergency dump"));
+        assert!(lines.next().unwrap().starts_with("allocated "));
+        let top = format!("{} bytes in 1 allocations @ ", isize::MAX as usize >> 4);
+        assert!(lines.next().unwrap().starts_with(&top));
+    }
+
+    #[test]
+    fn test_should_write_emergency_dump_from_signal_handler() {
+        unsafe extern "C" {
+            fn fork() -> c_int;
+            fn waitpid(pid: c_int, status: *mut c_int, options: c_int) -> c_int;
+            fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+            fn raise(signum: c_int) -> c_int;
+            fn _exit(status: c_int) -> !;
+        }
+        const SIGSEGV: c_int = 11;
+        static FD: AtomicUsize = AtomicUsize::new(0);
+
+        extern "C" fn on_segv(_: c_int) {
+            let ok = emergency_dump(FD.load(Ordering::Relaxed) as RawFd).is_ok();
+            unsafe { _exit(if ok { 0 } else { 2 }) }
+        }
+
+        let mut file = tempfile::tempfile().unwrap();
+        FD.store(file.as_raw_fd() as usize, Ordering::Relaxed);
+        let pid = unsafe { fork() };
+        assert!(pid >= 0, "fork failed");
+        if pid == 0 {
+            unsafe {
+                signal(SIGSEGV, on_segv);
+                raise(SIGSEGV);
+                _exit(1)
+            }
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { waitpid(pid, &mut status, 0) }, pid);
+        assert_eq!(status, 0, "the signal handler failed");
+        file.rewind().unwrap();
+        let mut dump = String::new();
+        file.read_to_string(&mut dump).unwrap();
+        assert!(dump.starts_with("leaktracer emergency dump\nallocated "));
+    }
+}
@@ -0,0 +1,103 @@
+//! C interface, to query the tracer and trigger dumps from C code or from a debugger.
+//!
+//! The functions are exported unmangled, so they can be declared in C or called from gdb, e.g.
+//! `call leaktracer_dump_json("/tmp/leaktracer.json")`.
+//!
+//! They never unwind into the caller: panics are caught at the boundary and reported as an error code.
+//! The error codes are errno-style: `0` on success, a positive `errno` value on failure.
+
+use std::ffi::{CStr, c_char, c_int};
+use std::fs::File;
+use std::io::{self, BufWriter, Write as _};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use crate::report::{JsonReporter, report};
+
+/// Returned when an argument is invalid, e.g. a null or non UTF-8 path.
+pub const EINVAL: c_int = 22;
+
+/// Returned when the operation fails without an OS error code, e.g. if the symbol table is not initialized.
+pub const EIO: c_int = 5;
+
+/// Returns the live bytes traced by the allocator (see [`crate::global_allocated`]).
+#[unsafe(no_mangle)]
+pub extern "C" fn leaktracer_allocated() -> usize {
+    catch_unwind(crate::global_allocated).unwrap_or_default()
+}
+
+/// Writes a JSON report of the symbol table (see [`JsonReporter`]) to the file at `path`, replacing it.
+///
+/// Returns `0` on success, [`EINVAL`] if `path` is null or not valid UTF-8, the OS error code if the file
+/// can't be written or [`EIO`] if the report can't be taken, e.g. because the symbol table is not initialized.
+///
+/// # Safety
+///
+/// `path` must be null or point to a nul-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn leaktracer_dump_json(path: *const c_char) -> c_int {
+    if path.is_null() {
+        return EINVAL;
+    }
+    // SAFETY: the caller guarantees `path` points to a nul-terminated string
+    let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+        return EINVAL;
+    };
+    if crate::alloc::configured_modules().is_none() {
+        return EIO;
+    }
+
+    let dump = || -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        report(&JsonReporter::default(), &mut file)?;
+        file.flush()
+    };
+    match catch_unwind(AssertUnwindSafe(dump)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(err)) => err.raw_os_error().unwrap_or(EIO),
+        Err(_) => EIO,
+    }
+}
+
+/// Enables or disables tracing of new allocations (see [`crate::set_enabled`]).
+///
+/// Always returns `0`.
+#[unsafe(no_mangle)]
+pub extern "C" fn leaktracer_set_enabled(enabled: bool) -> c_int {
+    match catch_unwind(|| crate::set_enabled(enabled)) {
+        Ok(()) => 0,
+        Err(_) => EIO,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::ffi::CString;
+
+    use super::*;
+
+    #[test]
+    fn test_should_call_ffi_functions_through_pointers() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocated: extern "C" fn() -> usize = leaktracer_allocated;
+        let dump_json: unsafe extern "C" fn(*const c_char) -> c_int = leaktracer_dump_json;
+        let set_enabled: extern "C" fn(bool) -> c_int = leaktracer_set_enabled;
+
+        assert_eq!(allocated(), crate::global_allocated());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().join("dump.json").to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { dump_json(path.as_ptr()) }, 0);
+        let dump = std::fs::read_to_string(dir.path().join("dump.json")).unwrap();
+        assert!(dump.starts_with("{\"generation\":"));
+
+        assert_eq!(unsafe { dump_json(std::ptr::null()) }, EINVAL);
+        let missing = CString::new(dir.path().join("missing/dump.json").to_str().unwrap()).unwrap();
+        assert_ne!(unsafe { dump_json(missing.as_ptr()) }, 0);
+
+        // tracing is global, so it's only (re-)enabled here
+        assert_eq!(set_enabled(true), 0);
+        assert!(crate::is_enabled());
+    }
+}
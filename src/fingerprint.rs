@@ -0,0 +1,109 @@
+//! Grouping of the allocations by shape, to spot the functions repeating identical allocations.
+
+use crate::alloc::SYMBOL_TABLE;
+
+/// The shape of an allocation: its size class, alignment and symbol.
+///
+/// The allocations sharing a fingerprint are the same allocation repeated, e.g. a 64-byte block aligned to 8
+/// allocated by a function in a loop, which may be worth pooling or hoisting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint {
+    /// The allocation size rounded up to a power of two, e.g. `64` for the allocations of 33 to 64 bytes.
+    pub size_class: usize,
+    /// The alignment of the allocation.
+    pub align: usize,
+    /// The symbol which made the allocation.
+    pub symbol: &'static str,
+}
+
+impl Fingerprint {
+    /// Returns the fingerprint of an allocation of `size` bytes aligned to `align` made by `symbol`.
+    pub(crate) fn new(size: usize, align: usize, symbol: &'static str) -> Self {
+        Self {
+            size_class: size.checked_next_power_of_two().unwrap_or(usize::MAX),
+            align,
+            symbol,
+        }
+    }
+}
+
+/// Returns the number of traced allocations of each [`Fingerprint`], sorted by count in descending order.
+///
+/// The counts are cumulative since the tracer started or the last [`crate::reset`], including the allocations
+/// already freed, and scaled by the sampling weight. The allocations skipped by sampling are not counted.
+/// Returns an empty list if the symbol table is not initialized or its lock is poisoned.
+///
+/// ```rust
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// for (fingerprint, count) in leaktracer::fingerprints().into_iter().take(10) {
+///     println!(
+///         "{}: {count} allocations of {} bytes aligned to {}",
+///         fingerprint.symbol, fingerprint.size_class, fingerprint.align
+///     );
+/// }
+/// ```
+pub fn fingerprints() -> Vec<(Fingerprint, usize)> {
+    if SYMBOL_TABLE.get().is_none() {
+        return Vec::new();
+    }
+    crate::with_symbol_table(|table| {
+        let mut fingerprints = table.fingerprints();
+        fingerprints.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        fingerprints
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+
+    #[test]
+    fn test_should_round_size_class() {
+        assert_eq!(Fingerprint::new(33, 8, "app::f").size_class, 64);
+        assert_eq!(Fingerprint::new(64, 8, "app::f").size_class, 64);
+        assert_eq!(Fingerprint::new(65, 8, "app::f").size_class, 128);
+        assert_eq!(
+            Fingerprint::new(usize::MAX, 8, "app::f").size_class,
+            usize::MAX
+        );
+    }
+
+    #[test]
+    fn test_should_group_identical_allocations() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(40, 128).unwrap();
+        let ptrs = [fake_ptr(), fake_ptr(), fake_ptr()];
+        let count = |symbol| {
+            fingerprints()
+                .into_iter()
+                .find(|(fingerprint, _)| *fingerprint == Fingerprint::new(40, 128, symbol))
+                .map_or(0, |(_, count)| count)
+        };
+
+        allocator.trace(ptrs[0], layout, AllocOp::Alloc);
+        let symbol = crate::with_symbol_table(|table| table.allocation_symbol(ptrs[0] as usize))
+            .unwrap()
+            .unwrap();
+        let before = count(symbol);
+        for ptr in &ptrs[1..] {
+            allocator.trace(*ptr, layout, AllocOp::Alloc);
+        }
+        let after = count(symbol);
+        for ptr in ptrs {
+            allocator.trace(ptr, layout, AllocOp::Dealloc);
+        }
+
+        assert!(before >= 1);
+        assert_eq!(after - before, 2);
+    }
+}
@@ -0,0 +1,102 @@
+//! Folding of the nested allocations attributed to the same symbol into a single logical allocation.
+
+use std::cell::RefCell;
+
+use crate::alloc::bookkeeping;
+
+thread_local! {
+    /// The folding windows opened on the current thread, the innermost last,
+    /// with the symbol each one is attributing to, once known.
+    static FOLDS: RefCell<Vec<Option<&'static str>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A guard folding the nested allocations of the current thread into a single logical allocation, until dropped.
+///
+/// Created by [`fold_nested`].
+#[derive(Debug)]
+#[must_use = "the folding window is closed as soon as the guard is dropped"]
+pub struct FoldGuard {
+    // the windows are per-thread, so the guard must be dropped on the thread which opened it
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for FoldGuard {
+    fn drop(&mut self) {
+        FOLDS.with_borrow_mut(Vec::pop);
+    }
+}
+
+/// Opens a folding window on the current thread, until the guard is dropped.
+///
+/// The first allocation made in the window sets the symbol the window is attributing to, say `X`, and it's counted
+/// as usual. The following allocations of the window resolving to `X`, e.g. made by the `Drop` of a value replaced
+/// by `X`, or by a callback, are folded into it: their bytes are added to `X`, but they don't increment
+/// its allocation count ([`crate::Symbol::count`] and [`crate::Symbol::total_count`]).
+/// The allocations resolving to any other symbol are counted as usual.
+///
+/// Folding only changes the counts of the symbols: the global counters, the size classes and the module gauges
+/// still count every allocation, and the folded allocations are still freed one by one.
+/// Nested windows fold into the innermost one only.
+///
+/// ```rust
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// let mut names = vec![String::from("a")];
+/// {
+///     let _fold = leaktracer::fold_nested();
+///     // one logical allocation, whatever the drop of the replaced value allocates
+///     names[0] = String::from("b");
+/// }
+/// ```
+pub fn fold_nested() -> FoldGuard {
+    bookkeeping(|| FOLDS.with_borrow_mut(|folds| folds.push(None)));
+
+    FoldGuard {
+        _not_send: std::marker::PhantomData,
+    }
+}
+
+/// Returns whether an allocation attributed to `symbol` is folded into the innermost window of the current thread,
+/// setting the symbol of the window on its first allocation.
+pub(crate) fn fold(symbol: &'static str) -> bool {
+    FOLDS
+        .try_with(|folds| {
+            let Ok(mut folds) = folds.try_borrow_mut() else {
+                return false;
+            };
+            match folds.last_mut() {
+                Some(Some(current)) => *current == symbol,
+                Some(current) => {
+                    *current = Some(symbol);
+                    false
+                }
+                None => false,
+            }
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_fold_into_innermost_window() {
+        assert!(!fold("app::outer"));
+        {
+            let _outer = fold_nested();
+            assert!(!fold("app::outer"));
+            assert!(fold("app::outer"));
+            assert!(!fold("app::other"));
+            {
+                let _inner = fold_nested();
+                assert!(!fold("app::inner"));
+                assert!(!fold("app::outer"));
+                assert!(fold("app::inner"));
+            }
+            assert!(fold("app::outer"));
+        }
+        assert!(!fold("app::outer"));
+    }
+}
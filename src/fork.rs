@@ -0,0 +1,175 @@
+//! Fork safety of the tracer state.
+//!
+//! A forked child only inherits the thread which called `fork()`: if another thread held the symbol table lock,
+//! or any other lock taken by the allocator, it stays locked forever in the child, which deadlocks on its first
+//! traced allocation. So the forking thread takes all the tracer locks right before the fork, and releases them
+//! right after it, both in the parent and in the child, where they are consistent again.
+//!
+//! On unix the handlers are registered with `pthread_atfork` when the symbol table is initialized.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::{Mutex, PoisonError};
+
+use crate::alloc::{SYMBOL_TABLE, bookkeeping};
+
+thread_local! {
+    /// The guards of the tracer locks taken by [`before_fork`] on the current thread.
+    static FORK_GUARDS: RefCell<Vec<Box<dyn Any>>> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(unix)]
+unsafe extern "C" {
+    fn pthread_atfork(
+        prepare: Option<unsafe extern "C" fn()>,
+        parent: Option<unsafe extern "C" fn()>,
+        child: Option<unsafe extern "C" fn()>,
+    ) -> std::ffi::c_int;
+}
+
+/// Registers [`before_fork`], [`after_fork_parent`] and [`after_fork_child`] as `pthread_atfork` handlers, once.
+#[cfg(unix)]
+pub(crate) fn register_atfork() {
+    static REGISTERED: std::sync::Once = std::sync::Once::new();
+
+    unsafe extern "C" fn prepare() {
+        before_fork();
+    }
+    unsafe extern "C" fn parent() {
+        after_fork_parent();
+    }
+    unsafe extern "C" fn child() {
+        after_fork_child();
+    }
+
+    REGISTERED.call_once(|| {
+        // SAFETY: the handlers never unwind, since taking and releasing the locks can't panic
+        unsafe {
+            pthread_atfork(Some(prepare), Some(parent), Some(child));
+        }
+    });
+}
+
+/// Takes all the tracer locks on the current thread, until [`after_fork_parent`] or [`after_fork_child`] is called.
+///
+/// Called automatically before `fork()` on unix. Elsewhere, e.g. when forking with a raw syscall which skips
+/// the `pthread_atfork` handlers, call it right before forking, then [`after_fork_parent`] in the parent
+/// and [`after_fork_child`] in the child. Meanwhile the other threads block on their next traced allocation.
+///
+/// Must not be called while accessing the symbol table, e.g. from [`crate::with_symbol_table`], or it deadlocks.
+pub fn before_fork() {
+    // every lock taken on the allocation path must be held here, which `test_should_hold_every_allocation_path_lock`
+    // checks: the settings read on each operation are better stored without a lock (see `crate::atomic_ref`)
+    bookkeeping(|| {
+        // the symbol table lock first, since the other locks are taken while holding it
+        if let Some(table) = SYMBOL_TABLE.get() {
+            hold(table);
+        }
+        hold(&crate::symbols::INTERNED);
+        hold(&crate::largest::LARGEST);
+        #[cfg(feature = "heaptrack")]
+        hold(&crate::heaptrack::STREAM);
+        hold(&crate::accounting::ACCOUNTING_LOG);
+    });
+}
+
+/// Releases the tracer locks taken by [`before_fork`] in the parent process.
+///
+/// Called automatically after `fork()` on unix.
+pub fn after_fork_parent() {
+    release();
+}
+
+/// Releases the tracer locks taken by [`before_fork`] in the child process, so it starts from a consistent state.
+///
+/// Called automatically after `fork()` on unix. The child keeps the stats inherited from the parent,
+/// including the live allocations of the threads which didn't survive the fork; call [`crate::reset`]
+/// in the child to start from scratch instead.
+pub fn after_fork_child() {
+    release();
+    // a thread which panicked while holding the lock didn't survive the fork anyway
+    if let Some(table) = SYMBOL_TABLE.get() {
+        table.clear_poison();
+    }
+}
+
+/// Takes `mutex`, poisoned or not, keeping its guard on the current thread until [`release`].
+fn hold<T: 'static>(mutex: &'static Mutex<T>) {
+    let guard = mutex.lock().unwrap_or_else(PoisonError::into_inner);
+    FORK_GUARDS.with_borrow_mut(|guards| guards.push(Box::new(guard)));
+}
+
+/// Releases the locks held by the current thread, in reverse order.
+fn release() {
+    bookkeeping(|| {
+        let mut guards = FORK_GUARDS.with_borrow_mut(std::mem::take);
+        while guards.pop().is_some() {}
+    });
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_hold_locks_until_released() {
+        static LOCK: Mutex<usize> = Mutex::new(0);
+
+        hold(&LOCK);
+        assert!(LOCK.try_lock().is_err());
+        release();
+        assert!(LOCK.try_lock().is_ok());
+    }
+
+    #[test]
+    #[cfg(not(target_family = "wasm"))]
+    fn test_should_hold_every_allocation_path_lock() {
+        // the locks which are never taken by the allocator, or only on Windows, where there's no fork
+        const OFF_ALLOCATION_PATH: &[&str] = &[
+            "CALL_STACKS",
+            "CHECKPOINTS",
+            "PATH",
+            "PREVIOUS",
+            "QUEUE",
+            "RESOLVED",
+        ];
+
+        let source = include_str!("fork.rs");
+        let start = source.find("pub fn before_fork()").unwrap();
+        let end = start + source[start..].find("\n}\n").unwrap();
+        let held = &source[start..end];
+
+        let mut pending = vec![std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src")];
+        while let Some(path) = pending.pop() {
+            if path.is_dir() {
+                pending.extend(
+                    std::fs::read_dir(&path)
+                        .unwrap()
+                        .map(|entry| entry.unwrap().path()),
+                );
+                continue;
+            }
+            // the locks local to a function, e.g. in the tests, are indented
+            for line in std::fs::read_to_string(&path).unwrap().lines() {
+                let Some(declaration) = line
+                    .strip_prefix("static ")
+                    .or_else(|| line.strip_prefix("pub(crate) static "))
+                else {
+                    continue;
+                };
+                let Some((name, ty)) = declaration.split_once(':') else {
+                    continue;
+                };
+                if !ty.contains("Mutex<") && !ty.contains("RwLock<") {
+                    continue;
+                }
+                assert!(
+                    held.contains(name) || OFF_ALLOCATION_PATH.contains(&name),
+                    "{name} in {} is not held by before_fork",
+                    path.display()
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Attribution of the allocations where the call stacks can't be captured.
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use crate::atomic_ref::AtomicRef;
+use crate::symbols::intern;
+
+/// The frame provider set with [`set_frame_provider`].
+static FRAME_PROVIDER: AtomicRef<dyn FrameProvider> = AtomicRef::new();
+
+/// Supplies the names the allocations are attributed to where the call stacks can't be captured,
+/// i.e. on WebAssembly or without the `symbols` feature.
+///
+/// For instance, on `wasm32-unknown-unknown` an implementation can parse the JavaScript `new Error().stack`
+/// through `wasm-bindgen` to find the caller.
+///
+/// The provider is invoked on each traced allocation from inside the allocator, with the symbol table locked
+/// and tracing suppressed, so it must not access the symbol table. If it panics, the panic is caught
+/// and the allocation is not attributed. Where the call stacks can be captured, the provider is never invoked.
+pub trait FrameProvider: Send + Sync {
+    /// Returns the name of the function making the current allocation, if known.
+    ///
+    /// `modules` are the modules being traced (see [`crate::init_symbol_table`]), to pick the caller among the frames.
+    /// When [`None`] is returned, the allocation is not attributed to any symbol.
+    fn caller(&self, modules: &[&'static str]) -> Option<String>;
+}
+
+/// Sets the [`FrameProvider`] supplying the names the allocations are attributed to,
+/// where the call stacks can't be captured.
+///
+/// The names are interned, so each distinct name is leaked only once.
+pub fn set_frame_provider(provider: &'static dyn FrameProvider) {
+    FRAME_PROVIDER.store(Some(provider));
+}
+
+/// Returns the name of the caller supplied by the frame provider, if any.
+#[cfg_attr(stack_capture, allow(dead_code))]
+pub(crate) fn caller(modules: &[&'static str]) -> Option<&'static str> {
+    let provider = FRAME_PROVIDER.load()?;
+    // the panics must not unwind out of the allocator
+    catch_unwind(AssertUnwindSafe(|| provider.caller(modules)))
+        .ok()
+        .flatten()
+        .map(|name| intern(&name))
+}
+
+#[cfg(test)]
+mod test {
+
+    #[test]
+    #[cfg(not(stack_capture))]
+    fn test_should_attribute_allocations_to_provided_caller() {
+        use std::cell::Cell;
+
+        use super::*;
+        use crate::symbols::SymbolTable;
+
+        thread_local! {
+            static PROVIDE: Cell<bool> = const { Cell::new(false) };
+        }
+
+        struct TestProvider;
+
+        impl FrameProvider for TestProvider {
+            fn caller(&self, modules: &[&'static str]) -> Option<String> {
+                // the provider is global, so only provide to this test thread
+                PROVIDE.get().then(|| format!("{}::provided", modules[0]))
+            }
+        }
+
+        set_frame_provider(&TestProvider);
+        let mut table = SymbolTable::new(10, &["app"]);
+        PROVIDE.set(true);
+        assert_eq!(table.alloc(0x1000, 100, 1), "app::provided");
+        table.alloc(0x2000, 50, 1);
+        PROVIDE.set(false);
+        table.alloc(0x3000, 25, 1);
+
+        let symbol = table.get("app::provided").expect("symbol should exist");
+        assert_eq!(symbol.allocated(), 150);
+        assert_eq!(symbol.count(), 2);
+        assert_eq!(table.total_allocated(), 150);
+        assert_eq!(table.live_allocations().count(), 3);
+    }
+}
@@ -0,0 +1,531 @@
+//! Streaming of the traced allocations in the [heaptrack](https://github.com/KDE/heaptrack) format.
+//!
+//! heaptrack records the raw events of a run, then interprets them into a line based text format, compressed,
+//! which is what `heaptrack_print` and `heaptrack_gui` read. [`HeaptrackWriter`] writes the interpreted format
+//! directly, always compressed with gzip (heaptrack can also read zstd, which is never written):
+//!
+//! - `v <heaptrack version> <file format version>`: the header;
+//! - `X <command line>`: the profiled command line;
+//! - `s <string>`: an entry of the string table, referenced by its 1-based index;
+//! - `i <ip> <module> <function> <file> <line>`: an instruction pointer, with its module and frame,
+//!   referenced by its 1-based index;
+//! - `t <ip> <parent>`: a node of the trace tree, referenced by its 1-based index, `0` being the root;
+//! - `a <size> <trace>`: an allocation info, i.e. an allocation size and trace, referenced by its 0-based index;
+//! - `+ <allocation info>` and `- <allocation info>`: an allocation and a deallocation;
+//! - `c <timestamp>`: the milliseconds elapsed since the start, applying to the following events.
+//!
+//! All the numbers are hexadecimal.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::alloc::bookkeeping;
+use crate::time;
+
+/// Version of heaptrack whose format is written, `1.2.0`.
+const HEAPTRACK_VERSION: u32 = 0x010200;
+
+/// Version of the interpreted file format.
+const FILE_FORMAT_VERSION: u32 = 2;
+
+/// The stream of the active [`HeaptrackWriter`], if any.
+///
+/// Locked while holding the symbol table lock, never the other way around.
+pub(crate) static STREAM: Mutex<Option<Stream>> = Mutex::new(None);
+
+/// Whether a [`HeaptrackWriter`] is active, checked without taking the lock on each allocation.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Streams the traced allocations and deallocations to a gzipped file in the heaptrack format,
+/// to be opened with `heaptrack_gui` or `heaptrack_print`.
+///
+/// The call stacks are not kept by the tracer, so each trace is a single frame named after the [`crate::Symbol`]
+/// which made the allocation, in a module named after its crate. Only the allocations traced while the writer
+/// is active are written, and only one writer can be active at a time.
+///
+/// heaptrack detects the compression by the file extension, so the file name must end with `.gz`.
+///
+/// ```rust,no_run
+/// let writer = leaktracer::HeaptrackWriter::create("heaptrack.my_app.gz").unwrap();
+/// // ...
+/// writer.finish().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct HeaptrackWriter {
+    finished: bool,
+}
+
+impl HeaptrackWriter {
+    /// Creates the file at `path`, replacing it, and starts streaming the traced allocations to it.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = bookkeeping(|| File::create(path))?;
+        Self::new(BufWriter::new(file))
+    }
+
+    /// Starts streaming the traced allocations to `w`, gzipped.
+    ///
+    /// Fails with [`io::ErrorKind::AlreadyExists`] if another writer is active.
+    pub fn new<W>(w: W) -> io::Result<Self>
+    where
+        W: Write + Send + 'static,
+    {
+        bookkeeping(|| {
+            let mut stream = STREAM.lock().unwrap_or_else(PoisonError::into_inner);
+            if stream.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "a heaptrack writer is already active",
+                ));
+            }
+            *stream = Some(Stream::new(Box::new(w))?);
+            ACTIVE.store(true, Ordering::Relaxed);
+
+            Ok(Self { finished: false })
+        })
+    }
+
+    /// Stops streaming and finishes the file, returning the first error hit while streaming, if any.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finished = true;
+        stop()
+    }
+}
+
+impl Drop for HeaptrackWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = stop();
+        }
+    }
+}
+
+/// Stops streaming, finishing the active stream.
+fn stop() -> io::Result<()> {
+    bookkeeping(|| {
+        ACTIVE.store(false, Ordering::Relaxed);
+        let stream = STREAM.lock().unwrap_or_else(PoisonError::into_inner).take();
+        match stream {
+            Some(stream) => stream.finish(),
+            None => Ok(()),
+        }
+    })
+}
+
+/// Writes the allocation of `size` bytes at `ptr` made by `symbol`, if a [`HeaptrackWriter`] is active.
+///
+/// Must be called with tracing suppressed, since writing the event allocates.
+pub(crate) fn record_alloc(ptr: usize, size: usize, symbol: &'static str) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut stream) = STREAM.lock() {
+        if let Some(stream) = stream.as_mut() {
+            stream.alloc(ptr, size, symbol);
+        }
+    }
+}
+
+/// Writes the deallocation of `ptr`, if a [`HeaptrackWriter`] is active and it was streamed its allocation.
+///
+/// Must be called with tracing suppressed, since writing the event allocates.
+pub(crate) fn record_dealloc(ptr: usize) {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut stream) = STREAM.lock() {
+        if let Some(stream) = stream.as_mut() {
+            stream.dealloc(ptr);
+        }
+    }
+}
+
+/// The state of a heaptrack stream: the tables written so far and the live allocations.
+pub(crate) struct Stream {
+    out: GzEncoder<Box<dyn Write + Send>>,
+    /// Uptime at the start of the stream, in milliseconds.
+    start: u128,
+    /// The last timestamp written, in milliseconds since the start.
+    timestamp: u64,
+    /// Index of each string in the string table.
+    strings: HashMap<&'static str, usize>,
+    /// Index of the trace, and of its instruction pointer, of each symbol.
+    traces: HashMap<&'static str, usize>,
+    /// Index of each allocation info, by size and trace.
+    infos: HashMap<(usize, usize), usize>,
+    /// Allocation info of each live allocation streamed, by pointer.
+    live: HashMap<usize, usize>,
+    /// The first error hit while streaming, after which the events are discarded.
+    error: Option<io::Error>,
+}
+
+impl Stream {
+    /// Creates a new stream writing to `w`, writing the header.
+    fn new(w: Box<dyn Write + Send>) -> io::Result<Self> {
+        let mut out = GzEncoder::new(w, Compression::default());
+        writeln!(out, "v {HEAPTRACK_VERSION:x} {FILE_FORMAT_VERSION:x}")?;
+        let command_line = std::env::args().collect::<Vec<_>>().join(" ");
+        writeln!(out, "X {}", single_line(&command_line))?;
+
+        Ok(Self {
+            out,
+            start: time::uptime().as_millis(),
+            timestamp: 0,
+            strings: HashMap::new(),
+            traces: HashMap::new(),
+            infos: HashMap::new(),
+            live: HashMap::new(),
+            error: None,
+        })
+    }
+
+    /// Writes the allocation of `size` bytes at `ptr` made by `symbol`.
+    fn alloc(&mut self, ptr: usize, size: usize, symbol: &'static str) {
+        if self.error.is_some() {
+            return;
+        }
+        match self.write_alloc(size, symbol) {
+            Ok(info) => {
+                self.live.insert(ptr, info);
+            }
+            Err(err) => self.fail(err),
+        }
+    }
+
+    /// Writes the deallocation of `ptr`, if its allocation was streamed.
+    fn dealloc(&mut self, ptr: usize) {
+        if self.error.is_some() {
+            return;
+        }
+        let Some(info) = self.live.remove(&ptr) else {
+            return;
+        };
+        if let Err(err) = self.write_event('-', info) {
+            self.fail(err);
+        }
+    }
+
+    /// Writes the tables needed by an allocation of `size` bytes made by `symbol`, then the allocation itself.
+    ///
+    /// Returns the index of its allocation info.
+    fn write_alloc(&mut self, size: usize, symbol: &'static str) -> io::Result<usize> {
+        let trace = self.trace(symbol)?;
+        let info = match self.infos.get(&(size, trace)) {
+            Some(info) => *info,
+            None => {
+                writeln!(self.out, "a {size:x} {trace:x}")?;
+                let info = self.infos.len();
+                self.infos.insert((size, trace), info);
+                info
+            }
+        };
+        self.write_event('+', info)?;
+
+        Ok(info)
+    }
+
+    /// Writes the `mode` event of the allocation info `info`, preceded by the timestamp if it changed.
+    fn write_event(&mut self, mode: char, info: usize) -> io::Result<()> {
+        let timestamp = time::uptime().as_millis().saturating_sub(self.start) as u64;
+        if timestamp > self.timestamp {
+            writeln!(self.out, "c {timestamp:x}")?;
+            self.timestamp = timestamp;
+        }
+        writeln!(self.out, "{mode} {info:x}")
+    }
+
+    /// Returns the index of the trace of `symbol`, writing its instruction pointer and trace if it's new.
+    ///
+    /// The trace has a single frame, whose synthetic instruction pointer is its own index.
+    fn trace(&mut self, symbol: &'static str) -> io::Result<usize> {
+        if let Some(trace) = self.traces.get(symbol) {
+            return Ok(*trace);
+        }
+
+        let module = self.string(crate_name(symbol))?;
+        let function = self.string(symbol)?;
+        let trace = self.traces.len() + 1;
+        writeln!(self.out, "i {trace:x} {module:x} {function:x} 0 0")?;
+        writeln!(self.out, "t {trace:x} 0")?;
+        self.traces.insert(symbol, trace);
+
+        Ok(trace)
+    }
+
+    /// Returns the index of `s` in the string table, writing it if it's new.
+    fn string(&mut self, s: &'static str) -> io::Result<usize> {
+        if let Some(index) = self.strings.get(s) {
+            return Ok(*index);
+        }
+
+        writeln!(self.out, "s {}", single_line(s))?;
+        let index = self.strings.len() + 1;
+        self.strings.insert(s, index);
+
+        Ok(index)
+    }
+
+    /// Records the first error hit while streaming.
+    fn fail(&mut self, err: io::Error) {
+        self.error.get_or_insert(err);
+    }
+
+    /// Writes the final timestamp and finishes the compressed stream.
+    fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let timestamp = time::uptime().as_millis().saturating_sub(self.start) as u64;
+        writeln!(self.out, "c {:x}", timestamp.max(self.timestamp))?;
+        self.out.finish()?.flush()
+    }
+}
+
+/// Returns the crate of `symbol`, i.e. its first path segment, e.g. `app` for `<app::Foo as Drop>::drop`.
+///
+/// Labels without a path, e.g. `<unknown>`, are returned as they are.
+fn crate_name(symbol: &str) -> &str {
+    let path = symbol
+        .strip_prefix('<')
+        .filter(|path| path.contains("::"))
+        .unwrap_or(symbol);
+    path.split("::").next().unwrap_or(path)
+}
+
+/// Replaces the line breaks of `s`, which would break the line based format.
+fn single_line(s: &str) -> String {
+    s.replace(['\n', '\r'], " ")
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+    use std::io::Read as _;
+    use std::sync::Arc;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+
+    /// The allocations of a heaptrack file, resolved as `heaptrack_print` does.
+    #[derive(Debug, Default)]
+    struct Parsed {
+        header: Option<(u32, u32)>,
+        strings: Vec<String>,
+        ips: Vec<(usize, usize)>,
+        traces: Vec<(usize, usize)>,
+        infos: Vec<(usize, usize)>,
+        /// Allocations and deallocations, as `(size, function, allocation)`.
+        events: Vec<(usize, String, bool)>,
+    }
+
+    /// Parses a heaptrack file, checking that each index refers to an entry defined before.
+    fn parse(data: &str) -> Parsed {
+        let hex = |field: Option<&str>| usize::from_str_radix(field.unwrap(), 16).unwrap();
+        let mut parsed = Parsed::default();
+        for line in data.lines() {
+            let (mode, rest) = line.split_at(1);
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            let mut fields = rest.split(' ');
+            match mode {
+                "v" => {
+                    parsed.header = Some((hex(fields.next()) as u32, hex(fields.next()) as u32));
+                }
+                "X" => {}
+                "s" => parsed.strings.push(rest.to_string()),
+                "i" => {
+                    let _ip = hex(fields.next());
+                    let module = hex(fields.next());
+                    let function = hex(fields.next());
+                    assert!(module >= 1 && module <= parsed.strings.len(), "{line}");
+                    assert!(function >= 1 && function <= parsed.strings.len(), "{line}");
+                    assert_eq!(hex(fields.next()), 0);
+                    assert_eq!(hex(fields.next()), 0);
+                    parsed.ips.push((module, function));
+                }
+                "t" => {
+                    let ip = hex(fields.next());
+                    let parent = hex(fields.next());
+                    assert!(ip >= 1 && ip <= parsed.ips.len(), "{line}");
+                    assert!(parent <= parsed.traces.len(), "{line}");
+                    parsed.traces.push((ip, parent));
+                }
+                "a" => {
+                    let size = hex(fields.next());
+                    let trace = hex(fields.next());
+                    assert!(trace >= 1 && trace <= parsed.traces.len(), "{line}");
+                    parsed.infos.push((size, trace));
+                }
+                "+" | "-" => {
+                    let (size, trace) = parsed.infos[hex(fields.next())];
+                    let (_, function) = parsed.ips[parsed.traces[trace - 1].0 - 1];
+                    let function = parsed.strings[function - 1].clone();
+                    parsed.events.push((size, function, mode == "+"));
+                }
+                "c" => {
+                    hex(fields.next());
+                }
+                _ => panic!("unexpected line {line}"),
+            }
+        }
+
+        parsed
+    }
+
+    #[test]
+    fn test_should_write_heaptrack_file() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heaptrack.leaktracer.gz");
+        let writer = HeaptrackWriter::create(&path).expect("failed to create writer");
+        assert_eq!(
+            HeaptrackWriter::new(Vec::new()).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+
+        let allocator = LeaktracerAllocator::init();
+        // sizes no other test allocates
+        let layout = Layout::from_size_align(4243, 1).unwrap();
+        let (freed, leaked) = (fake_ptr(), fake_ptr());
+        allocator.trace(freed, layout, AllocOp::Alloc);
+        allocator.trace(leaked, layout, AllocOp::Alloc);
+        allocator.trace(freed, layout, AllocOp::Dealloc);
+        writer.finish().expect("failed to finish");
+
+        let mut data = String::new();
+        GzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut data)
+            .expect("not gzipped");
+        assert!(data.starts_with("v 10200 2\nX "), "{data}");
+        assert!(data.ends_with('\n'));
+
+        let parsed = parse(&data);
+        assert_eq!(
+            parsed.header,
+            Some((HEAPTRACK_VERSION, FILE_FORMAT_VERSION))
+        );
+        let events: Vec<_> = parsed
+            .events
+            .iter()
+            .filter(|(size, _, _)| *size == 4243)
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events
+                .iter()
+                .map(|(_, _, alloc)| *alloc)
+                .collect::<Vec<_>>(),
+            vec![true, true, false]
+        );
+        #[cfg(stack_capture)]
+        assert!(
+            events
+                .iter()
+                .all(|(_, function, _)| function.ends_with("test_should_write_heaptrack_file")),
+            "{events:?}"
+        );
+
+        // the writer is gone, so nothing else is streamed
+        assert!(STREAM.lock().unwrap().is_none());
+        allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
+    }
+
+    /// A writer appending to a buffer shared with the test.
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_should_match_golden_heaptrack_file() {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let mut stream =
+            Stream::new(Box::new(SharedBuffer(buffer.clone()))).expect("failed to create stream");
+        stream.alloc(0x1000, 64, "app::main");
+        stream.alloc(0x2000, 64, "app::main");
+        stream.alloc(0x3000, 4096, "<app::Foo as core::ops::Drop>::drop");
+        stream.alloc(0x4000, 24, "app::parse::<u32>");
+        stream.alloc(0x5000, 8, "<unknown>");
+        stream.dealloc(0x1000);
+        stream.dealloc(0x3000);
+        // not streamed
+        stream.dealloc(0x6000);
+        stream.alloc(0x1000, 4096, "<app::Foo as core::ops::Drop>::drop");
+        stream.finish().expect("failed to finish");
+
+        let mut data = String::new();
+        GzDecoder::new(buffer.lock().unwrap().as_slice())
+            .read_to_string(&mut data)
+            .expect("not gzipped");
+        // the command line and the timestamps depend on the run
+        let normalized: Vec<_> = data
+            .lines()
+            .filter(|line| !line.starts_with("c "))
+            .map(|line| {
+                if line.starts_with("X ") {
+                    "X <command line>"
+                } else {
+                    line
+                }
+            })
+            .collect();
+        // checked line by line against the interpreted format of heaptrack, see the module docs
+        let golden: Vec<_> = include_str!("../tests/fixtures/heaptrack.txt")
+            .lines()
+            .collect();
+        assert_eq!(normalized, golden);
+        assert!(data.lines().last().unwrap().starts_with("c "), "{data}");
+
+        let parsed = parse(&data);
+        assert_eq!(
+            parsed.events,
+            vec![
+                (64, "app::main".to_string(), true),
+                (64, "app::main".to_string(), true),
+                (
+                    4096,
+                    "<app::Foo as core::ops::Drop>::drop".to_string(),
+                    true
+                ),
+                (24, "app::parse::<u32>".to_string(), true),
+                (8, "<unknown>".to_string(), true),
+                (64, "app::main".to_string(), false),
+                (
+                    4096,
+                    "<app::Foo as core::ops::Drop>::drop".to_string(),
+                    false
+                ),
+                (
+                    4096,
+                    "<app::Foo as core::ops::Drop>::drop".to_string(),
+                    true
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_get_crate_name() {
+        assert_eq!(crate_name("app::main"), "app");
+        assert_eq!(crate_name("<app::Foo as core::ops::Drop>::drop"), "app");
+        assert_eq!(crate_name("<unknown>"), "<unknown>");
+    }
+}
@@ -0,0 +1,310 @@
+//! User callbacks invoked by the allocator.
+//!
+//! Callbacks are invoked from inside the allocator, with the symbol table locked and tracing suppressed,
+//! so they can't access the symbol table, except through the reference they are given:
+//! [`crate::with_symbol_table`] and the functions built on it fail with [`crate::TableError::WouldDeadlock`].
+//! They are read without taking a lock, and their panics are caught, since they must not unwind out of the allocator.
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::atomic_ref::AtomicRef;
+use crate::symbols::SymbolTable;
+
+/// Callback invoked on each deallocation of an untracked pointer.
+static UNTRACKED_DEALLOC_HOOK: AtomicRef<fn(usize)> = AtomicRef::new();
+
+/// A callback invoked with a symbol name and its live bytes.
+type SymbolHook = fn(&'static str, usize);
+
+/// Callback invoked when the live bytes of a symbol exceed [`THRESHOLD_LIMIT`].
+static THRESHOLD_HOOK: AtomicRef<SymbolHook> = AtomicRef::new();
+
+/// Live bytes of a symbol above which the threshold callback fires, `usize::MAX` if unset.
+///
+/// Checked without taking the lock on each allocation.
+static THRESHOLD_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Live bytes of a symbol below which the threshold callback is re-armed for it.
+static THRESHOLD_REARM_BELOW: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Callback invoked every given number of allocations.
+static PERIODIC_HOOK: AtomicRef<PeriodicHook> = AtomicRef::new();
+
+/// A callback invoked whenever the total allocation count crosses a multiple of `every`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PeriodicHook {
+    every: usize,
+    hook: fn(&SymbolTable),
+}
+
+impl PeriodicHook {
+    /// Invokes the callback if the total allocation count crossed a multiple of `every` going from `before` to `after`.
+    ///
+    /// The panics of the callback are contained, since they must not unwind out of the allocator.
+    fn fire(&self, before: usize, after: usize, table: &SymbolTable) {
+        if before / self.every != after / self.every {
+            contain(|| (self.hook)(table));
+        }
+    }
+}
+
+/// Sets a callback invoked with the size of each deallocation of a pointer missing from the pointer registry.
+///
+/// Untracked pointers are usually allocated before the symbol table was initialized,
+/// so this helps quantifying how much accounting drift comes from untracked frees.
+///
+/// The callback is invoked with tracing suppressed and the symbol table locked, so it can't access the symbol table:
+/// [`crate::with_symbol_table`] fails with [`crate::TableError::WouldDeadlock`].
+pub fn on_untracked_dealloc(hook: fn(usize)) {
+    UNTRACKED_DEALLOC_HOOK.store_value(Some(hook));
+}
+
+/// Invokes the untracked deallocation hook, if any.
+pub(crate) fn untracked_dealloc(size: usize) {
+    if let Some(hook) = UNTRACKED_DEALLOC_HOOK.load() {
+        contain(|| hook(size));
+    }
+}
+
+/// Sets a callback invoked with the symbol name and its live bytes when the live bytes of a symbol exceed `limit`.
+///
+/// After firing, the callback is disarmed for that symbol until its live bytes drop below `rearm_below`,
+/// so a symbol oscillating around `limit` doesn't fire on every crossing.
+/// `rearm_below` is capped at `limit`, so passing `limit` fires on every crossing.
+///
+/// The callback is invoked with tracing suppressed and the symbol table locked, so it can't access the symbol table:
+/// [`crate::with_symbol_table`] fails with [`crate::TableError::WouldDeadlock`].
+pub fn on_threshold_exceeded(limit: usize, rearm_below: usize, hook: fn(&'static str, usize)) {
+    THRESHOLD_HOOK.store_value(Some(hook));
+    THRESHOLD_REARM_BELOW.store(rearm_below.min(limit), Ordering::Relaxed);
+    THRESHOLD_LIMIT.store(limit, Ordering::Relaxed);
+}
+
+/// Returns whether `allocated` live bytes exceed the threshold limit.
+pub(crate) fn threshold_exceeded(allocated: usize) -> bool {
+    allocated > THRESHOLD_LIMIT.load(Ordering::Relaxed)
+}
+
+/// Returns whether `allocated` live bytes re-arm the threshold callback.
+pub(crate) fn threshold_rearmed(allocated: usize) -> bool {
+    allocated < THRESHOLD_REARM_BELOW.load(Ordering::Relaxed)
+}
+
+/// Invokes the threshold hook, if any, for the symbol `name` with `allocated` live bytes.
+pub(crate) fn threshold(name: &'static str, allocated: usize) {
+    if let Some(hook) = THRESHOLD_HOOK.load() {
+        contain(|| hook(name, allocated));
+    }
+}
+
+/// Sets a callback invoked with the symbol table every `every` traced allocations, e.g. to dump the stats periodically
+/// without running a timer thread. `every` equal to `0` removes the callback.
+///
+/// The callback is invoked by the allocation which makes the total allocation count (see [`crate::QuickStats`])
+/// cross a multiple of `every`; with sampling, an allocation may count more than one, so a multiple may be skipped.
+///
+/// The callback is invoked from inside the allocator, on the allocating thread, with tracing suppressed
+/// and the symbol table locked: it can read the table it's given, but it can't access the symbol table
+/// in any other way (e.g. [`crate::with_symbol_table`] fails with [`crate::TableError::WouldDeadlock`]).
+/// Its own allocations are not traced, and it should be quick, since it blocks all the traced allocations.
+/// If it panics, the panic is caught and the allocation goes on.
+pub fn set_periodic_hook(every: usize, hook: fn(&SymbolTable)) {
+    PERIODIC_HOOK.store_value((every > 0).then_some(PeriodicHook { every, hook }));
+}
+
+/// Invokes the periodic hook, if any, for the total allocation count going from `before` to `after`.
+pub(crate) fn periodic(before: usize, after: usize, table: &SymbolTable) {
+    if let Some(hook) = PERIODIC_HOOK.load() {
+        hook.fire(before, after, table);
+    }
+}
+
+/// Maximum number of callbacks which can be registered on a [`crate::LeaktracerAllocator`],
+/// e.g. with [`crate::LeaktracerAllocator::on_alloc`].
+pub const MAX_ALLOC_CALLBACKS: usize = 8;
+
+/// A callback invoked with the size of an allocation or deallocation and the symbol which made the allocation.
+pub type AllocHook = fn(usize, &'static str);
+
+/// A callback registered on a [`crate::LeaktracerAllocator`].
+#[derive(Debug, Clone, Copy)]
+enum AllocCallback {
+    Alloc(AllocHook),
+    Dealloc(AllocHook),
+    LargeAlloc { threshold: usize, hook: AllocHook },
+}
+
+/// The callbacks registered on a [`crate::LeaktracerAllocator`], in a fixed array so dispatching them
+/// never allocates.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AllocCallbacks {
+    slots: [Option<AllocCallback>; MAX_ALLOC_CALLBACKS],
+    /// Whether any [`AllocCallback::Dealloc`] is registered, so the deallocations look up the symbol only if needed.
+    dealloc: bool,
+}
+
+impl AllocCallbacks {
+    pub(crate) const fn new() -> Self {
+        Self {
+            slots: [None; MAX_ALLOC_CALLBACKS],
+            dealloc: false,
+        }
+    }
+
+    /// Registers a callback invoked on each traced allocation.
+    pub(crate) const fn with_alloc(self, hook: AllocHook) -> Self {
+        self.with(AllocCallback::Alloc(hook))
+    }
+
+    /// Registers a callback invoked on each traced deallocation.
+    pub(crate) const fn with_dealloc(mut self, hook: AllocHook) -> Self {
+        self.dealloc = true;
+        self.with(AllocCallback::Dealloc(hook))
+    }
+
+    /// Registers a callback invoked on each traced allocation of at least `threshold` bytes.
+    pub(crate) const fn with_large_alloc(self, threshold: usize, hook: AllocHook) -> Self {
+        self.with(AllocCallback::LargeAlloc { threshold, hook })
+    }
+
+    /// Stores `callback` in the first free slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all the [`MAX_ALLOC_CALLBACKS`] slots are taken.
+    const fn with(mut self, callback: AllocCallback) -> Self {
+        let mut i = 0;
+        while i < MAX_ALLOC_CALLBACKS {
+            if self.slots[i].is_none() {
+                self.slots[i] = Some(callback);
+                return self;
+            }
+            i += 1;
+        }
+        panic!("too many allocator callbacks");
+    }
+
+    /// Returns whether any deallocation callback is registered.
+    pub(crate) fn has_dealloc(&self) -> bool {
+        self.dealloc
+    }
+
+    /// Invokes the allocation callbacks for an allocation of `size` bytes made by `symbol`.
+    pub(crate) fn alloc(&self, size: usize, symbol: &'static str) {
+        for callback in self.slots.iter().map_while(|slot| *slot) {
+            match callback {
+                AllocCallback::Alloc(hook) => invoke(hook, size, symbol),
+                AllocCallback::LargeAlloc { threshold, hook } if size >= threshold => {
+                    invoke(hook, size, symbol)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Invokes the deallocation callbacks for a deallocation of `size` bytes allocated by `symbol`.
+    pub(crate) fn dealloc(&self, size: usize, symbol: &'static str) {
+        for callback in self.slots.iter().map_while(|slot| *slot) {
+            if let AllocCallback::Dealloc(hook) = callback {
+                invoke(hook, size, symbol);
+            }
+        }
+    }
+}
+
+/// Invokes `hook`, containing its panics, which must not unwind out of the allocator.
+fn invoke(hook: AllocHook, size: usize, symbol: &'static str) {
+    contain(|| hook(size, symbol));
+}
+
+/// Runs the user callback `f` from inside the allocator, containing its panics, which must not unwind out of it.
+///
+/// The state the callback is given is only read by it, so it's consistent even if it panics.
+pub(crate) fn contain(f: impl FnOnce()) {
+    let _ = catch_unwind(AssertUnwindSafe(f));
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn test_should_fire_periodic_hook_every_n_allocations() {
+        static FIRED: AtomicUsize = AtomicUsize::new(0);
+
+        let hook = PeriodicHook {
+            every: 3,
+            hook: |_| {
+                FIRED.fetch_add(1, Ordering::Relaxed);
+            },
+        };
+        let table = SymbolTable::new(10, &["leaktracer"]);
+        let fired_at: Vec<usize> = (1..=7)
+            .filter(|allocation| {
+                let fired = FIRED.load(Ordering::Relaxed);
+                hook.fire(allocation - 1, *allocation, &table);
+                FIRED.load(Ordering::Relaxed) > fired
+            })
+            .collect();
+        assert_eq!(fired_at, vec![3, 6]);
+
+        // an allocation counting more than one fires once
+        hook.fire(7, 13, &table);
+        assert_eq!(FIRED.load(Ordering::Relaxed), 3);
+
+        // a panicking callback doesn't unwind out of the allocator
+        let hook = PeriodicHook {
+            every: 1,
+            hook: |_| panic!("contained"),
+        };
+        hook.fire(0, 1, &table);
+    }
+
+    #[test]
+    fn test_should_dispatch_alloc_callbacks() {
+        thread_local! {
+            static CALLS: std::cell::RefCell<Vec<(&'static str, usize, &'static str)>> =
+                const { std::cell::RefCell::new(Vec::new()) };
+        }
+
+        let callbacks = AllocCallbacks::new()
+            .with_alloc(|size, symbol| {
+                CALLS.with_borrow_mut(|calls| calls.push(("alloc", size, symbol)))
+            })
+            .with_large_alloc(1024, |size, symbol| {
+                CALLS.with_borrow_mut(|calls| calls.push(("large", size, symbol)))
+            })
+            .with_alloc(|_, _| panic!("contained"))
+            .with_dealloc(|size, symbol| {
+                CALLS.with_borrow_mut(|calls| calls.push(("dealloc", size, symbol)))
+            });
+        assert!(callbacks.has_dealloc());
+
+        callbacks.alloc(64, "app::small");
+        callbacks.alloc(1024, "app::large");
+        callbacks.dealloc(64, "app::small");
+
+        assert_eq!(
+            CALLS.take(),
+            [
+                ("alloc", 64, "app::small"),
+                ("alloc", 1024, "app::large"),
+                ("large", 1024, "app::large"),
+                ("dealloc", 64, "app::small"),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "too many allocator callbacks")]
+    fn test_should_bound_alloc_callbacks() {
+        let mut callbacks = AllocCallbacks::new();
+        for _ in 0..=MAX_ALLOC_CALLBACKS {
+            callbacks = callbacks.with_alloc(|_, _| {});
+        }
+    }
+}
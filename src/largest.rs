@@ -0,0 +1,191 @@
+//! Leaderboard of the largest single allocations ever traced.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Number of allocations kept in the leaderboard.
+pub(crate) const LARGEST_ALLOCATIONS_CAPACITY: usize = 32;
+
+/// The largest allocations traced so far.
+///
+/// This lock is independent from the symbol table lock, and it's never held while acquiring another lock.
+pub(crate) static LARGEST: Mutex<Option<Largest>> = Mutex::new(None);
+
+/// Size of the smallest allocation in the leaderboard once it's full, `0` otherwise.
+///
+/// Allocations not exceeding it are discarded without taking the lock.
+static MIN_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// An allocation in the leaderboard.
+///
+/// Ordered by size, then by recency, so the oldest allocation of the same size is evicted first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    size: usize,
+    /// Inverted, so among allocations of the same size the oldest is the smallest.
+    at: Reverse<Duration>,
+    symbol: &'static str,
+}
+
+/// A bounded min-heap of the largest allocations.
+#[derive(Debug)]
+pub(crate) struct Largest {
+    capacity: usize,
+    heap: BinaryHeap<Reverse<Entry>>,
+}
+
+impl Largest {
+    /// Creates an empty leaderboard keeping up to `capacity` allocations.
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            heap: BinaryHeap::with_capacity(capacity + 1),
+        }
+    }
+
+    /// Records an allocation of `size` bytes made by `symbol` at `at`, evicting the smallest one if full.
+    ///
+    /// Returns the size of the smallest allocation in the leaderboard if it's full, `0` otherwise.
+    fn record(&mut self, size: usize, symbol: &'static str, at: Duration) -> usize {
+        let entry = Entry {
+            size,
+            at: Reverse(at),
+            symbol,
+        };
+        if self.heap.len() < self.capacity {
+            self.heap.push(Reverse(entry));
+        } else if self
+            .heap
+            .peek()
+            .is_some_and(|Reverse(min)| min.size < entry.size)
+        {
+            self.heap.pop();
+            self.heap.push(Reverse(entry));
+        }
+
+        match self.heap.peek() {
+            Some(Reverse(min)) if self.heap.len() == self.capacity => min.size,
+            _ => 0,
+        }
+    }
+
+    /// Returns the `n` largest allocations as `(size, symbol)`, from the largest.
+    fn top(&self, n: usize) -> Vec<(usize, &'static str)> {
+        let mut entries: Vec<_> = self.heap.iter().map(|Reverse(entry)| *entry).collect();
+        entries.sort_unstable_by(|a, b| b.cmp(a));
+        entries
+            .into_iter()
+            .take(n)
+            .map(|entry| (entry.size, entry.symbol))
+            .collect()
+    }
+}
+
+/// Records a traced allocation of `size` bytes made by `symbol` into the leaderboard.
+///
+/// Must be called with tracing suppressed, since updating the heap may allocate.
+pub(crate) fn record(size: usize, symbol: &'static str) {
+    if size <= MIN_SIZE.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let Ok(mut largest) = LARGEST.lock() else {
+        return;
+    };
+    let min = largest
+        .get_or_insert_with(|| Largest::new(LARGEST_ALLOCATIONS_CAPACITY))
+        .record(size, symbol, crate::uptime());
+    MIN_SIZE.store(min, Ordering::Relaxed);
+}
+
+/// Forgets all the allocations in the leaderboard.
+pub(crate) fn clear() {
+    if let Ok(mut largest) = LARGEST.lock() {
+        *largest = None;
+    }
+    MIN_SIZE.store(0, Ordering::Relaxed);
+}
+
+/// Returns the `n` largest single allocations traced so far as `(size, symbol)`, from the largest.
+///
+/// The leaderboard keeps the 32 largest allocations, including the ones already freed, so `n` is capped at 32.
+/// It's meant to catch giant one-off allocations, which barely move the per-symbol totals.
+/// Allocations skipped by sampling are not in the leaderboard.
+pub fn largest_allocations(n: usize) -> Vec<(usize, &'static str)> {
+    crate::alloc::untraced(|| {
+        LARGEST
+            .lock()
+            .ok()
+            .and_then(|largest| largest.as_ref().map(|largest| largest.top(n)))
+            .unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_keep_largest_allocations() {
+        let mut largest = Largest::new(3);
+        let mut min = 0;
+        for (i, size) in [64, 4096, 16, 1024, 8192, 512, 4096]
+            .into_iter()
+            .enumerate()
+        {
+            min = largest.record(size, "app::main", Duration::from_millis(i as u64));
+        }
+
+        assert_eq!(min, 4096);
+        assert_eq!(
+            largest.top(3),
+            vec![
+                (8192, "app::main"),
+                (4096, "app::main"),
+                (4096, "app::main")
+            ]
+        );
+        assert_eq!(largest.top(1), vec![(8192, "app::main")]);
+        assert_eq!(largest.top(10).len(), 3);
+    }
+
+    #[test]
+    fn test_should_not_report_full_until_capacity() {
+        let mut largest = Largest::new(3);
+        assert_eq!(largest.record(100, "a", Duration::ZERO), 0);
+        assert_eq!(largest.record(200, "b", Duration::ZERO), 0);
+        assert_eq!(largest.record(50, "c", Duration::ZERO), 50);
+        // not larger than the minimum, so it's discarded
+        assert_eq!(largest.record(50, "d", Duration::ZERO), 50);
+        assert_eq!(largest.top(3), vec![(200, "b"), (100, "a"), (50, "c")]);
+    }
+
+    #[test]
+    fn test_should_record_traced_allocations() {
+        use std::alloc::Layout;
+
+        use crate::LeaktracerAllocator;
+        use crate::alloc::AllocOp;
+
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        // bigger than any allocation traced by the other tests
+        let size = isize::MAX as usize >> 8;
+        let ptr = crate::test_utils::fake_ptr();
+        let layout = Layout::from_size_align(size, 8).unwrap();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        // freed allocations stay in the leaderboard
+        assert!(
+            largest_allocations(LARGEST_ALLOCATIONS_CAPACITY)
+                .iter()
+                .any(|(largest, _)| *largest == size)
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Leak regression checks around a test, the runtime of the `#[leak_test]` attribute (see the `macros` feature).
+
+use std::collections::HashMap;
+
+use crate::report::format_bytes;
+use crate::snapshot::{self, SymbolTableSnapshot};
+
+/// Checks that the live bytes attributed to the traced modules didn't grow across a section of code, e.g. a test.
+///
+/// [`LeakCheck::start`] takes a snapshot of the symbol table, and [`LeakCheck::finish`] takes another one
+/// and panics with the symbols which grew, if the live bytes of the traced modules grew by more than the tolerance.
+/// The `#[leak_test]` attribute wraps a test with them, but they can be used directly too:
+///
+/// ```rust
+/// #[global_allocator]
+/// static ALLOCATOR: leaktracer::LeaktracerAllocator = leaktracer::LeaktracerAllocator::init();
+///
+/// let check = leaktracer::LeakCheck::start(&["rust_out"], 0);
+/// let buffer = vec![0u8; 1024];
+/// drop(buffer);
+/// check.finish();
+/// ```
+///
+/// The symbol table is global, so the allocations made meanwhile by the other threads count too,
+/// including the ones of the tests running in parallel: run the leak tests with `--test-threads=1`,
+/// in their own test binary, or give them a tolerance.
+/// Without the call stacks (see the counter-only mode), the growth of all the traced live bytes is checked instead.
+#[derive(Debug)]
+pub struct LeakCheck {
+    before: SymbolTableSnapshot,
+    tolerance: usize,
+}
+
+impl LeakCheck {
+    /// Takes the snapshot the live bytes are compared to by [`LeakCheck::finish`],
+    /// initializing the symbol table with `modules` if needed (see [`crate::init_symbol_table`]).
+    ///
+    /// The live bytes may grow by `tolerance` bytes at most.
+    pub fn start(modules: &'static [&'static str], tolerance: usize) -> Self {
+        crate::init_symbol_table(modules);
+        Self {
+            before: take_snapshot(),
+            tolerance,
+        }
+    }
+
+    /// Returns the net growth of the live bytes attributed to the traced modules since [`LeakCheck::start`],
+    /// negative if more bytes were freed than allocated.
+    pub fn growth(&self) -> i64 {
+        growth(&self.before, &take_snapshot())
+    }
+
+    /// Panics with the symbols which grew if the live bytes attributed to the traced modules grew
+    /// by more than the tolerance since [`LeakCheck::start`].
+    pub fn finish(self) {
+        let after = take_snapshot();
+        let growth = growth(&self.before, &after);
+        if growth > self.tolerance as i64 {
+            panic!(
+                "{}",
+                render_diff(&self.before, &after, growth, self.tolerance)
+            );
+        }
+    }
+}
+
+/// Takes a snapshot of the symbol table, panicking if its lock can't be taken.
+fn take_snapshot() -> SymbolTableSnapshot {
+    snapshot::snapshot().expect("leak check: failed to take snapshot")
+}
+
+/// Returns the live bytes of each symbol of `snapshot` belonging to the traced modules.
+fn module_bytes(snapshot: &SymbolTableSnapshot) -> HashMap<&'static str, usize> {
+    let modules = crate::alloc::configured_modules().unwrap_or_default();
+    snapshot
+        .symbols
+        .iter()
+        .filter(|symbol| crate::symbols::module_of(&modules, symbol.name).is_some())
+        .map(|symbol| (symbol.name, symbol.allocated))
+        .collect()
+}
+
+/// Returns the net growth of the live bytes of the traced modules from `before` to `after`.
+fn growth(before: &SymbolTableSnapshot, after: &SymbolTableSnapshot) -> i64 {
+    if cfg!(stack_capture) {
+        let sum = |snapshot| module_bytes(snapshot).values().sum::<usize>() as i64;
+        sum(after) - sum(before)
+    } else {
+        after.allocated as i64 - before.allocated as i64
+    }
+}
+
+/// Renders the failure message of a leak check, with the symbols whose live bytes changed, the largest growth first.
+fn render_diff(
+    before: &SymbolTableSnapshot,
+    after: &SymbolTableSnapshot,
+    growth: i64,
+    tolerance: usize,
+) -> String {
+    let before = module_bytes(before);
+    let after = module_bytes(after);
+    let mut changed: Vec<(&'static str, i64)> = after
+        .iter()
+        .map(|(name, bytes)| {
+            (
+                *name,
+                *bytes as i64 - *before.get(name).unwrap_or(&0) as i64,
+            )
+        })
+        .chain(
+            before
+                .iter()
+                .filter(|(name, _)| !after.contains_key(*name))
+                .map(|(name, bytes)| (*name, -(*bytes as i64))),
+        )
+        .filter(|(_, delta)| *delta != 0)
+        .collect();
+    changed.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+
+    let mut message = format!(
+        "leak check failed: live bytes grew by {} ({growth} bytes), over the tolerance of {tolerance} bytes",
+        format_bytes(growth.unsigned_abs() as usize)
+    );
+    for (name, delta) in changed {
+        let sign = if delta < 0 { '-' } else { '+' };
+        let bytes = format!("{sign}{}", format_bytes(delta.unsigned_abs() as usize));
+        message.push_str(&format!("\n  {bytes:>11}  {name}"));
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::{AlignmentStats, SymbolSnapshot};
+
+    fn table(symbols: &[(&'static str, usize)]) -> SymbolTableSnapshot {
+        SymbolTableSnapshot {
+            allocated: symbols.iter().map(|(_, allocated)| allocated).sum(),
+            symbols: symbols
+                .iter()
+                .map(|(name, allocated)| SymbolSnapshot {
+                    name,
+                    allocated: *allocated,
+                    count: 1,
+                    peak: *allocated,
+                    peak_at: Default::default(),
+                    total_allocated: *allocated,
+                    total_count: 1,
+                    turnover: 0,
+                    inclusive_allocated: *allocated,
+                    recent_allocated: 0,
+                    recent_count: 0,
+                    reallocs: 0,
+                    realloc_moves: 0,
+                    bytes_grown: 0,
+                    bytes_shrunk: 0,
+                    alignment: AlignmentStats::default(),
+                    size_cardinality: Default::default(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_should_compute_growth_of_traced_modules() {
+        crate::init_symbol_table(&["leaktracer"]);
+        let before = table(&[
+            ("leaktracer::test::leak_check::kept", 1024),
+            ("leaktracer::test::leak_check::freed", 512),
+            ("<unknown>", 64),
+        ]);
+        let after = table(&[
+            ("leaktracer::test::leak_check::kept", 4096),
+            ("leaktracer::test::leak_check::new", 256),
+            ("<unknown>", 1 << 20),
+        ]);
+
+        if cfg!(stack_capture) {
+            // the symbols out of the traced modules don't count
+            assert_eq!(growth(&before, &after), 3072 + 256 - 512);
+        } else {
+            assert_eq!(growth(&before, &after), (1 << 20) + 4096 + 256 - 1600);
+        }
+        assert_eq!(growth(&after, &after), 0);
+    }
+
+    #[test]
+    fn test_should_render_diff_of_changed_symbols() {
+        crate::init_symbol_table(&["leaktracer"]);
+        let before = table(&[
+            ("leaktracer::test::leak_check::kept", 1024),
+            ("leaktracer::test::leak_check::same", 64),
+            ("leaktracer::test::leak_check::freed", 512),
+        ]);
+        let after = table(&[
+            ("leaktracer::test::leak_check::kept", 4096),
+            ("leaktracer::test::leak_check::same", 64),
+            ("leaktracer::test::leak_check::new", 256),
+        ]);
+
+        assert_eq!(
+            render_diff(&before, &after, 2816, 1024),
+            "leak check failed: live bytes grew by 2.8 KiB (2816 bytes), over the tolerance of 1024 bytes\n     \
+             +3.0 KiB  leaktracer::test::leak_check::kept\n       \
+             +256 B  leaktracer::test::leak_check::new\n       \
+             -512 B  leaktracer::test::leak_check::freed"
+        );
+    }
+}
@@ -60,6 +60,20 @@
 //! is quite *polluted* by other non-relevant calls (such as [`std::alloc`], [`std::vec`], etc.),
 //! so you need to specify which crates you want to trace.
 //!
+//! Modules loaded later, such as plugins, can be traced with [`add_traced_module`].
+//! [`traced_modules`] returns the modules being traced, e.g. to check the ones loaded from a configuration file.
+//! The current list is returned by [`SymbolTable::modules`].
+//!
+//! Only the first call to [`init_symbol_table`] initializes the table, and it returns whether it did,
+//! so a library calling it can't know if the application will trace other modules. Libraries should call
+//! [`init_symbol_table`], while the application can call [`reconfigure`] to replace the module list in any case:
+//!
+//! ```rust
+//! if !leaktracer::init_symbol_table(&["my_crate_name"]) {
+//!     leaktracer::reconfigure(&["my_crate_name"], false).expect("Failed to access symbol table");
+//! }
+//! ```
+//!
 //! ### Accessing the stats
 //!
 //! Of course, once initialized you want to access the stats, to see how many allocations were made, and where they were made.
@@ -80,6 +94,26 @@
 //! }).expect("Failed to access symbol table");
 //! ```
 //!
+//! To look up a single symbol, [`symbol_stats`] returns an owned copy of its stats:
+//!
+//! ```rust
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! if let Some(stats) = leaktracer::symbol_stats("my_crate_name::load_config") {
+//!     println!("Allocated: {}, Count: {}", stats.allocated, stats.count);
+//! }
+//! ```
+//!
+//! For the top symbols, [`stats`] returns plain [`SymbolStats`], sorted by a [`SortKey`] and limited:
+//!
+//! ```rust
+//! use leaktracer::SortKey;
+//!
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! let top = leaktracer::stats(SortKey::Allocated, Some(10));
+//! ```
+//!
 //! You can also access the full amount of memory allocated and the total count of allocations by using the [`LeaktracerAllocator`] methods:
 //!
 //! ```rust
@@ -98,6 +132,522 @@
 //! }
 //! ```
 //!
+//! [`sum_symbol_allocated`] sums the bytes attributed to the symbols, which can be compared with [`global_allocated`]
+//! to reveal how many bytes are not attributed to any symbol. [`attribution_coverage`] tells the fraction of the live bytes
+//! attributed to a function of the traced modules, as a quality metric of the module configuration.
+//!
+//! Deallocations are always attributed to the symbol which made the allocation, since the symbol table keeps a registry
+//! of the live allocations. The registry can be inspected with [`live_allocations`], which tells exactly what's still
+//! allocated and who allocated it:
+//!
+//! ```rust
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! for (ptr, size, symbol) in leaktracer::live_allocations().expect("Failed to access symbol table") {
+//!     println!("{ptr:#x}: {size} bytes allocated by {symbol}");
+//! }
+//! ```
+//!
+//! Pointers missing from the registry, usually allocated before the symbol table was initialized, are not attributed
+//! to any symbol when freed. Use [`on_untracked_dealloc`] to get notified of these deallocations.
+//! The allocator calls with a zero-sized layout, which only unsafe code or FFI can make, are not traced either:
+//! [`zero_size_events`] counts them.
+//! The allocations made during the thread teardown, once the tracer thread-locals are destroyed (e.g. by the
+//! destructors of other thread-locals), can't be traced safely: they are forwarded untraced, and [`tls_fallbacks`]
+//! counts them.
+//!
+//! To dump the stats periodically without a timer thread, [`set_periodic_hook`] sets a callback invoked
+//! with the symbol table every N traced allocations. It runs inside the allocator with the table locked,
+//! so it must only read the table it's given: accessing the table in any other way, e.g. with [`with_symbol_table`],
+//! fails with [`TableError::WouldDeadlock`] rather than deadlocking:
+//!
+//! ```rust
+//! leaktracer::set_periodic_hook(10_000, |table| {
+//!     eprintln!("{} bytes in {} symbols", table.total_allocated(), table.iter().count());
+//! });
+//! ```
+//!
+//! To get alerted when a symbol grows too much, [`on_threshold_exceeded`] sets a callback fired when the live bytes
+//! of a symbol exceed a limit. It fires once, then it's re-armed only after the symbol drops below a lower threshold,
+//! so a symbol hovering around the limit doesn't flood the alerts:
+//!
+//! ```rust
+//! leaktracer::on_threshold_exceeded(64 * 1024 * 1024, 48 * 1024 * 1024, |symbol, allocated| {
+//!     eprintln!("{symbol} is holding {allocated} bytes");
+//! });
+//! ```
+//!
+//! Per-event callbacks are registered on the allocator itself, with [`LeaktracerAllocator::on_alloc`],
+//! [`LeaktracerAllocator::on_dealloc`] and [`LeaktracerAllocator::on_large_alloc`], e.g. to abort a test
+//! on any allocation from a forbidden module:
+//!
+//! ```rust
+//! use leaktracer::LeaktracerAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().on_alloc(|size, symbol| {
+//!     if symbol.starts_with("my_crate::hot_path") {
+//!         eprintln!("{symbol} allocated {size} bytes");
+//!         std::process::abort();
+//!     }
+//! });
+//! ```
+//!
+//! In debug builds of code with `unsafe` blocks, the strict mode ([`LeaktracerAllocator::with_strict`]) can be enabled
+//! to detect double frees and deallocations with a mismatched layout, which normally surface as heisencrashes.
+//! The quarantine ([`LeaktracerAllocator::with_quarantine`]) holds the memory of the last freed pointers back from
+//! the system allocator, so their addresses can't be reused and freeing them again is always detected as a double free.
+//! In tests, the strict accounting ([`LeaktracerAllocator::with_strict_accounting`]) records the deallocations which would
+//! drive the counters of a symbol below zero into [`accounting_violations`], and panics in debug builds.
+//!
+//! The allocator also splits the traced allocations into [`SizeClass`]es, which tells whether the heap is made of
+//! many small objects or a few giant buffers, with [`LeaktracerAllocator::size_class_stats`].
+//!
+//! Giant one-off allocations barely move the per-symbol totals: [`largest_allocations`] returns the largest single
+//! allocations ever traced, with the symbol which made them, even if they have been freed since.
+//!
+//! Each [`Symbol`] also keeps a histogram of its allocation sizes ([`Symbol::histogram`]), bucketed by powers of two
+//! by default. Custom bucket boundaries can be set with [`set_histogram_buckets`].
+//! [`Symbol::ema_size`] returns the typical allocation size, as an exponential moving average whose smoothing
+//! factor can be set with [`set_ema_alpha`]. [`Symbol::recent`] returns the bytes and count allocated in the last
+//! seconds, up to [`RECENT_WINDOW`], from sliding window counters, which snapshots carry as
+//! [`SymbolSnapshot::recent_allocated`] and [`SymbolSnapshot::recent_count`].
+//!
+//! The same call stack resolution used to attribute allocations is available as [`caller_symbol`],
+//! which returns the first function on the call stack belonging to the traced modules.
+//! When both the application and a traced dependency are on the call stack, the dependency's inner frame
+//! is the first one: [`set_module_priority`] ranks the traced modules, so the allocations are attributed
+//! to the frames of the highest priority module instead. When the matching frame is a thin allocation helper,
+//! [`set_attribution_offset`] shifts the attribution up the call stack, to whoever called it.
+//!
+//! Allocations made by C code called via FFI have no frame belonging to the traced modules, so they're attributed to
+//! `<unknown>`, like the resolution failures. With [`set_ffi_label`], the allocations whose call stack contains frames
+//! without a symbol name (typical of FFI code) are attributed to the given label instead.
+//!
+//! Symbols are keyed by their demangled name, without the trailing hash. Tools which do their own demangling
+//! can key them by the raw mangled name (e.g. `_ZN...`) instead, with [`set_keep_mangled`]. In generic code the same
+//! allocation site may be split across many monomorphizations (e.g. `app::parse::<u32>` and `app::parse::<String>`):
+//! [`set_collapse_generics`] strips the generic parameters, merging them into one symbol.
+//! For anything else, e.g. shortening long paths, [`set_name_rewriter`] sets a function rewriting the names
+//! before they become keys.
+//!
+//! Each allocation is attributed to a single function, so the callers of the allocating functions show no bytes.
+//! With [`set_inclusive_accounting`], each allocation is also credited to every function of the traced modules
+//! on its call stack, as [`Symbol::inclusive_allocated`], so e.g. `main` accounts for everything allocated beneath it.
+//!
+//! Arbitrary instruction pointers can be symbolized with [`resolve_addr`], which returns the same names used as keys
+//! in the symbol table, along with the source file and line when debug info is available.
+//!
+//! Allocations made during the startup phase, such as the lazy statics initialization, are usually one-time setup
+//! rather than leaks. With [`LeaktracerAllocator::with_startup_bucket`], the allocations made before
+//! [`mark_startup_complete`] are attributed to the `<startup>` symbol, separating the warmup from the steady state.
+//!
+//! The call stack resolution depends on the build, so the tests asserting on the symbols can use
+//! [`set_fixed_symbol`] instead, which attributes every allocation to a given name without capturing the call stack.
+//!
+//! ### Tags
+//!
+//! Call stacks can't express logical phases, such as loading the configuration or serving a given request.
+//! With [`push_tag`] and [`pop_tag`] (or the [`TagGuard`]), each thread keeps a stack of tags, and the allocations
+//! made while a tag is active are also accounted to it. Only the innermost tag is accounted.
+//! The per-tag stats are returned by [`tag_stats`]:
+//!
+//! ```rust
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! {
+//!     let _tag = leaktracer::TagGuard::new("phase:load");
+//!     let config = vec![0u8; 1024];
+//! }
+//!
+//! for tag in leaktracer::tag_stats().expect("Failed to access symbol table") {
+//!     println!("{}: {} bytes in {} allocations", tag.name, tag.allocated, tag.count);
+//! }
+//! ```
+//!
+//! With the `tracing` feature enabled, `tracing_layer` returns a `tracing_subscriber` layer pushing the name of
+//! each entered span on the tag stack, so the allocations are grouped by the active span without manual tagging.
+//! The tag stack is per-thread, so the attribution of async tasks moving across threads between polls is approximate.
+//!
+//! ```toml
+//! [dependencies]
+//! leaktracer = { version = "0.1", features = ["tracing"] }
+//! ```
+//!
+//! With the `tokio` feature enabled, `instrument_task` wraps a future with a task-local id, and the allocations
+//! made while polling it are attributed to a symbol named after the id instead of the caller from the call stack,
+//! which in async code is dominated by the executor frames. Unlike the tags, the id follows the task across threads.
+//!
+//! ```rust,ignore
+//! tokio::spawn(leaktracer::instrument_task("task:ingest", ingest()));
+//! ```
+//!
+//! ### Named tables
+//!
+//! To isolate the accounting of a subsystem, such as a plugin, without string-prefix gymnastics, [`scoped_table`]
+//! returns a guard attributing the allocations of the current thread to a named table until it's dropped.
+//! Deallocations follow the pointer registry to the table which recorded the allocation.
+//! [`tables`] returns the symbols of all the tables, starting with the global one:
+//!
+//! ```rust
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! {
+//!     let _scope = leaktracer::scoped_table("plugin_a");
+//!     let plugin_state = vec![0u8; 1024];
+//! }
+//!
+//! for (table, symbols) in leaktracer::tables().expect("Failed to access symbol table") {
+//!     let allocated: usize = symbols.iter().map(|symbol| symbol.allocated).sum();
+//!     println!("{table}: {allocated} bytes");
+//! }
+//! ```
+//!
+//! When an allocation triggers further allocations attributed to the same symbol, e.g. through the `Drop`
+//! of the value it replaces, the allocation count of the symbol inflates. The guard returned by [`fold_nested`]
+//! folds them into a single logical allocation: while it lives, the allocations resolving to the symbol
+//! of the first allocation of the window add their bytes to it without being counted separately.
+//!
+//! ### Snapshots
+//!
+//! To inspect the table without holding the lock, take a [`SymbolTableSnapshot`] with [`snapshot`].
+//! Each snapshot records the symbol table [`generation`] at the time it was taken,
+//! so comparing two snapshots tells how much activity happened in between.
+//!
+//! ```rust
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! let before = leaktracer::snapshot().expect("Failed to take snapshot");
+//! let after = leaktracer::snapshot().expect("Failed to take snapshot");
+//! println!("{} table mutations", after.generation - before.generation);
+//! ```
+//!
+//! Rather than diffing snapshots by hand, [`record_generation`] records a [`Generation`] marker,
+//! and [`delta_since`] returns the [`SymbolDiff`] of each symbol which changed since then.
+//!
+//! The allocations below the attribution threshold, or whose symbol was filtered out, are missing from the symbols
+//! of a snapshot. [`consistent_snapshot`] adds them as an `<unattributed>` entry, so that the symbols always add up
+//! to the global `allocated` bytes, even while other threads allocate.
+//!
+//! Snapshots taken by several processes, e.g. the shards of a service, can be aggregated with [`merge_snapshots`].
+//! To aggregate them offline, each process writes a compact binary dump with [`dump_to`], which can be loaded
+//! by any other process with [`dump::read`] and merged with [`dump::Dump::merge`], e.g. from a cron job.
+//!
+//! For hierarchical views, [`symbol_tree`] organizes the symbols as a tree of [`SymbolNode`]s by their `::` path segments,
+//! with the totals of all the descendants aggregated at each node. [`SymbolTableSnapshot::module_tree`] builds
+//! the same tree from a snapshot, and [`SymbolNode::write_tree`] renders it as an indented `du -h` style view.
+//! For coarse dashboards, [`module_stats`] returns the live bytes and count of each traced module, kept up to date
+//! on each allocation, so it's cheap even with thousands of symbols.
+//! Likewise, [`peak_by_thread`] returns the highest live bytes reached by the allocations of each thread,
+//! e.g. to size per-thread memory budgets.
+//! To spot the functions repeating the same allocation, [`fingerprints`] counts the allocations by [`Fingerprint`]:
+//! their size rounded up to a power of two, their alignment and their symbol.
+//!
+//! With the `regex` feature, `query` returns the symbols whose name matches a regex, for ad-hoc exploration.
+//!
+//! ### Reports
+//!
+//! A plain text report, including the time elapsed since the first traced allocation (see [`uptime`]),
+//! can be written to any [`std::io::Write`] with [`write_report`]:
+//!
+//! The report also contains a summary comparing the process resident set size (see [`process_rss`])
+//! with the traced live bytes and the tracer overhead (see [`self_overhead_bytes`]), since the traced heap
+//! never matches what tools like `top` show.
+//!
+//! ```rust
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! leaktracer::write_report(&mut std::io::stdout()).expect("Failed to write report");
+//! ```
+//!
+//! The uptime and all the other timestamps are read from a [`Clock`], the [`std::time::Instant`] based [`MonotonicClock`]
+//! by default, which can be replaced with [`set_clock`] or injected with [`LeaktracerAllocator::with_clock`],
+//! e.g. with a fake clock to test the time-dependent features deterministically. With the `test-util` feature,
+//! `ManualClock` is a clock advanced by hand, to write deterministic tests of the leak assertions downstream too.
+//!
+//! Other formats are provided as [`Reporter`] implementors ([`TextReporter`], [`JsonReporter`], [`CsvReporter`],
+//! [`MarkdownReporter`] and [`PrometheusReporter`]), which all write a [`SymbolTableSnapshot`] and share the same
+//! [`ReportOptions`] (top-N, sort key, human readable sizes, churn and inclusive columns). [`report`] takes the snapshot and writes it:
+//!
+//! ```rust
+//! use leaktracer::{JsonReporter, ReportOptions};
+//!
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! let reporter = JsonReporter::new(ReportOptions {
+//!     top: Some(10),
+//!     ..Default::default()
+//! });
+//! leaktracer::report(&reporter, &mut std::io::stdout()).expect("Failed to write report");
+//! ```
+//!
+//! Besides the live bytes, each [`Symbol`] keeps its cumulative counters, so [`Symbol::avg_allocation_size`]
+//! tells its bytes per allocation and [`Symbol::turnover`] its freed bytes since the table was reset.
+//! A symbol with a high turnover isn't leaking, but it's hammering the allocator: sort the report by
+//! [`SortKey::Churn`] and set [`ReportOptions::churn`] to find them.
+//! [`SymbolSnapshot::alloc_rate`] and [`SymbolSnapshot::recent_alloc_rate`] tell the allocations per second
+//! of a symbol, since the tracer started and in the last [`RECENT_WINDOW`], and [`SortKey::Rate`] sorts by the former,
+//! while [`LeaktracerAllocator::alloc_rate`] tells the global rate.
+//! [`Symbol::realloc_moves`] counts the reallocations which moved the block, copying it: many moves on a growing
+//! collection call for a `with_capacity`. [`Symbol::bytes_grown`] and [`Symbol::bytes_shrunk`] sum the size changes
+//! of the reallocations, and [`ReportOptions::realloc_details`] adds them to the reports.
+//! [`Symbol::alignment_stats`] counts the allocations by alignment class, from the natural one up to the pages,
+//! and [`SymbolTableSnapshot::alignment_stats`] sums them; [`ReportOptions::alignment`] adds them to the text report.
+//! [`Symbol::peak_at`] tells when a symbol reached its [`Symbol::peak`], to correlate its blow-up with the logs,
+//! and [`ReportOptions::peak`] adds both to the reports, e.g. `peak 8.0 KiB at t+5m 42s`.
+//! [`Symbol::size_cardinality`] counts the distinct allocation sizes, exactly up to [`SIZE_CARDINALITY_CUTOFF`]:
+//! a single size over lots of allocations points at a pool candidate, many sizes at a buffer builder.
+//! [`ReportOptions::size_cardinality`] adds it to the reports.
+//!
+//! With the `pprof` feature, `write_pprof` writes a gzipped [pprof](https://github.com/google/pprof) heap profile,
+//! with one single-frame sample per symbol, which can be explored with `go tool pprof`.
+//! With the `heaptrack` feature, `HeaptrackWriter` streams each traced allocation and deallocation to a gzipped file
+//! in the [heaptrack](https://github.com/KDE/heaptrack) format, to be opened with `heaptrack_gui`.
+//!
+//! For long running services, [`file_reporter`] writes a JSON report to a directory on a fixed interval,
+//! keeping only the most recent files. For unattended runs, [`auto_report_to_file`] requests a report whenever
+//! the live bytes change by more than a given delta, which [`maybe_flush`] writes from the caller's thread,
+//! so the allocation path never does any I/O:
+//!
+//! ```rust,no_run
+//! leaktracer::auto_report_to_file("leaktracer.json", 64 * 1024 * 1024);
+//! loop {
+//!     // ...
+//!     leaktracer::maybe_flush().expect("failed to write report");
+//! }
+//! ```
+//!
+//! To log periodically without flooding the logs, [`DeltaReporter`] remembers the previous report and writes only
+//! the symbols whose live bytes changed by more than a [`DeltaThreshold`], e.g. `app::cache: 12.3 MiB (+1.2 MiB)`,
+//! and a final `(freed)` line for the symbols which freed everything.
+//!
+//! For a quick look without wiring up a reporter, [`print_top`] prints to stderr a colorized table of the biggest
+//! symbols, with the growth of each symbol since the previous call:
+//!
+//! ```rust
+//! leaktracer::init_symbol_table(&["my_crate_name"]);
+//!
+//! leaktracer::print_top(10).expect("Failed to print top");
+//! ```
+//!
+//! ### Quick stats
+//!
+//! Per-symbol data requires locking the symbol table, which contends with the allocation path.
+//! For high-frequency polling, [`quick_stats`] reads a few global counters (live bytes, peak, allocation counts
+//! and symbol count) without locking:
+//!
+//! ```rust
+//! let stats = leaktracer::quick_stats();
+//! println!("live {} bytes, peak {} bytes", stats.allocated, stats.peak);
+//! ```
+//!
+//! [`summary`] formats them as a single compact line for a heartbeat log,
+//! e.g. `live=4.2MiB peak=9.1MiB allocs=12345 syms=87 untracked=3`.
+//!
+//! [`op_breakdown`] splits the traced allocations by op (plain, zeroed and reallocations), e.g. to find out
+//! that most of the churn comes from growing collections.
+//!
+//! For simple deltas of the live bytes, [`checkpoint`] stores them under a name, and [`since_checkpoint`] returns
+//! the signed difference since then:
+//!
+//! ```rust
+//! leaktracer::checkpoint("after_load");
+//! // ...
+//! let delta = leaktracer::since_checkpoint("after_load");
+//! ```
+//!
+//! For a one-line measurement, the [`Measure`] guard returned by [`measure`] prints the delta of the live bytes
+//! and of the allocations when dropped, e.g. `load_config: +2.1 MiB (+340 allocs)`:
+//!
+//! ```rust
+//! let _measure = leaktracer::measure("load_config");
+//! // ...
+//! ```
+//!
+//! The memory consumed by the tracer itself (symbol table, pointer registry, interned names and symbolization caches)
+//! is never attributed to any symbol, but it's real process memory: [`self_overhead_bytes`] tells how much it is.
+//! The symbol table starts with room for 1024 symbols: each time it grows inside the allocation path,
+//! [`SymbolTable::rehash_count`] is incremented and, the first time, a warning is emitted through `log` or `tracing`.
+//! Call [`reserve`] at startup if the program has more symbols.
+//!
+//! Capturing the call stack of each allocation is expensive. Under allocation pressure, sampling can be enabled
+//! with [`LeaktracerAllocator::with_sampling`]: only 1 out of N allocations is attributed, with its bytes scaled by N.
+//! With [`Sampling::Adaptive`], N is adjusted automatically to keep the capture time within a budget.
+//! Each [`SizeClass`] can also be sampled with its own divisor ([`LeaktracerAllocator::with_size_class_sampling`]),
+//! e.g. to sample few of the numerous small allocations, but every huge one.
+//! [`set_attribution_size_threshold`] goes further, attributing every allocation above a size but none below it,
+//! which are only counted globally.
+//! [`with_full_fidelity`] disables the sampling on the current thread for the duration of a closure,
+//! e.g. to capture the full detail of a suspicious phase.
+//! Without sampling, [`LeaktracerAllocator::with_caller_cache`] lets each thread remember the symbols of the call sites
+//! it already resolved, trading a slightly coarser attribution for a much cheaper allocation path.
+//!
+//! ### Pausing and resetting
+//!
+//! Tracing of new allocations can be paused with [`set_enabled`], e.g. around a noisy startup phase,
+//! and all the stats can be cleared with [`reset`], to measure only what happens from a given point onwards.
+//!
+//! For real-time code, [`assert_no_alloc`] runs a closure and panics if it allocated on the current thread.
+//! For performance work, [`bench::measured`] runs a closure a number of times, returning the elapsed time
+//! and the allocations it made on the current thread. With the `criterion` feature, `bench::Allocations` is a
+//! [criterion](https://docs.rs/criterion) measurement reporting the allocations per iteration, see `benches/allocations.rs`.
+//!
+//! ### Leak tests
+//!
+//! With the `macros` feature enabled, the `#[leak_test]` attribute turns a function into a test which fails
+//! if the live bytes attributed to the traced modules grew while it ran, listing the symbols which grew.
+//! It keeps the other attributes, so it works along `#[tokio::test]`, and takes an optional `tolerance` in bytes.
+//! The test binary must use the [`LeaktracerAllocator`], and since the symbol table is global,
+//! the leak tests should run in their own test binary, or with `--test-threads=1`.
+//! The check itself is [`LeakCheck`], which can be used directly.
+//!
+//! ```toml
+//! [dev-dependencies]
+//! leaktracer = { version = "0.1", features = ["macros"] }
+//! ```
+//!
+//! ```rust,ignore
+//! #[global_allocator]
+//! static ALLOCATOR: leaktracer::LeaktracerAllocator = leaktracer::LeaktracerAllocator::init();
+//!
+//! #[leaktracer::leak_test(tolerance = 1024)]
+//! #[tokio::test]
+//! async fn test_should_not_leak_connections() {
+//!     // ...
+//! }
+//! ```
+//!
+//! To gate the memory in CI instead, with the `budget` feature, `enforce_budget_file` checks the live bytes
+//! and the peak of each symbol against a budget file, with a TOML table of limits for each symbol prefix
+//! (or a JSON object, for a `.json` file), and returns all the violations in a `BudgetReport`:
+//!
+//! ```rust,ignore
+//! if let Err(report) = leaktracer::enforce_budget_file("leaktracer-budget.toml") {
+//!     eprintln!("{report}");
+//!     std::process::exit(1);
+//! }
+//! ```
+//!
+//! ### Heap monitor
+//!
+//! With the `tui` feature enabled, `tui::run` starts an interactive heap monitor in the terminal,
+//! with a live table of the symbols and a sparkline of the traced live bytes.
+//!
+//! ### Allocation logging
+//!
+//! With the `log` feature enabled, `enable_alloc_logging` logs each traced allocation at trace level,
+//! as `alloc {size} bytes @ {symbol}`. Since every allocation emits a record, this is only sane for tiny workloads.
+//!
+//! ```toml
+//! [dependencies]
+//! leaktracer = { version = "0.1", features = ["log"] }
+//! ```
+//!
+//! ### C interface
+//!
+//! With the `ffi` feature enabled, the `ffi` module exports unmangled `extern "C"` functions,
+//! to query the tracer from C code or from a debugger attached to a running process:
+//! `leaktracer_allocated`, `leaktracer_dump_json` and `leaktracer_set_enabled`.
+//! They never unwind into the caller and return errno-style codes.
+//!
+//! ```text
+//! (gdb) call leaktracer_dump_json("/tmp/leaktracer.json")
+//! $1 = 0
+//! ```
+//!
+//! ### Emergency dump
+//!
+//! On unix, [`emergency_dump`] writes the top symbols to a file descriptor from a lock-free mirror of the symbol table,
+//! using only `write(2)`, so it can be called from a signal handler, e.g. on `SIGSEGV`, when the process is about to die.
+//! The mirror is refreshed every 1024 traced allocations, so the dump may lag behind the symbol table.
+//!
+//! ### Unix socket
+//!
+//! On unix, with the `unix-socket` feature, [`serve_unix`] binds a Unix domain socket and spawns a thread
+//! writing the plain text report of a fresh snapshot to each connection, then closing it,
+//! for live introspection without embedding an HTTP server:
+//!
+//! ```text
+//! $ nc -U /tmp/my_app.sock
+//! ```
+//!
+//! ### Counter-only mode
+//!
+//! The call stack capture and the symbols live behind the `symbols` feature, enabled by default.
+//! With it disabled, the `backtrace` crate is not a dependency anymore and the allocator only tracks the counters
+//! (live bytes, peak, allocation counts and size classes), which is cheap enough to leave on.
+//!
+//! [`init_symbol_table`] and [`with_symbol_table`] still exist, and the table keeps the registry of the live
+//! allocations, so deallocations, sampling and strict mode work as usual. The allocations are attributed to a symbol
+//! only when it's named without a call stack: the fixed symbol ([`set_fixed_symbol`]), the `<startup>` symbol
+//! ([`LeaktracerAllocator::with_startup_bucket`]), the tokio task id (`instrument_task`) and the caller supplied
+//! by a [`FrameProvider`]. Any other allocation is only registered, under no symbol.
+//! The functions which need the call stack resolution (`caller_symbol`, `resolve_addr`, `set_ffi_label`, `set_keep_mangled`, `set_collapse_generics`, `set_name_rewriter`, `set_module_priority`, `set_attribution_offset` and `set_inclusive_accounting`)
+//! are not available, so using them fails to compile. There's no `no-backtrace` feature: since cargo features
+//! are additive, a feature can't remove the call stack capture, so the default features must be disabled.
+//!
+//! ```toml
+//! [dependencies]
+//! leaktracer = { version = "0.1", default-features = false }
+//! ```
+//!
+//! ### WebAssembly
+//!
+//! The call stacks can't be captured on WebAssembly, so the `backtrace` crate is never a dependency there
+//! and the allocator works as in the [counter-only mode](#counter-only-mode), even with the `symbols` feature,
+//! attributing only the allocations to the symbols named without a call stack:
+//!
+//! | Target                   | Counters | Symbols                | `caller_symbol`, `resolve_addr` |
+//! |--------------------------|----------|------------------------|---------------------------------|
+//! | native                   | yes      | yes, from call stacks  | yes                             |
+//! | `wasm32-wasip1`          | yes      | with a frame provider  | no                              |
+//! | `wasm32-unknown-unknown` | yes      | with a frame provider  | no                              |
+//!
+//! To still attribute the allocations, implement a [`FrameProvider`], e.g. parsing the JavaScript
+//! `new Error().stack` through `wasm-bindgen`, and set it with [`set_frame_provider`]:
+//!
+//! ```rust
+//! struct MyProvider;
+//!
+//! impl leaktracer::FrameProvider for MyProvider {
+//!     fn caller(&self, modules: &[&'static str]) -> Option<String> {
+//!         // find the first frame belonging to `modules`
+//!         None
+//!     }
+//! }
+//!
+//! leaktracer::set_frame_provider(&MyProvider);
+//! ```
+//!
+//! `wasm32-unknown-unknown` has no clock either: there the [`MonotonicClock`] always reads zero, so the [`uptime`]
+//! and the recent allocation windows don't move until a clock is set with [`set_clock`], e.g. reading
+//! `performance.now()`, and [`Sampling::Adaptive`] traces every allocation.
+//!
+//! ### Windows
+//!
+//! On Windows the symbols are resolved through dbghelp, which is serialized by a process-wide lock,
+//! so resolving the call stacks inside the allocator can deadlock with another thread allocating while symbolicating.
+//! Therefore, on Windows the allocator only captures the call stacks, while a helper thread (`leaktracer-resolver`)
+//! resolves them in the background. This comes with some limitations:
+//!
+//! - until its call stack is resolved, usually within milliseconds, an allocation is attributed to a
+//!   `<pending:...>` placeholder symbol, which is then merged into the resolved symbol;
+//! - the names already handed out, e.g. by [`largest_allocations`] or to the strict mode callbacks,
+//!   may be placeholders;
+//! - without the PDB files no frame has a name, so the allocations are attributed to the module and offset of the
+//!   allocation call site, e.g. `myapp.exe+0x1234`, which in debug builds often falls inside the standard library;
+//! - on 32-bit Windows walking the stack goes through dbghelp too, so the deadlock is not avoided there.
+//!
+//! ### Fork
+//!
+//! A forked child only inherits the thread which called `fork()`, so a lock held by another thread stays locked
+//! in the child forever. On unix, [`init_symbol_table`] registers `pthread_atfork` handlers taking all the tracer locks
+//! right before the fork and releasing them right after it, so the child can allocate and dump right away.
+//! The child keeps the stats inherited from the parent: call [`reset`] in the child to start from scratch.
+//!
+//! When forking without going through the `pthread_atfork` handlers, call [`before_fork`] right before forking,
+//! then [`after_fork_parent`] in the parent and [`after_fork_child`] in the child.
+//!
 //! ## Example
 //!
 //! ```rust
@@ -132,8 +682,135 @@
 
 #![doc(html_playground_url = "https://play.rust-lang.org")]
 
+mod accounting;
+mod alignment;
 mod alloc;
+mod atomic_ref;
+pub mod bench;
+#[cfg(feature = "budget")]
+mod budget;
+mod checkpoint;
+mod delta;
+pub mod dump;
+#[cfg(unix)]
+mod emergency;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fingerprint;
+mod fold;
+mod fork;
+mod frame_provider;
+#[cfg(feature = "heaptrack")]
+mod heaptrack;
+mod hooks;
+mod largest;
+mod leak_check;
+#[cfg(feature = "log")]
+mod logging;
+mod measure;
+mod no_alloc;
+mod process;
+#[cfg(feature = "regex")]
+mod query;
+mod report;
+mod sampling;
+mod scopes;
+#[cfg(all(unix, feature = "unix-socket"))]
+mod serve;
+mod size_class;
+mod snapshot;
+#[cfg(feature = "tracing")]
+mod span_layer;
+mod stats;
+mod strict;
 mod symbols;
+mod tags;
+#[cfg(feature = "tokio")]
+mod task;
+#[cfg(test)]
+mod test_utils;
+mod threads;
+mod time;
+mod top;
+mod tree;
+#[cfg(feature = "tui")]
+pub mod tui;
 
-pub use self::alloc::{LeaktracerAllocator, init_symbol_table, with_symbol_table};
-pub use self::symbols::{Symbol, SymbolTable};
+pub use self::accounting::{AccountingViolation, accounting_violations};
+pub use self::alignment::AlignmentStats;
+#[cfg(stack_capture)]
+pub use self::alloc::set_inclusive_accounting;
+pub use self::alloc::{
+    LeaktracerAllocator, TableError, add_traced_module, attribution_coverage, init_symbol_table,
+    is_enabled, live_allocations, mark_startup_complete, module_stats, reconfigure, reserve, reset,
+    set_attribution_size_threshold, set_ema_alpha, set_enabled, set_fixed_symbol,
+    set_histogram_buckets, sum_symbol_allocated, traced_modules, with_symbol_table,
+};
+#[cfg(feature = "budget")]
+pub use self::budget::{BudgetLimit, BudgetReport, BudgetViolation, enforce_budget_file};
+pub use self::checkpoint::{checkpoint, since_checkpoint};
+pub use self::delta::{Generation, SymbolDiff, delta_since, record_generation};
+pub use self::dump::dump_to;
+#[cfg(unix)]
+pub use self::emergency::emergency_dump;
+pub use self::fingerprint::{Fingerprint, fingerprints};
+pub use self::fold::{FoldGuard, fold_nested};
+pub use self::fork::{after_fork_child, after_fork_parent, before_fork};
+pub use self::frame_provider::{FrameProvider, set_frame_provider};
+#[cfg(feature = "heaptrack")]
+pub use self::heaptrack::HeaptrackWriter;
+pub use self::hooks::{
+    AllocHook, MAX_ALLOC_CALLBACKS, on_threshold_exceeded, on_untracked_dealloc, set_periodic_hook,
+};
+pub use self::largest::largest_allocations;
+pub use self::leak_check::LeakCheck;
+#[cfg(feature = "log")]
+pub use self::logging::enable_alloc_logging;
+pub use self::measure::{Measure, measure, measure_to};
+pub use self::no_alloc::assert_no_alloc;
+pub use self::process::process_rss;
+#[cfg(feature = "regex")]
+pub use self::query::query;
+pub use self::report::{
+    CsvReporter, DeltaReporter, DeltaThreshold, FileReporterHandle, JsonReporter, MarkdownReporter,
+    PrometheusReporter, ReportOptions, Reporter, SortKey, TextReporter, auto_report_to_file,
+    file_reporter, maybe_flush, report, write_report,
+};
+#[cfg(feature = "pprof")]
+pub use self::report::{PprofReporter, write_pprof};
+pub use self::sampling::{Sampling, with_full_fidelity};
+pub use self::scopes::{GLOBAL_TABLE, ScopedTable, scoped_table, tables};
+#[cfg(all(unix, feature = "unix-socket"))]
+pub use self::serve::serve_unix;
+pub use self::size_class::{SizeClass, SizeClassStats};
+pub use self::snapshot::{
+    SymbolSnapshot, SymbolStats, SymbolTableSnapshot, consistent_snapshot, merge_snapshots,
+    snapshot, stats, symbol_stats,
+};
+#[cfg(feature = "tracing")]
+pub use self::span_layer::{TracingLayer, tracing_layer};
+pub use self::stats::{
+    OpBreakdown, OpStats, QuickStats, generation, global_allocated, op_breakdown, quick_stats,
+    self_overhead_bytes, summary, tls_fallbacks, zero_size_events,
+};
+pub use self::strict::{StrictViolation, StrictViolationKind, on_strict_violation};
+pub use self::symbols::{
+    DEFAULT_EMA_ALPHA, DEFAULT_HISTOGRAM_BUCKETS, RECENT_WINDOW, SIZE_CARDINALITY_CUTOFF,
+    SizeCardinality, Symbol, SymbolTable,
+};
+#[cfg(stack_capture)]
+pub use self::symbols::{
+    ResolvedSymbol, caller_symbol, resolve_addr, set_attribution_offset, set_collapse_generics,
+    set_ffi_label, set_keep_mangled, set_module_priority, set_name_rewriter,
+};
+pub use self::tags::{TagGuard, TagStats, pop_tag, push_tag, tag_stats};
+#[cfg(feature = "tokio")]
+pub use self::task::instrument_task;
+pub use self::threads::peak_by_thread;
+#[cfg(feature = "test-util")]
+pub use self::time::ManualClock;
+pub use self::time::{Clock, MonotonicClock, set_clock, uptime};
+pub use self::top::print_top;
+pub use self::tree::{SymbolNode, symbol_tree};
+#[cfg(feature = "macros")]
+pub use leaktracer_macros::leak_test;
@@ -0,0 +1,24 @@
+//! Logging of each traced allocation via the [`log`] crate.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether each traced allocation is logged.
+static ALLOC_LOGGING: AtomicBool = AtomicBool::new(false);
+
+/// Enables logging each traced allocation with [`log::trace!`], as `alloc {size} bytes @ {symbol}`.
+///
+/// This is only sane for tiny workloads, such as step-by-step debugging of small programs,
+/// since every single allocation emits a record.
+///
+/// Records are emitted from inside the allocator, with the symbol table locked and tracing suppressed,
+/// so the allocations made by the logger are not traced (nor logged), but the logger must not access the symbol table.
+pub fn enable_alloc_logging() {
+    ALLOC_LOGGING.store(true, Ordering::Relaxed);
+}
+
+/// Logs the allocation of `size` bytes by `symbol`, if allocation logging is enabled.
+pub(crate) fn log_alloc(size: usize, symbol: &str) {
+    if ALLOC_LOGGING.load(Ordering::Relaxed) {
+        log::trace!("alloc {size} bytes @ {symbol}");
+    }
+}
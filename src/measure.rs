@@ -0,0 +1,118 @@
+//! Named measurement windows, printing the delta of the live bytes and of the allocations on drop.
+
+use std::io::{self, Write};
+
+use crate::alloc::untraced;
+use crate::report::format_bytes;
+use crate::stats;
+
+/// A named measurement window, which prints the delta of the live bytes (see [`crate::global_allocated`])
+/// and of the traced allocations since its creation when dropped, e.g. `load_config: +2.1 MiB (+340 allocs)`.
+///
+/// It only reads the global counters, so it's cheap enough to be left around hot sections of code.
+/// The allocations made meanwhile by the other threads count too.
+///
+/// ```rust
+/// let config = {
+///     let _measure = leaktracer::measure("load_config");
+///     vec![0u8; 1024]
+/// };
+/// ```
+#[derive(Debug)]
+pub struct Measure<W: Write = io::Stderr> {
+    name: &'static str,
+    allocated: usize,
+    allocations: usize,
+    writer: W,
+}
+
+/// Starts a measurement window named `name`, which prints its delta to stderr when dropped.
+pub fn measure(name: &'static str) -> Measure {
+    measure_to(name, io::stderr())
+}
+
+/// Starts a measurement window named `name`, which writes its delta to `writer` when dropped.
+pub fn measure_to<W: Write>(name: &'static str, writer: W) -> Measure<W> {
+    Measure {
+        name,
+        allocated: stats::global_allocated(),
+        allocations: stats::total_allocations(),
+        writer,
+    }
+}
+
+impl<W: Write> Measure<W> {
+    /// Returns the signed difference of the live bytes since the creation of the window,
+    /// along with the number of allocations traced meanwhile.
+    pub fn delta(&self) -> (i64, usize) {
+        (
+            stats::global_allocated() as i64 - self.allocated as i64,
+            stats::total_allocations().saturating_sub(self.allocations),
+        )
+    }
+}
+
+impl<W: Write> Drop for Measure<W> {
+    fn drop(&mut self) {
+        let (bytes, allocations) = self.delta();
+        // the line is not part of the measurement
+        untraced(|| {
+            let _ = write_delta(&mut self.writer, self.name, bytes, allocations);
+        });
+    }
+}
+
+/// Writes the delta of the window `name` as a single line.
+fn write_delta(w: &mut dyn Write, name: &str, bytes: i64, allocations: usize) -> io::Result<()> {
+    let sign = if bytes < 0 { '-' } else { '+' };
+    writeln!(
+        w,
+        "{name}: {sign}{} (+{allocations} allocs)",
+        format_bytes(bytes.unsigned_abs() as usize)
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+
+    #[test]
+    fn test_should_write_delta() {
+        let mut buffer = Vec::new();
+        write_delta(&mut buffer, "load_config", 2_202_010, 340).unwrap();
+        write_delta(&mut buffer, "drop_cache", -512, 0).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "load_config: +2.1 MiB (+340 allocs)\ndrop_cache: -512 B (+0 allocs)\n"
+        );
+    }
+
+    #[test]
+    fn test_should_measure_delta_across_guard_lifetime() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(1 << 20, 8).unwrap();
+        let ptr = fake_ptr();
+        let mut buffer = Vec::new();
+        let measure = measure_to("test:measure", &mut buffer);
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        let (bytes, allocations) = measure.delta();
+        drop(measure);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        // the other tests allocate concurrently, but far less than this
+        assert!(bytes >= 1 << 19);
+        assert!(allocations >= 1);
+        let line = String::from_utf8(buffer).unwrap();
+        assert!(line.starts_with("test:measure: +"), "{line}");
+        assert!(line.ends_with(" allocs)\n"), "{line}");
+    }
+}
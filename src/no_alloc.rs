@@ -0,0 +1,84 @@
+//! Assertions that a code path doesn't allocate, e.g. the real-time loop of an audio callback.
+
+use crate::stats;
+
+/// Runs `f` and panics if it made any allocation on the current thread, returning its result otherwise.
+///
+/// The allocations are counted exactly on each thread, regardless of the sampling, so the allocations made
+/// by other threads meanwhile don't count, nor do the ones made by the threads spawned by `f`.
+/// Reallocations count as allocations. The allocations made while tracing is paused (see [`crate::set_enabled`])
+/// are not seen.
+///
+/// ```rust,should_panic
+/// #[global_allocator]
+/// static ALLOCATOR: leaktracer::LeaktracerAllocator = leaktracer::LeaktracerAllocator::init();
+///
+/// let sum = leaktracer::assert_no_alloc(|| (0..100u64).sum::<u64>());
+/// // panics
+/// leaktracer::assert_no_alloc(|| vec![sum; 16]);
+/// ```
+pub fn assert_no_alloc<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let before = stats::thread_allocations();
+    let res = f();
+    let allocations = stats::thread_allocations() - before;
+    if allocations > 0 {
+        panic!(
+            "assert_no_alloc: {allocations} allocations made in a region which must not allocate"
+        );
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+
+    #[test]
+    fn test_should_pass_without_allocations() {
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = fake_ptr();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+
+        // deallocations are allowed
+        let res = assert_no_alloc(|| {
+            allocator.trace(ptr, layout, AllocOp::Dealloc);
+            42
+        });
+        assert_eq!(res, 42);
+    }
+
+    #[test]
+    fn test_should_panic_on_allocation() {
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let ptr = fake_ptr();
+
+        let res = std::panic::catch_unwind(|| {
+            assert_no_alloc(|| allocator.trace(ptr, layout, AllocOp::Alloc));
+        });
+        assert!(res.is_err());
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+
+        // the allocations of the other threads don't count
+        assert_no_alloc(|| {
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    let ptr = fake_ptr();
+                    allocator.trace(ptr, layout, AllocOp::Alloc);
+                    allocator.trace(ptr, layout, AllocOp::Dealloc);
+                });
+            })
+        });
+    }
+}
@@ -0,0 +1,179 @@
+//! Process level memory information, implemented without external dependencies.
+
+/// Returns the resident set size (RSS) of the current process in bytes.
+///
+/// - On Linux this is read from `/proc/self/statm`
+/// - On macOS this is read with `task_info`
+/// - On Windows this is the working set size returned by `GetProcessMemoryInfo`
+///
+/// Returns [`None`] on unsupported platforms or if the information is not available.
+pub fn process_rss() -> Option<usize> {
+    imp::process_rss()
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+
+    use std::ffi::{c_int, c_long};
+
+    /// `_SC_PAGESIZE` for both glibc and musl.
+    const SC_PAGESIZE: c_int = 30;
+
+    unsafe extern "C" {
+        fn sysconf(name: c_int) -> c_long;
+    }
+
+    pub fn process_rss() -> Option<usize> {
+        let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+        let page_size = unsafe { sysconf(SC_PAGESIZE) };
+        if page_size <= 0 {
+            return None;
+        }
+
+        parse_statm(&statm, page_size as usize)
+    }
+
+    /// Parses the content of `/proc/self/statm`, returning the resident set size in bytes.
+    ///
+    /// The second field of `statm` is the resident set size in pages.
+    pub(super) fn parse_statm(statm: &str, page_size: usize) -> Option<usize> {
+        statm
+            .split_whitespace()
+            .nth(1)?
+            .parse::<usize>()
+            .ok()
+            .map(|pages| pages * page_size)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+
+    use std::ffi::c_int;
+
+    const MACH_TASK_BASIC_INFO: c_int = 20;
+    const KERN_SUCCESS: c_int = 0;
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct TimeValue {
+        seconds: c_int,
+        microseconds: c_int,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct MachTaskBasicInfo {
+        virtual_size: u64,
+        resident_size: u64,
+        resident_size_max: u64,
+        user_time: TimeValue,
+        system_time: TimeValue,
+        policy: c_int,
+        suspend_count: c_int,
+    }
+
+    unsafe extern "C" {
+        static mach_task_self_: u32;
+        fn task_info(
+            target_task: u32,
+            flavor: c_int,
+            task_info: *mut c_int,
+            count: *mut u32,
+        ) -> c_int;
+    }
+
+    pub fn process_rss() -> Option<usize> {
+        let mut info = MachTaskBasicInfo::default();
+        let mut count =
+            (std::mem::size_of::<MachTaskBasicInfo>() / std::mem::size_of::<c_int>()) as u32;
+        let res = unsafe {
+            task_info(
+                mach_task_self_,
+                MACH_TASK_BASIC_INFO,
+                &mut info as *mut MachTaskBasicInfo as *mut c_int,
+                &mut count,
+            )
+        };
+
+        (res == KERN_SUCCESS).then_some(info.resident_size as usize)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct ProcessMemoryCounters {
+        cb: u32,
+        page_fault_count: u32,
+        peak_working_set_size: usize,
+        working_set_size: usize,
+        quota_peak_paged_pool_usage: usize,
+        quota_paged_pool_usage: usize,
+        quota_peak_non_paged_pool_usage: usize,
+        quota_non_paged_pool_usage: usize,
+        pagefile_usage: usize,
+        peak_pagefile_usage: usize,
+    }
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetCurrentProcess() -> *mut c_void;
+        fn K32GetProcessMemoryInfo(
+            process: *mut c_void,
+            counters: *mut ProcessMemoryCounters,
+            cb: u32,
+        ) -> i32;
+    }
+
+    pub fn process_rss() -> Option<usize> {
+        let mut counters = ProcessMemoryCounters {
+            cb: std::mem::size_of::<ProcessMemoryCounters>() as u32,
+            ..Default::default()
+        };
+        let res =
+            unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.cb) };
+
+        (res != 0).then_some(counters.working_set_size)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod imp {
+
+    pub fn process_rss() -> Option<usize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_should_parse_statm() {
+        use super::imp::parse_statm;
+
+        assert_eq!(
+            parse_statm("5457 1312 1084 3 0 343 0\n", 4096),
+            Some(1312 * 4096)
+        );
+        assert_eq!(parse_statm("100 0 0 0 0 0 0", 4096), Some(0));
+        assert_eq!(parse_statm("100", 4096), None);
+        assert_eq!(parse_statm("", 4096), None);
+        assert_eq!(parse_statm("100 abc 0", 4096), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_should_get_process_rss() {
+        assert!(super::process_rss().is_some_and(|rss| rss > 0));
+    }
+}
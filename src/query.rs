@@ -0,0 +1,72 @@
+//! Ad-hoc queries of the symbols by a regex over their names.
+
+use regex::Regex;
+
+use crate::snapshot::SymbolSnapshot;
+use crate::symbols::SymbolTable;
+
+/// Returns all the symbols whose name matches the regex `pattern`.
+///
+/// The regex is compiled once, with tracing suppressed, before acquiring the lock; then the table is iterated under the lock.
+/// Returns no symbols if the symbol table lock is poisoned.
+///
+/// ```rust
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// for symbol in leaktracer::query(r"^my_crate_name::(cache|config)::").expect("Invalid pattern") {
+///     println!("{}: {} bytes", symbol.name, symbol.allocated);
+/// }
+/// ```
+pub fn query(pattern: &str) -> Result<Vec<SymbolSnapshot>, regex::Error> {
+    crate::alloc::untraced(|| {
+        let regex = Regex::new(pattern)?;
+        Ok(crate::with_symbol_table(|table| matching(table, &regex)).unwrap_or_default())
+    })
+}
+
+/// Returns the symbols of `table` whose name matches `regex`.
+fn matching(table: &SymbolTable, regex: &Regex) -> Vec<SymbolSnapshot> {
+    table
+        .iter_sorted()
+        .into_iter()
+        .filter(|(name, _)| regex.is_match(name))
+        .map(|(name, symbol)| SymbolSnapshot::new(name, symbol))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_query_matching_symbols() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        for name in [
+            "app::cache::insert",
+            "app::cache::evict",
+            "app::config::load",
+            "app::main",
+        ] {
+            table.insert(name);
+        }
+
+        let regex = Regex::new(r"^app::(cache|main)").unwrap();
+        let mut names: Vec<_> = matching(&table, &regex)
+            .into_iter()
+            .map(|symbol| symbol.name)
+            .collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            vec!["app::cache::evict", "app::cache::insert", "app::main"]
+        );
+    }
+
+    #[test]
+    fn test_should_reject_invalid_pattern() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        assert!(query("app::(").is_err());
+    }
+}
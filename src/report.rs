@@ -0,0 +1,260 @@
+//! Reports of the symbol table, written by the [`Reporter`] implementors.
+
+mod auto;
+mod csv;
+mod delta;
+mod file;
+mod json;
+mod markdown;
+#[cfg(feature = "pprof")]
+mod pprof;
+mod prometheus;
+mod text;
+
+use std::cmp::Reverse;
+use std::io::{self, Write};
+
+pub(crate) use self::auto::check_auto_report;
+pub use self::auto::{auto_report_to_file, maybe_flush};
+pub use self::csv::CsvReporter;
+pub use self::delta::{DeltaReporter, DeltaThreshold};
+pub use self::file::{FileReporterHandle, file_reporter};
+pub use self::json::JsonReporter;
+pub use self::markdown::MarkdownReporter;
+#[cfg(feature = "pprof")]
+pub use self::pprof::{PprofReporter, write_pprof};
+pub use self::prometheus::PrometheusReporter;
+pub use self::text::TextReporter;
+use crate::snapshot::{self, SymbolSnapshot, SymbolTableSnapshot};
+
+/// A report format, written from a [`SymbolTableSnapshot`].
+pub trait Reporter {
+    /// Writes the report of `snapshot` to the given writer.
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()>;
+}
+
+/// The key the symbols are sorted by in a report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// Allocated bytes, in descending order.
+    #[default]
+    Allocated,
+    /// Allocation count, in descending order.
+    Count,
+    /// Symbol name, in ascending order.
+    Name,
+    /// Freed bytes (see [`SymbolSnapshot::turnover`]), in descending order.
+    ///
+    /// Puts first the symbols hammering the allocator, which are good targets for pooling even if they don't leak.
+    Churn,
+    /// Allocations per second since the tracer started (see [`SymbolSnapshot::alloc_rate`]), in descending order.
+    Rate,
+}
+
+/// Options shared by all the [`Reporter`]s.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReportOptions {
+    /// Report only the first `top` symbols, after sorting. `None` reports all of them.
+    pub top: Option<usize>,
+    /// The key the symbols are sorted by.
+    pub sort: SortKey,
+    /// Write the sizes in human readable units (e.g. `1.5 MiB`).
+    ///
+    /// Only applies to the formats meant to be read by humans (text and markdown).
+    pub humanize: bool,
+    /// Add a churn column with the freed bytes of each symbol (see [`SymbolSnapshot::turnover`]).
+    ///
+    /// Only applies to the formats with per-symbol columns (text, markdown, CSV and JSON).
+    pub churn: bool,
+    /// Add an inclusive column with the bytes allocated by each symbol and by the functions it called
+    /// (see [`SymbolSnapshot::inclusive_allocated`]).
+    ///
+    /// Only applies to the formats with per-symbol columns (text, markdown, CSV and JSON).
+    pub inclusive: bool,
+    /// Add the reallocation columns of each symbol: the reallocations, how many of them moved the block,
+    /// and the bytes grown and shrunk in total (see [`SymbolSnapshot::reallocs`]).
+    ///
+    /// Only applies to the formats with per-symbol columns (text, markdown, CSV and JSON).
+    pub realloc_details: bool,
+    /// Add the peak columns of each symbol: the highest live bytes it reached and the uptime it reached them at
+    /// (see [`SymbolSnapshot::peak`] and [`SymbolSnapshot::peak_at`]), e.g. `peak 8.0 KiB at t+5m 42s`.
+    ///
+    /// Only applies to the formats with per-symbol columns (text, markdown, CSV and JSON).
+    pub peak: bool,
+    /// Add a column with the number of distinct allocation sizes of each symbol
+    /// (see [`SymbolSnapshot::size_cardinality`]), written as `>16` beyond the cutoff (`null` in JSON).
+    ///
+    /// Only applies to the formats with per-symbol columns (text, markdown, CSV and JSON).
+    pub size_cardinality: bool,
+    /// Add a section with the allocations in each alignment class, summed over all the symbols
+    /// (see [`SymbolTableSnapshot::alignment_stats`]).
+    ///
+    /// Only applies to the text format.
+    pub alignment: bool,
+}
+
+impl ReportOptions {
+    /// Returns the symbols of `snapshot` to report, sorted and truncated.
+    pub(crate) fn symbols<'a>(&self, snapshot: &'a SymbolTableSnapshot) -> Vec<&'a SymbolSnapshot> {
+        let mut symbols: Vec<&SymbolSnapshot> = snapshot.symbols.iter().collect();
+        match self.sort {
+            SortKey::Allocated => symbols.sort_by_key(|symbol| Reverse(symbol.allocated)),
+            SortKey::Count => symbols.sort_by_key(|symbol| Reverse(symbol.count)),
+            SortKey::Name => symbols.sort_by_key(|symbol| symbol.name),
+            SortKey::Churn => symbols.sort_by_key(|symbol| Reverse(symbol.turnover)),
+            SortKey::Rate => symbols.sort_by(|a, b| {
+                b.alloc_rate(snapshot.uptime)
+                    .total_cmp(&a.alloc_rate(snapshot.uptime))
+            }),
+        }
+        if let Some(top) = self.top {
+            symbols.truncate(top);
+        }
+
+        symbols
+    }
+
+    /// Formats `bytes`, humanized if requested.
+    pub(crate) fn bytes(&self, bytes: usize) -> String {
+        if self.humanize {
+            format_bytes(bytes)
+        } else {
+            format!("{bytes} bytes")
+        }
+    }
+}
+
+/// Formats `bytes` in binary units, e.g. `512 B` or `1.5 MiB`.
+pub(crate) fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Takes a [`snapshot`](crate::snapshot) of the symbol table and writes it with the given [`Reporter`].
+///
+/// The symbol table is copied under the lock and the lock is released before writing.
+pub fn report<R: Reporter + ?Sized>(reporter: &R, w: &mut dyn Write) -> io::Result<()> {
+    let snapshot = snapshot::snapshot()?;
+    reporter.write_report(&snapshot, w)
+}
+
+/// Writes a plain text report of the symbol table to the given writer.
+///
+/// This is a shorthand for [`report`] with the default [`TextReporter`].
+pub fn write_report<W: Write>(w: &mut W) -> io::Result<()> {
+    report(&TextReporter::default(), w)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_write_report_header() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let mut buf = Vec::new();
+        write_report(&mut buf).expect("failed to write report");
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.starts_with("leaktracer report (uptime "));
+        assert!(report.contains("sizes: "));
+        assert!(report.contains("traced live bytes: "));
+        assert!(report.contains("untracked frees: "));
+        #[cfg(target_os = "linux")]
+        assert!(report.contains("process rss: "));
+    }
+
+    #[test]
+    fn test_should_run_every_reporter_over_snapshot() {
+        let snapshot = synthetic_snapshot();
+        let options = ReportOptions {
+            top: Some(2),
+            ..Default::default()
+        };
+        let reporters: [&dyn Reporter; 5] = [
+            &TextReporter::new(options),
+            &JsonReporter::new(options),
+            &CsvReporter::new(options),
+            &MarkdownReporter::new(options),
+            &PrometheusReporter::new(options),
+        ];
+
+        for reporter in reporters {
+            let mut buf = Vec::new();
+            reporter
+                .write_report(&snapshot, &mut buf)
+                .expect("failed to write report");
+            let report = String::from_utf8(buf).unwrap();
+            assert!(report.contains("app::cache::insert"), "{report}");
+            assert!(report.contains("app::main"), "{report}");
+            // truncated by top
+            assert!(!report.contains("app::config::load"), "{report}");
+        }
+    }
+
+    #[test]
+    fn test_should_sort_and_truncate_symbols() {
+        let snapshot = synthetic_snapshot();
+        let names = |options: ReportOptions| {
+            options
+                .symbols(&snapshot)
+                .into_iter()
+                .map(|symbol| symbol.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(ReportOptions::default()),
+            vec!["app::cache::insert", "app::main", "app::config::load"]
+        );
+        assert_eq!(
+            names(ReportOptions {
+                sort: SortKey::Count,
+                ..Default::default()
+            }),
+            vec!["app::main", "app::config::load", "app::cache::insert"]
+        );
+        assert_eq!(
+            names(ReportOptions {
+                sort: SortKey::Name,
+                top: Some(1),
+                ..Default::default()
+            }),
+            vec!["app::cache::insert"]
+        );
+        assert_eq!(
+            names(ReportOptions {
+                sort: SortKey::Churn,
+                ..Default::default()
+            }),
+            vec!["app::main", "app::cache::insert", "app::config::load"]
+        );
+        assert_eq!(
+            names(ReportOptions {
+                sort: SortKey::Rate,
+                ..Default::default()
+            }),
+            vec!["app::main", "app::config::load", "app::cache::insert"]
+        );
+    }
+
+    #[test]
+    fn test_should_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
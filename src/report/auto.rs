@@ -0,0 +1,125 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use super::{JsonReporter, Reporter};
+use crate::snapshot;
+
+/// The file the report is written to by [`maybe_flush`], if [`auto_report_to_file`] was called.
+static PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Change of the live bytes since the last flush above which a flush is requested, `usize::MAX` if disabled.
+static DELTA: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// The live bytes at the last flush.
+static BASELINE: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether a flush has been requested by the allocator.
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Requests a JSON report (see [`JsonReporter`]) to be written to `path` whenever the traced live bytes change
+/// by more than `delta_bytes`, up or down, since the last report.
+///
+/// The allocator only raises a flag, so the allocation path never does any I/O: the report is written
+/// by the next call to [`maybe_flush`], which should be called periodically, e.g. from the main loop.
+/// Calling it again replaces the path and the delta, and restarts from the current live bytes.
+pub fn auto_report_to_file(path: impl Into<PathBuf>, delta_bytes: usize) {
+    let path = path.into();
+    crate::alloc::untraced(|| {
+        if let Ok(mut lock) = PATH.lock() {
+            *lock = Some(path);
+        }
+    });
+    BASELINE.store(crate::global_allocated(), Ordering::Relaxed);
+    PENDING.store(false, Ordering::Relaxed);
+    DELTA.store(delta_bytes, Ordering::Relaxed);
+}
+
+/// Writes the report to the file set with [`auto_report_to_file`], if the live bytes changed enough since
+/// the last report. Returns whether the report was written.
+///
+/// The report is written to a temporary file which is then renamed, so the file is never truncated.
+/// On error, the report is written again on the next call.
+pub fn maybe_flush() -> io::Result<bool> {
+    if !PENDING.swap(false, Ordering::Relaxed) {
+        return Ok(false);
+    }
+
+    crate::alloc::untraced(|| {
+        let Some(path) = PATH.lock().ok().and_then(|path| path.clone()) else {
+            return Ok(false);
+        };
+        let res = write_report_file(&path);
+        if res.is_err() {
+            PENDING.store(true, Ordering::Relaxed);
+        }
+
+        res.map(|()| true)
+    })
+}
+
+/// Raises the flush flag if `allocated` differs from the live bytes at the last report by more than the delta.
+///
+/// Called by the allocator after each traced operation, so it only reads atomics.
+pub(crate) fn check_auto_report(allocated: usize) {
+    let delta = DELTA.load(Ordering::Relaxed);
+    if delta != usize::MAX && allocated.abs_diff(BASELINE.load(Ordering::Relaxed)) > delta {
+        PENDING.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Writes the report to `path`, atomically, and moves the baseline to the reported live bytes.
+fn write_report_file(path: &Path) -> io::Result<()> {
+    let snapshot = snapshot::snapshot()?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "report path is not a file"))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+
+    let mut file = File::create(&tmp_path)?;
+    JsonReporter::default().write_report(&snapshot, &mut file)?;
+    file.flush()?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    BASELINE.store(snapshot.allocated, Ordering::Relaxed);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_flush_report_when_delta_exceeded() {
+        crate::init_symbol_table(&["leaktracer"]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+
+        // larger than any change made by the other tests running in parallel
+        let delta = usize::MAX / 4;
+        auto_report_to_file(&path, delta);
+        let baseline = BASELINE.load(Ordering::Relaxed);
+
+        check_auto_report(baseline + delta / 2);
+        assert!(!maybe_flush().unwrap());
+        assert!(!path.exists());
+
+        check_auto_report(baseline + delta + 1);
+        assert!(maybe_flush().unwrap());
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("{\"generation\":"));
+        // flushed only once
+        assert!(!maybe_flush().unwrap());
+
+        // shrinking counts as well
+        fs::remove_file(&path).unwrap();
+        BASELINE.store(delta + 1, Ordering::Relaxed);
+        check_auto_report(0);
+        assert!(maybe_flush().unwrap());
+        assert!(path.exists());
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::{self, Write};
+
+use super::{ReportOptions, Reporter};
+use crate::snapshot::SymbolTableSnapshot;
+
+/// Writes a report as CSV, with a header row and one row per symbol.
+///
+/// Only the symbols are written, since the global counters don't fit in the rows.
+/// Sizes are always written in bytes, regardless of [`ReportOptions::humanize`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CsvReporter {
+    options: ReportOptions,
+}
+
+impl CsvReporter {
+    /// Creates a new [`CsvReporter`] with the given options.
+    pub fn new(options: ReportOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Reporter for CsvReporter {
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+        write!(w, "symbol,allocated,count")?;
+        if self.options.churn {
+            write!(w, ",churn")?;
+        }
+        if self.options.inclusive {
+            write!(w, ",inclusive")?;
+        }
+        if self.options.realloc_details {
+            write!(w, ",reallocs,realloc_moves,bytes_grown,bytes_shrunk")?;
+        }
+        if self.options.size_cardinality {
+            write!(w, ",distinct_sizes")?;
+        }
+        if self.options.peak {
+            write!(w, ",peak,peak_at_ms")?;
+        }
+        writeln!(w)?;
+        for symbol in self.options.symbols(snapshot) {
+            // symbol names may contain commas, e.g. in generic parameters
+            write!(
+                w,
+                "\"{}\",{},{}",
+                symbol.name.replace('"', "\"\""),
+                symbol.allocated,
+                symbol.count
+            )?;
+            if self.options.churn {
+                write!(w, ",{}", symbol.turnover)?;
+            }
+            if self.options.inclusive {
+                write!(w, ",{}", symbol.inclusive_allocated)?;
+            }
+            if self.options.realloc_details {
+                write!(
+                    w,
+                    ",{},{},{},{}",
+                    symbol.reallocs, symbol.realloc_moves, symbol.bytes_grown, symbol.bytes_shrunk
+                )?;
+            }
+            if self.options.size_cardinality {
+                write!(w, ",{}", symbol.size_cardinality)?;
+            }
+            if self.options.peak {
+                write!(w, ",{},{}", symbol.peak, symbol.peak_at.as_millis())?;
+            }
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::report::SortKey;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_write_csv_report() {
+        let mut buf = Vec::new();
+        CsvReporter::default()
+            .write_report(&synthetic_snapshot(), &mut buf)
+            .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "symbol,allocated,count\n\
+             \"app::cache::insert\",4096,2\n\
+             \"app::main\",1024,10\n\
+             \"app::config::load\",512,5\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_csv_inclusive_column() {
+        let mut buf = Vec::new();
+        CsvReporter::new(ReportOptions {
+            top: Some(2),
+            inclusive: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "symbol,allocated,count,inclusive\n\
+             \"app::cache::insert\",4096,2,4096\n\
+             \"app::main\",1024,10,5632\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_csv_peak_columns() {
+        let mut buf = Vec::new();
+        CsvReporter::new(ReportOptions {
+            sort: SortKey::Count,
+            top: Some(1),
+            peak: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "symbol,allocated,count,peak,peak_at_ms\n\"app::main\",1024,10,8192,342000\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_csv_churn_column() {
+        let mut buf = Vec::new();
+        CsvReporter::new(ReportOptions {
+            churn: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "symbol,allocated,count,churn\n\
+             \"app::cache::insert\",4096,2,4096\n\
+             \"app::main\",1024,10,64512\n\
+             \"app::config::load\",512,5,0\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_csv_realloc_columns() {
+        let mut buf = Vec::new();
+        CsvReporter::new(ReportOptions {
+            sort: SortKey::Name,
+            realloc_details: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "symbol,allocated,count,reallocs,realloc_moves,bytes_grown,bytes_shrunk\n\
+             \"app::cache::insert\",4096,2,0,0,0,0\n\
+             \"app::config::load\",512,5,1,0,256,0\n\
+             \"app::main\",1024,10,8,6,3072,512\n"
+        );
+    }
+}
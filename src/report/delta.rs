@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use super::{Reporter, format_bytes};
+use crate::delta::diff;
+use crate::snapshot::SymbolTableSnapshot;
+
+/// The change of the live bytes of a symbol which makes [`DeltaReporter`] log it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeltaThreshold {
+    /// The live bytes changed by more than the given bytes.
+    Bytes(usize),
+    /// The live bytes changed by more than the given fraction of the previous ones, e.g. `0.1` for 10%.
+    ///
+    /// A symbol which had no live bytes is logged as soon as it allocates.
+    Ratio(f64),
+}
+
+impl Default for DeltaThreshold {
+    fn default() -> Self {
+        Self::Bytes(0)
+    }
+}
+
+impl DeltaThreshold {
+    /// Returns whether going from `previous` live bytes to `previous + delta` exceeds the threshold.
+    fn exceeded(&self, previous: usize, delta: i64) -> bool {
+        match *self {
+            Self::Bytes(bytes) => delta.unsigned_abs() > bytes as u64,
+            Self::Ratio(ratio) => delta.unsigned_abs() as f64 > previous as f64 * ratio,
+        }
+    }
+}
+
+/// Writes only the symbols whose live bytes changed by more than a [`DeltaThreshold`] since the previous report,
+/// one line each, e.g. `app::cache::insert: 12.3 MiB (+1.2 MiB)`, to be called periodically without flooding the logs.
+///
+/// The reporter remembers the previous snapshot, so the first report writes all the symbols with live bytes.
+/// A symbol freeing all its live bytes, or removed from the table, is written once as `app::cache::insert: (freed)`,
+/// whatever the threshold. Sizes are always humanized, and the symbols are sorted by name.
+///
+/// ```rust
+/// use leaktracer::{DeltaReporter, DeltaThreshold};
+///
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// let reporter = DeltaReporter::new(DeltaThreshold::Bytes(1024 * 1024));
+/// // e.g. on every tick of a timer
+/// leaktracer::report(&reporter, &mut std::io::stderr()).expect("Failed to write report");
+/// ```
+#[derive(Debug, Default)]
+pub struct DeltaReporter {
+    threshold: DeltaThreshold,
+    previous: Mutex<Option<SymbolTableSnapshot>>,
+}
+
+impl DeltaReporter {
+    /// Creates a new [`DeltaReporter`] logging the symbols whose live bytes change by more than `threshold`.
+    pub const fn new(threshold: DeltaThreshold) -> Self {
+        Self {
+            threshold,
+            previous: Mutex::new(None),
+        }
+    }
+}
+
+impl Reporter for DeltaReporter {
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+        let mut previous = self
+            .previous
+            .lock()
+            .map_err(|_| io::Error::other("delta reporter lock poisoned"))?;
+        let first = previous.is_none();
+        let diffs = diff(previous.get_or_insert_with(Default::default), snapshot);
+        let current: HashMap<&'static str, usize> = snapshot
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.name, symbol.allocated))
+            .collect();
+
+        for diff in diffs {
+            let allocated = current.get(diff.name).copied().unwrap_or_default();
+            if allocated == 0 {
+                if diff.allocated < 0 {
+                    writeln!(w, "{}: (freed)", diff.name)?;
+                }
+                continue;
+            }
+            let before = (allocated as i64 - diff.allocated).max(0) as usize;
+            if first || self.threshold.exceeded(before, diff.allocated) {
+                let sign = if diff.allocated < 0 { '-' } else { '+' };
+                writeln!(
+                    w,
+                    "{}: {} ({sign}{})",
+                    diff.name,
+                    format_bytes(allocated),
+                    format_bytes(diff.allocated.unsigned_abs() as usize)
+                )?;
+            }
+        }
+        *previous = Some(snapshot.clone());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    fn report(reporter: &DeltaReporter, snapshot: &SymbolTableSnapshot) -> String {
+        let mut buf = Vec::new();
+        reporter
+            .write_report(snapshot, &mut buf)
+            .expect("failed to write report");
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_should_write_only_changed_symbols() {
+        let reporter = DeltaReporter::new(DeltaThreshold::Bytes(1024));
+        let first = synthetic_snapshot();
+        assert_eq!(
+            report(&reporter, &first),
+            "app::cache::insert: 4.0 KiB (+4.0 KiB)\n\
+             app::config::load: 512 B (+512 B)\n\
+             app::main: 1.0 KiB (+1.0 KiB)\n"
+        );
+        assert_eq!(report(&reporter, &first), "");
+
+        // app::main grows beyond the threshold, app::config::load within it, app::cache::insert is freed
+        let mut second = first.clone();
+        second.symbols[0].allocated += 2048;
+        second.symbols[1].allocated = 0;
+        second.symbols[2].allocated += 512;
+        assert_eq!(
+            report(&reporter, &second),
+            "app::cache::insert: (freed)\napp::main: 3.0 KiB (+2.0 KiB)\n"
+        );
+
+        // app::main shrinks, app::config::load is removed, the freed symbol is not written again
+        let mut third = second.clone();
+        third.symbols[0].allocated = 1024;
+        third.symbols.remove(2);
+        assert_eq!(
+            report(&reporter, &third),
+            "app::config::load: (freed)\napp::main: 1.0 KiB (-2.0 KiB)\n"
+        );
+        assert_eq!(report(&reporter, &third), "");
+    }
+
+    #[test]
+    fn test_should_apply_relative_threshold() {
+        let reporter = DeltaReporter::new(DeltaThreshold::Ratio(0.5));
+        let first = synthetic_snapshot();
+        report(&reporter, &first);
+
+        // app::main +50% isn't more than the threshold, app::cache::insert +100% is
+        let mut second = first.clone();
+        second.symbols[0].allocated += 512;
+        second.symbols[1].allocated += 4096;
+        assert_eq!(
+            report(&reporter, &second),
+            "app::cache::insert: 8.0 KiB (+4.0 KiB)\n"
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{JsonReporter, report};
+
+/// Prefix of the report file names.
+const FILE_PREFIX: &str = "leaktracer-";
+
+/// Extension of the report file names.
+const FILE_EXTENSION: &str = ".json";
+
+/// Handle of the thread started by [`file_reporter`].
+///
+/// Dropping the handle stops the thread, without waiting for it.
+#[derive(Debug)]
+pub struct FileReporterHandle {
+    stop: Sender<()>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl FileReporterHandle {
+    /// Stops the reporter thread and waits for it, returning the error which stopped it, if any.
+    pub fn stop(mut self) -> io::Result<()> {
+        let _ = self.stop.send(());
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(res)) => res,
+            Some(Err(_)) => Err(io::Error::other("file reporter thread panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileReporterHandle {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+    }
+}
+
+/// Starts a thread writing a JSON report (see [`JsonReporter`]) to `dir` every `interval`,
+/// as `leaktracer-<timestamp>.json`, keeping only the `max_files` most recent reports.
+///
+/// Each report is written to a temporary file which is then renamed, so a crash never leaves a truncated report.
+/// The allocations made by the reporter thread are not traced.
+///
+/// The thread stops on the first I/O error, which is returned by [`FileReporterHandle::stop`].
+///
+/// A zero `interval` is rejected with [`io::ErrorKind::InvalidInput`], since the thread would write reports in a busy loop.
+pub fn file_reporter(
+    dir: impl Into<PathBuf>,
+    interval: Duration,
+    max_files: usize,
+) -> io::Result<FileReporterHandle> {
+    if interval.is_zero() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "file reporter interval is zero",
+        ));
+    }
+    let dir = dir.into();
+    fs::create_dir_all(&dir)?;
+
+    let (stop, stopped) = mpsc::channel();
+    let thread = std::thread::spawn(move || {
+        crate::alloc::untraced(|| {
+            loop {
+                write_report_file(&dir)?;
+                rotate(&dir, max_files)?;
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+            }
+        })
+    });
+
+    Ok(FileReporterHandle {
+        stop,
+        thread: Some(thread),
+    })
+}
+
+/// Writes a report file to `dir`, atomically.
+fn write_report_file(dir: &Path) -> io::Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros();
+    let name = format!("{FILE_PREFIX}{timestamp:020}{FILE_EXTENSION}");
+    // the temporary file doesn't match the report names, so it's never rotated
+    let tmp_path = dir.join(format!(".{name}.tmp"));
+
+    let mut file = File::create(&tmp_path)?;
+    report(&JsonReporter::default(), &mut file)?;
+    file.flush()?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, dir.join(name))
+}
+
+/// Deletes the oldest report files in `dir`, keeping the `max_files` most recent ones.
+fn rotate(dir: &Path, max_files: usize) -> io::Result<()> {
+    let mut reports = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name.starts_with(FILE_PREFIX) && name.ends_with(FILE_EXTENSION) {
+            reports.push(name.to_string());
+        }
+    }
+    // the timestamps are zero-padded, so the names sort chronologically
+    reports.sort();
+
+    let excess = reports.len().saturating_sub(max_files);
+    for name in &reports[..excess] {
+        fs::remove_file(dir.join(name))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn report_files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    // the reporter runs on its own thread, which can't be spawned on WebAssembly
+    #[cfg(not(target_family = "wasm"))]
+    fn test_should_rotate_report_files() {
+        crate::init_symbol_table(&["leaktracer"]);
+        let dir = tempfile::tempdir().unwrap();
+
+        let handle = file_reporter(dir.path(), Duration::from_millis(10), 3)
+            .expect("failed to start file reporter");
+        std::thread::sleep(Duration::from_millis(200));
+        handle.stop().expect("file reporter failed");
+
+        let files = report_files(dir.path());
+        assert_eq!(files.len(), 3, "{files:?}");
+        for file in &files {
+            assert!(file.starts_with(FILE_PREFIX) && file.ends_with(FILE_EXTENSION));
+            let content = fs::read_to_string(dir.path().join(file)).unwrap();
+            assert!(content.starts_with("{\"generation\":"));
+            assert!(content.ends_with("]}\n"));
+        }
+    }
+
+    #[test]
+    fn test_should_reject_zero_interval() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = file_reporter(dir.path(), Duration::ZERO, 3).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(report_files(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_should_keep_most_recent_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "leaktracer-1.json",
+            "leaktracer-2.json",
+            "leaktracer-3.json",
+            "other.json",
+        ] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        rotate(dir.path(), 2).unwrap();
+        assert_eq!(
+            report_files(dir.path()),
+            vec!["leaktracer-2.json", "leaktracer-3.json", "other.json"]
+        );
+    }
+}
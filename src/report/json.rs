@@ -0,0 +1,168 @@
+use std::io::{self, Write};
+
+use super::{ReportOptions, Reporter};
+use crate::snapshot::SymbolTableSnapshot;
+use crate::symbols::SizeCardinality;
+
+/// Writes a report as a single JSON object.
+///
+/// Sizes are always written in bytes, regardless of [`ReportOptions::humanize`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonReporter {
+    options: ReportOptions,
+}
+
+impl JsonReporter {
+    /// Creates a new [`JsonReporter`] with the given options.
+    pub fn new(options: ReportOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+        write!(
+            w,
+            "{{\"generation\":{},\"uptime_ms\":{},\"padded_size\":{},\"sampling_divisor\":{},\"allocated\":{},\"process_rss\":",
+            snapshot.generation,
+            snapshot.uptime.as_millis(),
+            snapshot.padded_size,
+            snapshot.sampling_divisor,
+            snapshot.allocated
+        )?;
+        match snapshot.process_rss {
+            Some(rss) => write!(w, "{rss}")?,
+            None => write!(w, "null")?,
+        }
+        write!(
+            w,
+            ",\"untracked_frees\":{},\"untracked_freed_bytes\":{},\"symbols\":[",
+            snapshot.untracked_frees, snapshot.untracked_freed_bytes
+        )?;
+        for (i, symbol) in self.options.symbols(snapshot).into_iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            write!(
+                w,
+                "{{\"name\":\"{}\",\"allocated\":{},\"count\":{}",
+                escape(symbol.name),
+                symbol.allocated,
+                symbol.count
+            )?;
+            if self.options.churn {
+                write!(w, ",\"turnover\":{}", symbol.turnover)?;
+            }
+            if self.options.inclusive {
+                write!(w, ",\"inclusive_allocated\":{}", symbol.inclusive_allocated)?;
+            }
+            if self.options.realloc_details {
+                write!(
+                    w,
+                    ",\"reallocs\":{},\"realloc_moves\":{},\"bytes_grown\":{},\"bytes_shrunk\":{}",
+                    symbol.reallocs, symbol.realloc_moves, symbol.bytes_grown, symbol.bytes_shrunk
+                )?;
+            }
+            if self.options.size_cardinality {
+                match symbol.size_cardinality {
+                    SizeCardinality::Exact(count) => write!(w, ",\"distinct_sizes\":{count}")?,
+                    SizeCardinality::Many => write!(w, ",\"distinct_sizes\":null")?,
+                }
+            }
+            if self.options.peak {
+                write!(
+                    w,
+                    ",\"peak\":{},\"peak_at_ms\":{}",
+                    symbol.peak,
+                    symbol.peak_at.as_millis()
+                )?;
+            }
+            write!(w, "}}")?;
+        }
+        writeln!(w, "]}}")
+    }
+}
+
+/// Escapes `s` to be written in a JSON string.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::report::SortKey;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_write_json_report() {
+        let mut buf = Vec::new();
+        JsonReporter::new(ReportOptions {
+            top: Some(1),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"generation\":42,\"uptime_ms\":1500,\"padded_size\":false,\"sampling_divisor\":1,\"allocated\":5632,\"process_rss\":8192,\
+             \"untracked_frees\":1,\"untracked_freed_bytes\":16,\
+             \"symbols\":[{\"name\":\"app::cache::insert\",\"allocated\":4096,\"count\":2}]}\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_json_turnover() {
+        let mut buf = Vec::new();
+        JsonReporter::new(ReportOptions {
+            top: Some(1),
+            sort: SortKey::Churn,
+            churn: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert!(
+            String::from_utf8(buf)
+                .unwrap()
+                .ends_with("\"symbols\":[{\"name\":\"app::main\",\"allocated\":1024,\"count\":10,\"turnover\":64512}]}\n")
+        );
+    }
+
+    #[test]
+    fn test_should_write_json_size_cardinality() {
+        let mut buf = Vec::new();
+        JsonReporter::new(ReportOptions {
+            size_cardinality: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.contains(
+            "\"name\":\"app::cache::insert\",\"allocated\":4096,\"count\":2,\"distinct_sizes\":1}"
+        ));
+        assert!(report.contains(
+            "\"name\":\"app::main\",\"allocated\":1024,\"count\":10,\"distinct_sizes\":null}"
+        ));
+    }
+
+    #[test]
+    fn test_should_escape_json_string() {
+        assert_eq!(escape("a\"b\\c\n"), "a\\\"b\\\\c\\u000a");
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{self, Write};
+
+use super::{ReportOptions, Reporter};
+use crate::snapshot::SymbolTableSnapshot;
+use crate::time;
+
+/// Writes a report as a markdown table, e.g. to be pasted in an issue.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownReporter {
+    options: ReportOptions,
+}
+
+impl MarkdownReporter {
+    /// Creates a new [`MarkdownReporter`] with the given options.
+    pub fn new(options: ReportOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Reporter for MarkdownReporter {
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+        let mut header = String::from("| Symbol | Allocated | Count |");
+        let mut separator = String::from("|---|---:|---:|");
+        for (enabled, column) in [
+            (self.options.churn, "Churn"),
+            (self.options.inclusive, "Inclusive"),
+        ] {
+            if enabled {
+                header.push_str(&format!(" {column} |"));
+                separator.push_str("---:|");
+            }
+        }
+        if self.options.realloc_details {
+            header.push_str(" Reallocs | Moves | Grown | Shrunk |");
+            separator.push_str("---:|---:|---:|---:|");
+        }
+        if self.options.size_cardinality {
+            header.push_str(" Sizes |");
+            separator.push_str("---:|");
+        }
+        if self.options.peak {
+            header.push_str(" Peak | Peaked at |");
+            separator.push_str("---:|---:|");
+        }
+        writeln!(w, "{header}")?;
+        writeln!(w, "{separator}")?;
+        for symbol in self.options.symbols(snapshot) {
+            write!(
+                w,
+                "| `{}` | {} | {} |",
+                symbol.name.replace('|', "\\|"),
+                self.options.bytes(symbol.allocated),
+                symbol.count
+            )?;
+            if self.options.churn {
+                write!(w, " {} |", self.options.bytes(symbol.turnover))?;
+            }
+            if self.options.inclusive {
+                write!(w, " {} |", self.options.bytes(symbol.inclusive_allocated))?;
+            }
+            if self.options.realloc_details {
+                write!(
+                    w,
+                    " {} | {} | {} | {} |",
+                    symbol.reallocs,
+                    symbol.realloc_moves,
+                    self.options.bytes(symbol.bytes_grown),
+                    self.options.bytes(symbol.bytes_shrunk)
+                )?;
+            }
+            if self.options.size_cardinality {
+                write!(w, " {} |", symbol.size_cardinality)?;
+            }
+            if self.options.peak {
+                write!(
+                    w,
+                    " {} | t+{} |",
+                    self.options.bytes(symbol.peak),
+                    time::format_duration(symbol.peak_at)
+                )?;
+            }
+            writeln!(w)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_write_markdown_report() {
+        let mut buf = Vec::new();
+        MarkdownReporter::new(ReportOptions {
+            top: Some(2),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "| Symbol | Allocated | Count |\n\
+             |---|---:|---:|\n\
+             | `app::cache::insert` | 4096 bytes | 2 |\n\
+             | `app::main` | 1024 bytes | 10 |\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_markdown_churn_column() {
+        let mut buf = Vec::new();
+        MarkdownReporter::new(ReportOptions {
+            top: Some(1),
+            humanize: true,
+            churn: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "| Symbol | Allocated | Count | Churn |\n\
+             |---|---:|---:|---:|\n\
+             | `app::cache::insert` | 4.0 KiB | 2 | 4.0 KiB |\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_markdown_inclusive_column() {
+        let mut buf = Vec::new();
+        MarkdownReporter::new(ReportOptions {
+            top: Some(2),
+            churn: true,
+            inclusive: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "| Symbol | Allocated | Count | Churn | Inclusive |\n\
+             |---|---:|---:|---:|---:|\n\
+             | `app::cache::insert` | 4096 bytes | 2 | 4096 bytes | 4096 bytes |\n\
+             | `app::main` | 1024 bytes | 10 | 64512 bytes | 5632 bytes |\n"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use super::{ReportOptions, Reporter, report};
+use crate::snapshot::SymbolTableSnapshot;
+
+/// Writes a report as a gzipped [pprof](https://github.com/google/pprof) heap profile,
+/// to be viewed e.g. with `go tool pprof -http=: profile.pb.gz`.
+///
+/// The profile has a single `inuse_space` sample type, in bytes, with one sample per symbol:
+/// the stack of each sample is a single frame named after the symbol, and its value is the symbol allocated bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PprofReporter {
+    options: ReportOptions,
+}
+
+impl PprofReporter {
+    /// Creates a new [`PprofReporter`] with the given options.
+    pub fn new(options: ReportOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Reporter for PprofReporter {
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+        // the string table must start with the empty string
+        let mut strings = vec!["", "inuse_space", "bytes"];
+        let mut profile = ProtoWriter::default();
+
+        let mut value_type = ProtoWriter::default();
+        value_type.varint(1, 1);
+        value_type.varint(2, 2);
+        profile.bytes(1, &value_type.buf);
+
+        let symbols = self.options.symbols(snapshot);
+        for (i, symbol) in symbols.iter().enumerate() {
+            // the ids are 1-based, one function and one location per symbol
+            let id = i as u64 + 1;
+
+            let mut sample = ProtoWriter::default();
+            sample.packed(1, &[id]);
+            sample.packed(2, &[symbol.allocated as u64]);
+            profile.bytes(2, &sample.buf);
+        }
+        for i in 0..symbols.len() {
+            let id = i as u64 + 1;
+
+            let mut line = ProtoWriter::default();
+            line.varint(1, id);
+            let mut location = ProtoWriter::default();
+            location.varint(1, id);
+            location.bytes(4, &line.buf);
+            profile.bytes(4, &location.buf);
+        }
+        for (i, symbol) in symbols.iter().enumerate() {
+            let id = i as u64 + 1;
+            strings.push(symbol.name);
+
+            let mut function = ProtoWriter::default();
+            function.varint(1, id);
+            function.varint(2, strings.len() as u64 - 1);
+            function.varint(3, strings.len() as u64 - 1);
+            profile.bytes(5, &function.buf);
+        }
+        for string in &strings {
+            profile.bytes(6, string.as_bytes());
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        profile.varint(9, now.as_nanos() as u64);
+        profile.varint(10, snapshot.uptime.as_nanos() as u64);
+        profile.bytes(11, &value_type.buf);
+
+        let mut encoder = GzEncoder::new(w, Compression::default());
+        encoder.write_all(&profile.buf)?;
+        encoder.finish()?.flush()
+    }
+}
+
+/// Writes a gzipped pprof heap profile of the symbol table to the given writer.
+///
+/// This is a shorthand for [`report`] with the default [`PprofReporter`].
+pub fn write_pprof<W: Write>(w: &mut W) -> io::Result<()> {
+    report(&PprofReporter::default(), w)
+}
+
+/// A minimal protobuf encoder, writing the fields of a single message.
+#[derive(Debug, Default)]
+struct ProtoWriter {
+    buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+    /// Wire type of the varint fields.
+    const VARINT: u64 = 0;
+    /// Wire type of the length-delimited fields.
+    const LEN: u64 = 2;
+
+    /// Writes a varint field.
+    fn varint(&mut self, field: u64, value: u64) {
+        self.raw_varint(field << 3 | Self::VARINT);
+        self.raw_varint(value);
+    }
+
+    /// Writes a length-delimited field, i.e. a string or an embedded message.
+    fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        self.raw_varint(field << 3 | Self::LEN);
+        self.raw_varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Writes a packed repeated varint field.
+    fn packed(&mut self, field: u64, values: &[u64]) {
+        let mut packed = ProtoWriter::default();
+        for value in values {
+            packed.raw_varint(*value);
+        }
+        self.bytes(field, &packed.buf);
+    }
+
+    /// Writes a varint, 7 bits at a time from the least significant.
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    /// A decoded protobuf field value.
+    #[derive(Debug, Clone, PartialEq)]
+    enum Value {
+        Varint(u64),
+        Bytes(Vec<u8>),
+    }
+
+    /// Decodes a varint from the start of `buf`, advancing it.
+    fn decode_varint(buf: &mut &[u8]) -> u64 {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let (byte, rest) = buf.split_first().expect("truncated varint");
+            *buf = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        value
+    }
+
+    /// Decodes the fields of a message.
+    fn decode(mut buf: &[u8]) -> Vec<(u64, Value)> {
+        let mut fields = Vec::new();
+        while !buf.is_empty() {
+            let key = decode_varint(&mut buf);
+            let value = match key & 0x7 {
+                0 => Value::Varint(decode_varint(&mut buf)),
+                2 => {
+                    let len = decode_varint(&mut buf) as usize;
+                    let (bytes, rest) = buf.split_at(len);
+                    buf = rest;
+                    Value::Bytes(bytes.to_vec())
+                }
+                wire_type => panic!("unexpected wire type {wire_type}"),
+            };
+            fields.push((key >> 3, value));
+        }
+        fields
+    }
+
+    /// Returns the values of the field `field` of a message.
+    fn field(fields: &[(u64, Value)], field: u64) -> Vec<Value> {
+        fields
+            .iter()
+            .filter(|(number, _)| *number == field)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    fn bytes(value: &Value) -> &[u8] {
+        match value {
+            Value::Bytes(bytes) => bytes,
+            Value::Varint(_) => panic!("expected bytes"),
+        }
+    }
+
+    fn varint(value: &Value) -> u64 {
+        match value {
+            Value::Varint(value) => *value,
+            Value::Bytes(_) => panic!("expected varint"),
+        }
+    }
+
+    #[test]
+    fn test_should_encode_varint() {
+        let mut writer = ProtoWriter::default();
+        writer.raw_varint(1);
+        writer.raw_varint(300);
+        assert_eq!(writer.buf, vec![0x01, 0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_should_write_pprof_profile() {
+        let mut gzipped = Vec::new();
+        PprofReporter::default()
+            .write_report(&synthetic_snapshot(), &mut gzipped)
+            .expect("failed to write report");
+        let mut buf = Vec::new();
+        GzDecoder::new(gzipped.as_slice())
+            .read_to_end(&mut buf)
+            .expect("profile should be gzipped");
+        let profile = decode(&buf);
+
+        let strings: Vec<String> = field(&profile, 6)
+            .iter()
+            .map(|value| String::from_utf8(bytes(value).to_vec()).unwrap())
+            .collect();
+        assert_eq!(strings[0], "");
+
+        let sample_types = field(&profile, 1);
+        assert_eq!(sample_types.len(), 1);
+        let sample_type = decode(bytes(&sample_types[0]));
+        assert_eq!(
+            strings[varint(&field(&sample_type, 1)[0]) as usize],
+            "inuse_space"
+        );
+        assert_eq!(
+            strings[varint(&field(&sample_type, 2)[0]) as usize],
+            "bytes"
+        );
+
+        // each sample points to a location, whose line points to a function named after the symbol
+        let functions: Vec<_> = field(&profile, 5)
+            .iter()
+            .map(|function| decode(bytes(function)))
+            .collect();
+        let locations: Vec<_> = field(&profile, 4)
+            .iter()
+            .map(|location| decode(bytes(location)))
+            .collect();
+        let samples: Vec<(String, u64)> = field(&profile, 2)
+            .iter()
+            .map(|sample| {
+                let sample = decode(bytes(sample));
+                let location_id = decode_varint(&mut bytes(&field(&sample, 1)[0]));
+                let value = decode_varint(&mut bytes(&field(&sample, 2)[0]));
+
+                let location = locations
+                    .iter()
+                    .find(|location| varint(&field(location, 1)[0]) == location_id)
+                    .expect("location should exist");
+                let line = decode(bytes(&field(location, 4)[0]));
+                let function_id = varint(&field(&line, 1)[0]);
+                let function = functions
+                    .iter()
+                    .find(|function| varint(&field(function, 1)[0]) == function_id)
+                    .expect("function should exist");
+                let name = strings[varint(&field(function, 2)[0]) as usize].clone();
+
+                (name, value)
+            })
+            .collect();
+
+        assert_eq!(
+            samples,
+            vec![
+                ("app::cache::insert".to_string(), 4096),
+                ("app::main".to_string(), 1024),
+                ("app::config::load".to_string(), 512),
+            ]
+        );
+    }
+}
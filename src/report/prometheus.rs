@@ -0,0 +1,105 @@
+use std::io::{self, Write};
+
+use super::{ReportOptions, Reporter};
+use crate::snapshot::SymbolTableSnapshot;
+
+/// Writes a report in the Prometheus text exposition format, e.g. to be served on a `/metrics` endpoint.
+///
+/// Sizes are always written in bytes, regardless of [`ReportOptions::humanize`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrometheusReporter {
+    options: ReportOptions,
+}
+
+impl PrometheusReporter {
+    /// Creates a new [`PrometheusReporter`] with the given options.
+    pub fn new(options: ReportOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Reporter for PrometheusReporter {
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+        writeln!(w, "# HELP leaktracer_allocated_bytes Traced live bytes.")?;
+        writeln!(w, "# TYPE leaktracer_allocated_bytes gauge")?;
+        writeln!(w, "leaktracer_allocated_bytes {}", snapshot.allocated)?;
+        writeln!(
+            w,
+            "# HELP leaktracer_untracked_frees_total Deallocations of untracked pointers."
+        )?;
+        writeln!(w, "# TYPE leaktracer_untracked_frees_total counter")?;
+        writeln!(
+            w,
+            "leaktracer_untracked_frees_total {}",
+            snapshot.untracked_frees
+        )?;
+
+        let symbols = self.options.symbols(snapshot);
+        writeln!(
+            w,
+            "# HELP leaktracer_symbol_allocated_bytes Live bytes allocated by symbol."
+        )?;
+        writeln!(w, "# TYPE leaktracer_symbol_allocated_bytes gauge")?;
+        for symbol in &symbols {
+            writeln!(
+                w,
+                "leaktracer_symbol_allocated_bytes{{symbol=\"{}\"}} {}",
+                escape(symbol.name),
+                symbol.allocated
+            )?;
+        }
+        writeln!(
+            w,
+            "# HELP leaktracer_symbol_allocations Live allocations by symbol."
+        )?;
+        writeln!(w, "# TYPE leaktracer_symbol_allocations gauge")?;
+        for symbol in &symbols {
+            writeln!(
+                w,
+                "leaktracer_symbol_allocations{{symbol=\"{}\"}} {}",
+                escape(symbol.name),
+                symbol.count
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Escapes `s` to be written as a label value.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_write_prometheus_report() {
+        let mut buf = Vec::new();
+        PrometheusReporter::new(ReportOptions {
+            top: Some(1),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.contains("leaktracer_allocated_bytes 5632\n"));
+        assert!(report.contains("leaktracer_untracked_frees_total 1\n"));
+        assert!(
+            report.contains(
+                "leaktracer_symbol_allocated_bytes{symbol=\"app::cache::insert\"} 4096\n"
+            )
+        );
+        assert!(
+            report.contains("leaktracer_symbol_allocations{symbol=\"app::cache::insert\"} 2\n")
+        );
+        assert!(!report.contains("app::main"));
+    }
+}
@@ -0,0 +1,248 @@
+use std::io::{self, Write};
+
+use super::{ReportOptions, Reporter};
+use crate::snapshot::SymbolTableSnapshot;
+use crate::time;
+
+/// Writes a plain text report.
+///
+/// The report starts with a header containing the tracer uptime, followed by a summary section
+/// and one line per symbol. The footer reports the deallocations of pointers which were never traced,
+/// followed by the alignment classes if [`ReportOptions::alignment`] is set.
+///
+/// The summary compares the process RSS (see [`crate::process_rss`]) with the traced live bytes and the memory
+/// of the tracer itself (see [`crate::self_overhead_bytes`]): the rest of the RSS is unexplained.
+/// The RSS line is omitted on platforms where the RSS is not available.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextReporter {
+    options: ReportOptions,
+}
+
+impl TextReporter {
+    /// Creates a new [`TextReporter`] with the given options.
+    pub fn new(options: ReportOptions) -> Self {
+        Self { options }
+    }
+}
+
+impl Reporter for TextReporter {
+    fn write_report(&self, snapshot: &SymbolTableSnapshot, w: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "leaktracer report (uptime {})",
+            time::format_duration(snapshot.uptime)
+        )?;
+        writeln!(
+            w,
+            "sizes: {}",
+            if snapshot.padded_size {
+                "padded to alignment"
+            } else {
+                "requested"
+            }
+        )?;
+        if snapshot.sampling_divisor > 1 {
+            writeln!(
+                w,
+                "sampling: 1 out of {} allocations, scaled",
+                snapshot.sampling_divisor
+            )?;
+        }
+        writeln!(w, "traced live bytes: {}", snapshot.allocated)?;
+        writeln!(w, "tracer overhead: {} bytes", snapshot.self_overhead_bytes)?;
+        if let Some(rss) = snapshot.process_rss {
+            writeln!(
+                w,
+                "process rss: {rss} bytes (unexplained {} bytes)",
+                rss.saturating_sub(snapshot.allocated)
+                    .saturating_sub(snapshot.self_overhead_bytes)
+            )?;
+        }
+        for symbol in self.options.symbols(snapshot) {
+            write!(
+                w,
+                "{}: allocated {}, count {}",
+                symbol.name,
+                self.options.bytes(symbol.allocated),
+                symbol.count
+            )?;
+            if self.options.churn {
+                write!(w, ", churn {}", self.options.bytes(symbol.turnover))?;
+            }
+            if self.options.inclusive {
+                write!(
+                    w,
+                    ", inclusive {}",
+                    self.options.bytes(symbol.inclusive_allocated)
+                )?;
+            }
+            if self.options.realloc_details {
+                write!(
+                    w,
+                    ", reallocs {} ({} moved), grown {}, shrunk {}",
+                    symbol.reallocs,
+                    symbol.realloc_moves,
+                    self.options.bytes(symbol.bytes_grown),
+                    self.options.bytes(symbol.bytes_shrunk)
+                )?;
+            }
+            if self.options.size_cardinality {
+                write!(w, ", distinct sizes {}", symbol.size_cardinality)?;
+            }
+            if self.options.peak {
+                write!(
+                    w,
+                    ", peak {} at t+{}",
+                    self.options.bytes(symbol.peak),
+                    time::format_duration(symbol.peak_at)
+                )?;
+            }
+            writeln!(w)?;
+        }
+        writeln!(
+            w,
+            "untracked frees: {} ({} bytes)",
+            snapshot.untracked_frees, snapshot.untracked_freed_bytes
+        )?;
+        if self.options.alignment {
+            let alignment = snapshot.alignment_stats();
+            writeln!(
+                w,
+                "alignment: <=16 {}, 32 {}, 64 {}, 4096 {}, other {} ({} over-aligned)",
+                alignment.natural,
+                alignment.align_32,
+                alignment.align_64,
+                alignment.page,
+                alignment.other,
+                alignment.over_aligned()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::report::SortKey;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_write_text_report() {
+        let mut buf = Vec::new();
+        TextReporter::new(ReportOptions {
+            humanize: true,
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "leaktracer report (uptime 1.500s)\n\
+             sizes: requested\n\
+             traced live bytes: 5632\n\
+             tracer overhead: 1024 bytes\n\
+             process rss: 8192 bytes (unexplained 1536 bytes)\n\
+             app::cache::insert: allocated 4.0 KiB, count 2\n\
+             app::main: allocated 1.0 KiB, count 10\n\
+             app::config::load: allocated 512 B, count 5\n\
+             untracked frees: 1 (16 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn test_should_write_churn_column() {
+        let mut buf = Vec::new();
+        TextReporter::new(ReportOptions {
+            sort: SortKey::Churn,
+            churn: true,
+            top: Some(2),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.contains(
+            "app::main: allocated 1024 bytes, count 10, churn 64512 bytes\n\
+             app::cache::insert: allocated 4096 bytes, count 2, churn 4096 bytes\n"
+        ));
+    }
+
+    #[test]
+    fn test_should_write_realloc_columns() {
+        let mut buf = Vec::new();
+        TextReporter::new(ReportOptions {
+            sort: SortKey::Count,
+            realloc_details: true,
+            humanize: true,
+            top: Some(1),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.contains(
+            "app::main: allocated 1.0 KiB, count 10, reallocs 8 (6 moved), grown 3.0 KiB, shrunk 512 B\n"
+        ));
+    }
+
+    #[test]
+    fn test_should_write_size_cardinality_column() {
+        let mut buf = Vec::new();
+        TextReporter::new(ReportOptions {
+            sort: SortKey::Count,
+            size_cardinality: true,
+            top: Some(2),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.contains(
+            "app::main: allocated 1024 bytes, count 10, distinct sizes >16\n\
+             app::config::load: allocated 512 bytes, count 5, distinct sizes 3\n"
+        ));
+    }
+
+    #[test]
+    fn test_should_write_peak_columns() {
+        let mut buf = Vec::new();
+        TextReporter::new(ReportOptions {
+            peak: true,
+            humanize: true,
+            top: Some(1),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.contains(
+            "app::cache::insert: allocated 4.0 KiB, count 2, peak 4.0 KiB at t+1.200s\n"
+        ));
+    }
+
+    #[test]
+    fn test_should_write_alignment_section() {
+        let mut buf = Vec::new();
+        TextReporter::new(ReportOptions {
+            alignment: true,
+            top: Some(1),
+            ..Default::default()
+        })
+        .write_report(&synthetic_snapshot(), &mut buf)
+        .expect("failed to write report");
+
+        let report = String::from_utf8(buf).unwrap();
+        assert!(report.ends_with(
+            "untracked frees: 1 (16 bytes)\n\
+             alignment: <=16 635, 32 0, 64 10, 4096 4, other 0 (14 over-aligned)\n"
+        ));
+    }
+}
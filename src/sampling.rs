@@ -0,0 +1,161 @@
+//! Sampling of the traced allocations, to bound the cost of the call stack capture.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+/// Upper bound of the adaptive sampling divisor.
+pub(crate) const MAX_SAMPLING_DIVISOR: usize = 1 << 16;
+
+/// Length of the window over which the adaptive sampling measures the call stack capture time.
+pub(crate) const ADAPTIVE_WINDOW: Duration = Duration::from_secs(1);
+
+thread_local! {
+    /// The sampling divisor overriding the allocator one on the current thread, if any.
+    static DIVISOR_OVERRIDE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// How the allocations are sampled (see [`crate::LeaktracerAllocator::with_sampling`]).
+///
+/// Only 1 out of every `divisor` allocations is attributed to a symbol, with its bytes and count scaled by the divisor,
+/// so the aggregates stay approximately correct.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    /// Traces 1 out of every `n` allocations. `Fixed(1)` traces every allocation.
+    Fixed(usize),
+    /// Adapts the divisor so the time spent capturing call stacks stays within `budget`,
+    /// as a fraction of the wall time (e.g. `0.05` for 5%).
+    ///
+    /// On `wasm32-unknown-unknown`, where the capture time can't be measured, every allocation is traced.
+    Adaptive {
+        /// The fraction of the wall time which can be spent capturing call stacks.
+        budget: f64,
+    },
+}
+
+impl Sampling {
+    /// Returns the divisor the sampling starts with.
+    pub(crate) const fn initial_divisor(&self) -> usize {
+        match self {
+            Sampling::Fixed(0) => 1,
+            Sampling::Fixed(n) => *n,
+            Sampling::Adaptive { .. } => 1,
+        }
+    }
+}
+
+/// Runs `f` with sampling disabled on the current thread, so every allocation it makes is traced,
+/// whatever the sampling set with [`crate::LeaktracerAllocator::with_sampling`] and
+/// [`crate::LeaktracerAllocator::with_size_class_sampling`].
+///
+/// Useful to capture the full detail of a short window, e.g. when a leak alarm fires, without paying for it
+/// the rest of the time. The other threads keep sampling as usual. The previous state is restored afterwards,
+/// so calls can be nested.
+pub fn with_full_fidelity<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let prev = DIVISOR_OVERRIDE.replace(Some(1));
+    let res = f();
+    DIVISOR_OVERRIDE.set(prev);
+
+    res
+}
+
+/// Returns the sampling divisor overriding the allocator one on the current thread, if any.
+pub(crate) fn divisor_override() -> Option<usize> {
+    DIVISOR_OVERRIDE.try_with(Cell::get).ok().flatten()
+}
+
+/// Returns the next adaptive sampling divisor, given the time spent capturing call stacks (`busy`)
+/// during the last `elapsed` window.
+///
+/// The divisor is doubled when the overhead exceeds the budget, and halved when it's below a quarter of it,
+/// so halving can't push the overhead back over the budget. The divisor is bounded to `1..=MAX_SAMPLING_DIVISOR`.
+pub(crate) fn next_divisor(
+    divisor: usize,
+    budget: f64,
+    busy: Duration,
+    elapsed: Duration,
+) -> usize {
+    if elapsed.is_zero() {
+        return divisor;
+    }
+    let overhead = busy.as_secs_f64() / elapsed.as_secs_f64();
+
+    if overhead > budget {
+        divisor.saturating_mul(2).min(MAX_SAMPLING_DIVISOR)
+    } else if overhead < budget / 4.0 {
+        (divisor / 2).max(1)
+    } else {
+        divisor
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    const BUDGET: f64 = 0.05;
+
+    #[test]
+    fn test_should_raise_divisor_over_budget() {
+        let busy = Duration::from_millis(200);
+        assert_eq!(next_divisor(1, BUDGET, busy, ADAPTIVE_WINDOW), 2);
+        assert_eq!(next_divisor(8, BUDGET, busy, ADAPTIVE_WINDOW), 16);
+        assert_eq!(
+            next_divisor(MAX_SAMPLING_DIVISOR, BUDGET, busy, ADAPTIVE_WINDOW),
+            MAX_SAMPLING_DIVISOR
+        );
+    }
+
+    #[test]
+    fn test_should_lower_divisor_when_idle() {
+        let idle = Duration::from_millis(1);
+        assert_eq!(next_divisor(16, BUDGET, idle, ADAPTIVE_WINDOW), 8);
+        assert_eq!(next_divisor(1, BUDGET, idle, ADAPTIVE_WINDOW), 1);
+    }
+
+    #[test]
+    fn test_should_keep_divisor_within_budget() {
+        let busy = Duration::from_millis(30);
+        assert_eq!(next_divisor(4, BUDGET, busy, ADAPTIVE_WINDOW), 4);
+        assert_eq!(next_divisor(4, BUDGET, busy, Duration::ZERO), 4);
+    }
+
+    #[test]
+    fn test_should_restore_sampling_after_full_fidelity() {
+        assert_eq!(divisor_override(), None);
+        let res = with_full_fidelity(|| {
+            assert_eq!(divisor_override(), Some(1));
+            with_full_fidelity(|| assert_eq!(divisor_override(), Some(1)));
+            // the nested call restores the outer override
+            assert_eq!(divisor_override(), Some(1));
+            // other threads keep sampling
+            std::thread::spawn(|| assert_eq!(divisor_override(), None))
+                .join()
+                .unwrap();
+            42
+        });
+        assert_eq!(res, 42);
+        assert_eq!(divisor_override(), None);
+    }
+
+    #[test]
+    fn test_should_converge_on_budget() {
+        // the capture time is proportional to the sampled allocations
+        let capture_per_window = |divisor: usize| Duration::from_millis(1000 / divisor as u64);
+
+        let mut divisor = 1;
+        for _ in 0..20 {
+            divisor = next_divisor(
+                divisor,
+                BUDGET,
+                capture_per_window(divisor),
+                ADAPTIVE_WINDOW,
+            );
+        }
+        let overhead = capture_per_window(divisor).as_secs_f64();
+        assert!((BUDGET / 4.0..=BUDGET).contains(&overhead), "{divisor}");
+    }
+}
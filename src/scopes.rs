@@ -0,0 +1,182 @@
+//! Named symbol tables, isolating the accounting of subsystems such as plugins.
+
+use std::cell::RefCell;
+
+use crate::alloc::{LockResult, bookkeeping};
+use crate::snapshot::SymbolSnapshot;
+use crate::symbols::{Symbol, intern};
+
+/// Name of the global table in [`tables`].
+pub const GLOBAL_TABLE: &str = "<global>";
+
+thread_local! {
+    /// The scopes entered on the current thread, the innermost last.
+    static SCOPES: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A guard attributing the allocations of the current thread to a named table, until dropped.
+///
+/// Created by [`scoped_table`].
+#[derive(Debug)]
+#[must_use = "the scope is left as soon as the guard is dropped"]
+pub struct ScopedTable {
+    name: &'static str,
+    // the scopes are per-thread, so the guard must be dropped on the thread which entered it
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl ScopedTable {
+    /// Returns the name of the table.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Drop for ScopedTable {
+    fn drop(&mut self) {
+        SCOPES.with_borrow_mut(Vec::pop);
+    }
+}
+
+/// Attributes the allocations made by the current thread to the table named `name`, until the guard is dropped.
+///
+/// The table is created on its first allocation. Nested scopes attribute to the innermost table only,
+/// and the global table is used again once all the scopes are left. Deallocations always follow the pointer registry
+/// to the table which recorded the allocation, whatever scope is active when the memory is freed.
+/// All the tables, including the global one, are returned by [`tables`].
+///
+/// ```rust
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// {
+///     let _scope = leaktracer::scoped_table("plugin_a");
+///     // allocations made here are attributed to the `plugin_a` table
+/// }
+/// ```
+pub fn scoped_table(name: &str) -> ScopedTable {
+    let name = intern(name);
+    bookkeeping(|| SCOPES.with_borrow_mut(|scopes| scopes.push(name)));
+
+    ScopedTable {
+        name,
+        _not_send: std::marker::PhantomData,
+    }
+}
+
+/// Returns the innermost scope of the current thread, if any.
+pub(crate) fn current_scope() -> Option<&'static str> {
+    SCOPES
+        .try_with(|scopes| {
+            scopes
+                .try_borrow()
+                .ok()
+                .and_then(|scopes| scopes.last().copied())
+        })
+        .ok()
+        .flatten()
+}
+
+/// Returns the symbols of each table as `(name, symbols)`, starting with the global one (named [`GLOBAL_TABLE`]),
+/// followed by the named tables (see [`scoped_table`]) sorted by name. The symbols of each table are sorted by name.
+pub fn tables() -> LockResult<Vec<(&'static str, Vec<SymbolSnapshot>)>> {
+    crate::with_symbol_table(|table| {
+        let snapshot = |(name, symbol): (&&'static str, &Symbol)| SymbolSnapshot::new(name, symbol);
+        let sorted = |mut symbols: Vec<SymbolSnapshot>| {
+            symbols.sort_unstable_by_key(|symbol| symbol.name);
+            symbols
+        };
+
+        let mut scoped: Vec<_> = table
+            .scoped_tables()
+            .map(|(name, symbols)| (name, sorted(symbols.iter().map(snapshot).collect())))
+            .collect();
+        scoped.sort_by_key(|(name, _)| *name);
+
+        std::iter::once((GLOBAL_TABLE, sorted(table.iter().map(snapshot).collect())))
+            .chain(scoped)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_nest_scopes() {
+        assert_eq!(current_scope(), None);
+        {
+            let outer = scoped_table("test:scope_outer");
+            assert_eq!(outer.name(), "test:scope_outer");
+            assert_eq!(current_scope(), Some("test:scope_outer"));
+            {
+                let _inner = scoped_table("test:scope_inner");
+                assert_eq!(current_scope(), Some("test:scope_inner"));
+            }
+            assert_eq!(current_scope(), Some("test:scope_outer"));
+        }
+        assert_eq!(current_scope(), None);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_isolate_scoped_tables() {
+        use std::alloc::Layout;
+
+        use crate::LeaktracerAllocator;
+        use crate::alloc::AllocOp;
+        use crate::test_utils::fake_ptr;
+
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = |size| Layout::from_size_align(size, 8).unwrap();
+        let (a_ptr, b_ptr) = (fake_ptr(), fake_ptr());
+        {
+            let _scope = scoped_table("test:plugin_a");
+            allocator.trace(a_ptr, layout(100), AllocOp::Alloc);
+            allocator.trace(fake_ptr(), layout(100), AllocOp::Alloc);
+        }
+        {
+            let _scope = scoped_table("test:plugin_b");
+            allocator.trace(b_ptr, layout(300), AllocOp::Alloc);
+            // freed in another scope, but released from the table which recorded it
+            allocator.trace(a_ptr, layout(100), AllocOp::Dealloc);
+        }
+
+        let tables = tables().unwrap();
+        assert_eq!(tables[0].0, GLOBAL_TABLE);
+        let allocated = |name: &str| -> usize {
+            tables
+                .iter()
+                .find(|(table, _)| *table == name)
+                .expect("table should exist")
+                .1
+                .iter()
+                .map(|symbol| symbol.allocated)
+                .sum()
+        };
+        assert_eq!(allocated("test:plugin_a"), 100);
+        assert_eq!(allocated("test:plugin_b"), 300);
+
+        // the scoped allocations are not in the global table
+        let name = crate::caller_symbol(&["leaktracer"]);
+        crate::with_symbol_table(|table| {
+            assert!(table.get(name).is_none());
+        })
+        .unwrap();
+
+        allocator.trace(b_ptr, layout(300), AllocOp::Dealloc);
+        let tables = super::tables().unwrap();
+        assert!(
+            tables
+                .iter()
+                .find(|(table, _)| *table == "test:plugin_b")
+                .unwrap()
+                .1
+                .iter()
+                .all(|symbol| symbol.allocated == 0)
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Serving of the report over a Unix domain socket, for live introspection without an HTTP server.
+
+use std::io::{self, BufWriter, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+
+use crate::alloc::bookkeeping;
+
+/// Binds a Unix domain socket at `path` and spawns a thread which writes a plain text report of a fresh snapshot
+/// (see [`crate::write_report`]) to each connection, then closes it.
+///
+/// The report can be read with any client, e.g. `nc -U /tmp/my_app.sock` or `socat - UNIX-CONNECT:/tmp/my_app.sock`.
+/// The listener thread is part of the tracer: its allocations are not traced, but they are accounted
+/// in [`crate::self_overhead_bytes`]. The connections are served one at a time, until the process exits.
+///
+/// Returns an error if the socket can't be bound, e.g. because `path` already exists: a socket file left
+/// by a previous run must be removed first.
+///
+/// ```rust,no_run
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// leaktracer::serve_unix("/tmp/my_app.sock").expect("Failed to serve the report");
+/// ```
+pub fn serve_unix(path: impl AsRef<Path>) -> io::Result<()> {
+    bookkeeping(|| {
+        let listener = UnixListener::bind(path)?;
+        std::thread::Builder::new()
+            .name("leaktracer-serve".to_string())
+            .spawn(move || bookkeeping(|| accept(listener)))
+            .map(|_| ())
+    })
+}
+
+/// Writes the report to each connection accepted by the `listener`.
+fn accept(listener: UnixListener) {
+    for stream in listener.incoming() {
+        // the client may hang up before the whole report is written
+        let _ = stream.and_then(write_to);
+    }
+}
+
+/// Writes the report to the `stream`, closing it afterwards.
+fn write_to(stream: UnixStream) -> io::Result<()> {
+    let mut w = BufWriter::new(stream);
+    crate::write_report(&mut w)?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io::Read;
+
+    use super::*;
+
+    fn read_report(path: &Path) -> String {
+        let mut stream = UnixStream::connect(path).expect("failed to connect");
+        let mut report = String::new();
+        stream
+            .read_to_string(&mut report)
+            .expect("failed to read report");
+        report
+    }
+
+    #[test]
+    fn test_should_serve_report_to_each_connection() {
+        crate::init_symbol_table(&["leaktracer"]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leaktracer.sock");
+
+        serve_unix(&path).expect("failed to serve");
+        for _ in 0..2 {
+            let report = read_report(&path);
+            assert!(report.starts_with("leaktracer report (uptime "));
+            assert!(report.contains("traced live bytes: "));
+        }
+        // the path is taken
+        assert!(serve_unix(&path).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default upper bounds (exclusive) of the [`SizeClass::Small`], [`SizeClass::Medium`] and [`SizeClass::Large`] classes.
+pub(crate) const DEFAULT_SIZE_CLASS_BOUNDS: [usize; 3] = [1024, 64 * 1024, 1024 * 1024];
+
+/// Size class of an allocation.
+///
+/// The class boundaries can be configured with [`crate::LeaktracerAllocator::with_size_classes`];
+/// by default they are `< 1 KiB`, `< 64 KiB`, `< 1 MiB` and `>= 1 MiB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SizeClass {
+    /// Below the first boundary, by default `< 1 KiB`.
+    Small,
+    /// From the first boundary to the second one, by default `1 KiB..64 KiB`.
+    Medium,
+    /// From the second boundary to the third one, by default `64 KiB..1 MiB`.
+    Large,
+    /// From the third boundary on, by default `>= 1 MiB`.
+    Huge,
+}
+
+impl SizeClass {
+    /// All the size classes, from the smallest to the biggest.
+    pub const ALL: [SizeClass; 4] = [
+        SizeClass::Small,
+        SizeClass::Medium,
+        SizeClass::Large,
+        SizeClass::Huge,
+    ];
+
+    /// Classifies an allocation of `size` bytes using the given upper bounds.
+    pub(crate) fn classify(size: usize, bounds: &[usize; 3]) -> Self {
+        match bounds.iter().position(|bound| size < *bound) {
+            Some(0) => SizeClass::Small,
+            Some(1) => SizeClass::Medium,
+            Some(_) => SizeClass::Large,
+            None => SizeClass::Huge,
+        }
+    }
+
+    /// Index of the class in [`SizeClass::ALL`].
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Stats of a [`SizeClass`], as returned by [`crate::LeaktracerAllocator::size_class_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeClassStats {
+    /// The size class.
+    pub class: SizeClass,
+    /// Live bytes allocated in this class.
+    pub live_bytes: usize,
+    /// Number of allocations in this class.
+    pub allocations: usize,
+    /// Number of deallocations in this class.
+    pub deallocations: usize,
+}
+
+/// Counters of a single [`SizeClass`].
+#[derive(Debug)]
+pub(crate) struct SizeClassCounters {
+    live_bytes: AtomicUsize,
+    allocations: AtomicUsize,
+    deallocations: AtomicUsize,
+}
+
+impl SizeClassCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            live_bytes: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
+            deallocations: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records `count` allocations of `bytes` in total in this class.
+    pub(crate) fn alloc(&self, bytes: usize, count: usize) {
+        self.live_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.allocations.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Records `count` deallocations of `bytes` in total in this class.
+    pub(crate) fn dealloc(&self, bytes: usize, count: usize) {
+        self.live_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        self.deallocations.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Zeroes the counters.
+    pub(crate) fn reset(&self) {
+        self.live_bytes.store(0, Ordering::Relaxed);
+        self.allocations.store(0, Ordering::Relaxed);
+        self.deallocations.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the [`SizeClassStats`] for this class.
+    pub(crate) fn stats(&self, class: SizeClass) -> SizeClassStats {
+        SizeClassStats {
+            class,
+            live_bytes: self.live_bytes.load(Ordering::Relaxed),
+            allocations: self.allocations.load(Ordering::Relaxed),
+            deallocations: self.deallocations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_classify_boundaries() {
+        let bounds = DEFAULT_SIZE_CLASS_BOUNDS;
+        assert_eq!(SizeClass::classify(0, &bounds), SizeClass::Small);
+        assert_eq!(SizeClass::classify(1023, &bounds), SizeClass::Small);
+        assert_eq!(SizeClass::classify(1024, &bounds), SizeClass::Medium);
+        assert_eq!(
+            SizeClass::classify(64 * 1024 - 1, &bounds),
+            SizeClass::Medium
+        );
+        assert_eq!(SizeClass::classify(64 * 1024, &bounds), SizeClass::Large);
+        assert_eq!(
+            SizeClass::classify(1024 * 1024 - 1, &bounds),
+            SizeClass::Large
+        );
+        assert_eq!(SizeClass::classify(1024 * 1024, &bounds), SizeClass::Huge);
+        assert_eq!(SizeClass::classify(usize::MAX, &bounds), SizeClass::Huge);
+    }
+
+    #[test]
+    fn test_should_classify_custom_boundaries() {
+        let bounds = [16, 32, 64];
+        assert_eq!(SizeClass::classify(15, &bounds), SizeClass::Small);
+        assert_eq!(SizeClass::classify(16, &bounds), SizeClass::Medium);
+        assert_eq!(SizeClass::classify(32, &bounds), SizeClass::Large);
+        assert_eq!(SizeClass::classify(64, &bounds), SizeClass::Huge);
+    }
+
+    #[test]
+    fn test_should_count_size_class() {
+        let counters = SizeClassCounters::new();
+        counters.alloc(100, 1);
+        counters.alloc(50, 1);
+        counters.dealloc(100, 1);
+
+        let stats = counters.stats(SizeClass::Small);
+        assert_eq!(stats.live_bytes, 50);
+        assert_eq!(stats.allocations, 2);
+        assert_eq!(stats.deallocations, 1);
+    }
+}
@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::alignment::AlignmentStats;
+use crate::alloc::LockResult;
+use crate::report::{ReportOptions, SortKey};
+use crate::symbols::{SizeCardinality, Symbol};
+use crate::tree::SymbolNode;
+use crate::{process, time};
+
+/// An owned copy of the stats of a [`crate::Symbol`] at a given point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolSnapshot {
+    /// The symbol name.
+    pub name: &'static str,
+    /// Allocated bytes for this symbol.
+    pub allocated: usize,
+    /// Allocation count for this symbol.
+    pub count: usize,
+    /// Highest allocated bytes reached by this symbol (see [`Symbol::peak`]).
+    pub peak: usize,
+    /// The uptime at which the peak was reached (see [`Symbol::peak_at`]).
+    pub peak_at: Duration,
+    /// Bytes ever allocated for this symbol, including the freed ones (see [`Symbol::total_allocated`]).
+    pub total_allocated: usize,
+    /// Allocations ever made for this symbol, including the freed ones (see [`Symbol::total_count`]).
+    pub total_count: usize,
+    /// Bytes freed for this symbol since the table was initialized or reset (see [`Symbol::turnover`]).
+    pub turnover: usize,
+    /// Live bytes allocated by this symbol and by the functions it called, in inclusive accounting
+    /// (see [`Symbol::inclusive_allocated`]).
+    pub inclusive_allocated: usize,
+    /// Bytes allocated for this symbol in the last [`crate::RECENT_WINDOW`] (see [`Symbol::recent`]).
+    pub recent_allocated: usize,
+    /// Allocations made for this symbol in the last [`crate::RECENT_WINDOW`] (see [`Symbol::recent`]).
+    pub recent_count: usize,
+    /// Reallocations made for this symbol (see [`Symbol::reallocs`]).
+    pub reallocs: usize,
+    /// Reallocations made for this symbol which moved the block (see [`Symbol::realloc_moves`]).
+    pub realloc_moves: usize,
+    /// Bytes added to the blocks by the reallocations made for this symbol (see [`Symbol::bytes_grown`]).
+    pub bytes_grown: usize,
+    /// Bytes removed from the blocks by the reallocations made for this symbol (see [`Symbol::bytes_shrunk`]).
+    pub bytes_shrunk: usize,
+    /// Allocations made for this symbol in each alignment class (see [`Symbol::alignment_stats`]).
+    pub alignment: AlignmentStats,
+    /// The distinct sizes of the allocations made for this symbol (see [`Symbol::size_cardinality`]).
+    pub size_cardinality: SizeCardinality,
+}
+
+impl SymbolSnapshot {
+    /// Takes a snapshot of `symbol`, named `name`.
+    pub(crate) fn new(name: &'static str, symbol: &Symbol) -> Self {
+        let (recent_allocated, recent_count) = symbol.recent(crate::RECENT_WINDOW);
+        Self {
+            name,
+            allocated: symbol.allocated(),
+            count: symbol.count(),
+            peak: symbol.peak(),
+            peak_at: symbol.peak_at(),
+            total_allocated: symbol.total_allocated(),
+            total_count: symbol.total_count(),
+            turnover: symbol.turnover(),
+            inclusive_allocated: symbol.inclusive_allocated(),
+            recent_allocated,
+            recent_count,
+            reallocs: symbol.reallocs(),
+            realloc_moves: symbol.realloc_moves(),
+            bytes_grown: symbol.bytes_grown(),
+            bytes_shrunk: symbol.bytes_shrunk(),
+            alignment: symbol.alignment_stats(),
+            size_cardinality: symbol.size_cardinality(),
+        }
+    }
+
+    /// Returns the average allocation size in bytes, `0.0` if the symbol has never allocated
+    /// (see [`Symbol::avg_allocation_size`]).
+    pub fn avg_allocation_size(&self) -> f64 {
+        crate::symbols::avg_allocation_size(self.total_allocated, self.total_count)
+    }
+
+    /// Returns the allocations per second made for this symbol over `elapsed`, `0.0` if `elapsed` is zero.
+    ///
+    /// Pass the [`SymbolTableSnapshot::uptime`] of the snapshot to get the average rate since the tracer started.
+    /// A high steady rate with balanced frees points at a churn hotspot, worth pooling.
+    pub fn alloc_rate(&self, elapsed: Duration) -> f64 {
+        time::rate(self.total_count, elapsed)
+    }
+
+    /// Returns the allocations per second made for this symbol in the last [`crate::RECENT_WINDOW`].
+    pub fn recent_alloc_rate(&self) -> f64 {
+        time::rate(self.recent_count, crate::RECENT_WINDOW)
+    }
+}
+
+/// An owned copy of the [`crate::SymbolTable`] at a given point in time.
+///
+/// Since the snapshot doesn't hold the lock, it can be iterated and compared to other snapshots freely.
+///
+/// Along with the symbols, the snapshot carries the global counters needed to write a report
+/// (see [`crate::Reporter`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolTableSnapshot {
+    /// The [`crate::generation`] at the time the snapshot was taken.
+    ///
+    /// Comparing the generation of two snapshots tells how many table mutations happened between them.
+    pub generation: u64,
+    /// The [`crate::uptime`] at the time the snapshot was taken.
+    pub uptime: Duration,
+    /// Whether the sizes are padded to the layout alignment (see [`crate::LeaktracerAllocator::with_padded_size`]).
+    pub padded_size: bool,
+    /// The traced live bytes.
+    pub allocated: usize,
+    /// The sampling divisor at the time the snapshot was taken (see [`crate::Sampling`]), `1` without sampling.
+    ///
+    /// The bytes and counts are already scaled by the divisor; dividing by it gives the sampled values.
+    pub sampling_divisor: usize,
+    /// The process resident set size, if available on this platform (see [`crate::process_rss`]).
+    pub process_rss: Option<usize>,
+    /// The bytes consumed by the tracer itself (see [`crate::self_overhead_bytes`]).
+    pub self_overhead_bytes: usize,
+    /// The number of deallocations of untracked pointers.
+    pub untracked_frees: usize,
+    /// The bytes released by deallocations of untracked pointers.
+    pub untracked_freed_bytes: usize,
+    /// The symbols in the table.
+    pub symbols: Vec<SymbolSnapshot>,
+}
+
+impl SymbolTableSnapshot {
+    /// Returns the symbols organized as a tree by their `::` path segments (see [`SymbolNode`]),
+    /// e.g. to render a `du -h` style view of the heap with [`SymbolNode::write_tree`].
+    ///
+    /// Unlike [`crate::symbol_tree`], it works offline, on a snapshot taken earlier or loaded from elsewhere.
+    pub fn module_tree(&self) -> SymbolNode {
+        SymbolNode::from_symbols(
+            self.symbols
+                .iter()
+                .map(|symbol| (symbol.name, symbol.allocated, symbol.count)),
+        )
+    }
+
+    /// Returns the number of allocations in each alignment class, summed over all the symbols.
+    ///
+    /// A growing share of over-aligned allocations (see [`AlignmentStats::over_aligned`]) points at SIMD
+    /// or page-aligned buffers, which the system allocator may pad.
+    pub fn alignment_stats(&self) -> AlignmentStats {
+        let mut stats = AlignmentStats::default();
+        for symbol in &self.symbols {
+            stats.add(&symbol.alignment);
+        }
+
+        stats
+    }
+}
+
+/// Takes a [`SymbolTableSnapshot`] of the symbol table.
+///
+/// The table is copied under the lock, so the snapshot is consistent with its [`SymbolTableSnapshot::generation`].
+/// The symbols are sorted by name (see [`crate::SymbolTable::iter_sorted`]), so the reports are reproducible.
+/// The process RSS is read before acquiring the lock.
+pub fn snapshot() -> LockResult<SymbolTableSnapshot> {
+    take_snapshot(false)
+}
+
+/// Takes a [`SymbolTableSnapshot`] of the symbol table whose [`SymbolTableSnapshot::allocated`] is exactly the sum
+/// of the live bytes of its symbols.
+///
+/// Like [`snapshot`], it's copied under the symbol table lock, which the allocation path holds while updating
+/// the counters, so no allocation is half-recorded. In addition, the live bytes not attributed to any symbol
+/// of the global table, e.g. below the attribution size threshold ([`crate::set_attribution_size_threshold`]),
+/// without a resolved call stack, or attributed to a named table ([`crate::scoped_table`]),
+/// are gathered into an `<unattributed>` entry, which is omitted if there are none.
+///
+/// Meanwhile all the traced allocations and deallocations wait: on top of copying the symbols, this scans
+/// the whole pointer registry, so the pause is proportional to the number of live allocations.
+pub fn consistent_snapshot() -> LockResult<SymbolTableSnapshot> {
+    take_snapshot(true)
+}
+
+/// Takes a [`SymbolTableSnapshot`], adding the `<unattributed>` entry if `unattributed` is set.
+fn take_snapshot(unattributed: bool) -> LockResult<SymbolTableSnapshot> {
+    let process_rss = process::process_rss();
+    crate::with_symbol_table(|table| {
+        let (untracked_frees, untracked_freed_bytes) = crate::stats::untracked_frees();
+        let mut snapshot = SymbolTableSnapshot {
+            generation: crate::stats::generation(),
+            uptime: time::uptime(),
+            padded_size: crate::stats::padded_size(),
+            allocated: crate::stats::allocated(),
+            sampling_divisor: crate::stats::sampling_divisor(),
+            process_rss,
+            self_overhead_bytes: crate::self_overhead_bytes(),
+            untracked_frees,
+            untracked_freed_bytes,
+            symbols: table
+                .iter_sorted()
+                .into_iter()
+                .map(|(name, symbol)| SymbolSnapshot::new(name, symbol))
+                .collect(),
+        };
+        let (allocated, count) = if unattributed {
+            table.unattributed()
+        } else {
+            (0, 0)
+        };
+        if count > 0 {
+            snapshot.symbols.push(SymbolSnapshot {
+                name: crate::symbols::UNATTRIBUTED,
+                allocated,
+                count,
+                peak: allocated,
+                peak_at: Duration::ZERO,
+                total_allocated: allocated,
+                total_count: count,
+                turnover: 0,
+                inclusive_allocated: 0,
+                recent_allocated: 0,
+                recent_count: 0,
+                reallocs: 0,
+                realloc_moves: 0,
+                bytes_grown: 0,
+                bytes_shrunk: 0,
+                alignment: AlignmentStats::default(),
+                size_cardinality: SizeCardinality::default(),
+            });
+        }
+
+        snapshot
+    })
+}
+
+/// Returns an owned copy of the stats of the symbol named `name`, or `None` if there's no such symbol.
+///
+/// A shortcut for the single lookups, which would otherwise go through [`crate::with_symbol_table`]:
+/// the lock is released before returning. Returns `None` as well if the lock is poisoned.
+pub fn symbol_stats(name: &str) -> Option<SymbolSnapshot> {
+    crate::with_symbol_table(|table| {
+        table
+            .get_key_value(name)
+            .map(|(name, symbol)| SymbolSnapshot::new(name, symbol))
+    })
+    .ok()
+    .flatten()
+}
+
+/// The main counters of a symbol, owned and detached from the tracer, e.g. to be handed to the application,
+/// serialized or asserted in tests. Returned by [`stats`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SymbolStats {
+    /// The symbol name.
+    pub name: String,
+    /// Allocated bytes for this symbol.
+    pub allocated: usize,
+    /// Allocation count for this symbol.
+    pub count: usize,
+    /// Highest allocated bytes reached by this symbol (see [`Symbol::peak`]).
+    pub peak: usize,
+    /// The uptime at which the peak was reached (see [`Symbol::peak_at`]).
+    pub peak_at: Duration,
+    /// Bytes ever allocated for this symbol, including the freed ones (see [`Symbol::total_allocated`]).
+    pub total_allocated: usize,
+    /// Allocations ever made for this symbol, including the freed ones (see [`Symbol::total_count`]).
+    ///
+    /// The rates of all the symbols are measured over the same uptime, so [`SortKey::Rate`] sorts them by this count.
+    pub total_count: usize,
+    /// Bytes freed for this symbol, the key of [`SortKey::Churn`] (see [`Symbol::turnover`]).
+    pub turnover: usize,
+    /// Bytes allocated for this symbol in the last [`crate::RECENT_WINDOW`] (see [`Symbol::recent`]).
+    pub recent_allocated: usize,
+    /// Allocations made for this symbol in the last [`crate::RECENT_WINDOW`] (see [`Symbol::recent`]).
+    pub recent_count: usize,
+}
+
+impl From<&SymbolSnapshot> for SymbolStats {
+    fn from(symbol: &SymbolSnapshot) -> Self {
+        Self {
+            name: symbol.name.to_string(),
+            allocated: symbol.allocated,
+            count: symbol.count,
+            peak: symbol.peak,
+            peak_at: symbol.peak_at,
+            total_allocated: symbol.total_allocated,
+            total_count: symbol.total_count,
+            turnover: symbol.turnover,
+            recent_allocated: symbol.recent_allocated,
+            recent_count: symbol.recent_count,
+        }
+    }
+}
+
+/// Returns the stats of the symbols sorted by `sort`, keeping only the first `limit` ones if any.
+///
+/// A shortcut for taking a [`snapshot`], then sorting and truncating its symbols as the reports do
+/// (see [`ReportOptions`]), so it sees the same symbols as the reports, with the same accounting.
+/// Returns an empty list if the lock is poisoned.
+///
+/// ```rust
+/// use leaktracer::SortKey;
+///
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// for symbol in leaktracer::stats(SortKey::Allocated, Some(10)) {
+///     println!("{}: {} bytes in {} allocations", symbol.name, symbol.allocated, symbol.count);
+/// }
+/// ```
+pub fn stats(sort: SortKey, limit: Option<usize>) -> Vec<SymbolStats> {
+    snapshot()
+        .map(|snapshot| sorted_stats(&snapshot, sort, limit))
+        .unwrap_or_default()
+}
+
+/// Returns the stats of the symbols of `snapshot` sorted by `sort`, keeping only the first `limit` ones if any.
+fn sorted_stats(
+    snapshot: &SymbolTableSnapshot,
+    sort: SortKey,
+    limit: Option<usize>,
+) -> Vec<SymbolStats> {
+    ReportOptions {
+        top: limit,
+        sort,
+        ..Default::default()
+    }
+    .symbols(snapshot)
+    .into_iter()
+    .map(SymbolStats::from)
+    .collect()
+}
+
+/// Merges the symbols of several snapshots, e.g. taken by different processes,
+/// summing the counters of the symbols with the same name.
+///
+/// The symbols are returned in the order they first appear in the inputs.
+pub fn merge_snapshots(snapshots: &[Vec<SymbolSnapshot>]) -> Vec<SymbolSnapshot> {
+    let mut merged: Vec<SymbolSnapshot> = Vec::new();
+    let mut index: HashMap<&'static str, usize> = HashMap::new();
+
+    for symbol in snapshots.iter().flatten() {
+        match index.get(symbol.name) {
+            Some(&i) => {
+                merged[i].allocated += symbol.allocated;
+                merged[i].count += symbol.count;
+                // the peaks may not have been reached at the same time, so their sum is an upper bound
+                merged[i].peak += symbol.peak;
+                merged[i].peak_at = merged[i].peak_at.max(symbol.peak_at);
+                merged[i].total_allocated += symbol.total_allocated;
+                merged[i].total_count += symbol.total_count;
+                merged[i].turnover += symbol.turnover;
+                merged[i].inclusive_allocated += symbol.inclusive_allocated;
+                merged[i].recent_allocated += symbol.recent_allocated;
+                merged[i].recent_count += symbol.recent_count;
+                merged[i].reallocs += symbol.reallocs;
+                merged[i].realloc_moves += symbol.realloc_moves;
+                merged[i].bytes_grown += symbol.bytes_grown;
+                merged[i].bytes_shrunk += symbol.bytes_shrunk;
+                merged[i].alignment.add(&symbol.alignment);
+                // the sizes themselves are gone, so the union is at least the biggest of the cardinalities
+                merged[i].size_cardinality =
+                    merged[i].size_cardinality.max(symbol.size_cardinality);
+            }
+            None => {
+                index.insert(symbol.name, merged.len());
+                merged.push(symbol.clone());
+            }
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_take_snapshot_with_generation() {
+        use std::alloc::Layout;
+
+        use crate::LeaktracerAllocator;
+
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let first = snapshot().expect("failed to take snapshot");
+
+        let allocator = LeaktracerAllocator::init();
+        let ptr = crate::test_utils::fake_ptr();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        allocator.trace(ptr, layout, crate::alloc::AllocOp::Alloc);
+
+        let second = snapshot().expect("failed to take snapshot");
+        assert!(second.generation > first.generation);
+        assert!(!second.symbols.is_empty());
+    }
+
+    #[test]
+    fn test_should_get_symbol_stats() {
+        crate::init_symbol_table(&["leaktracer"]);
+        assert_eq!(symbol_stats("leaktracer::test::no_such_symbol"), None);
+
+        #[cfg(stack_capture)]
+        {
+            use std::alloc::Layout;
+
+            use crate::LeaktracerAllocator;
+
+            let allocator = LeaktracerAllocator::init();
+            let ptr = crate::test_utils::fake_ptr();
+            let layout = Layout::from_size_align(64, 8).unwrap();
+            allocator.trace(ptr, layout, crate::alloc::AllocOp::Alloc);
+
+            let name = crate::caller_symbol(&["leaktracer"]);
+            let stats = symbol_stats(name).expect("symbol should exist");
+            assert_eq!(stats.name, name);
+            assert!(stats.count >= 1);
+            assert!(stats.allocated >= 64);
+        }
+    }
+
+    #[test]
+    fn test_should_build_module_tree() {
+        let snapshot = crate::test_utils::synthetic_snapshot();
+        let root = snapshot.module_tree();
+        assert_eq!(root.allocated, 1024 + 4096 + 512);
+        let app = root.child("app").expect("app should exist");
+        assert_eq!(app.count, 17);
+        assert_eq!(app.child("cache").unwrap().allocated, 4096);
+        assert_eq!(app.child("main").unwrap().self_allocated, 1024);
+    }
+
+    #[test]
+    fn test_should_compare_and_sort_snapshots() {
+        let snapshot = crate::test_utils::synthetic_snapshot();
+        let mut copy = snapshot.clone();
+        assert_eq!(copy, snapshot);
+
+        copy.symbols
+            .sort_by_key(|symbol| std::cmp::Reverse(symbol.allocated));
+        assert_ne!(copy, snapshot);
+        assert_eq!(copy.symbols[0].name, "app::cache::insert");
+        copy.symbols[0].allocated += 1;
+        assert_ne!(copy.symbols[0], snapshot.symbols[1]);
+    }
+
+    #[test]
+    fn test_should_sort_and_limit_stats() {
+        let snapshot = crate::test_utils::synthetic_snapshot();
+        let names = |sort, limit| {
+            sorted_stats(&snapshot, sort, limit)
+                .into_iter()
+                .map(|symbol| symbol.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(SortKey::Allocated, None),
+            ["app::cache::insert", "app::main", "app::config::load"]
+        );
+        assert_eq!(
+            names(SortKey::Count, Some(2)),
+            ["app::main", "app::config::load"]
+        );
+        assert_eq!(names(SortKey::Name, Some(1)), ["app::cache::insert"]);
+        assert!(names(SortKey::Rate, Some(0)).is_empty());
+
+        let stats = sorted_stats(&snapshot, SortKey::Count, Some(1));
+        assert_eq!(
+            stats,
+            [SymbolStats {
+                name: String::from("app::main"),
+                allocated: 1024,
+                count: 10,
+                peak: 8192,
+                peak_at: Duration::from_secs(342),
+                total_allocated: 65536,
+                total_count: 640,
+                turnover: 64512,
+                recent_allocated: 0,
+                recent_count: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_should_sort_stats_by_churn_and_rate() {
+        let snapshot = crate::test_utils::synthetic_snapshot();
+
+        let churn = sorted_stats(&snapshot, SortKey::Churn, None);
+        assert_eq!(
+            churn
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.turnover))
+                .collect::<Vec<_>>(),
+            [
+                ("app::main", 64512),
+                ("app::cache::insert", 4096),
+                ("app::config::load", 0),
+            ]
+        );
+
+        // the rates are measured over the same uptime, so they follow the total counts
+        let rate = sorted_stats(&snapshot, SortKey::Rate, Some(2));
+        assert_eq!(
+            rate.iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.total_count))
+                .collect::<Vec<_>>(),
+            [("app::main", 640), ("app::config::load", 5)]
+        );
+    }
+
+    #[test]
+    fn test_should_merge_snapshots() {
+        let symbol = |name, allocated, count| SymbolSnapshot {
+            name,
+            allocated,
+            count,
+            peak: allocated * 2,
+            // the merged peak is reached at the latest of the peaks
+            peak_at: Duration::from_millis(allocated.min(200) as u64),
+            total_allocated: allocated * 2,
+            total_count: count * 2,
+            turnover: allocated,
+            inclusive_allocated: allocated * 3,
+            recent_allocated: allocated / 2,
+            recent_count: count,
+            reallocs: count,
+            realloc_moves: count / 2,
+            bytes_grown: allocated / 4,
+            bytes_shrunk: 0,
+            alignment: AlignmentStats {
+                natural: count,
+                ..Default::default()
+            },
+            // the merged cardinality is the biggest one
+            size_cardinality: SizeCardinality::Exact(count.min(2)),
+        };
+        let first = vec![symbol("a", 100, 1), symbol("b", 200, 2)];
+        let second = vec![symbol("b", 50, 1), symbol("c", 10, 3)];
+
+        assert_eq!(
+            merge_snapshots(&[first, second]),
+            vec![symbol("a", 100, 1), symbol("b", 250, 3), symbol("c", 10, 3)]
+        );
+    }
+
+    #[test]
+    fn test_should_compute_alloc_rates() {
+        let snapshot = crate::test_utils::synthetic_snapshot();
+        let mut symbol = snapshot.symbols[0].clone();
+        assert_eq!(symbol.total_count, 640);
+        assert_eq!(symbol.alloc_rate(Duration::from_secs(64)), 10.0);
+        assert_eq!(symbol.alloc_rate(snapshot.uptime), 640.0 / 1.5);
+        assert_eq!(symbol.alloc_rate(Duration::ZERO), 0.0);
+
+        assert_eq!(symbol.recent_alloc_rate(), 0.0);
+        symbol.recent_count = 120;
+        assert_eq!(
+            symbol.recent_alloc_rate(),
+            120.0 / crate::RECENT_WINDOW.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn test_should_compute_average_allocation_size() {
+        let mut symbol = SymbolSnapshot {
+            name: "app::main",
+            allocated: 0,
+            count: 0,
+            peak: 0,
+            peak_at: Duration::ZERO,
+            total_allocated: 0,
+            total_count: 0,
+            turnover: 0,
+            inclusive_allocated: 0,
+            recent_allocated: 0,
+            recent_count: 0,
+            reallocs: 0,
+            realloc_moves: 0,
+            bytes_grown: 0,
+            bytes_shrunk: 0,
+            alignment: AlignmentStats::default(),
+            size_cardinality: SizeCardinality::default(),
+        };
+        assert_eq!(symbol.avg_allocation_size(), 0.0);
+
+        symbol.total_allocated = 1000;
+        symbol.total_count = 8;
+        assert_eq!(symbol.avg_allocation_size(), 125.0);
+    }
+}
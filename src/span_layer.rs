@@ -0,0 +1,91 @@
+//! Attribution of the allocations to the active `tracing` span, via the tag stack.
+
+use tracing::Subscriber;
+use tracing::span::Id;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A [`Layer`] pushing the name of each entered span on the tag stack of the current thread
+/// (see [`crate::push_tag`]), and popping it on exit.
+///
+/// This groups the allocations by the active span, without manual tagging (see [`crate::tag_stats`]).
+/// Created by [`tracing_layer`].
+///
+/// The tag stack is per-thread, so the span is pushed when entered and popped when exited on each thread.
+/// An async task instrumented with a span is entered on each poll, so the allocations made while polling are
+/// attributed to its span, but the attribution across polls is approximate: the memory allocated by a poll
+/// and freed by another runs in different contexts, and spans exited out of order pop the innermost tag anyway.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TracingLayer {
+    _private: (),
+}
+
+/// Returns a [`TracingLayer`], to be composed with [`tracing_subscriber::registry()`].
+///
+/// ```rust
+/// use tracing_subscriber::layer::SubscriberExt as _;
+///
+/// let subscriber = tracing_subscriber::registry().with(leaktracer::tracing_layer());
+/// tracing::subscriber::set_global_default(subscriber).expect("failed to set subscriber");
+/// ```
+pub fn tracing_layer() -> TracingLayer {
+    TracingLayer::default()
+}
+
+impl<S> Layer<S> for TracingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            crate::push_tag(span.name());
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if ctx.span(id).is_some() {
+            crate::pop_tag();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::tags::current_tag;
+
+    #[test]
+    fn test_should_tag_allocations_with_span() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(256, 8).unwrap();
+        let subscriber = tracing_subscriber::registry().with(tracing_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("test_span_outer");
+            let _outer = outer.enter();
+            assert_eq!(current_tag(), Some("test_span_outer"));
+
+            tracing::info_span!("test_span_inner").in_scope(|| {
+                assert_eq!(current_tag(), Some("test_span_inner"));
+                allocator.trace(crate::test_utils::fake_ptr(), layout, AllocOp::Alloc);
+            });
+            assert_eq!(current_tag(), Some("test_span_outer"));
+        });
+        assert_eq!(current_tag(), None);
+
+        let tags = crate::tag_stats().unwrap();
+        let inner = tags
+            .iter()
+            .find(|tag| tag.name == "test_span_inner")
+            .expect("span should be tagged");
+        assert_eq!(inner.allocated, 256);
+    }
+}
@@ -0,0 +1,467 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate::alloc::AllocOp;
+
+/// Live bytes traced by any [`crate::LeaktracerAllocator`].
+///
+/// This mirrors the allocator counter, so it can be read without a reference to the allocator.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Peak of the live bytes traced by the allocator.
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Total number of traced allocations.
+static TOTAL_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Total number of traced deallocations.
+static TOTAL_DEALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of symbols in the symbol table.
+static SYMBOL_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Generation of the symbol table, incremented on every table mutation.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Deallocations of pointers missing from the registry.
+static UNTRACKED_FREES: AtomicUsize = AtomicUsize::new(0);
+
+/// Bytes freed by deallocations of pointers missing from the registry.
+static UNTRACKED_FREED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocations, deallocations and reallocations to or from a zero-sized layout.
+static ZERO_SIZE_EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocator calls not traced because the thread-locals of the calling thread were already destroyed.
+static TLS_FALLBACKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Bytes currently allocated by the tracer internal structures.
+static OVERHEAD: AtomicUsize = AtomicUsize::new(0);
+
+/// Incremented on each [`crate::reset`], so the allocators reset their own counters.
+static RESET_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// The current sampling divisor (see [`crate::Sampling`]).
+static SAMPLING_DIVISOR: AtomicUsize = AtomicUsize::new(1);
+
+/// Traced bytes by allocation op: plain, zeroed and reallocations.
+static OP_BYTES: [AtomicUsize; 3] = [const { AtomicUsize::new(0) }; 3];
+
+/// Traced allocations by allocation op: plain, zeroed and reallocations.
+static OP_COUNTS: [AtomicUsize; 3] = [const { AtomicUsize::new(0) }; 3];
+
+/// Whether the traced sizes are padded to the layout alignment.
+static PADDED_SIZE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Allocations made by the current thread, counted exactly, regardless of sampling.
+    static THREAD_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    /// Bytes allocated by the current thread, counted exactly, regardless of sampling.
+    static THREAD_ALLOCATED_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Records an allocation of `bytes` made by the current thread.
+pub(crate) fn record_thread_alloc(bytes: usize) {
+    // during the thread teardown the counters may be gone already
+    let _ = THREAD_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    let _ = THREAD_ALLOCATED_BYTES.try_with(|total| total.set(total.get().wrapping_add(bytes)));
+}
+
+/// Returns the allocations made by the current thread since it started.
+pub(crate) fn thread_allocations() -> usize {
+    THREAD_ALLOCATIONS.try_with(Cell::get).unwrap_or_default()
+}
+
+/// Returns the bytes allocated by the current thread since it started, wrapping on overflow.
+pub(crate) fn thread_allocated_bytes() -> usize {
+    THREAD_ALLOCATED_BYTES
+        .try_with(Cell::get)
+        .unwrap_or_default()
+}
+
+/// Records `count` traced allocations of `bytes` in total (more than one when scaled by sampling).
+///
+/// Returns the total allocation count before recording them.
+pub(crate) fn record_alloc(bytes: usize, count: usize) -> usize {
+    let allocated = ALLOCATED.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK.fetch_max(allocated, Ordering::Relaxed);
+    TOTAL_ALLOCATIONS.fetch_add(count, Ordering::Relaxed)
+}
+
+/// Returns the total number of traced allocations.
+pub(crate) fn total_allocations() -> usize {
+    TOTAL_ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// Records `count` traced deallocations of `bytes` in total (more than one when scaled by sampling).
+pub(crate) fn record_dealloc(bytes: usize, count: usize) {
+    ALLOCATED.fetch_sub(bytes, Ordering::Relaxed);
+    TOTAL_DEALLOCATIONS.fetch_add(count, Ordering::Relaxed);
+}
+
+/// Records `count` traced allocations of `bytes` in total made by `op` into the [`OpBreakdown`].
+pub(crate) fn record_op(op: AllocOp, bytes: usize, count: usize) {
+    let index = match op {
+        AllocOp::Alloc => 0,
+        AllocOp::AllocZeroed => 1,
+        AllocOp::Realloc { .. } => 2,
+        AllocOp::Dealloc => return,
+    };
+    OP_BYTES[index].fetch_add(bytes, Ordering::Relaxed);
+    OP_COUNTS[index].fetch_add(count, Ordering::Relaxed);
+}
+
+/// Records the number of symbols in the symbol table.
+pub(crate) fn set_symbol_count(count: usize) {
+    SYMBOL_COUNT.store(count, Ordering::Relaxed);
+}
+
+/// Records a deallocation of `bytes` of a pointer missing from the registry.
+pub(crate) fn record_untracked_free(bytes: usize) {
+    UNTRACKED_FREES.fetch_add(1, Ordering::Relaxed);
+    UNTRACKED_FREED_BYTES.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Returns the number of deallocations and freed bytes of pointers missing from the registry.
+pub(crate) fn untracked_frees() -> (usize, usize) {
+    (
+        UNTRACKED_FREES.load(Ordering::Relaxed),
+        UNTRACKED_FREED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// Records an allocator call with a zero-sized layout.
+pub(crate) fn record_zero_size_event() {
+    ZERO_SIZE_EVENTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of allocator calls made with a zero-sized layout: allocations, deallocations,
+/// and reallocations to or from zero bytes.
+///
+/// Safe Rust never calls the allocator for zero-sized types, but unsafe code and FFI may. These calls are
+/// forwarded to the system allocator without tracing them, so they never reach the symbol table
+/// nor the byte counters, and are only counted here.
+pub fn zero_size_events() -> usize {
+    ZERO_SIZE_EVENTS.load(Ordering::Relaxed)
+}
+
+/// Records an allocator call made once the thread-locals of the calling thread were destroyed.
+pub(crate) fn record_tls_fallback() {
+    TLS_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of allocator calls which were not traced because they were made during the thread teardown,
+/// after the tracer thread-locals were destroyed, e.g. by the destructor of another thread-local.
+///
+/// These calls are forwarded to the system allocator as tracer internal ones: the allocations are not counted,
+/// and the deallocations of traced pointers are not accounted, so their bytes stay live in the stats.
+pub fn tls_fallbacks() -> usize {
+    TLS_FALLBACKS.load(Ordering::Relaxed)
+}
+
+/// Records an allocation of `bytes` made by the tracer bookkeeping.
+pub(crate) fn record_overhead_alloc(bytes: usize) {
+    OVERHEAD.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Records a deallocation of `bytes` made by the tracer bookkeeping.
+pub(crate) fn record_overhead_dealloc(bytes: usize) {
+    // memory allocated before the first bookkeeping (e.g. thread locals) may be released while bookkeeping
+    let _ = OVERHEAD.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |overhead| {
+        Some(overhead.saturating_sub(bytes))
+    });
+}
+
+/// Returns how many bytes the tracer internal structures currently consume.
+///
+/// This accounts the memory allocated while tracing and by the other tracer bookkeeping: the symbol table,
+/// the pointer registry, the interned symbol names and the symbolization caches of the `backtrace` crate.
+/// This memory is never attributed to any symbol, nor counted in [`crate::LeaktracerAllocator::allocated`].
+///
+/// The overhead is only accounted when the [`crate::LeaktracerAllocator`] is the global allocator.
+pub fn self_overhead_bytes() -> usize {
+    OVERHEAD.load(Ordering::Relaxed)
+}
+
+/// Returns the live bytes traced by the allocator.
+pub(crate) fn allocated() -> usize {
+    ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// Returns the live bytes traced by any [`crate::LeaktracerAllocator`], without a reference to the allocator.
+///
+/// See [`crate::sum_symbol_allocated`] to check it against the bytes attributed to the symbols.
+pub fn global_allocated() -> usize {
+    allocated()
+}
+
+/// Records whether the traced sizes are padded to the layout alignment.
+pub(crate) fn set_padded_size(padded: bool) {
+    PADDED_SIZE.store(padded, Ordering::Relaxed);
+}
+
+/// Returns whether the traced sizes are padded to the layout alignment.
+pub(crate) fn padded_size() -> bool {
+    PADDED_SIZE.load(Ordering::Relaxed)
+}
+
+/// Records the current sampling divisor.
+pub(crate) fn set_sampling_divisor(divisor: usize) {
+    SAMPLING_DIVISOR.store(divisor, Ordering::Relaxed);
+}
+
+/// Returns the current sampling divisor.
+pub(crate) fn sampling_divisor() -> usize {
+    SAMPLING_DIVISOR.load(Ordering::Relaxed)
+}
+
+/// Advances the symbol table generation.
+pub(crate) fn advance_generation() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Zeroes the global counters and advances the reset epoch.
+///
+/// The generation is advanced rather than zeroed, so it keeps increasing across resets.
+pub(crate) fn reset() {
+    ALLOCATED.store(0, Ordering::Relaxed);
+    PEAK.store(0, Ordering::Relaxed);
+    TOTAL_ALLOCATIONS.store(0, Ordering::Relaxed);
+    TOTAL_DEALLOCATIONS.store(0, Ordering::Relaxed);
+    SYMBOL_COUNT.store(0, Ordering::Relaxed);
+    UNTRACKED_FREES.store(0, Ordering::Relaxed);
+    UNTRACKED_FREED_BYTES.store(0, Ordering::Relaxed);
+    ZERO_SIZE_EVENTS.store(0, Ordering::Relaxed);
+    TLS_FALLBACKS.store(0, Ordering::Relaxed);
+    for counter in OP_BYTES.iter().chain(&OP_COUNTS) {
+        counter.store(0, Ordering::Relaxed);
+    }
+    RESET_EPOCH.fetch_add(1, Ordering::Relaxed);
+    advance_generation();
+}
+
+/// Returns the number of resets happened so far.
+pub(crate) fn reset_epoch() -> u64 {
+    RESET_EPOCH.load(Ordering::Relaxed)
+}
+
+/// Returns the current generation of the symbol table.
+///
+/// The generation is incremented on every allocation and deallocation recorded into the symbol table,
+/// so the difference between two generations tells how many table mutations happened in between.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Relaxed)
+}
+
+/// Global counters maintained by the allocator, readable without locking the symbol table.
+///
+/// See [`quick_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuickStats {
+    /// Live bytes traced by the allocator.
+    pub allocated: usize,
+    /// Peak of the live bytes traced by the allocator.
+    pub peak: usize,
+    /// Total number of traced allocations.
+    pub total_allocations: usize,
+    /// Total number of traced deallocations.
+    pub total_deallocations: usize,
+    /// Number of symbols in the symbol table.
+    pub symbol_count: usize,
+}
+
+/// Returns the [`QuickStats`], reading only atomics without locking the symbol table.
+///
+/// This is cheap enough for high-frequency polling (e.g. a metrics loop), since it doesn't contend with
+/// the allocation path. Each counter is read independently, so the values may be slightly out of sync
+/// with each other while allocations are in progress; per-symbol data still requires the lock.
+pub fn quick_stats() -> QuickStats {
+    QuickStats {
+        allocated: ALLOCATED.load(Ordering::Relaxed),
+        peak: PEAK.load(Ordering::Relaxed),
+        total_allocations: TOTAL_ALLOCATIONS.load(Ordering::Relaxed),
+        total_deallocations: TOTAL_DEALLOCATIONS.load(Ordering::Relaxed),
+        symbol_count: SYMBOL_COUNT.load(Ordering::Relaxed),
+    }
+}
+
+/// Returns a compact one-line summary of the [`QuickStats`] and the untracked frees, e.g. for a heartbeat log:
+///
+/// ```text
+/// live=4.2MiB peak=9.1MiB allocs=12345 syms=87 untracked=3
+/// ```
+///
+/// `allocs` is the total number of traced allocations and `untracked` the number of deallocations of pointers
+/// missing from the registry. Like [`quick_stats`], it only reads atomics.
+pub fn summary() -> String {
+    format_summary(&quick_stats(), untracked_frees().0)
+}
+
+/// Formats the line returned by [`summary`].
+fn format_summary(stats: &QuickStats, untracked_frees: usize) -> String {
+    let bytes = |bytes| crate::report::format_bytes(bytes).replace(' ', "");
+    format!(
+        "live={} peak={} allocs={} syms={} untracked={untracked_frees}",
+        bytes(stats.allocated),
+        bytes(stats.peak),
+        stats.total_allocations,
+        stats.symbol_count,
+    )
+}
+
+/// Bytes and count of the allocations made by an allocation op.
+///
+/// See [`OpBreakdown`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpStats {
+    /// Traced bytes allocated by the op.
+    pub bytes: usize,
+    /// Number of traced allocations made by the op.
+    pub count: usize,
+}
+
+impl OpStats {
+    /// Reads the counters of the op at `index`.
+    fn load(index: usize) -> Self {
+        Self {
+            bytes: OP_BYTES[index].load(Ordering::Relaxed),
+            count: OP_COUNTS[index].load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Split of the traced allocations by the [`std::alloc::GlobalAlloc`] method which made them.
+///
+/// See [`op_breakdown`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpBreakdown {
+    /// Allocations made by [`std::alloc::GlobalAlloc::alloc`].
+    pub alloc: OpStats,
+    /// Allocations made by [`std::alloc::GlobalAlloc::alloc_zeroed`].
+    pub alloc_zeroed: OpStats,
+    /// Allocations made by [`std::alloc::GlobalAlloc::realloc`], with their new size.
+    pub realloc: OpStats,
+}
+
+/// Returns the [`OpBreakdown`] of the traced allocations, reading only atomics without locking the symbol table.
+///
+/// A large share of reallocations usually means growing collections, which can be preallocated instead.
+/// A reallocation also releases the old block, which is counted as a deallocation.
+/// The counters are zeroed by [`crate::reset`].
+pub fn op_breakdown() -> OpBreakdown {
+    OpBreakdown {
+        alloc: OpStats::load(0),
+        alloc_zeroed: OpStats::load(1),
+        realloc: OpStats::load(2),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::{GlobalAlloc as _, Layout};
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+
+    #[test]
+    fn test_should_advance_generation() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let ptr = crate::test_utils::fake_ptr();
+        let layout = Layout::from_size_align(32, 8).unwrap();
+
+        let before = generation();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+        allocator.trace(ptr, layout, AllocOp::Dealloc);
+        assert!(generation() >= before + 2);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_match_quick_stats_with_locked_totals() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let ptr = crate::test_utils::fake_ptr();
+        let layout = Layout::from_size_align(128, 8).unwrap();
+        allocator.trace(ptr, layout, AllocOp::Alloc);
+
+        // counters are updated under the lock, so they match the table while it's locked
+        crate::with_symbol_table(|table| {
+            let stats = quick_stats();
+            assert_eq!(stats.symbol_count, table.len());
+            // including the symbols of the named tables
+            assert_eq!(stats.allocated, table.total_allocated());
+            assert!(stats.peak >= stats.allocated);
+            assert!(stats.total_allocations >= stats.total_deallocations);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_should_format_summary() {
+        let stats = QuickStats {
+            allocated: 4404019,
+            peak: 9542041,
+            total_allocations: 12345,
+            total_deallocations: 12000,
+            symbol_count: 87,
+        };
+        assert_eq!(
+            format_summary(&stats, 3),
+            "live=4.2MiB peak=9.1MiB allocs=12345 syms=87 untracked=3"
+        );
+
+        let summary = summary();
+        assert!(summary.starts_with("live="));
+        assert!(summary.contains(" peak="));
+    }
+
+    #[test]
+    fn test_should_break_down_allocations_by_op() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(256, 8).unwrap();
+        let before = op_breakdown();
+
+        let plain = unsafe { allocator.alloc(layout) };
+        let zeroed = unsafe { allocator.alloc_zeroed(layout) };
+        assert!(
+            unsafe { std::slice::from_raw_parts(zeroed, 256) }
+                .iter()
+                .all(|byte| *byte == 0)
+        );
+        let grown = unsafe { allocator.realloc(zeroed, layout, 1024) };
+        assert!(!grown.is_null());
+
+        let after = op_breakdown();
+        // the other tests only trace plain allocations
+        assert!(after.alloc.count > before.alloc.count);
+        assert!(after.alloc.bytes >= before.alloc.bytes + 256);
+        assert_eq!(
+            after.alloc_zeroed,
+            OpStats {
+                bytes: before.alloc_zeroed.bytes + 256,
+                count: before.alloc_zeroed.count + 1,
+            }
+        );
+        assert_eq!(
+            after.realloc,
+            OpStats {
+                bytes: before.realloc.bytes + 1024,
+                count: before.realloc.count + 1,
+            }
+        );
+        // the reallocated block replaces the zeroed one
+        assert_eq!(allocator.allocated(), 256 + 1024);
+
+        unsafe {
+            allocator.dealloc(plain, layout);
+            allocator.dealloc(grown, Layout::from_size_align(1024, 8).unwrap());
+        }
+        assert_eq!(allocator.allocated(), 0);
+    }
+}
@@ -0,0 +1,193 @@
+//! Strict mode checks, which detect double frees and mismatched layouts on deallocation.
+
+use std::alloc::Layout;
+use std::collections::{HashMap, VecDeque};
+
+use crate::atomic_ref::AtomicRef;
+use crate::hooks;
+
+/// Maximum number of freed pointers remembered to detect double frees.
+const FREED_POINTERS_CAPACITY: usize = 1024;
+
+/// The kind of a [`StrictViolation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrictViolationKind {
+    /// A pointer was freed twice.
+    DoubleFree,
+    /// A pointer was freed with a layout size different from the one it was allocated with.
+    SizeMismatch {
+        /// Size recorded at allocation.
+        allocated: usize,
+        /// Size passed to the deallocation.
+        deallocated: usize,
+    },
+}
+
+/// A violation detected in strict mode (see [`crate::LeaktracerAllocator::with_strict`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrictViolation {
+    /// The kind of violation.
+    pub kind: StrictViolationKind,
+    /// The offending pointer.
+    pub ptr: usize,
+    /// The symbol which allocated the offending pointer.
+    pub symbol: &'static str,
+}
+
+/// Callback invoked on each [`StrictViolation`].
+static VIOLATION_HOOK: AtomicRef<fn(StrictViolation)> = AtomicRef::new();
+
+/// Sets a callback invoked on each [`StrictViolation`] detected in strict mode.
+///
+/// The callback is invoked from inside the allocator, with the symbol table locked and tracing suppressed,
+/// so it can't access the symbol table ([`crate::with_symbol_table`] fails with [`crate::TableError::WouldDeadlock`]).
+/// If it panics, the panic is caught and the deallocation goes on.
+pub fn on_strict_violation(hook: fn(StrictViolation)) {
+    VIOLATION_HOOK.store_value(Some(hook));
+}
+
+/// Invokes the violation hook, if any.
+pub(crate) fn report_violation(violation: StrictViolation) {
+    if let Some(hook) = VIOLATION_HOOK.load() {
+        hooks::contain(|| hook(violation));
+    }
+}
+
+/// A bounded FIFO of the recently freed pointers, with the symbol which allocated them.
+///
+/// Each entry is stamped with a sequence number, so pointers forgotten and freed again
+/// are not evicted by their stale FIFO entries.
+#[derive(Debug, Default)]
+pub(crate) struct FreedPointers {
+    order: VecDeque<(usize, u64)>,
+    pointers: HashMap<usize, (&'static str, u64)>,
+    seq: u64,
+}
+
+impl FreedPointers {
+    /// Remembers `ptr` as freed, evicting the oldest pointer if the FIFO is full.
+    pub(crate) fn insert(&mut self, ptr: usize, symbol: &'static str) {
+        self.seq += 1;
+        self.pointers.insert(ptr, (symbol, self.seq));
+        self.order.push_back((ptr, self.seq));
+
+        while self.order.len() > FREED_POINTERS_CAPACITY {
+            let Some((oldest, seq)) = self.order.pop_front() else {
+                break;
+            };
+            if self
+                .pointers
+                .get(&oldest)
+                .is_some_and(|(_, current)| *current == seq)
+            {
+                self.pointers.remove(&oldest);
+            }
+        }
+    }
+
+    /// Forgets `ptr`, because it has been allocated again.
+    pub(crate) fn remove(&mut self, ptr: usize) {
+        self.pointers.remove(&ptr);
+    }
+
+    /// Returns the symbol which allocated `ptr`, if `ptr` has been freed recently.
+    pub(crate) fn get(&self, ptr: usize) -> Option<&'static str> {
+        self.pointers.get(&ptr).map(|(symbol, _)| *symbol)
+    }
+}
+
+/// A bounded FIFO of the freed pointers whose memory is held back from the system allocator,
+/// with the symbol which allocated them (see [`crate::LeaktracerAllocator::with_quarantine`]).
+///
+/// While a pointer is quarantined its address can't be reused, so freeing it again is always a double free.
+#[derive(Debug, Default)]
+pub(crate) struct Quarantine {
+    order: VecDeque<(usize, Layout)>,
+    pointers: HashMap<usize, &'static str>,
+}
+
+impl Quarantine {
+    /// Quarantines `ptr`, allocated with `layout` by `symbol`.
+    ///
+    /// Returns the oldest pointer with its layout if the quarantine holds more than `capacity` pointers,
+    /// so that its memory is released to the system allocator.
+    pub(crate) fn push(
+        &mut self,
+        ptr: usize,
+        layout: Layout,
+        symbol: &'static str,
+        capacity: usize,
+    ) -> Option<(usize, Layout)> {
+        self.pointers.insert(ptr, symbol);
+        self.order.push_back((ptr, layout));
+        if self.order.len() <= capacity {
+            return None;
+        }
+
+        let (oldest, layout) = self.order.pop_front()?;
+        self.pointers.remove(&oldest);
+        Some((oldest, layout))
+    }
+
+    /// Returns the symbol which allocated `ptr`, if `ptr` is quarantined.
+    pub(crate) fn get(&self, ptr: usize) -> Option<&'static str> {
+        self.pointers.get(&ptr).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_remember_freed_pointers() {
+        let mut freed = FreedPointers::default();
+        freed.insert(0x10, "a");
+        assert_eq!(freed.get(0x10), Some("a"));
+
+        freed.remove(0x10);
+        assert_eq!(freed.get(0x10), None);
+    }
+
+    #[test]
+    fn test_should_not_evict_pointer_freed_again() {
+        let mut freed = FreedPointers::default();
+        freed.insert(0x10, "a");
+        freed.remove(0x10);
+        for ptr in 0..FREED_POINTERS_CAPACITY - 1 {
+            freed.insert(0x1000 + ptr, "b");
+        }
+        freed.insert(0x10, "c");
+
+        // evicts the stale entry of 0x10
+        freed.insert(0x20, "d");
+        assert_eq!(freed.get(0x10), Some("c"));
+    }
+
+    #[test]
+    fn test_should_evict_oldest_freed_pointer() {
+        let mut freed = FreedPointers::default();
+        for ptr in 0..=FREED_POINTERS_CAPACITY {
+            freed.insert(ptr, "a");
+        }
+
+        assert_eq!(freed.get(0), None);
+        assert_eq!(freed.get(1), Some("a"));
+        assert_eq!(freed.get(FREED_POINTERS_CAPACITY), Some("a"));
+    }
+
+    #[test]
+    fn test_should_release_oldest_quarantined_pointer() {
+        let mut quarantine = Quarantine::default();
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        assert_eq!(quarantine.push(0x10, layout, "a", 2), None);
+        assert_eq!(quarantine.push(0x20, layout, "b", 2), None);
+        assert_eq!(quarantine.get(0x10), Some("a"));
+
+        assert_eq!(quarantine.push(0x30, layout, "c", 2), Some((0x10, layout)));
+        assert_eq!(quarantine.get(0x10), None);
+        assert_eq!(quarantine.get(0x20), Some("b"));
+        assert_eq!(quarantine.get(0x30), Some("c"));
+    }
+}
@@ -1,25 +1,298 @@
+#[cfg(stack_capture)]
+mod caller_cache;
+mod cardinality;
+#[cfg(all(windows, stack_capture))]
+mod deferred;
+#[cfg(stack_capture)]
 mod demangle;
+mod ema;
+mod histogram;
+mod intern;
+#[cfg(stack_capture)]
+mod resolve;
+mod window;
 
+use self::cardinality::SizeSet;
+pub use self::cardinality::{SIZE_CARDINALITY_CUTOFF, SizeCardinality};
+#[cfg(all(windows, stack_capture, test))]
+pub(crate) use self::deferred::wait_resolved;
+#[cfg(stack_capture)]
+pub use self::demangle::{
+    caller_symbol, set_attribution_offset, set_collapse_generics, set_ffi_label, set_keep_mangled,
+    set_module_priority, set_name_rewriter,
+};
+pub use self::ema::DEFAULT_EMA_ALPHA;
+use self::ema::Ema;
+pub use self::histogram::DEFAULT_HISTOGRAM_BUCKETS;
+use self::histogram::Histogram;
+pub(crate) use self::intern::{INTERNED, intern};
+#[cfg(stack_capture)]
+pub use self::resolve::{ResolvedSymbol, resolve_addr};
+pub use self::window::RECENT_WINDOW;
+use self::window::Window;
+
+use std::alloc::Layout;
 use std::collections::HashMap;
-use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+use std::thread::ThreadId;
+use std::time::Duration;
+
+use crate::accounting::AccountingViolation;
+use crate::alignment::{ALIGN_CLASSES, AlignmentStats, align_class};
+use crate::fingerprint::Fingerprint;
+use crate::fold;
+#[cfg(not(stack_capture))]
+use crate::frame_provider;
+use crate::hooks;
+use crate::scopes;
+use crate::strict::{FreedPointers, Quarantine, StrictViolation, StrictViolationKind};
+use crate::tags::{self, TagStats};
+use crate::threads;
 
 /// A [`Symbol`] table.
 ///
 /// Each [`Symbol`] is identified by the module name (e.g. `leaktracer::alloc`).
+///
+/// The table also keeps a registry of the live allocations, keyed by pointer, with the [`Symbol`] which allocated them,
+/// so deallocations are always attributed to the allocating symbol, even when the memory is freed somewhere else.
+/// Each live allocation costs about 120 bytes in the registry on 64-bit targets: the pointer and the 112 bytes of
+/// its size, sampling weight, symbol, tag, table, module, thread and folding, plus the map overhead.
+/// With inclusive accounting (see [`crate::set_inclusive_accounting`]) the traced frames
+/// of the allocation are also boxed, 16 bytes each.
 #[derive(Debug)]
 pub struct SymbolTable {
     /// The modules that are being traced.
-    modules: &'static [&'static str],
+    ///
+    /// Adding a module swaps the whole list, so readers can keep a copy of it without holding the lock.
+    modules: Arc<[&'static str]>,
+    /// Boundaries of the allocation size histogram buckets.
+    histogram_buckets: &'static [usize],
+    /// Smoothing factor of the allocation size moving average of the symbols.
+    ema_alpha: f64,
     symbols: HashMap<&'static str, Symbol>,
+    /// The symbols of the named tables, keyed by table name (see [`crate::scoped_table`]).
+    scoped: HashMap<&'static str, HashMap<&'static str, Symbol>>,
+    /// Registry of the live allocations, keyed by pointer.
+    allocations: HashMap<usize, Allocation>,
+    /// Live bytes and count of the allocations made while each tag was active (see [`crate::push_tag`]).
+    tags: HashMap<&'static str, (usize, usize)>,
+    /// Live bytes and count of the allocations of each traced module, updated on each allocation and deallocation.
+    module_gauges: HashMap<&'static str, ModuleGauge>,
+    /// Live bytes and peak of the allocations made by each thread (see [`crate::peak_by_thread`]).
+    threads: HashMap<ThreadId, (usize, usize)>,
+    /// Number of allocations of each shape (see [`crate::fingerprints`]).
+    fingerprints: HashMap<Fingerprint, usize>,
+    /// Recently freed pointers, remembered in strict mode to detect double frees.
+    freed: FreedPointers,
+    /// Freed pointers whose memory is held back from the system allocator (see [`crate::LeaktracerAllocator::with_quarantine`]).
+    ///
+    /// Never cleared, since its memory is released only on eviction.
+    quarantine: Quarantine,
+    /// Whether the allocations are also credited to every traced frame on the call stack (see [`Symbol::inclusive_allocated`]).
+    #[cfg(stack_capture)]
+    inclusive: bool,
+    /// Number of times a symbols map grew inside the allocation path (see [`SymbolTable::rehash_count`]).
+    rehashes: usize,
+}
+
+/// Number of symbols reserved for each module added at runtime (see [`crate::add_traced_module`]).
+const MODULE_SYMBOLS_RESERVE: usize = 256;
+
+/// Whether the warning about the symbol table growing inside the allocation path has been emitted.
+#[cfg(any(feature = "log", feature = "tracing"))]
+static REHASH_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Name reported for the live allocations skipped by sampling.
+const UNSAMPLED: &str = "<unsampled>";
+
+/// Name reported for the live allocations below the attribution size threshold
+/// (see [`crate::set_attribution_size_threshold`]), and of the entry of the consistent snapshots
+/// gathering all the live bytes not attributed to the global table (see [`crate::consistent_snapshot`]).
+pub(crate) const UNATTRIBUTED: &str = "<unattributed>";
+
+/// Name reported for the quarantined pointers which were not in the registry.
+pub(crate) const UNTRACKED: &str = "<untracked>";
+
+/// Name of the [`Symbol`] the allocations made before [`crate::mark_startup_complete`] are attributed to.
+const STARTUP: &str = "<startup>";
+
+/// Name reported for the live allocations when the call stacks can't be captured and no frame provider is set.
+#[cfg(not(stack_capture))]
+const UNRESOLVED: &str = "<unresolved>";
+
+/// Outcome of a deallocation recorded into the [`SymbolTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Dealloc {
+    /// The pointer was in the registry, with the given allocation size and sampling weight.
+    Tracked { size: usize, weight: usize },
+    /// The pointer was in the registry, but its allocation was skipped by sampling.
+    Skipped,
+    /// The pointer was not in the registry.
+    Untracked,
+}
+
+/// A live allocation in the pointer registry.
+#[derive(Debug, Clone)]
+struct Allocation {
+    /// Size of the allocation in bytes.
+    size: usize,
+    /// Sampling weight the allocation was recorded with, `0` if it was skipped by sampling.
+    weight: usize,
+    /// Name of the [`Symbol`] which made the allocation.
+    symbol: &'static str,
+    /// The innermost tag active when the allocation was made, if any.
+    tag: Option<&'static str>,
+    /// The named table the allocation was attributed to, [`None`] for the global one.
+    table: Option<&'static str>,
+    /// The traced module the symbol belongs to, if any.
+    module: Option<&'static str>,
+    /// The [`Symbol`]s the allocation is credited to in inclusive accounting, if enabled.
+    inclusive: Option<Box<[&'static str]>>,
+    /// Whether the allocation was folded into a logical allocation of its symbol (see [`crate::fold_nested`]).
+    folded: bool,
+    /// The thread which made the allocation, if it's sampled.
+    thread: Option<ThreadId>,
+}
+
+impl Allocation {
+    /// Returns the count the allocation added to its [`Symbol`]: `0` if it was folded, its weight otherwise.
+    fn counted(&self) -> usize {
+        if self.folded { 0 } else { self.weight }
+    }
+}
+
+/// Live bytes and count of the allocations of a traced module.
+#[derive(Debug, Default)]
+struct ModuleGauge {
+    allocated: AtomicUsize,
+    count: AtomicUsize,
+}
+
+impl ModuleGauge {
+    /// Adds `weight` allocations of `bytes` to the gauge.
+    fn add(&self, bytes: usize, weight: usize) {
+        self.allocated.fetch_add(
+            bytes.saturating_mul(weight),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.count
+            .fetch_add(weight, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Removes `weight` allocations of `bytes` from the gauge.
+    fn sub(&self, bytes: usize, weight: usize) {
+        self.allocated.fetch_sub(
+            bytes.saturating_mul(weight),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.count
+            .fetch_sub(weight, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Returns the module among `modules` the symbol `name` belongs to, preferring the longest one.
+///
+/// A symbol belongs to a module if its path starts with the module path, e.g. `app::net::connect` to `app::net`.
+pub(crate) fn module_of(modules: &[&'static str], name: &str) -> Option<&'static str> {
+    modules
+        .iter()
+        .copied()
+        .filter(|module| {
+            name.strip_prefix(module)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .max_by_key(|module| module.len())
 }
 
 impl SymbolTable {
     /// Creates a new [`SymbolTable`] with the given size and modules.
     pub(crate) fn new(size: usize, modules: &'static [&'static str]) -> Self {
         Self {
-            modules,
+            modules: Arc::from(modules),
+            histogram_buckets: DEFAULT_HISTOGRAM_BUCKETS,
+            ema_alpha: DEFAULT_EMA_ALPHA,
             symbols: HashMap::with_capacity(size),
+            scoped: HashMap::new(),
+            allocations: HashMap::new(),
+            tags: HashMap::new(),
+            module_gauges: HashMap::new(),
+            threads: HashMap::new(),
+            fingerprints: HashMap::new(),
+            freed: FreedPointers::default(),
+            quarantine: Quarantine::default(),
+            #[cfg(stack_capture)]
+            inclusive: false,
+            rehashes: 0,
+        }
+    }
+
+    /// Returns the modules which are being traced, including the ones added with [`crate::add_traced_module`].
+    pub fn modules(&self) -> Arc<[&'static str]> {
+        Arc::clone(&self.modules)
+    }
+
+    /// Adds `module` to the modules which are being traced, if it's not already traced.
+    pub(crate) fn add_module(&mut self, module: &'static str) {
+        if self.modules.contains(&module) {
+            return;
         }
+        #[cfg(stack_capture)]
+        caller_cache::invalidate();
+        // the symbols of the new module would otherwise grow the table inside the allocation path
+        self.symbols.reserve(MODULE_SYMBOLS_RESERVE);
+        self.modules = self
+            .modules
+            .iter()
+            .copied()
+            .chain(std::iter::once(module))
+            .collect();
+    }
+
+    /// Replaces the modules which are being traced.
+    pub(crate) fn set_modules(&mut self, modules: &[&'static str]) {
+        #[cfg(stack_capture)]
+        caller_cache::invalidate();
+        self.modules = modules.into();
+    }
+
+    /// Returns the boundaries of the allocation size histogram buckets (see [`Symbol::histogram`]).
+    pub fn histogram_buckets(&self) -> &'static [usize] {
+        self.histogram_buckets
+    }
+
+    /// Sets the boundaries of the allocation size histogram buckets.
+    ///
+    /// The histograms of the existing symbols are reset.
+    pub(crate) fn set_histogram_buckets(&mut self, buckets: &'static [usize]) {
+        debug_assert!(
+            buckets.is_sorted(),
+            "histogram buckets must be sorted in ascending order"
+        );
+        self.histogram_buckets = buckets;
+        for symbol in self
+            .symbols
+            .values_mut()
+            .chain(self.scoped.values_mut().flat_map(HashMap::values_mut))
+        {
+            symbol.histogram = Histogram::new(buckets);
+        }
+    }
+
+    /// Sets whether the allocations are also credited to every traced frame on the call stack.
+    #[cfg(stack_capture)]
+    pub(crate) fn set_inclusive(&mut self, inclusive: bool) {
+        self.inclusive = inclusive;
+    }
+
+    /// Returns the smoothing factor of the allocation size moving average (see [`Symbol::ema_size`]).
+    pub fn ema_alpha(&self) -> f64 {
+        self.ema_alpha
+    }
+
+    /// Sets the smoothing factor of the allocation size moving average, clamped to `0.0..=1.0`.
+    pub(crate) fn set_ema_alpha(&mut self, alpha: f64) {
+        self.ema_alpha = alpha.clamp(0.0, 1.0);
     }
 
     /// Iterates over the [`Symbol`]s in the table, with their names.
@@ -27,53 +300,707 @@ impl SymbolTable {
         self.symbols.iter()
     }
 
+    /// Returns the [`Symbol`]s in the table, with their names, sorted by name.
+    ///
+    /// Unlike [`SymbolTable::iter`], whose order changes across runs, the order is deterministic,
+    /// so the snapshots and reports built from it are reproducible.
+    pub fn iter_sorted(&self) -> Vec<(&'static str, &Symbol)> {
+        let mut symbols: Vec<_> = self.iter().map(|(name, symbol)| (*name, symbol)).collect();
+        symbols.sort_unstable_by_key(|(name, _)| *name);
+        symbols
+    }
+
     /// Gets a [`Symbol`] by its name.
     pub fn get(&self, name: &'static str) -> Option<&Symbol> {
         self.symbols.get(&name)
     }
 
-    /// Increments the allocated bytes for a [`Symbol`].
-    pub(crate) fn alloc(&mut self, bytes: usize) {
-        let name = demangle::get_demangled_symbol(self.modules);
+    /// Gets a [`Symbol`] by a name which may not be `'static`, along with the interned name.
+    pub(crate) fn get_key_value(&self, name: &str) -> Option<(&'static str, &Symbol)> {
+        self.symbols
+            .get_key_value(name)
+            .map(|(name, symbol)| (*name, symbol))
+    }
 
-        // If the symbol does not exist, we create it with the given name.
-        if !self.symbols.contains_key(&name) {
-            self.insert(name);
+    /// Returns the number of symbols in the table.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns whether the table has no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns the number of symbols the table can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.symbols.capacity()
+    }
+
+    /// Returns the number of times the table grew inside the allocation path, since it was initialized or reset.
+    ///
+    /// Each growth rehashes all the symbols with the symbol table locked, which is a latency spike
+    /// for all the allocating threads: reserve more symbols upfront with [`crate::reserve`].
+    pub fn rehash_count(&self) -> usize {
+        self.rehashes
+    }
+
+    /// Reserves capacity for at least `additional` more symbols.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.symbols.reserve(additional);
+    }
+
+    /// Removes all the symbols and forgets all the live and freed pointers.
+    pub(crate) fn clear(&mut self) {
+        self.symbols.clear();
+        self.scoped.clear();
+        self.allocations.clear();
+        self.tags.clear();
+        self.module_gauges.clear();
+        self.threads.clear();
+        self.fingerprints.clear();
+        self.freed = FreedPointers::default();
+        self.rehashes = 0;
+    }
+
+    /// Returns the sum of the allocated bytes of all the [`Symbol`]s, including the ones of the named tables
+    /// (see [`crate::scoped_table`]).
+    pub fn total_allocated(&self) -> usize {
+        self.symbols
+            .values()
+            .chain(self.scoped.values().flat_map(HashMap::values))
+            .map(Symbol::allocated)
+            .sum()
+    }
+
+    /// Returns the sum of the allocated bytes of the [`Symbol`]s resolved to a function, i.e. [`SymbolTable::total_allocated`]
+    /// without the `<unknown>` symbol, which gathers the allocations made outside of the traced modules.
+    pub(crate) fn attributed_allocated(&self) -> usize {
+        self.symbols
+            .iter()
+            .chain(self.scoped.values().flat_map(HashMap::iter))
+            .filter(|(name, _)| !is_unknown(name))
+            .map(|(_, symbol)| symbol.allocated())
+            .sum()
+    }
+
+    /// Iterates over the live allocations as `(pointer, size, symbol)`.
+    ///
+    /// The allocations skipped by sampling are reported with the `<unsampled>` symbol.
+    pub fn live_allocations(&self) -> impl Iterator<Item = (usize, usize, &'static str)> {
+        self.allocations
+            .iter()
+            .map(|(ptr, allocation)| (*ptr, allocation.size, allocation.symbol))
+    }
+
+    /// Returns the live bytes and count, scaled by their sampling weight, of the allocations in the pointer registry
+    /// which are not attributed to any [`Symbol`] of the global table: the ones below the attribution size threshold,
+    /// the unresolved ones, and the ones attributed to the named tables.
+    pub(crate) fn unattributed(&self) -> (usize, usize) {
+        self.allocations
+            .values()
+            .filter(|allocation| {
+                allocation.table.is_some() || !self.symbols.contains_key(allocation.symbol)
+            })
+            .fold((0, 0), |(bytes, count), allocation| {
+                (
+                    bytes + allocation.size.saturating_mul(allocation.weight),
+                    count + allocation.weight,
+                )
+            })
+    }
+
+    /// Iterates over the named tables (see [`crate::scoped_table`]), with the [`Symbol`]s in each of them.
+    pub(crate) fn scoped_tables(
+        &self,
+    ) -> impl Iterator<Item = (&'static str, &HashMap<&'static str, Symbol>)> {
+        self.scoped.iter().map(|(name, symbols)| (*name, symbols))
+    }
+
+    /// Returns the stats of each tag (see [`crate::push_tag`]).
+    pub(crate) fn tag_stats(&self) -> Vec<TagStats> {
+        self.tags
+            .iter()
+            .map(|(name, (allocated, count))| TagStats {
+                name,
+                allocated: *allocated,
+                count: *count,
+            })
+            .collect()
+    }
+
+    /// Returns the number of allocations of each [`Fingerprint`].
+    pub(crate) fn fingerprints(&self) -> Vec<(Fingerprint, usize)> {
+        self.fingerprints
+            .iter()
+            .map(|(fingerprint, count)| (*fingerprint, *count))
+            .collect()
+    }
+
+    /// Returns the peak live bytes of the allocations made by each thread, as `(thread, peak)`.
+    pub(crate) fn thread_peaks(&self) -> Vec<(ThreadId, usize)> {
+        self.threads
+            .iter()
+            .map(|(thread, (_, peak))| (*thread, *peak))
+            .collect()
+    }
+
+    /// Returns the live bytes and count of the allocations of each traced module which has allocated,
+    /// as `(module, allocated, count)`.
+    pub(crate) fn module_stats(&self) -> Vec<(&'static str, usize, usize)> {
+        self.module_gauges
+            .iter()
+            .map(|(module, gauge)| {
+                (
+                    *module,
+                    gauge.allocated.load(std::sync::atomic::Ordering::Relaxed),
+                    gauge.count.load(std::sync::atomic::Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+
+    /// Increments the allocated bytes for the calling [`Symbol`] and records the allocation at `ptr`.
+    ///
+    /// The bytes and the count are scaled by the sampling `weight`, while the histogram records the actual size.
+    /// Returns the name of the calling symbol.
+    ///
+    /// On Windows the allocator uses [`SymbolTable::alloc_deferred`] instead.
+    #[cfg(stack_capture)]
+    #[cfg_attr(windows, allow(dead_code))]
+    pub(crate) fn alloc(&mut self, ptr: usize, bytes: usize, weight: usize) -> &'static str {
+        if !self.inclusive {
+            let name = demangle::get_demangled_symbol(&self.modules);
+            return self.alloc_to(ptr, bytes, weight, name);
         }
 
-        let symbol = self.symbols.get_mut(name).expect("Symbol should exist");
+        let (name, frames) = demangle::get_caller_frames(&self.modules);
+        let name = self.alloc_to(ptr, bytes, weight, name);
+        self.credit_inclusive(ptr, frames.into_boxed_slice());
+        name
+    }
 
-        symbol
-            .allocated
-            .fetch_add(bytes, std::sync::atomic::Ordering::Relaxed);
-        symbol
-            .count
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    /// Like [`SymbolTable::alloc`], but resolves the call stack only if the current thread hasn't already
+    /// resolved the same call site (see [`caller_cache`]).
+    ///
+    /// In inclusive accounting the whole call stack is needed, so it falls back to [`SymbolTable::alloc`].
+    #[cfg(stack_capture)]
+    #[cfg_attr(windows, allow(dead_code))]
+    pub(crate) fn alloc_cached(&mut self, ptr: usize, bytes: usize, weight: usize) -> &'static str {
+        if self.inclusive {
+            return self.alloc(ptr, bytes, weight);
+        }
+
+        let modules = &self.modules;
+        let name = caller_cache::cached_symbol(|| demangle::get_demangled_symbol(modules));
+        self.alloc_to(ptr, bytes, weight, name)
     }
 
-    /// Decrements the allocated bytes for a [`Symbol`].
-    pub(crate) fn dealloc(&mut self, bytes: usize) {
-        let name = demangle::get_demangled_symbol(self.modules);
+    /// Credits the live allocation at `ptr` to the inclusive bytes of the [`Symbol`]s of `frames`,
+    /// which are remembered in the registry to be debited on deallocation.
+    #[cfg(stack_capture)]
+    fn credit_inclusive(&mut self, ptr: usize, frames: Box<[&'static str]>) {
+        let Some(allocation) = self.allocations.get_mut(&ptr) else {
+            return;
+        };
+        let buckets = self.histogram_buckets;
+        let symbols = match allocation.table {
+            Some(table) => self.scoped.entry(table).or_default(),
+            None => &mut self.symbols,
+        };
+        let bytes = allocation.size.saturating_mul(allocation.weight);
+        let capacity = symbols.capacity();
+        for frame in &frames {
+            symbols
+                .entry(frame)
+                .or_insert_with(|| Symbol::new(buckets))
+                .inclusive_allocated
+                .fetch_add(bytes, std::sync::atomic::Ordering::Relaxed);
+        }
+        count_rehash(&mut self.rehashes, capacity, symbols.capacity());
+        allocation.inclusive = Some(frames);
+    }
 
-        if let Some(symbol) = self.symbols.get_mut(name) {
-            symbol
-                .allocated
-                .fetch_sub(bytes, std::sync::atomic::Ordering::Relaxed);
+    /// Like [`SymbolTable::alloc`], but without resolving the call stack, which is resolved later
+    /// by a helper thread (see [`deferred`]).
+    ///
+    /// Until then, the allocation is attributed to a `<pending:...>` placeholder [`Symbol`].
+    #[cfg(all(windows, stack_capture))]
+    pub(crate) fn alloc_deferred(
+        &mut self,
+        ptr: usize,
+        bytes: usize,
+        weight: usize,
+    ) -> &'static str {
+        let name = deferred::deferred_symbol(&self.modules);
+        self.alloc_to(ptr, bytes, weight, name)
+    }
+
+    /// Renames the [`Symbol`]s in the keys of `renames` to the symbols in the values, in all the tables.
+    ///
+    /// The stats of a renamed symbol are merged into the target symbol if it already exists,
+    /// and the live allocations are reattributed to the target symbol.
+    #[cfg_attr(not(all(windows, stack_capture)), allow(dead_code))]
+    pub(crate) fn rename(&mut self, renames: &HashMap<&'static str, &'static str>) {
+        let buckets = self.histogram_buckets;
+        for symbols in std::iter::once(&mut self.symbols).chain(self.scoped.values_mut()) {
+            for (from, to) in renames {
+                if let Some(renamed) = symbols.remove(from) {
+                    symbols
+                        .entry(to)
+                        .or_insert_with(|| Symbol::new(buckets))
+                        .merge(&renamed);
+                }
+            }
+        }
+        for allocation in self.allocations.values_mut() {
+            if let Some(to) = renames.get(allocation.symbol) {
+                allocation.symbol = to;
+                // the placeholder doesn't belong to any module, the renamed symbol may
+                let module = module_of(&self.modules, to);
+                if module != allocation.module {
+                    if let Some(gauge) = allocation.module.and_then(|m| self.module_gauges.get(m)) {
+                        gauge.sub(allocation.size, allocation.weight);
+                    }
+                    if let Some(module) = module {
+                        self.module_gauges
+                            .entry(module)
+                            .or_default()
+                            .add(allocation.size, allocation.weight);
+                    }
+                    allocation.module = module;
+                }
+            }
+        }
+    }
+
+    /// Like [`SymbolTable::alloc`], but attributes the allocation to the `<startup>` [`Symbol`],
+    /// without capturing the call stack, so even in the counter-only mode.
+    pub(crate) fn alloc_startup(
+        &mut self,
+        ptr: usize,
+        bytes: usize,
+        weight: usize,
+    ) -> &'static str {
+        self.alloc_fixed(ptr, bytes, weight, STARTUP)
+    }
+
+    /// Like [`SymbolTable::alloc`], but attributes the allocation to the [`Symbol`] `name`,
+    /// without capturing the call stack, so even in the counter-only mode.
+    pub(crate) fn alloc_fixed(
+        &mut self,
+        ptr: usize,
+        bytes: usize,
+        weight: usize,
+        name: &'static str,
+    ) -> &'static str {
+        self.alloc_to(ptr, bytes, weight, name)
+    }
+
+    /// Increments the allocated bytes for the [`Symbol`] `name` and records the allocation at `ptr`.
+    ///
+    /// The symbol belongs to the named table of the innermost scope of the current thread, if any,
+    /// or to the global one otherwise.
+    pub(crate) fn alloc_to(
+        &mut self,
+        ptr: usize,
+        bytes: usize,
+        weight: usize,
+        name: &'static str,
+    ) -> &'static str {
+        let table = scopes::current_scope();
+        let buckets = self.histogram_buckets;
+        let symbols = match table {
+            Some(table) => self.scoped.entry(table).or_default(),
+            None => &mut self.symbols,
+        };
+        let capacity = symbols.capacity();
+        // If the symbol does not exist, we create it with the given name.
+        let symbol = symbols.entry(name).or_insert_with(|| Symbol::new(buckets));
+        let folded = weight > 0 && fold::fold(name);
+        let counted = if folded { 0 } else { weight };
+
+        let allocated = symbol.allocated.fetch_add(
+            bytes.saturating_mul(weight),
+            std::sync::atomic::Ordering::Relaxed,
+        ) + bytes.saturating_mul(weight);
+        let now = crate::uptime();
+        if symbol
+            .peak
+            .fetch_max(allocated, std::sync::atomic::Ordering::Relaxed)
+            < allocated
+        {
             symbol
-                .count
-                .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                .peak_at
+                .store(now.as_millis() as u64, std::sync::atomic::Ordering::Relaxed);
+        }
+        symbol
+            .count
+            .fetch_add(counted, std::sync::atomic::Ordering::Relaxed);
+        symbol.total_allocated.fetch_add(
+            bytes.saturating_mul(weight),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        symbol
+            .total_count
+            .fetch_add(counted, std::sync::atomic::Ordering::Relaxed);
+        symbol.histogram.record(buckets, bytes);
+        symbol.sizes.record(bytes);
+        symbol.ema_size.record(bytes, self.ema_alpha);
+        symbol
+            .window
+            .record(now, bytes.saturating_mul(weight), counted);
+        // fire only once until re-armed by the deallocations
+        if hooks::threshold_exceeded(allocated)
+            && symbol
+                .armed
+                .swap(false, std::sync::atomic::Ordering::Relaxed)
+        {
+            hooks::threshold(name, allocated);
+        }
+        count_rehash(&mut self.rehashes, capacity, symbols.capacity());
+
+        self.register(ptr, bytes, weight, name, table);
+        if folded {
+            if let Some(allocation) = self.allocations.get_mut(&ptr) {
+                allocation.folded = true;
+            }
         }
+
+        name
     }
 
-    /// Inserts a new [`Symbol`] into the table.
-    fn insert(&mut self, name: &'static str) {
-        self.symbols.insert(
-            name,
-            Symbol {
-                allocated: AtomicUsize::new(0),
-                count: AtomicUsize::new(0),
+    /// Records the allocation at `ptr`, without capturing the call stack.
+    ///
+    /// The allocation is attributed to the caller supplied by the [`crate::FrameProvider`], if any.
+    /// Otherwise the pointer is only registered, so its deallocation is released with the `weight` it was recorded with.
+    #[cfg(not(stack_capture))]
+    pub(crate) fn alloc(&mut self, ptr: usize, bytes: usize, weight: usize) -> &'static str {
+        if let Some(name) = frame_provider::caller(&self.modules) {
+            return self.alloc_to(ptr, bytes, weight, name);
+        }
+        self.register(ptr, bytes, weight, UNRESOLVED, None);
+
+        UNRESOLVED
+    }
+
+    /// Records the allocation at `ptr` below the attribution size threshold, without capturing the call stack
+    /// nor attributing it to any [`Symbol`].
+    ///
+    /// The pointer is registered with its `weight`, so its deallocation is released from the global counters.
+    pub(crate) fn alloc_unattributed(
+        &mut self,
+        ptr: usize,
+        bytes: usize,
+        weight: usize,
+    ) -> &'static str {
+        self.register(ptr, bytes, weight, UNATTRIBUTED, None);
+
+        UNATTRIBUTED
+    }
+
+    /// Records the allocation at `ptr` skipped by sampling, without attributing it to any [`Symbol`].
+    ///
+    /// The pointer is still registered, so its deallocation is not mistaken for an untracked one.
+    pub(crate) fn skip(&mut self, ptr: usize, bytes: usize) {
+        self.register(ptr, bytes, 0, UNSAMPLED, None);
+    }
+
+    /// Registers the allocation at `ptr` made by `symbol` in `table`, accounting it to the current tag if it's sampled.
+    fn register(
+        &mut self,
+        ptr: usize,
+        bytes: usize,
+        weight: usize,
+        symbol: &'static str,
+        table: Option<&'static str>,
+    ) {
+        let tag = if weight > 0 {
+            tags::current_tag()
+        } else {
+            None
+        };
+        if let Some(tag) = tag {
+            let (allocated, count) = self.tags.entry(tag).or_default();
+            *allocated += bytes.saturating_mul(weight);
+            *count += weight;
+        }
+        let module = if weight > 0 {
+            module_of(&self.modules, symbol)
+        } else {
+            None
+        };
+        if let Some(module) = module {
+            self.module_gauges
+                .entry(module)
+                .or_default()
+                .add(bytes, weight);
+        }
+        let thread = if weight > 0 {
+            threads::current_thread()
+        } else {
+            None
+        };
+        if let Some(thread) = thread {
+            let (allocated, peak) = self.threads.entry(thread).or_default();
+            *allocated += bytes.saturating_mul(weight);
+            *peak = (*peak).max(*allocated);
+        }
+
+        self.allocations.insert(
+            ptr,
+            Allocation {
+                size: bytes,
+                weight,
+                symbol,
+                tag,
+                table,
+                module,
+                inclusive: None,
+                folded: false,
+                thread,
             },
         );
+        self.freed.remove(ptr);
+    }
+
+    /// Decrements the allocated bytes for the [`Symbol`] which allocated `ptr`.
+    ///
+    /// Pointers which are not in the registry (e.g. allocated before the table was initialized) are ignored,
+    /// and [`Dealloc::Untracked`] is returned.
+    ///
+    /// If `strict` is set, `bytes` is checked against the allocation size and freed pointers are remembered,
+    /// returning a [`StrictViolation`] on a mismatched size or a double free.
+    /// On a mismatched size, the size recorded at allocation is released anyway.
+    pub(crate) fn dealloc(
+        &mut self,
+        ptr: usize,
+        bytes: usize,
+        strict: bool,
+    ) -> (Dealloc, Option<StrictViolation>) {
+        let Some(allocation) = self.allocations.remove(&ptr) else {
+            let violation = match self.freed.get(ptr) {
+                Some(symbol) if strict => Some(StrictViolation {
+                    kind: StrictViolationKind::DoubleFree,
+                    ptr,
+                    symbol,
+                }),
+                _ => None,
+            };
+            return (Dealloc::Untracked, violation);
+        };
+
+        let symbols = match allocation.table {
+            Some(table) => self.scoped.get_mut(table),
+            None => Some(&mut self.symbols),
+        };
+        if let Some(symbols) = &symbols {
+            let bytes = allocation.size.saturating_mul(allocation.weight);
+            for symbol in allocation
+                .inclusive
+                .iter()
+                .flatten()
+                .filter_map(|frame| symbols.get(frame))
+            {
+                symbol
+                    .inclusive_allocated
+                    .fetch_sub(bytes, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        if let Some(symbol) = symbols.and_then(|symbols| symbols.get_mut(allocation.symbol)) {
+            // saturated, an underflow is reported by strict accounting instead of wrapping
+            let allocated = saturating_sub(
+                &symbol.allocated,
+                allocation.size.saturating_mul(allocation.weight),
+            );
+            saturating_sub(&symbol.count, allocation.counted());
+            symbol.freed.fetch_add(
+                allocation.size.saturating_mul(allocation.weight),
+                std::sync::atomic::Ordering::Relaxed,
+            );
+            if hooks::threshold_rearmed(allocated) {
+                symbol
+                    .armed
+                    .store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        if let Some((allocated, count)) = allocation.tag.and_then(|tag| self.tags.get_mut(tag)) {
+            *allocated =
+                allocated.saturating_sub(allocation.size.saturating_mul(allocation.weight));
+            *count = count.saturating_sub(allocation.weight);
+        }
+        if let Some(gauge) = allocation
+            .module
+            .and_then(|module| self.module_gauges.get(module))
+        {
+            gauge.sub(allocation.size, allocation.weight);
+        }
+        if let Some((allocated, _)) = allocation
+            .thread
+            .and_then(|thread| self.threads.get_mut(&thread))
+        {
+            *allocated =
+                allocated.saturating_sub(allocation.size.saturating_mul(allocation.weight));
+        }
+
+        let dealloc = if allocation.weight == 0 {
+            Dealloc::Skipped
+        } else {
+            Dealloc::Tracked {
+                size: allocation.size,
+                weight: allocation.weight,
+            }
+        };
+        if !strict {
+            return (dealloc, None);
+        }
+        self.freed.insert(ptr, allocation.symbol);
+        if allocation.size != bytes {
+            let violation = StrictViolation {
+                kind: StrictViolationKind::SizeMismatch {
+                    allocated: allocation.size,
+                    deallocated: bytes,
+                },
+                ptr,
+                symbol: allocation.symbol,
+            };
+            return (dealloc, Some(violation));
+        }
+
+        (dealloc, None)
+    }
+
+    /// Returns the [`AccountingViolation`] the deallocation of `ptr` would cause, if the counters of the [`Symbol`]
+    /// it's attributed to are lower than the allocation.
+    pub(crate) fn accounting_underflow(&self, ptr: usize) -> Option<AccountingViolation> {
+        let allocation = self.allocations.get(&ptr)?;
+        let symbols = match allocation.table {
+            Some(table) => self.scoped.get(table)?,
+            None => &self.symbols,
+        };
+        let symbol = symbols.get(allocation.symbol)?;
+        let size = allocation.size.saturating_mul(allocation.weight);
+        if symbol.allocated() >= size && symbol.count() >= allocation.counted() {
+            return None;
+        }
+
+        // on Windows the call stack can't be resolved inside the allocator (see [`deferred`])
+        #[cfg(all(not(windows), stack_capture))]
+        let freeing_symbol = Some(demangle::get_demangled_symbol(&self.modules));
+        #[cfg(not(all(not(windows), stack_capture)))]
+        let freeing_symbol = None;
+        Some(AccountingViolation {
+            ptr,
+            symbol: allocation.symbol,
+            size,
+            allocated: symbol.allocated(),
+            freeing_symbol,
+        })
+    }
+
+    /// Returns the symbol which allocated `ptr`, if it's in the registry.
+    pub(crate) fn allocation_symbol(&self, ptr: usize) -> Option<&'static str> {
+        self.allocations
+            .get(&ptr)
+            .map(|allocation| allocation.symbol)
+    }
+
+    /// Counts the reallocation of a block of `old_size` bytes which left it at `ptr`, to the symbol it's attributed to,
+    /// as a move if the block changed address.
+    pub(crate) fn record_realloc(&self, ptr: usize, moved: bool, old_size: usize) {
+        let Some(allocation) = self.allocations.get(&ptr) else {
+            return;
+        };
+        let symbols = match allocation.table {
+            Some(table) => self.scoped.get(table),
+            None => Some(&self.symbols),
+        };
+        let Some(symbol) = symbols.and_then(|symbols| symbols.get(allocation.symbol)) else {
+            return;
+        };
+        let counter = if moved {
+            &symbol.realloc_moves
+        } else {
+            &symbol.realloc_inplace
+        };
+        counter.fetch_add(allocation.weight, std::sync::atomic::Ordering::Relaxed);
+        let (counter, delta) = if allocation.size >= old_size {
+            (&symbol.bytes_grown, allocation.size - old_size)
+        } else {
+            (&symbol.bytes_shrunk, old_size - allocation.size)
+        };
+        counter.fetch_add(
+            delta.saturating_mul(allocation.weight),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+    }
+
+    /// Counts the live allocation at `ptr`, aligned to `align` bytes, in the alignment class of the [`Symbol`]
+    /// which made it (see [`Symbol::alignment_stats`]).
+    pub(crate) fn record_alignment(&self, ptr: usize, align: usize) {
+        let Some(allocation) = self.allocations.get(&ptr) else {
+            return;
+        };
+        let symbols = match allocation.table {
+            Some(table) => self.scoped.get(table),
+            None => Some(&self.symbols),
+        };
+        if let Some(symbol) = symbols.and_then(|symbols| symbols.get(allocation.symbol)) {
+            symbol.alignments[align_class(align)]
+                .fetch_add(allocation.weight, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    /// Counts `weight` allocations of `size` bytes aligned to `align` made by `symbol` in their [`Fingerprint`].
+    pub(crate) fn record_fingerprint(
+        &mut self,
+        size: usize,
+        align: usize,
+        symbol: &'static str,
+        weight: usize,
+    ) {
+        *self
+            .fingerprints
+            .entry(Fingerprint::new(size, align, symbol))
+            .or_default() += weight;
+    }
+
+    /// Returns the symbol which allocated `ptr`, if `ptr` is quarantined.
+    pub(crate) fn quarantined(&self, ptr: usize) -> Option<&'static str> {
+        self.quarantine.get(ptr)
+    }
+
+    /// Quarantines the freed `ptr`, allocated with `layout` by `symbol`, holding its memory back
+    /// from the system allocator.
+    ///
+    /// Returns the oldest quarantined pointer with its layout, once more than `capacity` pointers are quarantined,
+    /// so that its memory is released.
+    pub(crate) fn quarantine(
+        &mut self,
+        ptr: usize,
+        layout: Layout,
+        symbol: &'static str,
+        capacity: usize,
+    ) -> Option<(usize, Layout)> {
+        self.quarantine.push(ptr, layout, symbol, capacity)
+    }
+
+    /// Inserts a new [`Symbol`] into the table.
+    #[cfg(test)]
+    pub(crate) fn insert(&mut self, name: &'static str) {
+        self.symbols
+            .insert(name, Symbol::new(self.histogram_buckets));
+    }
+}
+
+impl<'a> IntoIterator for &'a SymbolTable {
+    type Item = (&'a &'static str, &'a Symbol);
+    type IntoIter = std::collections::hash_map::Iter<'a, &'static str, Symbol>;
+
+    /// Iterates over the [`Symbol`]s in the table, with their names, like [`SymbolTable::iter`].
+    fn into_iter(self) -> Self::IntoIter {
+        self.symbols.iter()
     }
 }
 
@@ -84,9 +1011,125 @@ pub struct Symbol {
     allocated: AtomicUsize,
     /// Allocation count for this symbol.
     count: AtomicUsize,
+    /// Highest allocated bytes reached by this symbol.
+    peak: AtomicUsize,
+    /// The [`crate::uptime`], in milliseconds, at which the peak was reached.
+    peak_at: AtomicU64,
+    /// Bytes ever allocated for this symbol, including the freed ones.
+    total_allocated: AtomicUsize,
+    /// Allocations ever made for this symbol, including the freed ones.
+    total_count: AtomicUsize,
+    /// Bytes ever freed for this symbol.
+    freed: AtomicUsize,
+    /// Live bytes allocated by this symbol and by the functions it called, in inclusive accounting.
+    inclusive_allocated: AtomicUsize,
+    /// Reallocations which moved the block to another address.
+    realloc_moves: AtomicUsize,
+    /// Reallocations which resized the block in place.
+    realloc_inplace: AtomicUsize,
+    /// Bytes added to the blocks by the growing reallocations.
+    bytes_grown: AtomicUsize,
+    /// Bytes removed from the blocks by the shrinking reallocations.
+    bytes_shrunk: AtomicUsize,
+    /// Allocations in each alignment class (see [`AlignmentStats`]).
+    alignments: [AtomicUsize; ALIGN_CLASSES],
+    /// The total traced allocations when the symbol was inserted.
+    first_seen_seq: AtomicUsize,
+    /// Histogram of the allocation sizes.
+    histogram: Histogram,
+    /// The distinct allocation sizes, up to [`SIZE_CARDINALITY_CUTOFF`].
+    sizes: SizeSet,
+    /// Exponential moving average of the allocation sizes.
+    ema_size: Ema,
+    /// Sliding window counters of the recent allocations.
+    window: Window,
+    /// Whether the threshold callback fires when the allocated bytes exceed the limit.
+    armed: AtomicBool,
 }
 
 impl Symbol {
+    /// Creates a new empty [`Symbol`] with the given histogram bucket boundaries.
+    fn new(histogram_buckets: &[usize]) -> Self {
+        Self {
+            allocated: AtomicUsize::new(0),
+            count: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            peak_at: AtomicU64::new(0),
+            total_allocated: AtomicUsize::new(0),
+            total_count: AtomicUsize::new(0),
+            freed: AtomicUsize::new(0),
+            inclusive_allocated: AtomicUsize::new(0),
+            realloc_moves: AtomicUsize::new(0),
+            realloc_inplace: AtomicUsize::new(0),
+            bytes_grown: AtomicUsize::new(0),
+            bytes_shrunk: AtomicUsize::new(0),
+            alignments: [const { AtomicUsize::new(0) }; ALIGN_CLASSES],
+            first_seen_seq: AtomicUsize::new(crate::stats::total_allocations()),
+            histogram: Histogram::new(histogram_buckets),
+            sizes: SizeSet::new(),
+            ema_size: Ema::new(),
+            window: Window::new(),
+            armed: AtomicBool::new(true),
+        }
+    }
+
+    /// Adds the stats of `other` to this symbol.
+    #[cfg_attr(not(all(windows, stack_capture)), allow(dead_code))]
+    fn merge(&self, other: &Symbol) {
+        self.allocated
+            .fetch_add(other.allocated(), std::sync::atomic::Ordering::Relaxed);
+        self.count
+            .fetch_add(other.count(), std::sync::atomic::Ordering::Relaxed);
+        // the peaks of the two symbols may not have been reached at the same time
+        let peak = self.allocated().max(other.peak());
+        if self
+            .peak
+            .fetch_max(peak, std::sync::atomic::Ordering::Relaxed)
+            < peak
+        {
+            let peak_at = if peak == other.peak() {
+                other.peak_at()
+            } else {
+                crate::uptime()
+            };
+            self.peak_at.store(
+                peak_at.as_millis() as u64,
+                std::sync::atomic::Ordering::Relaxed,
+            );
+        }
+        self.total_allocated.fetch_add(
+            other.total_allocated(),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.total_count
+            .fetch_add(other.total_count(), std::sync::atomic::Ordering::Relaxed);
+        self.freed
+            .fetch_add(other.turnover(), std::sync::atomic::Ordering::Relaxed);
+        self.inclusive_allocated.fetch_add(
+            other.inclusive_allocated(),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.realloc_moves
+            .fetch_add(other.realloc_moves(), std::sync::atomic::Ordering::Relaxed);
+        self.realloc_inplace.fetch_add(
+            other.realloc_inplace(),
+            std::sync::atomic::Ordering::Relaxed,
+        );
+        self.bytes_grown
+            .fetch_add(other.bytes_grown(), std::sync::atomic::Ordering::Relaxed);
+        self.bytes_shrunk
+            .fetch_add(other.bytes_shrunk(), std::sync::atomic::Ordering::Relaxed);
+        for (counter, count) in self.alignments.iter().zip(other.alignment_stats().counts()) {
+            counter.fetch_add(count, std::sync::atomic::Ordering::Relaxed);
+        }
+        self.first_seen_seq
+            .fetch_min(other.first_seen_seq(), std::sync::atomic::Ordering::Relaxed);
+        self.histogram.merge(&other.histogram);
+        self.sizes.merge(&other.sizes);
+        self.ema_size.merge(&other.ema_size);
+        self.window.merge(&other.window);
+    }
+
     /// Returns the number of bytes allocated for this symbol.
     pub fn allocated(&self) -> usize {
         self.allocated.load(std::sync::atomic::Ordering::Relaxed)
@@ -96,6 +1139,202 @@ impl Symbol {
     pub fn count(&self) -> usize {
         self.count.load(std::sync::atomic::Ordering::Relaxed)
     }
+
+    /// Returns the highest number of bytes allocated for this symbol at any time, i.e. the peak of [`Symbol::allocated`].
+    pub fn peak(&self) -> usize {
+        self.peak.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the [`crate::uptime`] at which [`Symbol::peak`] was reached, with a millisecond resolution,
+    /// to correlate the blow-up of a symbol with the logs. [`Duration::ZERO`] if the symbol never allocated.
+    pub fn peak_at(&self) -> Duration {
+        Duration::from_millis(self.peak_at.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    /// Returns the live bytes allocated by this symbol and by all the functions it called,
+    /// if inclusive accounting is enabled (see [`crate::set_inclusive_accounting`]), `0` otherwise.
+    ///
+    /// Each allocation is credited once to every function of the traced modules on its call stack,
+    /// even if it appears more than once, e.g. on recursion. So a function has at least the inclusive bytes
+    /// of each function it called, and at least its own [`Symbol::allocated`] bytes.
+    pub fn inclusive_allocated(&self) -> usize {
+        self.inclusive_allocated
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes ever allocated for this symbol, including the freed ones.
+    pub fn total_allocated(&self) -> usize {
+        self.total_allocated
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of allocations ever made for this symbol, including the freed ones.
+    pub fn total_count(&self) -> usize {
+        self.total_count.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the average allocation size in bytes, i.e. [`Symbol::total_allocated`] over [`Symbol::total_count`],
+    /// `0.0` if the symbol has never allocated.
+    pub fn avg_allocation_size(&self) -> f64 {
+        avg_allocation_size(self.total_allocated(), self.total_count())
+    }
+
+    /// Returns the number of bytes freed for this symbol since the table was initialized or reset.
+    ///
+    /// A symbol with a high turnover and few live bytes isn't leaking, but it's hammering the allocator,
+    /// which makes it a good target for pooling.
+    pub fn turnover(&self) -> usize {
+        self.freed.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of reallocations made by this symbol, i.e. [`Symbol::realloc_moves`]
+    /// plus [`Symbol::realloc_inplace`].
+    pub fn reallocs(&self) -> usize {
+        self.realloc_moves() + self.realloc_inplace()
+    }
+
+    /// Returns the number of reallocations made by this symbol which moved the block to another address.
+    ///
+    /// Each move copies the whole block, so many moves on a growing collection are worth a `reserve`
+    /// or a `with_capacity` upfront.
+    pub fn realloc_moves(&self) -> usize {
+        self.realloc_moves
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of reallocations made by this symbol which resized the block in place.
+    pub fn realloc_inplace(&self) -> usize {
+        self.realloc_inplace
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the bytes added to the blocks by the reallocations made by this symbol which grew them.
+    pub fn bytes_grown(&self) -> usize {
+        self.bytes_grown.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the bytes removed from the blocks by the reallocations made by this symbol which shrunk them.
+    pub fn bytes_shrunk(&self) -> usize {
+        self.bytes_shrunk.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the total number of traced allocations (see [`crate::QuickStats::total_allocations`])
+    /// when this symbol was inserted into the table, including its first allocation.
+    ///
+    /// Sorting the symbols by it gives the order they first allocated in, e.g. to match them with the phases
+    /// of the program.
+    pub fn first_seen_seq(&self) -> usize {
+        self.first_seen_seq
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the number of allocations ever made by this symbol in each alignment class.
+    pub fn alignment_stats(&self) -> AlignmentStats {
+        AlignmentStats::from_counts(
+            self.alignments
+                .each_ref()
+                .map(|count| count.load(std::sync::atomic::Ordering::Relaxed)),
+        )
+    }
+
+    /// Returns the number of allocations in each bin of the allocation size histogram.
+    ///
+    /// The bins are delimited by the [`SymbolTable::histogram_buckets`]: bin `0` counts the allocations smaller than the first boundary,
+    /// while the last bin counts the allocations greater or equal than the last boundary.
+    pub fn histogram(&self) -> Vec<usize> {
+        self.histogram.counts()
+    }
+
+    /// Returns the exponential moving average of the allocation sizes in bytes, i.e. the typical allocation size,
+    /// `0.0` if the symbol has never allocated.
+    ///
+    /// Unlike the raw min and max, the average is stable on noisy workloads. Its smoothing factor is set with
+    /// [`crate::set_ema_alpha`]: the higher, the faster it follows the recent allocations.
+    pub fn ema_size(&self) -> f64 {
+        self.ema_size.get()
+    }
+
+    /// Returns the number of distinct sizes of the allocations ever made by this symbol, counted exactly
+    /// up to [`SIZE_CARDINALITY_CUTOFF`], and as [`SizeCardinality::Many`] beyond.
+    ///
+    /// A single size over lots of allocations points at a pool candidate, many sizes at a buffer builder.
+    pub fn size_cardinality(&self) -> SizeCardinality {
+        self.sizes.cardinality()
+    }
+
+    /// Returns the bytes and count of the allocations made by this symbol in the last `window`,
+    /// including the ones already freed.
+    ///
+    /// The counters are kept in buckets of 5 seconds, so the window is rounded up to a multiple of 5 seconds,
+    /// and capped at [`RECENT_WINDOW`] (one minute).
+    pub fn recent(&self, window: Duration) -> (usize, usize) {
+        self.window.recent(crate::uptime(), window)
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    /// Renders the live bytes and count of the symbol, as `alloc=<bytes> count=<count>`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "alloc={} count={}", self.allocated(), self.count())
+    }
+}
+
+/// Returns `total_allocated` bytes over `total_count` allocations, `0.0` without allocations.
+pub(crate) fn avg_allocation_size(total_allocated: usize, total_count: usize) -> f64 {
+    if total_count == 0 {
+        return 0.0;
+    }
+
+    total_allocated as f64 / total_count as f64
+}
+
+/// Returns whether `name` is the symbol of the allocations made outside of the traced modules.
+fn is_unknown(name: &str) -> bool {
+    #[cfg(stack_capture)]
+    return name == demangle::UNKNOWN;
+    #[cfg(not(stack_capture))]
+    {
+        let _ = name;
+        false
+    }
+}
+
+/// Counts a rehash of a symbols map if it grew from `before` to `after` capacity inside the allocation path,
+/// warning about the first one through the `log` or `tracing` integration.
+fn count_rehash(rehashes: &mut usize, before: usize, after: usize) {
+    if after == before {
+        return;
+    }
+    *rehashes += 1;
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    if !REHASH_WARNED.swap(true, std::sync::atomic::Ordering::Relaxed) {
+        warn_rehash(after);
+    }
+}
+
+/// Warns that the symbol table grew to `capacity` symbols inside the allocation path.
+#[cfg(any(feature = "log", feature = "tracing"))]
+fn warn_rehash(capacity: usize) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        "leaktracer: the symbol table grew to {capacity} symbols inside the allocation path, reserve more upfront with leaktracer::reserve"
+    );
+    #[cfg(all(feature = "tracing", not(feature = "log")))]
+    tracing::warn!(
+        "leaktracer: the symbol table grew to {capacity} symbols inside the allocation path, reserve more upfront with leaktracer::reserve"
+    );
+}
+
+/// Subtracts `n` from `counter`, saturating at zero, and returns the new value.
+fn saturating_sub(counter: &AtomicUsize, n: usize) -> usize {
+    let prev = counter
+        .fetch_update(
+            std::sync::atomic::Ordering::Relaxed,
+            std::sync::atomic::Ordering::Relaxed,
+            |value| Some(value.saturating_sub(n)),
+        )
+        .unwrap_or_else(|value| value);
+    prev.saturating_sub(n)
 }
 
 #[cfg(test)]
@@ -104,9 +1343,18 @@ mod test {
     use super::*;
 
     #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_should_keep_registry_cost_documented() {
+        // keep the cost in the `SymbolTable` docs in sync when adding fields to `Allocation`
+        assert_eq!(std::mem::size_of::<Allocation>(), 112);
+        assert_eq!(std::mem::size_of::<(usize, Allocation)>(), 120);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
     fn test_should_allocate_symbol() {
         let mut table = SymbolTable::new(10, &["leaktracer"]);
-        table.alloc(100);
+        table.alloc(0x1000, 100, 1);
         // get name of the caller
         let name = demangle::get_demangled_symbol(&["leaktracer"]);
         let symbol = table.get(name).expect("Symbol should exist");
@@ -114,18 +1362,140 @@ mod test {
         assert_eq!(symbol.count(), 1);
 
         // allocate again
-        table.alloc(50);
+        table.alloc(0x2000, 50, 1);
         let symbol = table.get(name).expect("Symbol should exist");
         assert_eq!(symbol.allocated(), 150);
         assert_eq!(symbol.count(), 2);
 
         // deallocate
-        table.dealloc(40);
+        assert_eq!(
+            table.dealloc(0x1000, 100, false),
+            (
+                Dealloc::Tracked {
+                    size: 100,
+                    weight: 1
+                },
+                None
+            )
+        );
         let symbol = table.get(name).expect("Symbol should exist");
-        assert_eq!(symbol.allocated(), 110);
+        assert_eq!(symbol.allocated(), 50);
         assert_eq!(symbol.count(), 1);
     }
 
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_attribute_dealloc_to_allocating_symbol() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.alloc(0x1000, 100, 1);
+        let name = demangle::get_demangled_symbol(&["leaktracer"]);
+
+        free_elsewhere(&mut table, 0x1000);
+        let symbol = table.get(name).expect("Symbol should exist");
+        assert_eq!(symbol.allocated(), 0);
+        assert_eq!(symbol.count(), 0);
+
+        // unknown pointers are ignored
+        free_elsewhere(&mut table, 0x3000);
+        let symbol = table.get(name).expect("Symbol should exist");
+        assert_eq!(symbol.allocated(), 0);
+        assert_eq!(symbol.count(), 0);
+    }
+
+    #[cfg(stack_capture)]
+    fn free_elsewhere(table: &mut SymbolTable, ptr: usize) {
+        table.dealloc(ptr, 100, false);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_list_live_allocations() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.alloc(0x1000, 100, 1);
+        table.alloc(0x2000, 200, 1);
+        assert_eq!(
+            table.dealloc(0x1000, 100, false),
+            (
+                Dealloc::Tracked {
+                    size: 100,
+                    weight: 1
+                },
+                None
+            )
+        );
+
+        let live: Vec<_> = table.live_allocations().collect();
+        assert_eq!(live.len(), 1);
+        assert_eq!(live[0].0, 0x2000);
+        assert_eq!(live[0].1, 200);
+        assert_eq!(live[0].2, demangle::get_demangled_symbol(&["leaktracer"]));
+    }
+
+    #[test]
+    fn test_should_clear_symbol_table() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.alloc(0x1000, 100, 1);
+
+        table.clear();
+        assert_eq!(table.len(), 0);
+        assert_eq!(table.live_allocations().count(), 0);
+        // pointers allocated before the clear are untracked
+        assert_eq!(table.dealloc(0x1000, 100, true), (Dealloc::Untracked, None));
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_sum_symbols_allocated() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.insert("test_symbol");
+        table.alloc(0x1000, 100, 1);
+        table.alloc(0x2000, 200, 1);
+        table.alloc(0x3000, 0, 1);
+        assert_eq!(table.total_allocated(), 300);
+
+        table.dealloc(0x1000, 100, false);
+        assert_eq!(table.total_allocated(), 200);
+    }
+
+    #[test]
+    fn test_should_reserve_without_losing_symbols() {
+        let mut table = SymbolTable::new(2, &["leaktracer"]);
+        table.insert("test_symbol_1");
+        table.insert("test_symbol_2");
+
+        table.reserve(1000);
+        assert!(table.capacity() >= 1002);
+        assert!(table.get("test_symbol_1").is_some());
+        assert!(table.get("test_symbol_2").is_some());
+    }
+
+    #[test]
+    fn test_should_count_rehashes() {
+        let mut table = SymbolTable::new(2, &["app"]);
+        for i in 0..100 {
+            table.alloc_to(0x1000 + i, 16, 1, intern(&format!("app::symbol_{i}")));
+        }
+        let rehashes = table.rehash_count();
+        assert!(rehashes > 0);
+
+        // allocating from known symbols doesn't grow the table
+        table.alloc_to(0x2000, 16, 1, intern("app::symbol_0"));
+        assert_eq!(table.rehash_count(), rehashes);
+
+        table.clear();
+        assert_eq!(table.rehash_count(), 0);
+    }
+
+    #[test]
+    fn test_should_reserve_symbols_of_added_module() {
+        let mut table = SymbolTable::new(2, &["app"]);
+        table.add_module("plugin");
+        for i in 0..MODULE_SYMBOLS_RESERVE {
+            table.alloc_to(0x1000 + i, 16, 1, intern(&format!("plugin::symbol_{i}")));
+        }
+        assert_eq!(table.rehash_count(), 0);
+    }
+
     #[test]
     fn test_should_iter_symbol_table() {
         let mut table = SymbolTable::new(10, &["leaktracer"]);
@@ -145,4 +1515,650 @@ mod test {
                 .any(|(symbol, _)| **symbol == "test_symbol_2")
         );
     }
+
+    #[test]
+    fn test_should_iter_symbol_table_by_ref() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        assert!(table.is_empty());
+        table.insert("test_symbol_1");
+        table.insert("test_symbol_2");
+        assert!(!table.is_empty());
+
+        let mut names = Vec::new();
+        for (name, _) in &table {
+            names.push(*name);
+        }
+        names.sort_unstable();
+        assert_eq!(names, ["test_symbol_1", "test_symbol_2"]);
+        assert_eq!((&table).into_iter().count(), table.iter().count());
+    }
+
+    #[test]
+    fn test_should_fold_nested_allocations() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        {
+            let _fold = crate::fold_nested();
+            table.alloc_to(0x1000, 100, 1, "app::replace");
+            // e.g. allocated by the drop of the replaced value
+            table.alloc_to(0x2000, 20, 1, "app::replace");
+            table.alloc_to(0x3000, 50, 1, "app::other");
+        }
+        table.alloc_to(0x4000, 10, 1, "app::replace");
+
+        let replace = table.get("app::replace").unwrap();
+        assert_eq!(replace.allocated(), 130);
+        assert_eq!(replace.count(), 2);
+        assert_eq!(replace.total_count(), 2);
+        assert_eq!(table.get("app::other").unwrap().count(), 1);
+
+        // the folded allocation doesn't decrement the count when freed
+        table.dealloc(0x2000, 20, false);
+        let replace = table.get("app::replace").unwrap();
+        assert_eq!(replace.allocated(), 110);
+        assert_eq!(replace.count(), 2);
+        assert_eq!(table.accounting_underflow(0x1000), None);
+        table.dealloc(0x1000, 100, false);
+        table.dealloc(0x4000, 10, false);
+        assert_eq!(table.get("app::replace").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_should_track_symbol_peak() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 100, 1, "app::main");
+        table.alloc_to(0x2000, 50, 1, "app::main");
+        table.dealloc(0x1000, 100, false);
+        table.alloc_to(0x3000, 20, 1, "app::main");
+
+        let symbol = table.get("app::main").unwrap();
+        assert_eq!(symbol.allocated(), 70);
+        assert_eq!(symbol.peak(), 150);
+    }
+
+    #[test]
+    fn test_should_display_symbol() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 100, 1, "app::main");
+        table.alloc_to(0x2000, 28, 1, "app::main");
+
+        assert_eq!(
+            table.get("app::main").unwrap().to_string(),
+            "alloc=128 count=2"
+        );
+    }
+
+    #[test]
+    fn test_should_iter_symbol_table_sorted() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        for name in ["app::b", "app::c", "app::a", "app::a::b", "app"] {
+            table.insert(name);
+        }
+
+        let names = |table: &SymbolTable| -> Vec<&str> {
+            table
+                .iter_sorted()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+        assert_eq!(
+            names(&table),
+            ["app", "app::a", "app::a::b", "app::b", "app::c"]
+        );
+        assert_eq!(names(&table), names(&table));
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_sum_attributed_allocated() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 300, 1, "app::main");
+        table.alloc_to(0x2000, 100, 1, demangle::UNKNOWN);
+        table.alloc_unattributed(0x3000, 50, 1);
+
+        assert_eq!(table.total_allocated(), 400);
+        assert_eq!(table.attributed_allocated(), 300);
+    }
+
+    #[test]
+    fn test_should_detect_accounting_underflow() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 100, 1, "app::main");
+        table.alloc_to(0x2000, 100, 1, "app::main");
+        assert_eq!(table.accounting_underflow(0x1000), None);
+        assert_eq!(table.accounting_underflow(0x3000), None);
+
+        // a bug in the attribution lost some bytes of the symbol
+        table
+            .get("app::main")
+            .unwrap()
+            .allocated
+            .store(150, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(table.accounting_underflow(0x1000), None);
+        table.dealloc(0x1000, 100, false);
+
+        let violation = table.accounting_underflow(0x2000).unwrap();
+        assert_eq!(violation.ptr, 0x2000);
+        assert_eq!(violation.symbol, "app::main");
+        assert_eq!(violation.size, 100);
+        assert_eq!(violation.allocated, 50);
+        // the counters saturate at zero
+        table.dealloc(0x2000, 100, false);
+        let symbol = table.get("app::main").unwrap();
+        assert_eq!(symbol.allocated(), 0);
+        assert_eq!(symbol.count(), 0);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_report_strict_violations() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.alloc(0x1000, 100, 1);
+        let name = demangle::get_demangled_symbol(&["leaktracer"]);
+
+        assert_eq!(
+            table.dealloc(0x1000, 50, true),
+            (
+                Dealloc::Tracked {
+                    size: 100,
+                    weight: 1
+                },
+                Some(StrictViolation {
+                    kind: StrictViolationKind::SizeMismatch {
+                        allocated: 100,
+                        deallocated: 50
+                    },
+                    ptr: 0x1000,
+                    symbol: name,
+                })
+            )
+        );
+        // the recorded size is released anyway
+        assert_eq!(table.get(name).unwrap().allocated(), 0);
+
+        assert_eq!(
+            table.dealloc(0x1000, 100, true),
+            (
+                Dealloc::Untracked,
+                Some(StrictViolation {
+                    kind: StrictViolationKind::DoubleFree,
+                    ptr: 0x1000,
+                    symbol: name,
+                })
+            )
+        );
+
+        // reallocating the same address is not a double free
+        table.alloc(0x1000, 100, 1);
+        assert_eq!(
+            table.dealloc(0x1000, 100, true),
+            (
+                Dealloc::Tracked {
+                    size: 100,
+                    weight: 1
+                },
+                None
+            )
+        );
+        // untracked pointers are never checked
+        assert_eq!(table.dealloc(0x2000, 100, true), (Dealloc::Untracked, None));
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_scale_sampled_allocations() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.alloc(0x1000, 100, 4);
+        table.skip(0x2000, 100);
+        let name = demangle::get_demangled_symbol(&["leaktracer"]);
+
+        let symbol = table.get(name).expect("Symbol should exist");
+        assert_eq!(symbol.allocated(), 400);
+        assert_eq!(symbol.count(), 4);
+        assert_eq!(symbol.histogram().iter().sum::<usize>(), 1);
+
+        // skipped pointers are registered, so they are not untracked
+        assert_eq!(table.dealloc(0x2000, 100, true), (Dealloc::Skipped, None));
+        assert_eq!(
+            table.dealloc(0x1000, 100, true),
+            (
+                Dealloc::Tracked {
+                    size: 100,
+                    weight: 4
+                },
+                None
+            )
+        );
+        let symbol = table.get(name).expect("Symbol should exist");
+        assert_eq!(symbol.allocated(), 0);
+        assert_eq!(symbol.count(), 0);
+    }
+
+    #[test]
+    #[cfg(all(not(windows), stack_capture))]
+    fn test_should_attribute_cached_call_sites_like_full_scan() {
+        #[inline(never)]
+        fn allocate(table: &mut SymbolTable, ptr: usize, cached: bool) -> &'static str {
+            if cached {
+                table.alloc_cached(ptr, 100, 1)
+            } else {
+                table.alloc(ptr, 100, 1)
+            }
+        }
+
+        let mut slow = SymbolTable::new(10, &["leaktracer"]);
+        let mut cached = SymbolTable::new(10, &["leaktracer"]);
+        let mut name = "";
+        for ptr in 0x1000..0x1010 {
+            name = allocate(&mut slow, ptr, false);
+            assert_eq!(allocate(&mut cached, ptr, true), name);
+        }
+        assert_eq!(slow.len(), cached.len());
+        for (name, symbol) in slow.iter() {
+            let cached = cached.get(name).expect("Symbol should exist");
+            assert_eq!(cached.allocated(), symbol.allocated());
+            assert_eq!(cached.count(), symbol.count());
+        }
+        assert_eq!(cached.total_allocated(), 1600);
+
+        // the deallocations are attributed as usual
+        for ptr in 0x1000..0x1010 {
+            assert_eq!(
+                cached.dealloc(ptr, 100, false).0,
+                Dealloc::Tracked {
+                    size: 100,
+                    weight: 1
+                }
+            );
+        }
+        assert_eq!(cached.total_allocated(), 0);
+
+        // a change of the modules is seen right away
+        cached.set_modules(&["leaktracer_test_other"]);
+        assert_ne!(allocate(&mut cached, 0x2000, true), name);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_attribute_startup_allocations() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        assert_eq!(table.alloc_startup(0x1000, 100, 1), STARTUP);
+        table.alloc(0x2000, 50, 1);
+
+        assert_eq!(table.get(STARTUP).unwrap().allocated(), 100);
+        table.dealloc(0x1000, 100, false);
+        assert_eq!(table.get(STARTUP).unwrap().allocated(), 0);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_average_allocation_size() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.set_ema_alpha(0.5);
+        for ptr in 0..32 {
+            table.alloc(0x1000 + ptr, 256, 1);
+        }
+
+        let name = demangle::get_demangled_symbol(&["leaktracer"]);
+        let symbol = table.get(name).expect("Symbol should exist");
+        assert_eq!(symbol.ema_size(), 256.0);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_trace_added_module() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        // `<unknown>`, or the FFI label if set
+        let unknown = table.alloc(0x1000, 100, 1);
+        assert!(!unknown.starts_with("leaktracer"));
+
+        table.add_module("leaktracer");
+        table.add_module("leaktracer");
+        assert_eq!(&*table.modules(), &["app", "leaktracer"]);
+        table.alloc(0x2000, 50, 1);
+
+        let name = demangle::get_demangled_symbol(&["leaktracer"]);
+        assert_eq!(table.get(name).unwrap().allocated(), 50);
+        // the bytes recorded before are not reattributed
+        assert_eq!(table.get(unknown).unwrap().allocated(), 100);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_trace_reconfigured_modules() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        let unknown = table.alloc(0x1000, 100, 1);
+
+        table.set_modules(&["other", "leaktracer"]);
+        assert_eq!(&*table.modules(), &["other", "leaktracer"]);
+        table.alloc(0x2000, 50, 1);
+
+        let name = demangle::get_demangled_symbol(&["leaktracer"]);
+        assert_ne!(name, unknown);
+        assert_eq!(table.get(name).unwrap().allocated(), 50);
+        assert_eq!(table.get(unknown).unwrap().allocated(), 100);
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_record_custom_histogram_buckets() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.set_histogram_buckets(&[128, 1024, 8192]);
+        for (ptr, size) in [
+            (0x1000, 64),
+            (0x2000, 512),
+            (0x3000, 1000),
+            (0x4000, 10_000),
+        ] {
+            table.alloc(ptr, size, 1);
+        }
+
+        let name = demangle::get_demangled_symbol(&["leaktracer"]);
+        let symbol = table.get(name).expect("Symbol should exist");
+        assert_eq!(symbol.histogram(), vec![1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_should_compute_churn_metrics() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 100, 1, "app::main");
+        table.alloc_to(0x2000, 300, 1, "app::main");
+        table.alloc_to(0x3000, 50, 4, "app::main");
+        table.dealloc(0x1000, 100, false);
+        table.dealloc(0x3000, 50, false);
+
+        let symbol = table.get("app::main").unwrap();
+        assert_eq!(symbol.allocated(), 300);
+        assert_eq!(symbol.total_allocated(), 600);
+        assert_eq!(symbol.total_count(), 6);
+        assert_eq!(symbol.avg_allocation_size(), 100.0);
+        assert_eq!(symbol.turnover(), 300);
+
+        assert_eq!(avg_allocation_size(0, 0), 0.0);
+        assert_eq!(Symbol::new(&[]).avg_allocation_size(), 0.0);
+    }
+
+    #[test]
+    fn test_should_count_distinct_sizes_per_symbol() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        for i in 0..100 {
+            table.alloc_to(0x1000 + i * 0x100, 48, 1, "app::node::new");
+        }
+        for i in 0..=SIZE_CARDINALITY_CUTOFF {
+            table.alloc_to(0x100000 + i * 0x100, 64 << i, 1, "app::buffer::grow");
+        }
+
+        assert_eq!(
+            table.get("app::node::new").unwrap().size_cardinality(),
+            SizeCardinality::Exact(1)
+        );
+        assert_eq!(
+            table.get("app::buffer::grow").unwrap().size_cardinality(),
+            SizeCardinality::Many
+        );
+    }
+
+    #[test]
+    fn test_should_register_unattributed_allocation() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        assert_eq!(table.alloc_unattributed(0x1000, 64, 2), UNATTRIBUTED);
+        assert_eq!(table.len(), 0);
+        assert_eq!(
+            table.live_allocations().collect::<Vec<_>>(),
+            vec![(0x1000, 64, UNATTRIBUTED)]
+        );
+
+        table.alloc_to(0x2000, 16, 1, "app::main");
+        assert_eq!(table.unattributed(), (128, 2));
+
+        // released with the weight it was recorded with
+        assert_eq!(
+            table.dealloc(0x1000, 64, false),
+            (
+                Dealloc::Tracked {
+                    size: 64,
+                    weight: 2
+                },
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_should_record_first_seen_seq() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 16, 1, "app::first");
+        // another traced allocation happened meanwhile
+        crate::stats::record_alloc(0, 1);
+        table.alloc_to(0x2000, 16, 1, "app::second");
+        table.alloc_to(0x3000, 16, 1, "app::first");
+
+        let first = table.get("app::first").unwrap().first_seen_seq();
+        let second = table.get("app::second").unwrap().first_seen_seq();
+        assert!(first < second);
+        assert!(second <= crate::stats::total_allocations());
+    }
+
+    #[test]
+    fn test_should_count_realloc_moves() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 100, 1, "app::grow");
+        table.record_realloc(0x1000, false, 50);
+        table.alloc_to(0x2000, 200, 2, "app::grow");
+        table.record_realloc(0x2000, true, 100);
+        table.alloc_to(0x3000, 64, 1, "app::grow");
+        table.record_realloc(0x3000, false, 128);
+        // untracked pointers are ignored
+        table.record_realloc(0x4000, true, 16);
+
+        let symbol = table.get("app::grow").unwrap();
+        assert_eq!(symbol.realloc_inplace(), 2);
+        // scaled by the sampling weight
+        assert_eq!(symbol.realloc_moves(), 2);
+        assert_eq!(symbol.reallocs(), 4);
+        assert_eq!(symbol.bytes_grown(), 50 + 100 * 2);
+        assert_eq!(symbol.bytes_shrunk(), 64);
+    }
+
+    #[test]
+    fn test_should_count_recent_allocations_of_symbol() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.alloc_to(0x1000, 100, 1, "app::main");
+        table.alloc_to(0x2000, 50, 2, "app::main");
+        table.dealloc(0x1000, 100, false);
+
+        // freed allocations are still recent
+        let symbol = table.get("app::main").unwrap();
+        assert_eq!(symbol.recent(RECENT_WINDOW), (200, 3));
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_account_inclusive_bytes() {
+        fn parent(table: &mut SymbolTable) {
+            child(table);
+            table.alloc(0x1000, 64, 1);
+        }
+
+        fn child(table: &mut SymbolTable) {
+            recursive(table, 3);
+        }
+
+        fn recursive(table: &mut SymbolTable, depth: usize) {
+            if depth == 0 {
+                table.alloc(0x2000, 100, 2);
+            } else {
+                recursive(table, depth - 1);
+            }
+        }
+
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        table.set_inclusive(true);
+        parent(&mut table);
+
+        let symbol = |table: &SymbolTable, suffix: &str| {
+            table
+                .iter()
+                .find(|(name, _)| name.ends_with(suffix))
+                .map(|(_, symbol)| (symbol.allocated(), symbol.inclusive_allocated()))
+                .unwrap_or_else(|| panic!("no symbol ending with {suffix}"))
+        };
+        let test = symbol(&table, "test_should_account_inclusive_bytes");
+        let parent = symbol(&table, "::parent");
+        let child = symbol(&table, "::child");
+        let recursive = symbol(&table, "::recursive");
+
+        // counted once, even if the function appears 4 times on the stack
+        assert_eq!(recursive, (200, 200));
+        assert_eq!(child, (0, 200));
+        assert_eq!(parent, (64, 264));
+        assert_eq!(test, (0, 264));
+        assert!(test.1 >= parent.1 && parent.1 >= child.1 && child.1 >= recursive.1);
+
+        table.dealloc(0x2000, 100, false);
+        assert_eq!(symbol(&table, "::recursive"), (0, 0));
+        assert_eq!(symbol(&table, "::parent"), (64, 64));
+        table.dealloc(0x1000, 64, false);
+        assert_eq!(
+            symbol(&table, "test_should_account_inclusive_bytes"),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn test_should_keep_live_module_gauges() {
+        let mut table = SymbolTable::new(10, &["app", "app::net", "lib"]);
+        table.alloc_to(0x1000, 100, 1, "app::main");
+        table.alloc_to(0x2000, 50, 2, "app::net::connect");
+        table.alloc_to(0x3000, 30, 1, "lib::parse");
+        table.alloc_to(0x4000, 20, 1, "lib::parse");
+        table.alloc_to(0x5000, 10, 1, "std::rt::lang_start");
+        table.alloc_to(0x6000, 10, 1, "application::main");
+
+        let stats = |table: &SymbolTable| {
+            let mut stats = table.module_stats();
+            stats.sort();
+            stats
+        };
+        assert_eq!(
+            stats(&table),
+            vec![("app", 100, 1), ("app::net", 100, 2), ("lib", 50, 2)]
+        );
+
+        table.dealloc(0x2000, 50, false);
+        table.dealloc(0x3000, 30, false);
+        table.dealloc(0x5000, 10, false);
+        assert_eq!(
+            stats(&table),
+            vec![("app", 100, 1), ("app::net", 0, 0), ("lib", 20, 1)]
+        );
+
+        table.clear();
+        assert!(table.module_stats().is_empty());
+    }
+
+    #[test]
+    #[cfg(not(stack_capture))]
+    fn test_should_register_allocations_without_symbols() {
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        assert_eq!(table.alloc(0x1000, 100, 4), UNRESOLVED);
+
+        // the pointers are registered, but no symbol is ever created
+        assert_eq!(table.len(), 0);
+        assert_eq!(table.total_allocated(), 0);
+        assert_eq!(
+            table.live_allocations().collect::<Vec<_>>(),
+            vec![(0x1000, 100, UNRESOLVED)]
+        );
+        assert_eq!(
+            table.dealloc(0x1000, 100, true),
+            (
+                Dealloc::Tracked {
+                    size: 100,
+                    weight: 4
+                },
+                None
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(stack_capture)]
+    fn test_should_not_refire_threshold_until_rearmed() {
+        use std::sync::Mutex;
+
+        static FIRED: Mutex<Vec<(&'static str, usize)>> = Mutex::new(Vec::new());
+        // high enough not to be exceeded by the other tests
+        const LIMIT: usize = 1 << 50;
+
+        crate::on_threshold_exceeded(LIMIT, LIMIT / 2, |name, allocated| {
+            FIRED.lock().unwrap().push((name, allocated));
+        });
+        let mut table = SymbolTable::new(10, &["leaktracer"]);
+        let fired = |name: &'static str| {
+            FIRED
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(fired, _)| *fired == name)
+                .count()
+        };
+
+        // crossing up
+        let name = table.alloc(0x1000, LIMIT, 1);
+        assert_eq!(fired(name), 0);
+        table.alloc(0x2000, 1, 1);
+        assert_eq!(fired(name), 1);
+        // staying high
+        table.alloc(0x3000, 1, 1);
+        table.dealloc(0x3000, 1, false);
+        table.alloc(0x3000, 1, 1);
+        assert_eq!(fired(name), 1);
+        // dropping below the limit, but not below the rearm threshold
+        table.dealloc(0x2000, 1, false);
+        table.dealloc(0x3000, 1, false);
+        table.alloc(0x2000, 1, 1);
+        assert_eq!(fired(name), 1);
+        // dropping below the rearm threshold, then crossing again
+        table.dealloc(0x1000, LIMIT, false);
+        table.alloc(0x1000, LIMIT, 1);
+        assert_eq!(fired(name), 2);
+        assert!(FIRED.lock().unwrap().contains(&(name, LIMIT + 1)));
+    }
+
+    #[test]
+    fn test_should_rename_symbols() {
+        let mut table = SymbolTable::new(10, &["app"]);
+        table.insert("app::main");
+        table.insert("<pending:1>");
+        table.insert("<pending:2>");
+        for (ptr, name, size) in [
+            (0x1000, "app::main", 100),
+            (0x2000, "<pending:1>", 50),
+            (0x3000, "<pending:2>", 25),
+        ] {
+            let symbol = table.symbols.get(name).unwrap();
+            symbol
+                .allocated
+                .fetch_add(size, std::sync::atomic::Ordering::Relaxed);
+            symbol
+                .count
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            table.register(ptr, size, 1, name, None);
+        }
+
+        table.rename(&HashMap::from([
+            ("<pending:1>", "app::main"),
+            ("<pending:2>", "app::cache::insert"),
+        ]));
+
+        assert!(table.get("<pending:1>").is_none());
+        assert!(table.get("<pending:2>").is_none());
+        assert_eq!(table.get("app::main").unwrap().allocated(), 150);
+        assert_eq!(table.get("app::main").unwrap().count(), 2);
+        assert_eq!(table.get("app::cache::insert").unwrap().allocated(), 25);
+        // the live allocations are reattributed, so their deallocations release the renamed symbols
+        table.dealloc(0x2000, 50, false);
+        assert_eq!(table.get("app::main").unwrap().allocated(), 100);
+    }
 }
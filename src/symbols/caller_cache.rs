@@ -0,0 +1,121 @@
+//! Per-thread cache of the symbols of the recent call sites (see [`crate::LeaktracerAllocator::with_caller_cache`]).
+//!
+//! Resolving a call stack to a symbol is by far the most expensive part of the allocation path, while walking it
+//! without resolving the frames is cheap. So the call site is identified by the return addresses of the innermost
+//! [`DEPTH`] frames, and each thread remembers the symbols of the last call sites it resolved.
+//!
+//! The cache is direct-mapped: each call site has a single slot, which is overwritten by the next call site mapped
+//! to it. The slots are plain [`Cell`]s, so the cache never allocates and has no destructor.
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of slots of the cache of each thread.
+const SLOTS: usize = 256;
+
+/// Number of frames identifying a call site, including the allocator frames.
+const DEPTH: usize = 32;
+
+/// Incremented whenever the cached symbols may resolve differently, e.g. when the traced modules change,
+/// so the slots filled before are ignored.
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// A call site with its resolved symbol.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// The hash of the call site return addresses.
+    key: u64,
+    /// The [`EPOCH`] the symbol was resolved in, `u64::MAX` for an empty slot.
+    epoch: u64,
+    symbol: &'static str,
+}
+
+const EMPTY: Slot = Slot {
+    key: 0,
+    epoch: u64::MAX,
+    symbol: "",
+};
+
+thread_local! {
+    static CACHE: [Cell<Slot>; SLOTS] = const { [const { Cell::new(EMPTY) }; SLOTS] };
+}
+
+/// Invalidates the symbols cached by all the threads.
+pub(crate) fn invalidate() {
+    EPOCH.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the symbol of the current call site from the cache of the current thread, or resolves it with `resolve`
+/// and caches it.
+pub(crate) fn cached_symbol(resolve: impl FnOnce() -> &'static str) -> &'static str {
+    let key = call_site_key();
+    let epoch = EPOCH.load(Ordering::Relaxed);
+    let index = key as usize % SLOTS;
+
+    let cached = CACHE
+        .try_with(|cache| cache[index].get())
+        .ok()
+        .filter(|slot| slot.key == key && slot.epoch == epoch);
+    if let Some(slot) = cached {
+        return slot.symbol;
+    }
+
+    let symbol = resolve();
+    let _ = CACHE.try_with(|cache| cache[index].set(Slot { key, epoch, symbol }));
+    symbol
+}
+
+/// Hashes the return addresses of the innermost [`DEPTH`] frames of the current call stack, without resolving them.
+fn call_site_key() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut depth = 0;
+    backtrace::trace(|frame| {
+        hasher.write_usize(frame.ip() as usize);
+        depth += 1;
+        depth < DEPTH
+    });
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[inline(never)]
+    fn lookup(resolved: &Cell<usize>, symbol: &'static str) -> &'static str {
+        cached_symbol(|| {
+            resolved.set(resolved.get() + 1);
+            symbol
+        })
+    }
+
+    #[test]
+    fn test_should_resolve_call_site_once() {
+        let resolved = Cell::new(0);
+        for _ in 0..10 {
+            assert_eq!(lookup(&resolved, "app::first"), "app::first");
+        }
+        // the other tests may invalidate the cache meanwhile, at most once
+        assert!(resolved.get() <= 2);
+
+        // another call site
+        let before = resolved.get();
+        assert_eq!(lookup(&resolved, "app::second"), "app::second");
+        assert_eq!(resolved.get(), before + 1);
+    }
+
+    #[test]
+    fn test_should_resolve_again_after_invalidation() {
+        let resolved = Cell::new(0);
+        assert_eq!(lookup(&resolved, "app::main"), "app::main");
+        assert_eq!(resolved.get(), 1);
+
+        invalidate();
+        assert_eq!(lookup(&resolved, "app::main"), "app::main");
+        assert_eq!(resolved.get(), 2);
+    }
+}
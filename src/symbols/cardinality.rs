@@ -0,0 +1,181 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Number of distinct allocation sizes counted exactly by each symbol, beyond which they are just "many".
+pub const SIZE_CARDINALITY_CUTOFF: usize = 16;
+
+/// Slot of a [`SizeSet`] which holds no size; no layout can be that big.
+const EMPTY: usize = usize::MAX;
+
+/// The number of distinct allocation sizes of a symbol (see [`super::Symbol::size_cardinality`]).
+///
+/// A symbol making lots of allocations of a single size is usually allocating the nodes of a collection
+/// or the same object over and over, a candidate for a pool, while one with many sizes is usually building buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SizeCardinality {
+    /// Exactly this many distinct sizes, up to [`SIZE_CARDINALITY_CUTOFF`].
+    Exact(usize),
+    /// More than [`SIZE_CARDINALITY_CUTOFF`] distinct sizes.
+    Many,
+}
+
+impl Default for SizeCardinality {
+    fn default() -> Self {
+        Self::Exact(0)
+    }
+}
+
+impl fmt::Display for SizeCardinality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(count) => write!(f, "{count}"),
+            Self::Many => write!(f, ">{SIZE_CARDINALITY_CUTOFF}"),
+        }
+    }
+}
+
+impl SizeCardinality {
+    /// Returns the cardinality as a number, one more than the cutoff for [`SizeCardinality::Many`].
+    pub(crate) fn to_raw(self) -> usize {
+        match self {
+            Self::Exact(count) => count,
+            Self::Many => SIZE_CARDINALITY_CUTOFF + 1,
+        }
+    }
+
+    /// Returns the cardinality from its number (see [`SizeCardinality::to_raw`]).
+    pub(crate) fn from_raw(raw: usize) -> Self {
+        if raw > SIZE_CARDINALITY_CUTOFF {
+            Self::Many
+        } else {
+            Self::Exact(raw)
+        }
+    }
+}
+
+/// The distinct allocation sizes of a [`super::Symbol`], up to [`SIZE_CARDINALITY_CUTOFF`].
+///
+/// The sizes fill the slots in order, so the most common case, a symbol always allocating the same size,
+/// costs a single comparison on the allocation path, as does a saturated set.
+#[derive(Debug)]
+pub(crate) struct SizeSet {
+    sizes: [AtomicUsize; SIZE_CARDINALITY_CUTOFF],
+    saturated: AtomicBool,
+}
+
+impl SizeSet {
+    /// Creates a new empty [`SizeSet`].
+    pub(crate) fn new() -> Self {
+        Self {
+            sizes: [const { AtomicUsize::new(EMPTY) }; SIZE_CARDINALITY_CUTOFF],
+            saturated: AtomicBool::new(false),
+        }
+    }
+
+    /// Records an allocation of `size` bytes.
+    ///
+    /// Must be called with the symbol table locked, since filling a slot is not atomic.
+    pub(crate) fn record(&self, size: usize) {
+        if self.saturated.load(Ordering::Relaxed) {
+            return;
+        }
+        for slot in &self.sizes {
+            match slot.load(Ordering::Relaxed) {
+                EMPTY => {
+                    slot.store(size, Ordering::Relaxed);
+                    return;
+                }
+                current if current == size => return,
+                _ => {}
+            }
+        }
+        self.saturated.store(true, Ordering::Relaxed);
+    }
+
+    /// Adds the sizes of `other` to this set.
+    #[cfg_attr(not(all(windows, stack_capture)), allow(dead_code))]
+    pub(crate) fn merge(&self, other: &SizeSet) {
+        if other.saturated.load(Ordering::Relaxed) {
+            self.saturated.store(true, Ordering::Relaxed);
+            return;
+        }
+        for slot in &other.sizes {
+            match slot.load(Ordering::Relaxed) {
+                EMPTY => return,
+                size => self.record(size),
+            }
+        }
+    }
+
+    /// Returns the number of distinct sizes recorded.
+    pub(crate) fn cardinality(&self) -> SizeCardinality {
+        if self.saturated.load(Ordering::Relaxed) {
+            return SizeCardinality::Many;
+        }
+
+        SizeCardinality::Exact(
+            self.sizes
+                .iter()
+                .take_while(|slot| slot.load(Ordering::Relaxed) != EMPTY)
+                .count(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_count_distinct_sizes_exactly() {
+        let set = SizeSet::new();
+        assert_eq!(set.cardinality(), SizeCardinality::Exact(0));
+
+        for _ in 0..1000 {
+            set.record(64);
+        }
+        assert_eq!(set.cardinality(), SizeCardinality::Exact(1));
+        for size in 1..=SIZE_CARDINALITY_CUTOFF {
+            set.record(size * 8);
+            set.record(size * 8);
+        }
+        assert_eq!(
+            set.cardinality(),
+            SizeCardinality::Exact(SIZE_CARDINALITY_CUTOFF)
+        );
+        assert_eq!(set.cardinality().to_string(), "16");
+    }
+
+    #[test]
+    fn test_should_saturate_above_cutoff() {
+        let set = SizeSet::new();
+        for size in 1..=SIZE_CARDINALITY_CUTOFF + 1 {
+            set.record(size);
+        }
+        assert_eq!(set.cardinality(), SizeCardinality::Many);
+        // a known size doesn't bring it back
+        set.record(1);
+        assert_eq!(set.cardinality(), SizeCardinality::Many);
+        assert_eq!(set.cardinality().to_string(), ">16");
+    }
+
+    #[test]
+    fn test_should_merge_size_sets() {
+        let set = SizeSet::new();
+        let other = SizeSet::new();
+        set.record(8);
+        set.record(16);
+        other.record(16);
+        other.record(32);
+
+        set.merge(&other);
+        assert_eq!(set.cardinality(), SizeCardinality::Exact(3));
+
+        for size in 0..SIZE_CARDINALITY_CUTOFF * 2 {
+            other.record(size);
+        }
+        set.merge(&other);
+        assert_eq!(set.cardinality(), SizeCardinality::Many);
+    }
+}
@@ -0,0 +1,288 @@
+//! Deferred resolution of the call stacks of the traced allocations, used on Windows.
+//!
+//! On Windows, the symbols are resolved through dbghelp, which is serialized by a process-wide lock:
+//! resolving a call stack inside the allocator, with the symbol table locked, deadlocks with any other thread
+//! allocating while it holds the dbghelp lock (e.g. while printing a panic backtrace).
+//!
+//! So the allocation path only walks the stack, which doesn't go through dbghelp on 64-bit Windows,
+//! and attributes the allocation to a `<pending:...>` placeholder symbol keyed by the hash of the call stack.
+//! A single helper thread resolves the call stacks outside of the symbol table lock, then renames the placeholders
+//! to the resolved symbols. Once a call stack is resolved, the following allocations with the same call stack
+//! are attributed to the resolved symbol right away.
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::c_void;
+use std::hash::Hasher;
+use std::sync::{Arc, Condvar, Mutex, Once};
+
+use backtrace::{Backtrace, BacktraceFrame};
+
+use super::demangle::{self, UNKNOWN};
+use super::intern::intern;
+use crate::LeaktracerAllocator;
+
+/// The placeholders and resolved symbols of the call stacks, by call stack hash.
+///
+/// Taken with the symbol table locked, so it must never be held while acquiring the symbol table lock.
+static CALL_STACKS: Mutex<Option<CallStacks>> = Mutex::new(None);
+
+/// The call stacks waiting to be resolved by the helper thread.
+///
+/// Never held while acquiring another lock.
+static QUEUE: Mutex<Vec<Pending>> = Mutex::new(Vec::new());
+
+/// Notified when a call stack is pushed to the [`QUEUE`].
+static QUEUE_READY: Condvar = Condvar::new();
+
+/// Spawns the helper thread on the first deferred call stack.
+static RESOLVER: Once = Once::new();
+
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn GetModuleFileNameW(module: *mut c_void, filename: *mut u16, size: u32) -> u32;
+}
+
+/// The symbols of the call stacks already seen.
+#[derive(Debug, Default)]
+struct CallStacks {
+    /// The resolved symbols.
+    resolved: HashMap<u64, &'static str>,
+    /// The placeholder symbols of the call stacks waiting to be resolved.
+    pending: HashMap<u64, &'static str>,
+}
+
+/// A call stack waiting to be resolved.
+struct Pending {
+    /// The call stack hash.
+    key: u64,
+    /// The placeholder symbol the allocations are attributed to until the call stack is resolved.
+    placeholder: &'static str,
+    /// The unresolved frames.
+    frames: Vec<BacktraceFrame>,
+    /// The modules being traced when the call stack was captured.
+    modules: Arc<[&'static str]>,
+}
+
+/// Returns the symbol the current allocation is attributed to, without resolving the call stack.
+///
+/// Returns the resolved symbol if the same call stack has already been resolved, the placeholder symbol otherwise,
+/// queueing the call stack to the helper thread if it's new.
+///
+/// Must be called with the symbol table locked and tracing suppressed.
+pub(crate) fn deferred_symbol(modules: &Arc<[&'static str]>) -> &'static str {
+    let key = call_stack_hash();
+    let placeholder = {
+        let Ok(mut call_stacks) = CALL_STACKS.lock() else {
+            return UNKNOWN;
+        };
+        let call_stacks = call_stacks.get_or_insert_with(CallStacks::default);
+        if let Some(symbol) = call_stacks.resolved.get(&key) {
+            return symbol;
+        }
+        if let Some(placeholder) = call_stacks.pending.get(&key) {
+            return placeholder;
+        }
+
+        let placeholder = intern(&format!("<pending:{key:016x}>"));
+        call_stacks.pending.insert(key, placeholder);
+        placeholder
+    };
+
+    let pending = Pending {
+        key,
+        placeholder,
+        frames: capture(),
+        modules: modules.clone(),
+    };
+    if let Ok(mut queue) = QUEUE.lock() {
+        queue.push(pending);
+        QUEUE_READY.notify_one();
+    }
+    RESOLVER.call_once(spawn_resolver);
+
+    placeholder
+}
+
+/// Blocks until all the call stacks captured so far are resolved, or `timeout` elapses.
+///
+/// Returns whether all the call stacks are resolved.
+#[cfg(test)]
+pub(crate) fn wait_resolved(timeout: std::time::Duration) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < timeout {
+        let idle = CALL_STACKS
+            .lock()
+            .ok()
+            .is_some_and(|call_stacks| call_stacks.as_ref().is_none_or(|c| c.pending.is_empty()));
+        if idle {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    false
+}
+
+/// Hashes the instruction pointers of the current call stack, without allocating.
+fn call_stack_hash() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    // SAFETY: on 64-bit Windows the stack is walked with `RtlVirtualUnwind`, which doesn't go through dbghelp,
+    // so it's safe to walk it without the process-wide backtrace lock.
+    unsafe {
+        backtrace::trace_unsynchronized(|frame| {
+            hasher.write_usize(frame.ip() as usize);
+            true
+        });
+    }
+
+    hasher.finish()
+}
+
+/// Captures the unresolved frames of the current call stack.
+fn capture() -> Vec<BacktraceFrame> {
+    let mut frames = Vec::new();
+    // SAFETY: see `call_stack_hash`
+    unsafe {
+        backtrace::trace_unsynchronized(|frame| {
+            frames.push(BacktraceFrame::from(frame.clone()));
+            true
+        });
+    }
+
+    frames
+}
+
+/// Spawns the helper thread resolving the queued call stacks.
+fn spawn_resolver() {
+    let _ = std::thread::Builder::new()
+        .name("leaktracer-resolver".to_string())
+        .spawn(|| crate::alloc::bookkeeping(resolve_queued));
+}
+
+/// Resolves the queued call stacks in batches, renaming their placeholders in the symbol table.
+///
+/// The call stacks are resolved without holding any lock, so the dbghelp lock is never held
+/// while waiting for the symbol table lock.
+fn resolve_queued() {
+    loop {
+        let batch = {
+            let Ok(mut queue) = QUEUE.lock() else {
+                return;
+            };
+            while queue.is_empty() {
+                queue = match QUEUE_READY.wait(queue) {
+                    Ok(queue) => queue,
+                    Err(_) => return,
+                };
+            }
+            std::mem::take(&mut *queue)
+        };
+
+        let resolved: Vec<_> = batch
+            .into_iter()
+            .map(|pending| {
+                let mut backtrace = Backtrace::from(pending.frames);
+                backtrace.resolve();
+                let symbol = demangle::caller_name(&backtrace, &pending.modules);
+                (pending.key, pending.placeholder, symbol)
+            })
+            .collect();
+
+        let _ = crate::alloc::with_symbol_table_mut(|table| {
+            let Ok(mut call_stacks) = CALL_STACKS.lock() else {
+                return;
+            };
+            let call_stacks = call_stacks.get_or_insert_with(CallStacks::default);
+            let mut renames = HashMap::with_capacity(resolved.len());
+            for (key, placeholder, symbol) in resolved {
+                call_stacks.pending.remove(&key);
+                call_stacks.resolved.insert(key, symbol);
+                renames.insert(placeholder, symbol);
+            }
+            table.rename(&renames);
+        });
+    }
+}
+
+/// Names the caller after its module and offset, e.g. `myapp.exe+0x1234`, for binaries without debug info.
+///
+/// The caller is the frame following the allocator on the call stack, which is the allocation call site.
+/// Returns [`None`] if the allocator frame or the caller module can't be found.
+pub(super) fn module_offset_name(frames: &[BacktraceFrame]) -> Option<&'static str> {
+    let allocator = <LeaktracerAllocator as GlobalAlloc>::alloc
+        as unsafe fn(&LeaktracerAllocator, Layout) -> *mut u8;
+    let caller = frames
+        .iter()
+        .skip_while(|frame| frame.symbol_address() as usize != allocator as usize)
+        .nth(1)?;
+    let base = caller.module_base_address()? as usize;
+    let module = module_path(base)?;
+
+    Some(intern(&module_offset(
+        &module,
+        (caller.ip() as usize).wrapping_sub(base),
+    )))
+}
+
+/// Returns the path of the module loaded at `base`.
+fn module_path(base: usize) -> Option<String> {
+    let mut path = [0u16; 1024];
+    // SAFETY: `path` is valid for writes of `path.len()` UTF-16 units
+    let len =
+        unsafe { GetModuleFileNameW(base as *mut c_void, path.as_mut_ptr(), path.len() as u32) };
+    if len == 0 {
+        return None;
+    }
+
+    Some(String::from_utf16_lossy(
+        &path[..(len as usize).min(path.len())],
+    ))
+}
+
+/// Formats the file name of the module at `path` and the `offset` as `module+0xoffset`.
+fn module_offset(path: &str, offset: usize) -> String {
+    let module = path.rsplit(['\\', '/']).next().unwrap_or(path);
+    format!("{module}+{offset:#x}")
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_should_format_module_offset() {
+        assert_eq!(
+            module_offset(r"C:\app\bin\myapp.exe", 0x1234),
+            "myapp.exe+0x1234"
+        );
+        assert_eq!(module_offset("myapp.dll", 0), "myapp.dll+0x0");
+    }
+
+    #[test]
+    fn test_should_resolve_deferred_symbol() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let modules: Arc<[&'static str]> = Arc::from(["leaktracer"]);
+        let placeholder = crate::alloc::bookkeeping(|| deferred_symbol(&modules));
+        assert!(placeholder.starts_with("<pending:"));
+        assert!(wait_resolved(Duration::from_secs(10)));
+
+        let resolved = CALL_STACKS
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|call_stacks| {
+                call_stacks
+                    .resolved
+                    .values()
+                    .copied()
+                    .find(|symbol| symbol.ends_with("test_should_resolve_deferred_symbol"))
+            });
+        assert!(resolved.is_some());
+    }
+}
@@ -1,10 +1,38 @@
-use backtrace::BacktraceSymbol;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 
-const UNKNOWN: &str = "<unknown>";
+use backtrace::{Backtrace, BacktraceFrame, BacktraceSymbol, SymbolName};
+
+use super::intern::intern;
+use crate::atomic_ref::AtomicRef;
+
+pub(super) const UNKNOWN: &str = "<unknown>";
+
+/// Label of the allocations made by FFI code (see [`set_ffi_label`]).
+static FFI_LABEL: AtomicRef<str> = AtomicRef::new();
+
+/// Whether the symbols are keyed by their raw mangled name (see [`set_keep_mangled`]).
+static KEEP_MANGLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the generic parameters are stripped from the symbol names (see [`set_collapse_generics`]).
+static COLLAPSE_GENERICS: AtomicBool = AtomicBool::new(false);
+
+/// The function the symbol names are rewritten with (see [`set_name_rewriter`]), null for none.
+static NAME_REWRITER: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
+
+/// The modules the callers are picked by, the highest priority first (see [`set_module_priority`]).
+static MODULE_PRIORITY: AtomicRef<[&'static str]> = AtomicRef::new();
+
+/// The frames the attribution is shifted by toward the callers (see [`set_attribution_offset`]).
+static ATTRIBUTION_OFFSET: AtomicUsize = AtomicUsize::new(0);
 
 /// A list of symbols to ignore when getting the demangled symbol name.
 const IGNORE_LIST: &[&str] = &[
     "leaktracer::symbols::demangle::get_demangled_symbol",
+    "leaktracer::symbols::demangle::get_caller_frames",
+    "leaktracer::symbols::demangle::caller_symbol",
+    "leaktracer::alloc::untraced",
+    "leaktracer::alloc::bookkeeping",
     "leaktracer::symbols::SymbolTable::alloc",
     "leaktracer::symbols::SymbolTable::dealloc",
     "leaktracer::alloc::LeaktracerAllocator::trace_allocation",
@@ -12,64 +40,416 @@ const IGNORE_LIST: &[&str] = &[
     "leaktracer::alloc::LeaktracerAllocator::trace",
     "leaktracer::alloc::LeaktracerAllocator::alloc",
     "leaktracer::alloc::LeaktracerAllocator::dealloc",
+    "leaktracer::symbols::deferred::",
+    "leaktracer::symbols::caller_cache::",
 ];
 
+/// Returns the name of the first function on the current call stack belonging to one of the given `modules`.
+///
+/// This is the same resolution the allocator uses to attribute allocations, with the same module filtering,
+/// so it can be used e.g. to tag log events with the current user-code frame.
+/// If `modules` is empty, the modules the symbol table was initialized with are used.
+/// Returns `<unknown>` if no frame matches.
+///
+/// This is **expensive**: it captures and resolves a full backtrace on each call.
+/// The allocations made by the capture itself are not traced.
+///
+/// ```rust
+/// fn load_config() -> &'static str {
+///     let krate = module_path!().split("::").next().unwrap();
+///     leaktracer::caller_symbol(&[krate])
+/// }
+///
+/// assert!(load_config().ends_with("load_config"));
+/// ```
+pub fn caller_symbol(modules: &[&str]) -> &'static str {
+    crate::alloc::bookkeeping(|| {
+        if modules.is_empty() {
+            get_demangled_symbol(&crate::alloc::configured_modules().unwrap_or_default())
+        } else {
+            get_demangled_symbol(modules)
+        }
+    })
+}
+
+/// Sets the label the allocations made by FFI code are attributed to, e.g. `"ffi"`.
+///
+/// When no frame on the call stack belongs to the traced modules, but some frames have no symbol name
+/// (typical of C code called via FFI), the allocation is attributed to `label` instead of `<unknown>`.
+/// This separates the genuine FFI allocations from the resolution failures.
+pub fn set_ffi_label(label: &'static str) {
+    FFI_LABEL.store(Some(label));
+    super::caller_cache::invalidate();
+}
+
+/// Sets whether the symbols are keyed by their raw mangled name (e.g. `_ZN...`) instead of the demangled one.
+///
+/// By default, the symbols are demangled and the trailing hash (e.g. `::h3777baf656cd0c35`) is stripped,
+/// which loses information: tools which do their own demangling can enable this to get the exact names,
+/// e.g. to regroup the monomorphizations. The call stack frames are still matched against the traced modules
+/// by their demangled name.
+///
+/// Only the symbols resolved after the call are affected, so this should be set before tracing starts.
+pub fn set_keep_mangled(keep: bool) {
+    KEEP_MANGLED.store(keep, Ordering::Relaxed);
+    super::caller_cache::invalidate();
+}
+
+/// Sets whether the generic parameters are stripped from the demangled symbol names (e.g. `app::parse::<u32>`
+/// becomes `app::parse`), so all the monomorphizations of a function are merged into one symbol.
+///
+/// Only the turbofish parameters (`::<...>`) are stripped; the names of the impl blocks, e.g. `<T as Trait>::f`,
+/// are kept. It has no effect with [`set_keep_mangled`].
+///
+/// Only the symbols resolved after the call are affected, so this should be set before tracing starts.
+pub fn set_collapse_generics(collapse: bool) {
+    COLLAPSE_GENERICS.store(collapse, Ordering::Relaxed);
+    super::caller_cache::invalidate();
+}
+
+/// Sets a function rewriting the symbol names before they become the keys of the symbol table,
+/// e.g. to shorten long paths or to strip the crate hashes from the generic parameters.
+///
+/// The rewriter gets the name after demangling, stripping the trailing hash and collapsing the generics
+/// (see [`set_collapse_generics`]), or the mangled name with [`set_keep_mangled`]. The call stack frames are still
+/// matched against the traced modules by their demangled name. It runs inside the allocator, with tracing
+/// suppressed, so it may allocate but it must not panic nor access the symbol table.
+///
+/// Only the symbols resolved after the call are affected, so this should be set before tracing starts.
+///
+/// ```rust
+/// leaktracer::set_name_rewriter(|name| name.replace("my_crate::internal::", "~::"));
+/// ```
+pub fn set_name_rewriter(rewriter: fn(&str) -> String) {
+    NAME_REWRITER.store(rewriter as *mut (), Ordering::Release);
+    super::caller_cache::invalidate();
+}
+
+/// Sets the priority of the traced modules when more than one of them is on the call stack, the highest first,
+/// e.g. `&["my_crate", "my_dependency"]`.
+///
+/// By default, an allocation is attributed to the innermost frame belonging to the traced modules, so when both
+/// the application and a traced dependency are on the call stack, the dependency's inner frame wins.
+/// With a priority, the allocation is attributed to the innermost frame of the highest priority module instead;
+/// the traced frames matching no priority module come last. An empty slice restores the default.
+///
+/// Only the symbols resolved after the call are affected, so this should be set before tracing starts.
+///
+/// ```rust
+/// leaktracer::set_module_priority(&["my_crate", "my_dependency"]);
+/// leaktracer::init_symbol_table(&["my_crate", "my_dependency"]);
+/// ```
+pub fn set_module_priority(modules: &'static [&'static str]) {
+    MODULE_PRIORITY.store((!modules.is_empty()).then_some(modules));
+    super::caller_cache::invalidate();
+}
+
+/// Shifts the attribution `offset` frames up the call stack, toward the callers, from the frame matching
+/// the traced modules, e.g. to skip a thin allocation helper such as `my_crate::util::alloc_buffer`
+/// and attribute its allocations to whoever called it.
+///
+/// The frames are counted on the resolved call stack, whatever module they belong to, so inlined callers are
+/// not counted. If the stack isn't deep enough, or the frame found has no symbol name, the matching frame is kept.
+/// The offset applies to [`caller_symbol`] too, and `0` restores the default.
+///
+/// Only the symbols resolved after the call are affected, so this should be set before tracing starts.
+///
+/// ```rust
+/// leaktracer::set_attribution_offset(1);
+/// leaktracer::init_symbol_table(&["my_crate"]);
+/// ```
+pub fn set_attribution_offset(offset: usize) {
+    ATTRIBUTION_OFFSET.store(offset, Ordering::Relaxed);
+    super::caller_cache::invalidate();
+}
+
+/// Returns the priority set with [`set_module_priority`].
+fn module_priority() -> &'static [&'static str] {
+    MODULE_PRIORITY.load().unwrap_or_default()
+}
+
+/// Returns the rewriter set with [`set_name_rewriter`], if any.
+fn name_rewriter() -> Option<fn(&str) -> String> {
+    let rewriter = NAME_REWRITER.load(Ordering::Acquire);
+    if rewriter.is_null() {
+        return None;
+    }
+    // SAFETY: only `fn(&str) -> String` pointers are stored in `NAME_REWRITER`
+    Some(unsafe { std::mem::transmute::<*mut (), fn(&str) -> String>(rewriter) })
+}
+
+/// Returns the label set with [`set_ffi_label`], if any.
+fn ffi_label() -> Option<&'static str> {
+    FFI_LABEL.load()
+}
+
 /// Get the name of a symbol from the demangled name table.
 pub fn get_demangled_symbol(modules: &[&str]) -> &'static str {
-    let bt = backtrace::Backtrace::new();
-    let Some(caller) = get_symbol_from_backtrace(&bt, modules) else {
-        return UNKNOWN;
-    };
-
-    symbol_name(caller).unwrap_or(UNKNOWN)
+    caller_name(&Backtrace::new(), modules)
 }
 
-/// Get the symbol at a specific frame in the backtrace.
-fn get_symbol_from_backtrace<'a>(
-    backtrace: &'a backtrace::Backtrace,
-    modules: &[&str],
-) -> Option<&'a BacktraceSymbol> {
-    // we need to find the LAST frame, whose name starts with one of the modules
-    let frame = backtrace
+/// Get the name of the caller, like [`get_demangled_symbol`], along with the names of all the frames
+/// belonging to one of the given `modules`, from the innermost, without duplicates.
+pub(super) fn get_caller_frames(modules: &[&str]) -> (&'static str, Vec<&'static str>) {
+    let bt = Backtrace::new();
+    let mut frames = Vec::new();
+    for symbol in bt
         .frames()
         .iter()
-        .enumerate()
-        .find_map(|(index, frame)| {
-            let symbol = frame.symbols().first()?;
+        .filter_map(|frame| frame.symbols().first())
+    {
+        let Some(name) = symbol.name() else {
+            continue;
+        };
+        if !is_traced_frame(&format!("{name}"), modules) {
+            continue;
+        }
+        let name = interned_name(name);
+        if !frames.contains(&name) {
+            frames.push(name);
+        }
+    }
 
-            let name = symbol.name().map(|name| format!("{name}"))?;
+    (caller_name(&bt, modules), frames)
+}
 
-            // ignore this call
-            if IGNORE_LIST.iter().any(|ignore| name.starts_with(*ignore)) {
-                return None;
-            }
+/// Get the name of the first frame of the resolved backtrace `bt` belonging to one of the given `modules`.
+pub(super) fn caller_name(bt: &Backtrace, modules: &[&str]) -> &'static str {
+    let names = bt.frames().iter().map(|frame| {
+        frame
+            .symbols()
+            .first()
+            .and_then(|symbol| symbol.name())
+            .map(|name| format!("{name}"))
+    });
 
-            if modules.iter().any(|module| name.starts_with(*module)) {
-                Some(index)
-            } else {
-                None
-            }
-        })?;
+    let caller = find_caller(names, modules, module_priority());
+    // without debug info no frame has a name, so name the caller after its module and offset
+    #[cfg(windows)]
+    if caller == Caller::Nameless
+        && bt
+            .frames()
+            .iter()
+            .all(|frame| frame.symbols().iter().all(|symbol| symbol.name().is_none()))
+    {
+        if let Some(name) = super::deferred::module_offset_name(bt.frames()) {
+            return name;
+        }
+    }
 
-    backtrace
-        .frames()
-        .get(frame)
-        .and_then(|frame| frame.symbols().first())
+    caller_key(caller, ffi_label(), |index| {
+        shifted_symbol(
+            bt.frames(),
+            index,
+            ATTRIBUTION_OFFSET.load(Ordering::Relaxed),
+        )
+        .and_then(symbol_name)
+        .unwrap_or(UNKNOWN)
+    })
+}
+
+/// Returns the key the `caller` is attributed to: the name of its frame, given by `frame_name`,
+/// the `ffi_label` for a stack with nameless frames (see [`set_ffi_label`]), or `<unknown>`.
+fn caller_key(
+    caller: Caller,
+    ffi_label: Option<&'static str>,
+    frame_name: impl FnOnce(usize) -> &'static str,
+) -> &'static str {
+    match caller {
+        Caller::Frame(index) => frame_name(index),
+        Caller::Nameless => ffi_label.unwrap_or(UNKNOWN),
+        Caller::Unknown => UNKNOWN,
+    }
+}
+
+/// Returns the symbol of the frame `offset` frames above the one at `index`, toward the callers,
+/// or of the frame at `index` if there's no such frame or it has no name (see [`set_attribution_offset`]).
+fn shifted_symbol(
+    frames: &[BacktraceFrame],
+    index: usize,
+    offset: usize,
+) -> Option<&BacktraceSymbol> {
+    let named_symbol = |index: usize| {
+        frames
+            .get(index)
+            .and_then(|frame| frame.symbols().first())
+            .filter(|symbol| symbol.name().is_some())
+    };
+
+    index
+        .checked_add(offset)
+        .and_then(named_symbol)
+        .or_else(|| frames.get(index).and_then(|frame| frame.symbols().first()))
+}
+
+/// Outcome of the search of the caller on the call stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Caller {
+    /// The caller is the frame at the given index.
+    Frame(usize),
+    /// No frame matches, but some frames have no symbol name, which is typical of FFI code.
+    Nameless,
+    /// No frame matches.
+    Unknown,
+}
+
+/// Finds the first frame whose name starts with one of the modules, given the frame names from the top of the stack.
+///
+/// With a `priority`, the first frame of the highest priority module wins, see [`set_module_priority`].
+fn find_caller<I>(names: I, modules: &[&str], priority: &[&str]) -> Caller
+where
+    I: Iterator<Item = Option<String>>,
+{
+    let mut nameless = false;
+    // the rank of the best frame found so far, and its index
+    let mut best: Option<(usize, usize)> = None;
+    for (index, name) in names.enumerate() {
+        let Some(name) = name else {
+            nameless = true;
+            continue;
+        };
+        if !is_traced_frame(&name, modules) {
+            continue;
+        }
+
+        let rank = priority
+            .iter()
+            .position(|module| name.starts_with(*module))
+            .unwrap_or(priority.len());
+        // no frame can beat the highest priority
+        if rank == 0 {
+            return Caller::Frame(index);
+        }
+        if best.is_none_or(|(best_rank, _)| rank < best_rank) {
+            best = Some((rank, index));
+        }
+    }
+
+    if let Some((_, index)) = best {
+        Caller::Frame(index)
+    } else if nameless {
+        Caller::Nameless
+    } else {
+        Caller::Unknown
+    }
+}
+
+/// Returns whether the frame named `name` belongs to one of the given `modules`, and it's not a tracer frame.
+fn is_traced_frame(name: &str, modules: &[&str]) -> bool {
+    // ignore this call
+    !IGNORE_LIST.iter().any(|ignore| name.starts_with(*ignore))
+        && modules.iter().any(|module| name.starts_with(*module))
 }
 
 /// Get the name of a symbol from a [`BacktraceSymbol`].
 fn symbol_name(symbol: &BacktraceSymbol) -> Option<&'static str> {
-    // get the name of the symbol except the last part `backtrace::b::h3777baf656cd0c35`
-    let name_str = symbol.name().map(|name| format!("{name}"))?;
+    symbol.name().map(interned_name)
+}
 
-    let name_string = if let Some(pos) = name_str.rfind("::") {
-        &name_str[..pos]
-    } else {
-        &name_str
+/// Get the interned name of a symbol from its [`SymbolName`].
+pub(crate) fn interned_name(name: SymbolName<'_>) -> &'static str {
+    symbol_key(
+        &name,
+        KEEP_MANGLED.load(Ordering::Relaxed),
+        COLLAPSE_GENERICS.load(Ordering::Relaxed),
+        name_rewriter(),
+    )
+}
+
+/// Get the interned name a symbol is keyed by, either the raw mangled name or the demangled one,
+/// optionally without its generic parameters, and rewritten with `rewriter`.
+fn symbol_key(
+    name: &SymbolName<'_>,
+    keep_mangled: bool,
+    collapse_generics: bool,
+    rewriter: Option<fn(&str) -> String>,
+) -> &'static str {
+    let intern = |key: &str| match rewriter {
+        Some(rewriter) => intern(&crate::alloc::untraced(|| rewriter(key))),
+        None => intern(key),
+    };
+
+    if keep_mangled {
+        return match name.as_str() {
+            Some(mangled) => intern(mangled),
+            None => intern(&String::from_utf8_lossy(name.as_bytes())),
+        };
+    }
+
+    // the alternate form drops the hash of the legacy mangling, e.g. `backtrace::b::h3777baf656cd0c35`,
+    // while the v0 mangling has none
+    let name_str = match name.as_str().map(rustc_demangle::try_demangle) {
+        Some(Ok(demangled)) => format!("{demangled:#}"),
+        _ => format!("{name}"),
+    };
+
+    // a name which isn't mangled may still end with the hash
+    let name_string = match name_str.rsplit_once("::") {
+        Some((path, hash)) if is_rust_hash(hash) => path,
+        _ => &name_str,
     };
 
-    // convert to static str
-    Some(Box::leak(name_string.to_string().into_boxed_str()))
+    if collapse_generics {
+        intern(&strip_generics(name_string))
+    } else {
+        intern(name_string)
+    }
+}
+
+/// Returns whether `segment` is the hash ending a legacy mangled name, e.g. `h3777baf656cd0c35`.
+fn is_rust_hash(segment: &str) -> bool {
+    segment.len() == 17
+        && segment.starts_with('h')
+        && segment[1..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Strips the turbofish generic parameters (`::<...>`) from a demangled name, e.g. `app::parse::<u32>` to `app::parse`.
+///
+/// The generic arguments are the path segments in angle brackets, except for the qualified path the name may start
+/// with (`<T as Trait>`), which is kept as it is.
+fn strip_generics(name: &str) -> Cow<'_, str> {
+    if !name.contains("::<") {
+        return Cow::Borrowed(name);
+    }
+
+    let mut stripped = String::with_capacity(name.len());
+    for (index, segment) in path_segments(name).into_iter().enumerate() {
+        if index > 0 && segment.starts_with('<') {
+            continue;
+        }
+        if index > 0 {
+            stripped.push_str("::");
+        }
+        stripped.push_str(segment);
+    }
+
+    Cow::Owned(stripped)
+}
+
+/// Splits a demangled path at the `::` outside of the angle brackets,
+/// e.g. `<app::Foo as app::Parse>::parse::<app::Bar<u8>>` into `<app::Foo as app::Parse>`, `parse` and `<app::Bar<u8>>`.
+fn path_segments(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut prev = None;
+    let mut chars = path.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '<' => depth += 1,
+            // the arrow of a function pointer type doesn't close the brackets
+            '>' if prev != Some('-') => depth = depth.saturating_sub(1),
+            ':' if depth == 0 && chars.next_if(|&(_, next)| next == ':').is_some() => {
+                segments.push(&path[start..index]);
+                start = index + 2;
+            }
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    segments.push(&path[start..]);
+
+    segments
 }
 
 #[cfg(test)]
@@ -83,6 +463,240 @@ mod test {
         assert!(symbol.contains("symbols::demangle"));
     }
 
+    #[test]
+    fn test_should_get_caller_symbol() {
+        let symbol = caller_symbol(&["leaktracer"]);
+        assert!(symbol.ends_with("test_should_get_caller_symbol"));
+    }
+
+    #[test]
+    fn test_should_get_caller_symbol_with_configured_modules() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let symbol = caller_symbol(&[]);
+        assert!(symbol.ends_with("test_should_get_caller_symbol_with_configured_modules"));
+    }
+
+    #[test]
+    fn test_should_find_caller_frame() {
+        let names = [
+            Some("std::alloc::alloc"),
+            Some("leaktracer::alloc::LeaktracerAllocator::trace"),
+            Some("app::main"),
+        ];
+        assert_eq!(
+            find_caller(
+                names.into_iter().map(|name| name.map(String::from)),
+                &["app"],
+                &[]
+            ),
+            Caller::Frame(2)
+        );
+        assert_eq!(
+            find_caller(
+                names.into_iter().map(|name| name.map(String::from)),
+                &["other"],
+                &[]
+            ),
+            Caller::Unknown
+        );
+    }
+
+    #[test]
+    fn test_should_find_caller_frame_by_priority() {
+        let names = [
+            Some("leaktracer::alloc::LeaktracerAllocator::trace"),
+            Some("dep::buffer::grow"),
+            Some("app::other::helper"),
+            Some("dep::client::send"),
+            Some("app::main"),
+        ];
+        let find = |priority: &[&str]| {
+            find_caller(
+                names.into_iter().map(|name| name.map(String::from)),
+                &["app", "dep"],
+                priority,
+            )
+        };
+
+        assert_eq!(find(&[]), Caller::Frame(1));
+        assert_eq!(find(&["app", "dep"]), Caller::Frame(2));
+        assert_eq!(find(&["app::main"]), Caller::Frame(4));
+        assert_eq!(find(&["other"]), Caller::Frame(1));
+    }
+
+    #[test]
+    fn test_should_detect_nameless_frames() {
+        // the top frame is a C function without debug info
+        let names = [None, Some("std::alloc::alloc"), Some("__libc_start_main")];
+        assert_eq!(
+            find_caller(
+                names.into_iter().map(|name| name.map(String::from)),
+                &["app"],
+                &[]
+            ),
+            Caller::Nameless
+        );
+    }
+
+    #[test]
+    fn test_should_attribute_nameless_stack_to_ffi_label() {
+        // a C function without debug info allocating, called from outside the traced modules
+        let names = [None, Some("std::alloc::alloc"), Some("__libc_start_main")];
+        let caller = find_caller(
+            names.into_iter().map(|name| name.map(String::from)),
+            &["app"],
+            &[],
+        );
+        let frame_name = |_| "app::main";
+
+        assert_eq!(caller_key(caller, Some("ffi"), frame_name), "ffi");
+        assert_eq!(caller_key(caller, None, frame_name), UNKNOWN);
+        // the label is only for the nameless stacks
+        assert_eq!(
+            caller_key(Caller::Unknown, Some("ffi"), frame_name),
+            UNKNOWN
+        );
+        assert_eq!(
+            caller_key(Caller::Frame(0), Some("ffi"), frame_name),
+            "app::main"
+        );
+    }
+
+    #[test]
+    fn test_should_keep_mangled_name() {
+        let mut keys = None;
+        backtrace::trace(|frame| {
+            backtrace::resolve_frame(frame, |symbol| {
+                if keys.is_none() {
+                    keys = symbol.name().map(|name| {
+                        (
+                            symbol_key(&name, true, false, None),
+                            symbol_key(&name, false, false, None),
+                        )
+                    });
+                }
+            });
+            keys.is_none()
+        });
+
+        let (mangled, demangled) = keys.expect("no frame resolved");
+        // legacy or v0 mangling
+        assert!(
+            mangled.starts_with("_ZN") || mangled.starts_with("_R"),
+            "{mangled}"
+        );
+        assert!(!demangled.starts_with("_ZN") && !demangled.starts_with("_R"));
+    }
+
+    #[test]
+    fn test_should_collapse_monomorphizations() {
+        let first = SymbolName::new(b"app::parse::<u32>::h3777baf656cd0c35");
+        let second = SymbolName::new(b"app::parse::<alloc::vec::Vec<u8>>::h0123456789abcdef");
+
+        assert_ne!(
+            symbol_key(&first, false, false, None),
+            symbol_key(&second, false, false, None)
+        );
+        assert_eq!(symbol_key(&first, false, true, None), "app::parse");
+        assert_eq!(symbol_key(&second, false, true, None), "app::parse");
+    }
+
+    #[test]
+    fn test_should_rewrite_symbol_key() {
+        let name = SymbolName::new(b"app::parse::<u32>::h3777baf656cd0c35");
+        let uppercase: fn(&str) -> String = |name| name.to_uppercase();
+
+        assert_eq!(
+            symbol_key(&name, false, false, Some(uppercase)),
+            "APP::PARSE::<U32>"
+        );
+        // after collapsing the generics
+        assert_eq!(
+            symbol_key(&name, false, true, Some(uppercase)),
+            "APP::PARSE"
+        );
+    }
+
+    #[test]
+    fn test_should_key_mangled_symbol() {
+        // legacy mangling, with the hash
+        let legacy = SymbolName::new(b"_ZN3app5parse17h3777baf656cd0c35E");
+        assert_eq!(symbol_key(&legacy, false, false, None), "app::parse");
+        let closure =
+            SymbolName::new(b"_ZN3app4main28_$u7b$$u7b$closure$u7d$$u7d$17h0123456789abcdefE");
+        assert_eq!(
+            symbol_key(&closure, false, false, None),
+            "app::main::{{closure}}"
+        );
+        // v0 mangling, without the hash
+        let v0 = SymbolName::new(b"_RINvCskK7mfDs1mzF_3app5parsemEB2_");
+        assert_eq!(symbol_key(&v0, false, false, None), "app::parse::<u32>");
+        assert_eq!(symbol_key(&v0, false, true, None), "app::parse");
+        let closure = SymbolName::new(b"_RNCNvCskK7mfDs1mzF_3app4main0B3_");
+        assert_eq!(
+            symbol_key(&closure, false, true, None),
+            "app::main::{closure#0}"
+        );
+    }
+
+    #[test]
+    fn test_should_strip_generics() {
+        assert_eq!(strip_generics("app::main"), "app::main");
+        assert_eq!(
+            strip_generics("app::map::<fn(u8) -> u8, 4>::inner"),
+            "app::map::inner"
+        );
+        assert_eq!(
+            strip_generics("<app::Foo<T> as core::ops::Drop>::drop"),
+            "<app::Foo<T> as core::ops::Drop>::drop"
+        );
+        assert_eq!(
+            strip_generics("<app::Foo<T>>::get::<u32>"),
+            "<app::Foo<T>>::get"
+        );
+    }
+
+    #[test]
+    fn test_should_strip_generics_around_closures() {
+        assert_eq!(
+            strip_generics("app::run::<u8>::{{closure}}"),
+            "app::run::{{closure}}"
+        );
+        assert_eq!(
+            strip_generics("app::run::<u8>::{closure#0}::<alloc::string::String>"),
+            "app::run::{closure#0}"
+        );
+        assert_eq!(
+            strip_generics("app::spawn::<app::main::{closure#1}>::{closure#0}"),
+            "app::spawn::{closure#0}"
+        );
+    }
+
+    #[test]
+    fn test_should_strip_generics_after_qualified_path() {
+        assert_eq!(
+            strip_generics("<app::Foo<u8> as app::Parse<u16>>::parse::<u32>"),
+            "<app::Foo<u8> as app::Parse<u16>>::parse"
+        );
+        assert_eq!(
+            strip_generics("<alloc::vec::Vec<T> as core::ops::Drop>::drop::{{closure}}"),
+            "<alloc::vec::Vec<T> as core::ops::Drop>::drop::{{closure}}"
+        );
+    }
+
+    #[test]
+    fn test_should_strip_nested_turbofish() {
+        assert_eq!(
+            strip_generics("app::map::<app::Wrap<app::id::<u8>>>::call"),
+            "app::map::call"
+        );
+        assert_eq!(
+            strip_generics("app::fold::<app::sum::<u8>, fn(&[u8]) -> core::option::Option<u8>>"),
+            "app::fold"
+        );
+    }
+
     fn a() -> &'static str {
         b()
     }
@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Default smoothing factor of the allocation size moving average.
+pub const DEFAULT_EMA_ALPHA: f64 = 0.1;
+
+/// Number of fractional bits of the fixed-point moving average.
+const FRACTIONAL_BITS: u32 = 16;
+
+/// Value of an empty [`Ema`], which has never recorded any allocation.
+const EMPTY: u64 = u64::MAX;
+
+/// Exponential moving average of the allocation sizes of a [`super::Symbol`].
+///
+/// The average is stored as an unsigned fixed-point number with 16 fractional bits, i.e. `size * 2^16`,
+/// so sizes up to 2^48 bytes are represented with a precision of 1/65536 of byte.
+/// Each allocation updates it as `ema + alpha * (size - ema)`; the first allocation sets it to its size.
+#[derive(Debug)]
+pub(crate) struct Ema(AtomicU64);
+
+impl Ema {
+    /// Creates a new empty [`Ema`].
+    pub(crate) fn new() -> Self {
+        Self(AtomicU64::new(EMPTY))
+    }
+
+    /// Records an allocation of `size` bytes with the smoothing factor `alpha`.
+    ///
+    /// Must be called with the symbol table locked, since the update is not atomic.
+    pub(crate) fn record(&self, size: usize, alpha: f64) {
+        let sample = (size as u64).saturating_mul(1 << FRACTIONAL_BITS);
+        let ema = match self.0.load(Ordering::Relaxed) {
+            EMPTY => sample,
+            ema => (ema as f64 + alpha * (sample as f64 - ema as f64)).round() as u64,
+        };
+        self.0.store(ema.min(EMPTY - 1), Ordering::Relaxed);
+    }
+
+    /// Takes the moving average of `other` if this one has never recorded any allocation.
+    ///
+    /// Moving averages can't be combined, so the one of the oldest symbol wins.
+    #[cfg_attr(not(all(windows, stack_capture)), allow(dead_code))]
+    pub(crate) fn merge(&self, other: &Ema) {
+        if self.0.load(Ordering::Relaxed) == EMPTY {
+            self.0
+                .store(other.0.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the moving average in bytes, `0.0` if no allocation has been recorded.
+    pub(crate) fn get(&self) -> f64 {
+        match self.0.load(Ordering::Relaxed) {
+            EMPTY => 0.0,
+            ema => ema as f64 / (1u64 << FRACTIONAL_BITS) as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_converge_on_steady_size() {
+        let ema = Ema::new();
+        assert_eq!(ema.get(), 0.0);
+
+        ema.record(4096, DEFAULT_EMA_ALPHA);
+        assert_eq!(ema.get(), 4096.0);
+        for _ in 0..200 {
+            ema.record(100, DEFAULT_EMA_ALPHA);
+        }
+        assert!((ema.get() - 100.0).abs() < 0.01, "{}", ema.get());
+    }
+
+    #[test]
+    fn test_should_smooth_noisy_sizes() {
+        let ema = Ema::new();
+        for i in 0..1000 {
+            ema.record(if i % 2 == 0 { 64 } else { 192 }, 0.01);
+        }
+        assert!((ema.get() - 128.0).abs() < 2.0, "{}", ema.get());
+    }
+}
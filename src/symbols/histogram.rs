@@ -0,0 +1,98 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default histogram bucket boundaries: powers of two from 8 bytes to 1 MiB.
+pub const DEFAULT_HISTOGRAM_BUCKETS: &[usize] = &[
+    8,
+    16,
+    32,
+    64,
+    128,
+    256,
+    512,
+    1024,
+    2 * 1024,
+    4 * 1024,
+    8 * 1024,
+    16 * 1024,
+    32 * 1024,
+    64 * 1024,
+    128 * 1024,
+    256 * 1024,
+    512 * 1024,
+    1024 * 1024,
+];
+
+/// Histogram of the allocation sizes of a [`super::Symbol`].
+///
+/// Given the sorted bucket boundaries `b`, bin `0` counts the allocations smaller than `b[0]`,
+/// bin `i` counts the allocations in `b[i - 1]..b[i]` and the last bin counts the allocations greater or equal than the last boundary.
+#[derive(Debug)]
+pub(crate) struct Histogram {
+    bins: Box<[AtomicUsize]>,
+}
+
+impl Histogram {
+    /// Creates a new empty [`Histogram`] for the given bucket boundaries.
+    pub(crate) fn new(boundaries: &[usize]) -> Self {
+        Self {
+            bins: (0..=boundaries.len())
+                .map(|_| AtomicUsize::new(0))
+                .collect(),
+        }
+    }
+
+    /// Records an allocation of `size` bytes, bisecting the boundaries to pick the bin.
+    pub(crate) fn record(&self, boundaries: &[usize], size: usize) {
+        let bin = boundaries.partition_point(|boundary| *boundary <= size);
+        if let Some(bin) = self.bins.get(bin) {
+            bin.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Adds the allocation counts of `other`, which must have the same bucket boundaries.
+    #[cfg_attr(not(all(windows, stack_capture)), allow(dead_code))]
+    pub(crate) fn merge(&self, other: &Histogram) {
+        for (bin, other) in self.bins.iter().zip(other.bins.iter()) {
+            bin.fetch_add(other.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the allocation count of each bin.
+    pub(crate) fn counts(&self) -> Vec<usize> {
+        self.bins
+            .iter()
+            .map(|bin| bin.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_record_in_custom_bins() {
+        let boundaries = [128, 1024, 8192];
+        let histogram = Histogram::new(&boundaries);
+        for size in [0, 127, 128, 1023, 1024, 8191, 8192, 100_000] {
+            histogram.record(&boundaries, size);
+        }
+
+        assert_eq!(histogram.counts(), vec![2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_should_record_in_default_bins() {
+        let histogram = Histogram::new(DEFAULT_HISTOGRAM_BUCKETS);
+        histogram.record(DEFAULT_HISTOGRAM_BUCKETS, 7);
+        histogram.record(DEFAULT_HISTOGRAM_BUCKETS, 8);
+        histogram.record(DEFAULT_HISTOGRAM_BUCKETS, 2 * 1024 * 1024);
+
+        let counts = histogram.counts();
+        assert_eq!(counts.len(), DEFAULT_HISTOGRAM_BUCKETS.len() + 1);
+        assert_eq!(counts[0], 1);
+        assert_eq!(counts[1], 1);
+        assert_eq!(counts[DEFAULT_HISTOGRAM_BUCKETS.len()], 1);
+    }
+}
@@ -0,0 +1,42 @@
+//! Interning of the symbol names, so each name is leaked only once.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// The interned strings, i.e. the symbol names and the tags.
+///
+/// This lock is independent from the symbol table lock, and it's never held while acquiring another lock.
+pub(crate) static INTERNED: Mutex<Option<HashSet<&'static str>>> = Mutex::new(None);
+
+/// Interns `s`, returning a `'static` reference to the unique copy of it.
+///
+/// The interned strings are tracer bookkeeping, so they are accounted in [`crate::self_overhead_bytes`].
+pub(crate) fn intern(s: &str) -> &'static str {
+    crate::alloc::bookkeeping(|| -> &'static str {
+        let Ok(mut interned) = INTERNED.lock() else {
+            return Box::leak(s.to_string().into_boxed_str());
+        };
+        let interned = interned.get_or_insert_with(HashSet::new);
+        if let Some(existing) = interned.get(s) {
+            return existing;
+        }
+
+        let leaked: &'static str = Box::leak(s.to_string().into_boxed_str());
+        interned.insert(leaked);
+        leaked
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_intern_strings() {
+        let a = intern("leaktracer::intern::test");
+        let b = intern(&String::from("leaktracer::intern::test"));
+        assert_eq!(a, b);
+        assert!(std::ptr::eq(a, b));
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::sync::Mutex;
+
+use super::demangle::interned_name;
+use super::intern::intern;
+
+/// Cache of the resolved addresses.
+///
+/// This lock is independent from the symbol table lock, and it's never held while resolving an address.
+static RESOLVED: Mutex<Option<HashMap<usize, Option<ResolvedSymbol>>>> = Mutex::new(None);
+
+/// A symbol resolved from an instruction pointer by [`resolve_addr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResolvedSymbol {
+    /// The symbol name, in the same form used as key in the [`crate::SymbolTable`].
+    pub name: &'static str,
+    /// The source file, if debug info is available.
+    pub file: Option<&'static str>,
+    /// The source line, if debug info is available.
+    pub line: Option<u32>,
+}
+
+/// Resolves an arbitrary instruction pointer to a [`ResolvedSymbol`].
+///
+/// Like instruction pointers captured in backtraces, `addr` is treated as a return address,
+/// so the symbol resolved is the one containing `addr - 1`: to resolve a function from its start address
+/// (e.g. a function pointer), pass an address inside the function body.
+///
+/// Names are interned with the same cache the tracer uses, and resolved addresses are cached,
+/// so repeated lookups are cheap. Returns [`None`] if the address can't be resolved.
+///
+/// This function doesn't take the symbol table lock, so it's safe to call concurrently with allocation tracing.
+/// The allocations made while resolving are not traced.
+pub fn resolve_addr(addr: usize) -> Option<ResolvedSymbol> {
+    crate::alloc::bookkeeping(|| {
+        if let Some(cached) = RESOLVED
+            .lock()
+            .ok()
+            .and_then(|cache| cache.as_ref()?.get(&addr).copied())
+        {
+            return cached;
+        }
+
+        let mut resolved = None;
+        backtrace::resolve(addr as *mut c_void, |symbol| {
+            if resolved.is_some() {
+                return;
+            }
+            let Some(name) = symbol.name() else {
+                return;
+            };
+            resolved = Some(ResolvedSymbol {
+                name: interned_name(name),
+                file: symbol
+                    .filename()
+                    .map(|file| intern(&file.to_string_lossy())),
+                line: symbol.lineno(),
+            });
+        });
+
+        if let Ok(mut cache) = RESOLVED.lock() {
+            cache
+                .get_or_insert_with(HashMap::new)
+                .insert(addr, resolved);
+        }
+
+        resolved
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[inline(never)]
+    fn known_function() -> usize {
+        std::hint::black_box(42)
+    }
+
+    #[test]
+    fn test_should_resolve_function_address() {
+        assert_eq!(known_function(), 42);
+        // resolved as a return address, so point inside the function body
+        let addr = known_function as fn() -> usize as usize + 1;
+
+        let resolved = resolve_addr(addr).expect("address should resolve");
+        assert!(resolved.name.ends_with("known_function"));
+
+        // cached lookup returns the same interned name
+        let cached = resolve_addr(addr).expect("address should resolve");
+        assert!(std::ptr::eq(resolved.name, cached.name));
+    }
+}
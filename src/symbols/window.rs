@@ -0,0 +1,207 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Length of each bucket of a [`Window`].
+pub(crate) const BUCKET_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of buckets of a [`Window`].
+const BUCKETS: usize = 12;
+
+/// The longest window tracked by the sliding window counters: 12 buckets of 5 seconds.
+pub const RECENT_WINDOW: Duration = Duration::from_secs(BUCKET_INTERVAL.as_secs() * BUCKETS as u64);
+
+/// Epoch of a [`Bucket`] which has never recorded any allocation.
+const EMPTY: u64 = u64::MAX;
+
+/// Allocations made by a [`super::Symbol`] during one interval.
+#[derive(Debug)]
+struct Bucket {
+    /// Index of the interval the counters belong to, since the tracer start.
+    epoch: AtomicU64,
+    bytes: AtomicUsize,
+    count: AtomicUsize,
+}
+
+/// Sliding window counters of the allocations made by a [`super::Symbol`] in the last [`RECENT_WINDOW`].
+///
+/// The window is a ring of per-interval buckets, indexed by interval modulo the ring size. Buckets are
+/// rotated lazily: a recording into a bucket of an older interval resets it, while reads skip the expired ones,
+/// so there's no timer and the rotation costs a single atomic comparison on the allocation path.
+///
+/// Times are the tracer [`crate::uptime`], passed by the caller.
+#[derive(Debug)]
+pub(crate) struct Window {
+    buckets: [Bucket; BUCKETS],
+}
+
+impl Window {
+    /// Creates a new empty [`Window`].
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| Bucket {
+                epoch: AtomicU64::new(EMPTY),
+                bytes: AtomicUsize::new(0),
+                count: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Records `count` allocations of `bytes` in total at `now`.
+    ///
+    /// Must be called with the symbol table locked, since resetting an expired bucket is not atomic.
+    pub(crate) fn record(&self, now: Duration, bytes: usize, count: usize) {
+        let epoch = epoch(now);
+        let bucket = &self.buckets[epoch as usize % BUCKETS];
+        if bucket.epoch.load(Ordering::Relaxed) != epoch {
+            bucket.bytes.store(0, Ordering::Relaxed);
+            bucket.count.store(0, Ordering::Relaxed);
+            bucket.epoch.store(epoch, Ordering::Relaxed);
+        }
+        bucket.bytes.fetch_add(bytes, Ordering::Relaxed);
+        bucket.count.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Adds the buckets of `other` still in its window to this one.
+    #[cfg_attr(not(all(windows, stack_capture)), allow(dead_code))]
+    pub(crate) fn merge(&self, other: &Window) {
+        for (bucket, other) in self.buckets.iter().zip(&other.buckets) {
+            let epoch = other.epoch.load(Ordering::Relaxed);
+            if epoch == EMPTY {
+                continue;
+            }
+            let current = bucket.epoch.load(Ordering::Relaxed);
+            if current == EMPTY || current < epoch {
+                bucket.bytes.store(0, Ordering::Relaxed);
+                bucket.count.store(0, Ordering::Relaxed);
+                bucket.epoch.store(epoch, Ordering::Relaxed);
+            } else if current > epoch {
+                // expired in this window
+                continue;
+            }
+            bucket
+                .bytes
+                .fetch_add(other.bytes.load(Ordering::Relaxed), Ordering::Relaxed);
+            bucket
+                .count
+                .fetch_add(other.count.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the bytes and count of the allocations made in the last `window` at `now`.
+    ///
+    /// The window is rounded up to a whole number of buckets, including the current one, and capped at [`RECENT_WINDOW`].
+    pub(crate) fn recent(&self, now: Duration, window: Duration) -> (usize, usize) {
+        let current = epoch(now);
+        let buckets = window
+            .as_nanos()
+            .div_ceil(BUCKET_INTERVAL.as_nanos())
+            .clamp(1, BUCKETS as u128) as u64;
+
+        self.buckets
+            .iter()
+            .filter(|bucket| {
+                let epoch = bucket.epoch.load(Ordering::Relaxed);
+                epoch != EMPTY && epoch <= current && current - epoch < buckets
+            })
+            .fold((0, 0), |(bytes, count), bucket| {
+                (
+                    bytes + bucket.bytes.load(Ordering::Relaxed),
+                    count + bucket.count.load(Ordering::Relaxed),
+                )
+            })
+    }
+}
+
+/// Returns the index of the interval containing `now`.
+fn epoch(now: Duration) -> u64 {
+    (now.as_nanos() / BUCKET_INTERVAL.as_nanos()) as u64
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::time::{Clock, ManualClock, MonotonicClock};
+
+    /// Returns the reading of the `clock` as the tracer uptime.
+    fn now(clock: &dyn Clock) -> Duration {
+        Duration::from_millis(clock.now_millis())
+    }
+
+    /// Records into a new window through `clock`, moved forward by `advance`, returning the recent allocations.
+    fn record_through(clock: &dyn Clock, advance: impl Fn(Duration)) -> (usize, usize) {
+        let start = clock.now_millis();
+        let elapsed = || Duration::from_millis(clock.now_millis() - start);
+        let window = Window::new();
+        window.record(elapsed(), 100, 1);
+        advance(Duration::from_millis(20));
+        assert!(elapsed() >= Duration::from_millis(20));
+        window.record(elapsed(), 50, 2);
+
+        window.recent(elapsed(), RECENT_WINDOW)
+    }
+
+    #[test]
+    fn test_should_count_recent_allocations() {
+        let clock = ManualClock::new();
+        let window = Window::new();
+        assert_eq!(window.recent(now(&clock), RECENT_WINDOW), (0, 0));
+
+        window.record(now(&clock), 100, 1);
+        clock.advance(Duration::from_secs(1));
+        window.record(now(&clock), 50, 2);
+        assert_eq!(window.recent(now(&clock), RECENT_WINDOW), (150, 3));
+
+        clock.advance(Duration::from_secs(5));
+        window.record(now(&clock), 10, 1);
+        assert_eq!(window.recent(now(&clock), Duration::from_secs(5)), (10, 1));
+        // rounded up to the buckets
+        assert_eq!(window.recent(now(&clock), Duration::from_secs(6)), (160, 4));
+        assert_eq!(window.recent(now(&clock), Duration::ZERO), (10, 1));
+    }
+
+    #[test]
+    fn test_should_expire_and_rotate_buckets() {
+        let clock = ManualClock::new();
+        clock.set(Duration::from_secs(3));
+        let window = Window::new();
+        window.record(now(&clock), 100, 1);
+
+        clock.advance(RECENT_WINDOW - Duration::from_secs(5));
+        window.record(now(&clock), 20, 1);
+        assert_eq!(window.recent(now(&clock), RECENT_WINDOW), (120, 2));
+
+        // the first bucket expires
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(window.recent(now(&clock), RECENT_WINDOW), (20, 1));
+        // and it's reused by the new interval, which lands in the same slot
+        window.record(now(&clock), 7, 1);
+        assert_eq!(window.recent(now(&clock), RECENT_WINDOW), (27, 2));
+
+        clock.advance(RECENT_WINDOW * 2);
+        assert_eq!(window.recent(now(&clock), RECENT_WINDOW), (0, 0));
+    }
+
+    #[test]
+    fn test_should_count_the_same_with_any_clock() {
+        let manual = ManualClock::new();
+        let monotonic = record_through(&MonotonicClock, std::thread::sleep);
+
+        assert_eq!(monotonic, (150, 3));
+        assert_eq!(record_through(&manual, |by| manual.advance(by)), monotonic);
+    }
+
+    #[test]
+    fn test_should_merge_windows() {
+        let now = Duration::from_secs(100);
+        let window = Window::new();
+        let other = Window::new();
+        window.record(now, 10, 1);
+        other.record(now, 20, 2);
+        // expired in the merged window
+        other.record(now - Duration::from_secs(65), 1000, 1);
+
+        window.merge(&other);
+        assert_eq!(window.recent(now, RECENT_WINDOW), (30, 3));
+    }
+}
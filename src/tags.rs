@@ -0,0 +1,155 @@
+//! Manual grouping of the allocations by a per-thread stack of tags.
+//!
+//! Call stacks can't express logical phases (e.g. "loading config" vs "serving request #42"),
+//! so the allocations made while a tag is active are also accounted to the tag.
+
+use std::cell::RefCell;
+
+use crate::alloc::{LockResult, bookkeeping};
+use crate::symbols::intern;
+
+thread_local! {
+    /// The tags pushed on the current thread, the innermost last.
+    static TAGS: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The stats of the allocations made while a tag was active.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagStats {
+    /// The tag name.
+    pub name: &'static str,
+    /// Allocated bytes for this tag.
+    pub allocated: usize,
+    /// Allocation count for this tag.
+    pub count: usize,
+}
+
+/// Pushes `tag` on the tag stack of the current thread.
+///
+/// While the tag is the innermost one, the allocations made by the current thread are also accounted to it
+/// (see [`tag_stats`]). Only the innermost tag is accounted, so nested tags split the allocations of the outer ones.
+/// Tags are interned, so each distinct tag is leaked only once.
+pub fn push_tag(tag: &str) {
+    let tag = intern(tag);
+    bookkeeping(|| TAGS.with_borrow_mut(|tags| tags.push(tag)));
+}
+
+/// Pops the innermost tag from the tag stack of the current thread, returning it.
+pub fn pop_tag() -> Option<&'static str> {
+    TAGS.with_borrow_mut(Vec::pop)
+}
+
+/// Returns the innermost tag of the current thread, if any.
+///
+/// Allocations made while the tag stack is being updated or torn down are not tagged.
+pub(crate) fn current_tag() -> Option<&'static str> {
+    TAGS.try_with(|tags| tags.try_borrow().ok().and_then(|tags| tags.last().copied()))
+        .ok()
+        .flatten()
+}
+
+/// A guard popping its tag when dropped.
+///
+/// ```rust
+/// {
+///     let _tag = leaktracer::TagGuard::new("phase:load");
+///     // allocations made here are accounted to `phase:load`
+/// }
+/// // the tag has been popped
+/// ```
+#[derive(Debug)]
+#[must_use = "the tag is popped as soon as the guard is dropped"]
+pub struct TagGuard {
+    // the tag stack is per-thread, so the guard must be dropped on the thread which pushed it
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl TagGuard {
+    /// Pushes `tag` on the tag stack of the current thread (see [`push_tag`]), until the guard is dropped.
+    pub fn new(tag: &str) -> Self {
+        push_tag(tag);
+        Self {
+            _not_send: std::marker::PhantomData,
+        }
+    }
+}
+
+impl Drop for TagGuard {
+    fn drop(&mut self) {
+        pop_tag();
+    }
+}
+
+/// Returns the stats of each tag, sorted by allocated bytes in descending order.
+///
+/// Like the symbols, deallocations are accounted to the tag which was active when the memory was allocated.
+pub fn tag_stats() -> LockResult<Vec<TagStats>> {
+    crate::with_symbol_table(|table| {
+        let mut stats = table.tag_stats();
+        stats.sort_by(|a, b| b.allocated.cmp(&a.allocated).then(a.name.cmp(b.name)));
+        stats
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::LeaktracerAllocator;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+
+    #[test]
+    fn test_should_nest_tags() {
+        assert_eq!(current_tag(), None);
+        {
+            let _outer = TagGuard::new("test:outer");
+            assert_eq!(current_tag(), Some("test:outer"));
+            {
+                let _inner = TagGuard::new("test:inner");
+                assert_eq!(current_tag(), Some("test:inner"));
+            }
+            assert_eq!(current_tag(), Some("test:outer"));
+        }
+        assert_eq!(current_tag(), None);
+
+        push_tag("test:manual");
+        assert_eq!(pop_tag(), Some("test:manual"));
+        assert_eq!(pop_tag(), None);
+    }
+
+    #[test]
+    fn test_should_account_allocations_to_innermost_tag() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let allocator = LeaktracerAllocator::init();
+        let layout = Layout::from_size_align(128, 8).unwrap();
+        let (outer_ptr, inner_ptr) = (fake_ptr(), fake_ptr());
+        {
+            let _outer = TagGuard::new("test:tag_outer");
+            allocator.trace(outer_ptr, layout, AllocOp::Alloc);
+            let _inner = TagGuard::new("test:tag_inner");
+            allocator.trace(inner_ptr, layout, AllocOp::Alloc);
+            allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
+        }
+
+        let tag = |name: &str| {
+            tag_stats()
+                .unwrap()
+                .into_iter()
+                .find(|tag| tag.name == name)
+                .expect("tag should exist")
+        };
+        assert_eq!(tag("test:tag_outer").allocated, 128);
+        assert_eq!(tag("test:tag_inner").allocated, 256);
+        assert_eq!(tag("test:tag_inner").count, 2);
+
+        // deallocations are accounted to the tag active at allocation, even when untagged
+        allocator.trace(inner_ptr, layout, AllocOp::Dealloc);
+        assert_eq!(tag("test:tag_inner").allocated, 128);
+        assert_eq!(tag("test:tag_inner").count, 1);
+        assert_eq!(tag("test:tag_outer").allocated, 128);
+    }
+}
@@ -0,0 +1,82 @@
+//! Attribution of the allocations to async tasks, via a tokio task-local id.
+//!
+//! In async code the call stacks are dominated by the executor frames, and a task moves across threads
+//! between polls, so neither the call stacks nor the per-thread tags attribute its allocations reliably.
+//! A task-local id follows the task instead, whichever thread polls it.
+
+use std::future::Future;
+
+use crate::symbols::intern;
+
+tokio::task_local! {
+    /// The id of the task being polled.
+    static TASK_ID: &'static str;
+}
+
+/// Runs `future` with the task id `id`, attributing the allocations made while polling it to the [`crate::Symbol`] `id`
+/// instead of the caller resolved from the call stack.
+///
+/// Ids are interned, so each distinct id is leaked only once. Futures instrumented within `future` override the id.
+/// Since it needs no call stack, the attribution works in the [counter-only mode](crate#counter-only-mode) too.
+///
+/// ```rust
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let handle = tokio::spawn(leaktracer::instrument_task("task:ingest", async {
+///     // allocations made here are accounted to `task:ingest`
+///     vec![0u8; 1024]
+/// }));
+/// # handle.await.unwrap();
+/// # }
+/// ```
+pub fn instrument_task<F>(id: &str, future: F) -> impl Future<Output = F::Output>
+where
+    F: Future,
+{
+    TASK_ID.scope(intern(id), future)
+}
+
+/// Returns the id of the task being polled on the current thread, if it's instrumented.
+pub(crate) fn current_task() -> Option<&'static str> {
+    TASK_ID.try_with(|id| *id).ok()
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::alloc::Layout;
+
+    use super::*;
+    use crate::alloc::AllocOp;
+    use crate::test_utils::fake_ptr;
+    use crate::{LeaktracerAllocator, Symbol};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_should_split_allocations_between_tasks() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let task = |id: &'static str, allocations: usize| {
+            tokio::spawn(instrument_task(id, async move {
+                let allocator = LeaktracerAllocator::init();
+                let layout = Layout::from_size_align(64, 8).unwrap();
+                for _ in 0..allocations {
+                    assert_eq!(current_task(), Some(id));
+                    allocator.trace(fake_ptr(), layout, AllocOp::Alloc);
+                    tokio::task::yield_now().await;
+                }
+            }))
+        };
+        let (a, b) = (task("test:task_a", 3), task("test:task_b", 1));
+        a.await.unwrap();
+        b.await.unwrap();
+        assert_eq!(current_task(), None);
+
+        let allocated = |id: &'static str| {
+            crate::with_symbol_table(|table| table.get(id).map(Symbol::allocated))
+                .unwrap()
+                .expect("task symbol should exist")
+        };
+        assert_eq!(allocated("test:task_a"), 192);
+        assert_eq!(allocated("test:task_b"), 64);
+    }
+}
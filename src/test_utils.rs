@@ -0,0 +1,100 @@
+//! Utilities shared by the unit tests.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::{AlignmentStats, SizeCardinality, SymbolSnapshot, SymbolTableSnapshot};
+
+/// Next fake pointer returned by [`fake_ptr`].
+static NEXT_PTR: AtomicUsize = AtomicUsize::new(0x1000_0000);
+
+/// Returns a unique fake pointer, to trace allocations without actually allocating.
+///
+/// The pointers are unique across the whole test binary, so tests sharing the global symbol table don't collide.
+pub fn fake_ptr() -> *mut u8 {
+    NEXT_PTR.fetch_add(0x100, Ordering::Relaxed) as *mut u8
+}
+
+/// Returns a snapshot with a few known symbols, to test the reporters.
+pub fn synthetic_snapshot() -> SymbolTableSnapshot {
+    SymbolTableSnapshot {
+        generation: 42,
+        uptime: Duration::from_millis(1500),
+        padded_size: false,
+        allocated: 5632,
+        sampling_divisor: 1,
+        process_rss: Some(8192),
+        self_overhead_bytes: 1024,
+        untracked_frees: 1,
+        untracked_freed_bytes: 16,
+        symbols: vec![
+            SymbolSnapshot {
+                name: "app::main",
+                allocated: 1024,
+                count: 10,
+                peak: 8192,
+                peak_at: Duration::from_secs(342),
+                total_allocated: 65536,
+                total_count: 640,
+                turnover: 64512,
+                inclusive_allocated: 5632,
+                recent_allocated: 0,
+                recent_count: 0,
+                reallocs: 8,
+                realloc_moves: 6,
+                bytes_grown: 3072,
+                bytes_shrunk: 512,
+                alignment: AlignmentStats {
+                    natural: 630,
+                    align_64: 10,
+                    ..Default::default()
+                },
+                size_cardinality: SizeCardinality::Many,
+            },
+            SymbolSnapshot {
+                name: "app::cache::insert",
+                allocated: 4096,
+                count: 2,
+                peak: 4096,
+                peak_at: Duration::from_millis(1200),
+                total_allocated: 8192,
+                total_count: 4,
+                turnover: 4096,
+                inclusive_allocated: 4096,
+                recent_allocated: 0,
+                recent_count: 0,
+                reallocs: 0,
+                realloc_moves: 0,
+                bytes_grown: 0,
+                bytes_shrunk: 0,
+                alignment: AlignmentStats {
+                    page: 4,
+                    ..Default::default()
+                },
+                size_cardinality: SizeCardinality::Exact(1),
+            },
+            SymbolSnapshot {
+                name: "app::config::load",
+                allocated: 512,
+                count: 5,
+                peak: 512,
+                peak_at: Duration::from_millis(10),
+                total_allocated: 512,
+                total_count: 5,
+                turnover: 0,
+                inclusive_allocated: 512,
+                recent_allocated: 0,
+                recent_count: 0,
+                reallocs: 1,
+                realloc_moves: 0,
+                bytes_grown: 256,
+                bytes_shrunk: 0,
+                alignment: AlignmentStats {
+                    natural: 5,
+                    ..Default::default()
+                },
+                size_cardinality: SizeCardinality::Exact(3),
+            },
+        ],
+    }
+}
@@ -0,0 +1,100 @@
+//! Live bytes and peak of the allocations made by each thread.
+
+use std::cell::Cell;
+use std::thread::ThreadId;
+
+use crate::alloc::SYMBOL_TABLE;
+
+thread_local! {
+    /// The id of the current thread, cached on its first traced allocation.
+    static THREAD_ID: Cell<Option<ThreadId>> = const { Cell::new(None) };
+}
+
+/// Returns the id of the current thread, if its thread-locals are still accessible.
+pub(crate) fn current_thread() -> Option<ThreadId> {
+    THREAD_ID
+        .try_with(|id| {
+            let current = id.get().unwrap_or_else(|| std::thread::current().id());
+            id.set(Some(current));
+            current
+        })
+        .ok()
+}
+
+/// Returns the highest live bytes ever reached by the allocations of each thread, as `(thread, peak)`,
+/// sorted by peak in descending order, e.g. to size per-thread memory budgets.
+///
+/// Like the symbols, the deallocations are accounted to the thread which made the allocation,
+/// so the memory handed over to another thread still counts for the allocating one until it's freed.
+/// The threads which exited are still reported, until [`crate::reset`].
+/// Returns an empty list if the symbol table is not initialized or its lock is poisoned.
+///
+/// ```rust
+/// leaktracer::init_symbol_table(&["my_crate_name"]);
+///
+/// for (thread, peak) in leaktracer::peak_by_thread() {
+///     println!("{thread:?}: {peak} bytes at peak");
+/// }
+/// ```
+pub fn peak_by_thread() -> Vec<(ThreadId, usize)> {
+    if SYMBOL_TABLE.get().is_none() {
+        return Vec::new();
+    }
+    crate::with_symbol_table(|table| {
+        let mut peaks = table.thread_peaks();
+        peaks.sort_by_key(|(_, peak)| std::cmp::Reverse(*peak));
+        peaks
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_cache_current_thread() {
+        assert_eq!(current_thread(), Some(std::thread::current().id()));
+        assert_eq!(current_thread(), Some(std::thread::current().id()));
+    }
+
+    #[test]
+    // the allocating threads can't be spawned on WebAssembly
+    #[cfg(not(target_family = "wasm"))]
+    fn test_should_report_peak_by_thread() {
+        use std::alloc::Layout;
+
+        use crate::LeaktracerAllocator;
+        use crate::alloc::AllocOp;
+        use crate::test_utils::fake_ptr;
+
+        crate::init_symbol_table(&["leaktracer"]);
+
+        // traces an allocation of `size` bytes, then frees it, on its own thread
+        let spike = |size: usize| {
+            std::thread::spawn(move || {
+                let allocator = LeaktracerAllocator::init();
+                let layout = Layout::from_size_align(size, 8).unwrap();
+                let ptr = fake_ptr();
+                allocator.trace(ptr, layout, AllocOp::Alloc);
+                allocator.trace(ptr, layout, AllocOp::Dealloc);
+                std::thread::current().id()
+            })
+            .join()
+            .unwrap()
+        };
+        let high = spike(1 << 20);
+        let low = spike(64);
+
+        let peaks = peak_by_thread();
+        let peak = |thread| {
+            peaks
+                .iter()
+                .find(|(id, _)| *id == thread)
+                .map(|(_, peak)| *peak)
+        };
+        assert_eq!(peak(high), Some(1 << 20));
+        assert_eq!(peak(low), Some(64));
+    }
+}
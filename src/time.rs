@@ -0,0 +1,253 @@
+use std::panic::RefUnwindSafe;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::atomic_ref::AtomicRef;
+
+/// The clock read by the tracer, set with [`set_clock`] or [`crate::LeaktracerAllocator::with_clock`],
+/// the [`MonotonicClock`] if none.
+static CLOCK: AtomicRef<dyn Clock> = AtomicRef::new();
+
+/// The clock installed by [`crate::LeaktracerAllocator::with_clock`] on the first traced allocation.
+static INJECTED: OnceLock<&'static dyn Clock> = OnceLock::new();
+
+/// Whether [`Instant`] can be read: on `wasm32-unknown-unknown`, which has no clock, it panics.
+const HAS_INSTANT: bool = !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// The reading of the clock at which the tracer observed its first allocation, `u64::MAX` if not captured yet.
+static START: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// A monotonic clock, in milliseconds, read by all the timestamps of the tracer.
+///
+/// The tracer reads the clock on the allocation path, so [`Clock::now_millis`] must be cheap and must not allocate.
+/// The clock is shared by the allocator, so it must be [`RefUnwindSafe`] like the allocator itself.
+/// [`MonotonicClock`] is the default one; with the `test-util` feature, `ManualClock` is advanced by hand,
+/// which makes the time-dependent features deterministic in tests.
+pub trait Clock: Send + Sync + RefUnwindSafe {
+    /// Returns the current reading of the clock, in milliseconds.
+    fn now_millis(&self) -> u64;
+}
+
+/// The default [`Clock`], based on [`Instant`]: the milliseconds elapsed since its first reading.
+///
+/// On `wasm32-unknown-unknown`, where [`Instant`] is not available, it always reads zero:
+/// set a clock there with [`set_clock`], e.g. reading `performance.now()` through `wasm-bindgen`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now_millis(&self) -> u64 {
+        if !HAS_INSTANT {
+            return 0;
+        }
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        ORIGIN.get_or_init(Instant::now).elapsed().as_millis() as u64
+    }
+}
+
+/// A [`Clock`] which only moves when advanced by hand, to test the time-dependent features deterministically,
+/// e.g. the sliding windows of the recent allocations (see [`crate::RECENT_WINDOW`]) or the [`crate::uptime`].
+///
+/// Inject it with [`crate::LeaktracerAllocator::with_clock`]:
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use leaktracer::{LeaktracerAllocator, ManualClock};
+///
+/// static CLOCK: ManualClock = ManualClock::new();
+///
+/// #[global_allocator]
+/// static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_clock(&CLOCK);
+///
+/// CLOCK.advance(Duration::from_secs(90));
+/// assert_eq!(leaktracer::uptime(), Duration::from_secs(90));
+/// ```
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: AtomicU64,
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl ManualClock {
+    /// Creates a new [`ManualClock`] reading zero.
+    pub const fn new() -> Self {
+        Self {
+            now: AtomicU64::new(0),
+        }
+    }
+
+    /// Moves the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        self.now.fetch_add(by.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Sets the reading of the clock to `now`. Moving it backwards is allowed, but it's not monotonic anymore.
+    pub fn set(&self, now: Duration) {
+        self.now.store(now.as_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for ManualClock {
+    fn now_millis(&self) -> u64 {
+        self.now.load(Ordering::Relaxed)
+    }
+}
+
+/// The [`Clock`] of a function set with [`set_clock`].
+struct FnClock(fn() -> u64);
+
+impl Clock for FnClock {
+    fn now_millis(&self) -> u64 {
+        (self.0)()
+    }
+}
+
+/// Sets the monotonic clock, in milliseconds, used by all the timestamps of the tracer: the [`uptime`],
+/// the sliding windows of the recent allocations (see [`crate::RECENT_WINDOW`]), the adaptive sampling windows,
+/// the reports and the dumps.
+///
+/// By default the clock is the [`MonotonicClock`]. A custom clock makes the time-dependent features deterministic
+/// in tests, and it's needed where [`Instant`] is not available. The start of the tracer is captured again
+/// from the new clock, so the [`uptime`] restarts from zero: set it before tracing starts.
+/// To use a [`Clock`] implementor instead, inject it with [`crate::LeaktracerAllocator::with_clock`].
+///
+/// ```rust
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// static NOW: AtomicU64 = AtomicU64::new(0);
+///
+/// leaktracer::set_clock(|| NOW.load(Ordering::Relaxed));
+/// NOW.store(1500, Ordering::Relaxed);
+/// assert_eq!(leaktracer::uptime().as_millis(), 1500);
+/// ```
+pub fn set_clock(clock: fn() -> u64) {
+    replace_clock(crate::alloc::bookkeeping(|| {
+        Box::leak(Box::new(FnClock(clock)))
+    }));
+}
+
+/// Installs the `clock` injected into the allocator, the first time only.
+pub(crate) fn inject_clock(clock: &'static dyn Clock) {
+    INJECTED.get_or_init(|| {
+        replace_clock(clock);
+        clock
+    });
+}
+
+/// Replaces the clock read by the tracer, capturing the tracer start again from it.
+fn replace_clock(clock: &'static dyn Clock) {
+    CLOCK.store(Some(clock));
+    START.store(clock.now_millis(), Ordering::Relaxed);
+}
+
+/// Returns the current reading of the clock, in milliseconds.
+fn now() -> u64 {
+    CLOCK.load().unwrap_or(&MonotonicClock).now_millis()
+}
+
+/// Captures the tracer start, if it hasn't been captured yet, returning it.
+pub(crate) fn mark_start() -> u64 {
+    let now = now();
+    match START.compare_exchange(u64::MAX, now, Ordering::Relaxed, Ordering::Relaxed) {
+        Ok(_) => now,
+        Err(start) => start,
+    }
+}
+
+/// Returns the time elapsed since the tracer observed its first allocation, according to the clock
+/// (see [`set_clock`]), with a millisecond resolution.
+///
+/// If no allocation has been traced yet, the start is captured now.
+pub fn uptime() -> Duration {
+    let start = mark_start();
+    Duration::from_millis(now().saturating_sub(start))
+}
+
+/// Measures the time spent by the tracer on an allocation, for the adaptive sampling.
+///
+/// It reads [`Instant`] rather than the [`Clock`], which has a millisecond resolution.
+/// Where [`Instant`] is not available, the elapsed time is always zero.
+pub(crate) struct Stopwatch(Option<Instant>);
+
+impl Stopwatch {
+    /// Starts a new [`Stopwatch`].
+    pub(crate) fn start() -> Self {
+        Self(HAS_INSTANT.then(Instant::now))
+    }
+
+    /// Returns the time elapsed since the [`Stopwatch`] was started.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.0.map(|start| start.elapsed()).unwrap_or_default()
+    }
+}
+
+/// Returns the events per second of `count` events over `elapsed`, `0.0` if no time elapsed.
+pub(crate) fn rate(count: usize, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        return 0.0;
+    }
+
+    count as f64 / elapsed.as_secs_f64()
+}
+
+/// Formats a [`Duration`] in a human readable way (e.g. `5m 12s`).
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+
+    if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{}.{:03}s", seconds, duration.subsec_millis())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_increase_uptime() {
+        let before = uptime();
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(uptime() > before);
+    }
+
+    #[test]
+    fn test_should_advance_manual_clock() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.now_millis(), 0);
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now_millis(), 1500);
+        clock.set(Duration::from_secs(60));
+        assert_eq!(clock.now_millis(), 60_000);
+    }
+
+    #[test]
+    fn test_should_read_monotonic_clock() {
+        let before = MonotonicClock.now_millis();
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(MonotonicClock.now_millis() >= before + 10);
+    }
+
+    #[test]
+    fn test_should_compute_rate() {
+        assert_eq!(rate(300, Duration::from_secs(60)), 5.0);
+        assert_eq!(rate(3, Duration::from_millis(1500)), 2.0);
+        assert_eq!(rate(300, Duration::ZERO), 0.0);
+    }
+
+    #[test]
+    fn test_should_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.500s");
+        assert_eq!(format_duration(Duration::from_secs(312)), "5m 12s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h 2m 3s");
+    }
+}
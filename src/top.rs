@@ -0,0 +1,159 @@
+//! A colorized "top"-style table of the biggest symbols, printed to stderr.
+
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+
+use crate::report::{ReportOptions, format_bytes};
+use crate::snapshot::{self, SymbolTableSnapshot};
+use crate::time;
+
+/// Number of rows highlighted as the biggest symbols.
+const HIGHLIGHTED_ROWS: usize = 3;
+
+const BOLD: &str = "\x1b[1m";
+const YELLOW: &str = "\x1b[33m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Allocated bytes of each symbol at the previous [`print_top`] call.
+static PREVIOUS: Mutex<Option<HashMap<&'static str, usize>>> = Mutex::new(None);
+
+/// Prints to stderr a column-aligned table of the `n` biggest symbols, with the totals in the header.
+///
+/// Each row shows the difference of the allocated bytes since the previous `print_top` call,
+/// in red when the symbol grew and in green when it shrank. The biggest symbols are highlighted.
+///
+/// Colors are disabled when stderr isn't a terminal or the `NO_COLOR` environment variable is set.
+///
+/// The symbol table is copied under the lock and the lock is released before printing.
+pub fn print_top(n: usize) -> io::Result<()> {
+    let snapshot = snapshot::snapshot()?;
+    let color = io::stderr().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+
+    let mut previous = PREVIOUS
+        .lock()
+        .map_err(|_| io::Error::other("previous top lock poisoned"))?;
+    write_top(
+        &snapshot,
+        previous.as_ref(),
+        n,
+        color,
+        &mut io::stderr().lock(),
+    )?;
+    *previous = Some(
+        snapshot
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.name, symbol.allocated))
+            .collect(),
+    );
+
+    Ok(())
+}
+
+/// Writes the top table of `snapshot`, with the deltas against `previous` if any.
+fn write_top(
+    snapshot: &SymbolTableSnapshot,
+    previous: Option<&HashMap<&'static str, usize>>,
+    n: usize,
+    color: bool,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let paint = |code: &'static str| if color { code } else { "" };
+    let reset = paint(RESET);
+
+    writeln!(
+        w,
+        "{}leaktracer top: {} live in {} symbols, uptime {}{reset}",
+        paint(BOLD),
+        format_bytes(snapshot.allocated),
+        snapshot.symbols.len(),
+        time::format_duration(snapshot.uptime)
+    )?;
+    writeln!(w, "{:>12} {:>12} {:>8}  SYMBOL", "BYTES", "DELTA", "COUNT")?;
+
+    let options = ReportOptions {
+        top: Some(n),
+        ..Default::default()
+    };
+    for (i, symbol) in options.symbols(snapshot).into_iter().enumerate() {
+        let (delta, delta_color) = match previous {
+            None => ("-".to_string(), ""),
+            Some(previous) => {
+                let before = previous.get(symbol.name).copied().unwrap_or_default();
+                if symbol.allocated > before {
+                    (format!("+{}", symbol.allocated - before), paint(RED))
+                } else if symbol.allocated < before {
+                    (format!("-{}", before - symbol.allocated), paint(GREEN))
+                } else {
+                    ("0".to_string(), "")
+                }
+            }
+        };
+        let row_color = if i < HIGHLIGHTED_ROWS {
+            paint(YELLOW)
+        } else {
+            ""
+        };
+        writeln!(
+            w,
+            "{row_color}{:>12}{reset} {delta_color}{delta:>12}{reset} {:>8}  {row_color}{}{reset}",
+            format_bytes(symbol.allocated),
+            symbol.count,
+            symbol.name,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_write_top_without_color() {
+        let previous = HashMap::from([("app::cache::insert", 1024), ("app::main", 2048)]);
+
+        let mut buf = Vec::new();
+        write_top(&synthetic_snapshot(), Some(&previous), 3, false, &mut buf)
+            .expect("failed to write top");
+
+        let expected = [
+            "leaktracer top: 5.5 KiB live in 3 symbols, uptime 1.500s",
+            "       BYTES        DELTA    COUNT  SYMBOL",
+            "     4.0 KiB        +3072        2  app::cache::insert",
+            "     1.0 KiB        -1024       10  app::main",
+            "       512 B         +512        5  app::config::load",
+        ];
+        assert_eq!(String::from_utf8(buf).unwrap(), expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn test_should_write_top_without_previous() {
+        let mut buf = Vec::new();
+        write_top(&synthetic_snapshot(), None, 1, false, &mut buf).expect("failed to write top");
+
+        let top = String::from_utf8(buf).unwrap();
+        assert_eq!(top.lines().count(), 3);
+        assert_eq!(
+            top.lines().last().unwrap(),
+            "     4.0 KiB            -        2  app::cache::insert"
+        );
+    }
+
+    #[test]
+    fn test_should_write_top_with_color() {
+        let mut buf = Vec::new();
+        write_top(&synthetic_snapshot(), None, 1, true, &mut buf).expect("failed to write top");
+
+        let top = String::from_utf8(buf).unwrap();
+        assert!(top.contains(YELLOW));
+        assert!(top.contains(RESET));
+    }
+}
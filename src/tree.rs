@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::alloc::LockResult;
+
+/// A node of the symbol tree built by [`symbol_tree`], grouping the symbols by their `::` path segments.
+///
+/// Each node holds the totals of its own symbol (if any) and of all its descendants. A name can be both a symbol
+/// and the prefix of other symbols, e.g. `app::cache` and `app::cache::insert`: its own bytes are in
+/// [`SymbolNode::self_allocated`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolNode {
+    /// The path segment of this node (e.g. `alloc` for `leaktracer::alloc`). Empty for the root node.
+    pub name: String,
+    /// Allocated bytes of this node and all its descendants.
+    pub allocated: usize,
+    /// Allocation count of this node and all its descendants.
+    pub count: usize,
+    /// Allocated bytes of the symbol named after the full path of this node, excluding its descendants.
+    /// `0` if there is no such symbol.
+    pub self_allocated: usize,
+    /// The child nodes, sorted by name.
+    pub children: Vec<SymbolNode>,
+}
+
+impl SymbolNode {
+    /// Builds a tree from `(name, allocated, count)` tuples, returning the root node.
+    pub fn from_symbols<'a, I>(symbols: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, usize, usize)>,
+    {
+        let mut root = Builder::default();
+        for (name, allocated, count) in symbols {
+            let mut node = &mut root;
+            node.allocated += allocated;
+            node.count += count;
+            for segment in name.split("::") {
+                node = node.children.entry(segment.to_string()).or_default();
+                node.allocated += allocated;
+                node.count += count;
+            }
+            node.self_allocated += allocated;
+        }
+
+        root.build(String::new())
+    }
+
+    /// Gets a child node by its segment name.
+    pub fn child(&self, name: &str) -> Option<&SymbolNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Writes the descendants of this node as an indented tree, like `du -h`: one line per node with its
+    /// total bytes, followed by its own bytes when it's both a symbol and a prefix of other symbols.
+    ///
+    /// The children are sorted by total bytes, descending. Nodes under `min_bytes` are hidden, and chains of
+    /// nodes with a single child and no bytes of their own are folded into one line, e.g. `std::vec::push`.
+    ///
+    /// ```text
+    ///   5.6 KiB  app
+    ///   4.1 KiB    cache (self 100 B)
+    ///   4.0 KiB      insert
+    ///     512 B    config::load
+    /// ```
+    pub fn write_tree(&self, w: &mut dyn Write, min_bytes: usize) -> io::Result<()> {
+        self.write_children(w, min_bytes, 0)
+    }
+
+    /// Writes the children of this node at `depth`, recursively.
+    fn write_children(&self, w: &mut dyn Write, min_bytes: usize, depth: usize) -> io::Result<()> {
+        let mut children: Vec<_> = self
+            .children
+            .iter()
+            .filter(|child| child.allocated >= min_bytes)
+            .collect();
+        children.sort_by(|a, b| b.allocated.cmp(&a.allocated).then(a.name.cmp(&b.name)));
+
+        for child in children {
+            let mut node = child;
+            let mut name = node.name.clone();
+            while node.self_allocated == 0 && node.children.len() == 1 {
+                node = &node.children[0];
+                name.push_str("::");
+                name.push_str(&node.name);
+            }
+
+            write!(
+                w,
+                "{:>9}  {:indent$}{name}",
+                crate::report::format_bytes(node.allocated),
+                "",
+                indent = depth * 2
+            )?;
+            if node.self_allocated > 0 && !node.children.is_empty() {
+                write!(
+                    w,
+                    " (self {})",
+                    crate::report::format_bytes(node.self_allocated)
+                )?;
+            }
+            writeln!(w)?;
+            node.write_children(w, min_bytes, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Intermediate node used to build the tree.
+#[derive(Default)]
+struct Builder {
+    allocated: usize,
+    count: usize,
+    self_allocated: usize,
+    children: BTreeMap<String, Builder>,
+}
+
+impl Builder {
+    fn build(self, name: String) -> SymbolNode {
+        SymbolNode {
+            name,
+            allocated: self.allocated,
+            count: self.count,
+            self_allocated: self.self_allocated,
+            children: self
+                .children
+                .into_iter()
+                .map(|(name, child)| child.build(name))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the symbols organized as a tree by their `::` path segments, with aggregated totals at each node.
+///
+/// The tree is built under the symbol table lock, and its allocations are not traced.
+pub fn symbol_tree() -> LockResult<SymbolNode> {
+    crate::with_symbol_table(|table| {
+        SymbolNode::from_symbols(
+            table
+                .iter()
+                .map(|(name, symbol)| (*name, symbol.allocated(), symbol.count())),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn test_should_build_symbol_tree() {
+        let root =
+            SymbolNode::from_symbols([("a::b::c", 100, 1), ("a::b::d", 50, 2), ("e", 10, 1)]);
+        assert_eq!(root.name, "");
+        assert_eq!(root.allocated, 160);
+        assert_eq!(root.count, 4);
+
+        let a = root.child("a").expect("a should exist");
+        assert_eq!(a.allocated, 150);
+        assert_eq!(a.count, 3);
+        assert_eq!(a.children.len(), 1);
+
+        let b = a.child("b").expect("b should exist");
+        assert_eq!(b.allocated, 150);
+        let names: Vec<_> = b.children.iter().map(|child| child.name.as_str()).collect();
+        assert_eq!(names, ["c", "d"]);
+        assert_eq!(b.child("c").unwrap().allocated, 100);
+        assert_eq!(b.child("d").unwrap().count, 2);
+        assert!(b.child("c").unwrap().children.is_empty());
+
+        assert_eq!(root.child("e").unwrap().allocated, 10);
+        assert_eq!(root.child("e").unwrap().self_allocated, 10);
+        assert_eq!(b.self_allocated, 0);
+    }
+
+    fn render(root: &SymbolNode, min_bytes: usize) -> String {
+        let mut out = Vec::new();
+        root.write_tree(&mut out, min_bytes).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn tree_fixture() -> SymbolNode {
+        SymbolNode::from_symbols([
+            ("app::main", 1024, 10),
+            // both a symbol and the prefix of another one
+            ("app::cache", 100, 1),
+            ("app::cache::insert", 4096, 2),
+            ("app::config::load", 512, 5),
+            ("std::vec::push", 300, 3),
+        ])
+    }
+
+    #[test]
+    fn test_should_write_tree() {
+        let root = tree_fixture();
+        assert_eq!(
+            root.child("app")
+                .unwrap()
+                .child("cache")
+                .unwrap()
+                .self_allocated,
+            100
+        );
+
+        assert_eq!(
+            render(&root, 0),
+            concat!(
+                "  5.6 KiB  app\n",
+                "  4.1 KiB    cache (self 100 B)\n",
+                "  4.0 KiB      insert\n",
+                "  1.0 KiB    main\n",
+                "    512 B    config::load\n",
+                "    300 B  std::vec::push\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_should_hide_tree_nodes_under_threshold() {
+        assert_eq!(
+            render(&tree_fixture(), 1000),
+            concat!(
+                "  5.6 KiB  app\n",
+                "  4.1 KiB    cache (self 100 B)\n",
+                "  4.0 KiB      insert\n",
+                "  1.0 KiB    main\n",
+            )
+        );
+        assert_eq!(render(&tree_fixture(), usize::MAX), "");
+    }
+
+    #[test]
+    fn test_should_get_symbol_tree() {
+        crate::init_symbol_table(&["leaktracer"]);
+
+        let root = symbol_tree().expect("failed to build symbol tree");
+        assert_eq!(root.name, "");
+    }
+}
@@ -0,0 +1,301 @@
+//! An interactive heap monitor for the terminal.
+//!
+//! [`run`] takes over the terminal and shows a continuously refreshing table of the symbols,
+//! sortable by allocated bytes, allocation count or growth since the previous refresh,
+//! along with a sparkline of the traced live bytes.
+//!
+//! Keybindings:
+//!
+//! - `b`, `c`, `d`: sort by bytes, count or delta
+//! - `p`: pause or resume tracing (see [`crate::set_enabled`])
+//! - `r`: reset the stats (see [`crate::reset`])
+//! - `q`: quit, restoring the terminal
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use ratatui::widgets::{Block, Paragraph, Row, Sparkline, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::report::format_bytes;
+use crate::snapshot::{self, SymbolTableSnapshot};
+use crate::time;
+
+/// Interval between two refreshes of the table.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of samples of the live bytes kept for the sparkline.
+const HISTORY_LEN: usize = 256;
+
+/// Starts the heap monitor on its own thread, taking over the terminal.
+///
+/// The monitor exits on `q`, restoring the terminal. The allocations made by the monitor are not traced.
+pub fn run() -> JoinHandle<io::Result<()>> {
+    std::thread::spawn(|| {
+        crate::alloc::untraced(|| {
+            let mut terminal = ratatui::try_init()?;
+            let res = run_app(&mut terminal);
+            ratatui::restore();
+
+            res
+        })
+    })
+}
+
+/// Runs the event loop until the user quits.
+fn run_app(terminal: &mut DefaultTerminal) -> io::Result<()> {
+    let mut app = App::default();
+    loop {
+        let snapshot = snapshot::snapshot()?;
+        app.update(&snapshot);
+        terminal.draw(|frame| app.render(frame))?;
+
+        // handle the keys until the next refresh
+        let deadline = Instant::now() + REFRESH_INTERVAL;
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            if !event::poll(timeout)? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                if app.on_key(key.code) {
+                    return Ok(());
+                }
+                terminal.draw(|frame| app.render(frame))?;
+            }
+        }
+    }
+}
+
+/// The column the symbols are sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SortBy {
+    #[default]
+    Bytes,
+    Count,
+    Delta,
+}
+
+/// A row of the symbol table.
+#[derive(Debug)]
+struct SymbolRow {
+    name: &'static str,
+    allocated: usize,
+    count: usize,
+    /// Difference of the allocated bytes since the previous refresh.
+    delta: isize,
+}
+
+/// The state of the heap monitor.
+#[derive(Debug, Default)]
+struct App {
+    sort: SortBy,
+    rows: Vec<SymbolRow>,
+    /// Allocated bytes of each symbol at the previous refresh.
+    previous: HashMap<&'static str, usize>,
+    /// Samples of the live bytes, one per refresh.
+    history: VecDeque<u64>,
+    allocated: usize,
+    uptime: Duration,
+}
+
+impl App {
+    /// Updates the state with a new snapshot.
+    fn update(&mut self, snapshot: &SymbolTableSnapshot) {
+        self.rows = snapshot
+            .symbols
+            .iter()
+            .map(|symbol| {
+                let before = self
+                    .previous
+                    .get(symbol.name)
+                    .copied()
+                    .unwrap_or(symbol.allocated);
+                SymbolRow {
+                    name: symbol.name,
+                    allocated: symbol.allocated,
+                    count: symbol.count,
+                    delta: symbol.allocated as isize - before as isize,
+                }
+            })
+            .collect();
+        self.previous = snapshot
+            .symbols
+            .iter()
+            .map(|symbol| (symbol.name, symbol.allocated))
+            .collect();
+
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(snapshot.allocated as u64);
+        self.allocated = snapshot.allocated;
+        self.uptime = snapshot.uptime;
+
+        self.sort_rows();
+    }
+
+    /// Sorts the rows by the current column, in descending order.
+    fn sort_rows(&mut self) {
+        match self.sort {
+            SortBy::Bytes => self
+                .rows
+                .sort_by_key(|row| std::cmp::Reverse(row.allocated)),
+            SortBy::Count => self.rows.sort_by_key(|row| std::cmp::Reverse(row.count)),
+            SortBy::Delta => self.rows.sort_by_key(|row| std::cmp::Reverse(row.delta)),
+        }
+    }
+
+    /// Handles a key press. Returns whether the monitor must quit.
+    fn on_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') => return true,
+            KeyCode::Char('b') => self.sort = SortBy::Bytes,
+            KeyCode::Char('c') => self.sort = SortBy::Count,
+            KeyCode::Char('d') => self.sort = SortBy::Delta,
+            KeyCode::Char('p') => crate::set_enabled(!crate::is_enabled()),
+            KeyCode::Char('r') => {
+                // a poisoned table can't be reset; the next refresh reports the error
+                let _ = crate::reset();
+                self.previous.clear();
+                self.history.clear();
+            }
+            _ => {}
+        }
+        self.sort_rows();
+
+        false
+    }
+
+    /// Renders the monitor on the frame.
+    fn render(&self, frame: &mut Frame) {
+        let [header, sparkline, table, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(6),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(format!(
+                "leaktracer: {} live in {} symbols, uptime {}{}",
+                format_bytes(self.allocated),
+                self.rows.len(),
+                time::format_duration(self.uptime),
+                if crate::is_enabled() { "" } else { " [PAUSED]" }
+            ))
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+            header,
+        );
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::bordered().title("live bytes"))
+                .data(&self.history)
+                .style(Style::default().fg(Color::Cyan)),
+            sparkline,
+        );
+
+        let header_style = |sort: SortBy| {
+            if self.sort == sort {
+                Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            } else {
+                Style::default().add_modifier(Modifier::BOLD)
+            }
+        };
+        let rows = self.rows.iter().map(|row| {
+            let delta_style = match row.delta {
+                delta if delta > 0 => Style::default().fg(Color::Red),
+                delta if delta < 0 => Style::default().fg(Color::Green),
+                _ => Style::default(),
+            };
+            Row::new(vec![
+                Span::raw(format_bytes(row.allocated)),
+                Span::styled(format!("{:+}", row.delta), delta_style),
+                Span::raw(row.count.to_string()),
+                Span::raw(row.name),
+            ])
+        });
+        frame.render_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Length(12),
+                    Constraint::Length(12),
+                    Constraint::Length(8),
+                    Constraint::Min(0),
+                ],
+            )
+            .header(Row::new(vec![
+                Span::styled("BYTES", header_style(SortBy::Bytes)),
+                Span::styled("DELTA", header_style(SortBy::Delta)),
+                Span::styled("COUNT", header_style(SortBy::Count)),
+                Span::styled("SYMBOL", Style::default().add_modifier(Modifier::BOLD)),
+            ])),
+            table,
+        );
+        frame.render_widget(
+            Paragraph::new("b/c/d: sort by bytes/count/delta  p: pause  r: reset  q: quit"),
+            help,
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+    use crate::test_utils::synthetic_snapshot;
+
+    #[test]
+    fn test_should_render_frame() {
+        let mut app = App::default();
+        app.update(&synthetic_snapshot());
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 16)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("leaktracer: 5.5 KiB live in 3 symbols"));
+        assert!(screen.contains("app::cache::insert"));
+        assert!(screen.contains("app::config::load"));
+    }
+
+    #[test]
+    fn test_should_sort_rows_and_quit() {
+        let mut app = App::default();
+        app.update(&synthetic_snapshot());
+        assert_eq!(app.rows[0].name, "app::cache::insert");
+
+        assert!(!app.on_key(KeyCode::Char('c')));
+        assert_eq!(app.rows[0].name, "app::main");
+
+        // the deltas are computed against the previous refresh
+        let mut snapshot = synthetic_snapshot();
+        snapshot.symbols[2].allocated += 4096;
+        app.update(&snapshot);
+        assert!(!app.on_key(KeyCode::Char('d')));
+        assert_eq!(app.rows[0].name, "app::config::load");
+        assert_eq!(app.rows[0].delta, 4096);
+        assert_eq!(app.history.len(), 2);
+
+        assert!(app.on_key(KeyCode::Char('q')));
+    }
+}
@@ -0,0 +1,58 @@
+//! The allocation logging goes through the process-global logger, so it's tested in its own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use std::sync::Mutex;
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+/// Logger capturing the messages of the records.
+struct CapturingLogger;
+
+static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() == log::Level::Trace {
+            RECORDS.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger;
+
+/// Allocates a buffer of 4242 bytes.
+#[inline(never)]
+fn allocate() -> Vec<u8> {
+    Vec::with_capacity(4242)
+}
+
+#[test]
+fn test_should_log_each_allocation() {
+    leaktracer::init_symbol_table(&["alloc_logging"]);
+    log::set_logger(&LOGGER).expect("failed to set logger");
+    log::set_max_level(log::LevelFilter::Trace);
+    leaktracer::enable_alloc_logging();
+
+    let buffers: Vec<_> = (0..3).map(|_| std::hint::black_box(allocate())).collect();
+
+    let records = RECORDS.lock().unwrap();
+    assert_eq!(
+        records
+            .iter()
+            .filter(|record| record.starts_with("alloc 4242 bytes @ "))
+            .count(),
+        3
+    );
+    drop(records);
+    drop(buffers);
+}
@@ -0,0 +1,45 @@
+//! The attribution coverage is computed from the global counters, so it's tested in its own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+const BUFFER_SIZE: usize = 8 * 1024 * 1024;
+
+mod traced {
+    #[inline(never)]
+    pub fn allocate() -> Vec<u8> {
+        vec![0; super::BUFFER_SIZE]
+    }
+}
+
+mod untraced {
+    #[inline(never)]
+    pub fn allocate() -> Vec<u8> {
+        vec![0; super::BUFFER_SIZE]
+    }
+}
+
+#[test]
+fn test_should_compute_attribution_coverage() {
+    leaktracer::init_symbol_table(&["attribution_coverage::traced"]);
+
+    let traced = traced::allocate();
+    let untraced = untraced::allocate();
+    let coverage = leaktracer::attribution_coverage();
+    assert!(
+        (0.45..0.55).contains(&coverage),
+        "coverage of half of the bytes: {coverage}"
+    );
+
+    drop(untraced);
+    let coverage = leaktracer::attribution_coverage();
+    assert!(
+        (0.9..=1.0).contains(&coverage),
+        "coverage of the traced bytes: {coverage}"
+    );
+    drop(traced);
+}
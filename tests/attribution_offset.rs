@@ -0,0 +1,55 @@
+//! The attribution offset is global, so it's tested in its own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+/// A thin allocation helper, which shouldn't get the blame.
+#[inline(never)]
+fn alloc_buffer(size: usize) -> Vec<u8> {
+    std::hint::black_box(vec![0; size])
+}
+
+#[inline(never)]
+fn real_caller() -> Vec<u8> {
+    alloc_buffer(1024)
+}
+
+#[inline(never)]
+fn outer_caller() -> Vec<u8> {
+    alloc_buffer(2048)
+}
+
+/// Returns the live bytes attributed to the symbol ending with `suffix`.
+fn allocated(suffix: &str) -> usize {
+    leaktracer::with_symbol_table(|table| {
+        table
+            .iter()
+            .filter(|(name, _)| name.ends_with(suffix))
+            .map(|(_, symbol)| symbol.allocated())
+            .sum()
+    })
+    .expect("failed to access symbol table")
+}
+
+#[test]
+fn test_should_attribute_to_caller_of_helper() {
+    leaktracer::init_symbol_table(&["attribution_offset"]);
+
+    leaktracer::set_attribution_offset(1);
+    let shifted = real_caller();
+    assert_eq!(allocated("::real_caller"), 1024);
+    assert_eq!(allocated("::alloc_buffer"), 0);
+
+    // past the stack, the matching frame is kept
+    leaktracer::set_attribution_offset(10_000);
+    let kept = outer_caller();
+    assert_eq!(allocated("::alloc_buffer"), 2048);
+    assert_eq!(allocated("::outer_caller"), 0);
+
+    leaktracer::set_attribution_offset(0);
+    drop((shifted, kept));
+}
@@ -0,0 +1,38 @@
+//! The attribution size threshold is global, so it's tested in its own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+#[inline(never)]
+fn small() -> Vec<u8> {
+    vec![0; 1024]
+}
+
+#[inline(never)]
+fn large() -> Vec<u8> {
+    vec![0; 2 * 1024 * 1024]
+}
+
+#[test]
+fn test_should_attribute_only_large_allocations() {
+    leaktracer::init_symbol_table(&["attribution_threshold"]);
+    leaktracer::set_attribution_size_threshold(1024 * 1024);
+
+    let before = leaktracer::global_allocated();
+    let small = small();
+    assert!(leaktracer::global_allocated() >= before + small.len());
+    let large = large();
+
+    assert_eq!(
+        leaktracer::symbol_stats("attribution_threshold::small"),
+        None
+    );
+    let stats =
+        leaktracer::symbol_stats("attribution_threshold::large").expect("large should be traced");
+    assert_eq!(stats.allocated, large.len());
+    assert_eq!(stats.count, 1);
+}
@@ -0,0 +1,59 @@
+//! The clock is global, so the custom clock is tested in its own process.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use leaktracer::{LeaktracerAllocator, RECENT_WINDOW};
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+static NOW: AtomicU64 = AtomicU64::new(1_000);
+
+fn fake_clock() -> u64 {
+    NOW.load(Ordering::Relaxed)
+}
+
+fn advance(by: Duration) {
+    NOW.fetch_add(by.as_millis() as u64, Ordering::Relaxed);
+}
+
+#[inline(never)]
+fn allocate() -> Vec<u8> {
+    vec![0; 1024]
+}
+
+fn recent_allocated() -> usize {
+    leaktracer::with_symbol_table(|table| {
+        table
+            .iter()
+            .find(|(name, _)| name.ends_with("::allocate"))
+            .map(|(_, symbol)| symbol.recent(RECENT_WINDOW).0)
+            .expect("allocate should be traced")
+    })
+    .expect("failed to access symbol table")
+}
+
+#[test]
+fn test_should_use_custom_clock() {
+    leaktracer::set_clock(fake_clock);
+    leaktracer::init_symbol_table(&["clock"]);
+    assert_eq!(leaktracer::uptime(), Duration::ZERO);
+
+    advance(Duration::from_millis(1500));
+    assert_eq!(leaktracer::uptime(), Duration::from_millis(1500));
+
+    let buf = allocate();
+    assert_eq!(recent_allocated(), 1024);
+    // the allocation leaves the window only when the fake clock says so
+    advance(RECENT_WINDOW - Duration::from_secs(10));
+    assert_eq!(recent_allocated(), 1024);
+    advance(RECENT_WINDOW);
+    assert_eq!(recent_allocated(), 0);
+    assert_eq!(
+        leaktracer::uptime(),
+        Duration::from_millis(1500) + RECENT_WINDOW * 2 - Duration::from_secs(10)
+    );
+
+    drop(buf);
+}
@@ -0,0 +1,51 @@
+//! The consistent snapshots are tested against allocating threads, through the global allocator, in their own process.
+//! There are no threads on WebAssembly.
+#![cfg(not(target_family = "wasm"))]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+/// Allocates and frees buffers of varying sizes until `stop` is set.
+fn churn(stop: &AtomicBool, seed: usize) {
+    let mut held = Vec::with_capacity(64);
+    let mut size = seed;
+    while !stop.load(Ordering::Relaxed) {
+        size = (size * 31 + 7) % 4096;
+        held.push(vec![0u8; size + 1]);
+        if held.len() == 64 {
+            held.clear();
+        }
+    }
+}
+
+#[test]
+fn test_should_take_consistent_snapshots_while_allocating() {
+    leaktracer::init_symbol_table(&["consistent_snapshot"]);
+    // some allocations are not attributed to any symbol
+    leaktracer::set_attribution_size_threshold(256);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let threads: Vec<_> = (0..4)
+        .map(|seed| {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || churn(&stop, seed))
+        })
+        .collect();
+
+    for _ in 0..100 {
+        let snapshot = leaktracer::consistent_snapshot().expect("failed to take snapshot");
+        let allocated: usize = snapshot.symbols.iter().map(|symbol| symbol.allocated).sum();
+        assert_eq!(snapshot.allocated, allocated);
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
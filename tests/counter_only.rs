@@ -0,0 +1,52 @@
+//! The counter-only mode, without the `symbols` feature, is tested through the global allocator, in its own process.
+#![cfg(not(feature = "symbols"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+const BUFFER_SIZE: usize = 1 << 20;
+
+#[test]
+fn test_should_count_without_symbols() {
+    leaktracer::init_symbol_table(&["counter_only"]);
+    let allocated = leaktracer::global_allocated();
+    let before = leaktracer::quick_stats();
+
+    let buffer = std::hint::black_box(vec![0u8; BUFFER_SIZE]);
+    assert!(leaktracer::global_allocated() >= allocated + BUFFER_SIZE);
+    let after = leaktracer::quick_stats();
+    assert!(after.total_allocations > before.total_allocations);
+    assert!(after.peak >= allocated + BUFFER_SIZE);
+
+    // the allocations are registered, but no symbol is named without a call stack
+    assert_eq!(after.symbol_count, 0);
+    assert!(
+        leaktracer::with_symbol_table(|table| table.is_empty())
+            .expect("failed to access symbol table")
+    );
+    assert!(
+        leaktracer::snapshot()
+            .expect("failed to take snapshot")
+            .symbols
+            .is_empty()
+    );
+
+    drop(buffer);
+    assert!(leaktracer::global_allocated() < allocated + BUFFER_SIZE);
+    assert!(leaktracer::quick_stats().total_deallocations > before.total_deallocations);
+
+    // a symbol named without a call stack is honored
+    leaktracer::set_fixed_symbol(Some("counter_only::fixed"));
+    let buffer = std::hint::black_box(vec![0u8; BUFFER_SIZE]);
+    leaktracer::set_fixed_symbol(None);
+    let fixed = leaktracer::with_symbol_table(|table| {
+        table
+            .get("counter_only::fixed")
+            .map(|symbol| symbol.allocated())
+    })
+    .expect("failed to access symbol table");
+    assert_eq!(fixed, Some(BUFFER_SIZE));
+    drop(buffer);
+}
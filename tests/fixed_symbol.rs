@@ -0,0 +1,29 @@
+//! The fixed symbol is global, so it's tested in its own process.
+//! It needs no call stack, so it's tested without the `symbols` feature and on WebAssembly too.
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+fn allocated(name: &'static str) -> usize {
+    leaktracer::with_symbol_table(|table| table.get(name).map_or(0, |symbol| symbol.allocated()))
+        .expect("failed to access symbol table")
+}
+
+#[test]
+fn test_should_attribute_allocations_to_fixed_symbol() {
+    leaktracer::init_symbol_table(&["fixed_symbol"]);
+    leaktracer::set_fixed_symbol(Some("test::sym"));
+
+    let before = allocated("test::sym");
+    let buffer = std::hint::black_box(vec![0u8; 4096]);
+    assert_eq!(allocated("test::sym") - before, 4096);
+    drop(buffer);
+    assert_eq!(allocated("test::sym"), before);
+
+    leaktracer::set_fixed_symbol(None);
+    let buffer = std::hint::black_box(vec![0u8; 4096]);
+    assert_eq!(allocated("test::sym"), before);
+    drop(buffer);
+}
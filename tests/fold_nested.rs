@@ -0,0 +1,50 @@
+//! The folding windows apply to the global allocator, so they're tested in their own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+/// A value whose drop allocates, e.g. to log a message.
+struct Noisy(Vec<u8>);
+
+impl Drop for Noisy {
+    fn drop(&mut self) {
+        let message = format!("dropping {} bytes", self.0.len());
+        std::hint::black_box(message);
+    }
+}
+
+#[inline(never)]
+fn replace(slot: &mut Noisy) {
+    *slot = Noisy(vec![0; 64]);
+}
+
+fn total_count() -> usize {
+    leaktracer::symbol_stats("fold_nested::replace")
+        .expect("replace should be traced")
+        .total_count
+}
+
+#[test]
+fn test_should_fold_allocations_of_drop() {
+    leaktracer::init_symbol_table(&["fold_nested"]);
+    let mut slot = Noisy(Vec::new());
+
+    // the drop of the replaced value allocates too
+    replace(&mut slot);
+    let unfolded = total_count();
+    assert_eq!(unfolded, 2);
+
+    {
+        let _fold = leaktracer::fold_nested();
+        replace(&mut slot);
+    }
+    assert_eq!(total_count(), unfolded + 1);
+
+    let stats = leaktracer::symbol_stats("fold_nested::replace").unwrap();
+    assert_eq!(stats.count, 1);
+    assert_eq!(stats.allocated, 64);
+}
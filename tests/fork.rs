@@ -0,0 +1,94 @@
+//! A forked child must be able to allocate and dump, even if another thread was allocating while forking,
+//! so this test runs in its own binary, with the tracer as the global allocator.
+#![cfg(unix)]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+unsafe extern "C" {
+    fn fork() -> i32;
+    fn waitpid(pid: i32, status: *mut i32, options: i32) -> i32;
+    fn kill(pid: i32, signal: i32) -> i32;
+    fn _exit(status: i32) -> !;
+}
+
+const WNOHANG: i32 = 1;
+const SIGKILL: i32 = 9;
+
+/// Allocates and writes a report to `path` in the child, then exits with `0` on success.
+fn child(path: &std::path::Path) -> ! {
+    let ok = std::panic::catch_unwind(|| {
+        let buf = vec![0u8; 1024];
+        let mut file = std::fs::File::create(path).expect("failed to create report");
+        leaktracer::write_report(&mut file).is_ok() && buf.len() == 1024
+    })
+    .unwrap_or(false);
+    unsafe { _exit(if ok { 0 } else { 1 }) }
+}
+
+/// Waits for `pid` to exit, killing it if it doesn't within `timeout`, e.g. because it deadlocked.
+///
+/// Returns the exit status, [`None`] if it was killed.
+fn wait(pid: i32, timeout: Duration) -> Option<i32> {
+    let start = Instant::now();
+    let mut status = 0;
+    while start.elapsed() < timeout {
+        if unsafe { waitpid(pid, &mut status, WNOHANG) } == pid {
+            return Some(status);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    unsafe {
+        kill(pid, SIGKILL);
+        waitpid(pid, &mut status, 0);
+    }
+
+    None
+}
+
+#[test]
+fn test_should_allocate_and_dump_in_forked_child() {
+    leaktracer::init_symbol_table(&["fork"]);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let allocator = std::thread::spawn({
+        let stop = stop.clone();
+        move || {
+            while !stop.load(Ordering::Relaxed) {
+                std::hint::black_box(vec![0u8; 64]);
+            }
+        }
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    // fork repeatedly, so some forks happen while the other thread holds the symbol table lock
+    for i in 0..20 {
+        let path = dir.path().join(format!("child-{i}.txt"));
+        // the allocations above synchronize with the other thread, so give it time to take the lock again
+        let spin = Instant::now();
+        while spin.elapsed() < Duration::from_millis(1) {
+            std::hint::spin_loop();
+        }
+        let pid = unsafe { fork() };
+        assert!(pid >= 0, "fork failed");
+        if pid == 0 {
+            child(&path);
+        }
+
+        assert_eq!(
+            wait(pid, Duration::from_secs(10)),
+            Some(0),
+            "child {i} deadlocked or failed"
+        );
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    allocator.join().unwrap();
+}
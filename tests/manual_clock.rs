@@ -0,0 +1,50 @@
+//! The clock is global, so the clock injected into the allocator is tested in its own process.
+
+use std::time::Duration;
+
+use leaktracer::{LeaktracerAllocator, ManualClock, RECENT_WINDOW};
+
+static CLOCK: ManualClock = ManualClock::new();
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_clock(&CLOCK);
+
+#[inline(never)]
+fn allocate() -> Vec<u8> {
+    vec![0; 1024]
+}
+
+fn recent_allocated() -> usize {
+    leaktracer::with_symbol_table(|table| {
+        table
+            .iter()
+            .find(|(name, _)| name.ends_with("::allocate"))
+            .map(|(_, symbol)| symbol.recent(RECENT_WINDOW).0)
+            .expect("allocate should be traced")
+    })
+    .expect("failed to access symbol table")
+}
+
+#[test]
+fn test_should_use_injected_clock() {
+    leaktracer::init_symbol_table(&["manual_clock"]);
+    // the clock was installed by the allocations made before the test, and it didn't move since
+    assert_eq!(leaktracer::uptime(), Duration::ZERO);
+
+    CLOCK.advance(Duration::from_millis(1500));
+    assert_eq!(leaktracer::uptime(), Duration::from_millis(1500));
+
+    let buf = allocate();
+    assert_eq!(recent_allocated(), 1024);
+    // the allocation leaves the window only when the manual clock says so
+    CLOCK.advance(RECENT_WINDOW - Duration::from_secs(10));
+    assert_eq!(recent_allocated(), 1024);
+    CLOCK.advance(RECENT_WINDOW);
+    assert_eq!(recent_allocated(), 0);
+    assert_eq!(
+        leaktracer::uptime(),
+        Duration::from_millis(1500) + RECENT_WINDOW * 2 - Duration::from_secs(10)
+    );
+
+    drop(buf);
+}
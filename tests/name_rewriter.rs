@@ -0,0 +1,32 @@
+//! The name rewriter is global, so it's tested in its own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+#[inline(never)]
+fn allocate() -> Vec<u8> {
+    vec![0; 1024]
+}
+
+#[test]
+fn test_should_rewrite_symbol_names() {
+    leaktracer::set_name_rewriter(|name| name.to_uppercase());
+    leaktracer::init_symbol_table(&["name_rewriter"]);
+
+    let buffer = allocate();
+    assert_eq!(buffer.len(), 1024);
+
+    leaktracer::with_symbol_table(|table| {
+        let names: Vec<&str> = table.iter().map(|(name, _)| *name).collect();
+        assert!(
+            names.contains(&"NAME_REWRITER::ALLOCATE"),
+            "allocate should be traced as uppercase: {names:?}"
+        );
+        assert!(names.iter().all(|name| *name == name.to_uppercase()));
+    })
+    .expect("failed to access symbol table");
+}
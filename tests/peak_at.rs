@@ -0,0 +1,58 @@
+//! The clock is global, so the timestamps of the peaks are tested with a manual clock in their own process.
+
+use std::time::Duration;
+
+use leaktracer::{LeaktracerAllocator, ManualClock};
+
+static CLOCK: ManualClock = ManualClock::new();
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_clock(&CLOCK);
+
+#[inline(never)]
+fn allocate() -> Vec<u8> {
+    vec![0; 1024]
+}
+
+/// Returns the peak of the `allocate` symbol and the uptime it was reached at.
+fn peak() -> (usize, Duration) {
+    leaktracer::with_symbol_table(|table| {
+        table
+            .iter()
+            .find(|(name, _)| name.ends_with("::allocate"))
+            .map(|(_, symbol)| (symbol.peak(), symbol.peak_at()))
+            .expect("allocate should be traced")
+    })
+    .expect("failed to access symbol table")
+}
+
+#[test]
+fn test_should_record_peak_timestamp_on_new_peaks_only() {
+    leaktracer::init_symbol_table(&["peak_at"]);
+
+    CLOCK.advance(Duration::from_secs(10));
+    let first = allocate();
+    assert_eq!(peak(), (1024, Duration::from_secs(10)));
+
+    // back to the same live bytes, which is not a new peak
+    CLOCK.advance(Duration::from_secs(5));
+    drop(first);
+    let second = allocate();
+    assert_eq!(peak(), (1024, Duration::from_secs(10)));
+
+    CLOCK.advance(Duration::from_secs(5));
+    let third = allocate();
+    assert_eq!(peak(), (2048, Duration::from_secs(20)));
+
+    // the snapshots carry it too
+    drop((second, third));
+    CLOCK.advance(Duration::from_secs(5));
+    let snapshot = leaktracer::snapshot().expect("failed to take snapshot");
+    let symbol = snapshot
+        .symbols
+        .iter()
+        .find(|symbol| symbol.name.ends_with("::allocate"))
+        .expect("allocate should be in the snapshot");
+    assert_eq!((symbol.allocated, symbol.peak), (0, 2048));
+    assert_eq!(symbol.peak_at, Duration::from_secs(20));
+}
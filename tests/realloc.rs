@@ -0,0 +1,43 @@
+//! Reallocations are tested through the global allocator, in their own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+/// Grows a vector one element at a time.
+#[inline(never)]
+fn grow() -> Vec<u64> {
+    let mut values = Vec::new();
+    for i in 0..65536 {
+        values.push(i);
+    }
+
+    values
+}
+
+#[test]
+fn test_should_trace_growing_vec() {
+    leaktracer::init_symbol_table(&["realloc"]);
+
+    let values = grow();
+    assert_eq!(values.len(), 65536);
+
+    leaktracer::with_symbol_table(|table| {
+        let (_, symbol) = table
+            .iter()
+            .find(|(name, _)| **name == "realloc::grow")
+            .expect("grow should be traced");
+        assert!(symbol.reallocs() > 0);
+        assert!(symbol.realloc_moves() > 0);
+        // the first block, of 4 elements, is allocated, then only grown
+        assert_eq!(symbol.bytes_grown(), values.capacity() * 8 - 4 * 8);
+        assert_eq!(symbol.bytes_shrunk(), 0);
+        // the moved blocks are released, only the last one is live
+        assert_eq!(symbol.count(), 1);
+        assert_eq!(symbol.allocated(), values.capacity() * 8);
+    })
+    .expect("failed to access symbol table");
+}
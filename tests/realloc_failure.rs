@@ -0,0 +1,57 @@
+//! A failed reallocation is tested through the global allocator, in its own process, so no other test
+//! moves the global counters meanwhile.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+const SYMBOL: &str = "realloc_failure::allocate";
+
+/// Allocates a buffer of 4096 bytes.
+#[inline(never)]
+fn allocate() -> Vec<u8> {
+    Vec::with_capacity(4096)
+}
+
+/// Returns the live bytes, count and reallocations of the [`SYMBOL`].
+fn symbol_stats() -> (usize, usize, usize) {
+    leaktracer::with_symbol_table(|table| {
+        let symbol = table.get(SYMBOL).expect("allocate should be traced");
+        (symbol.allocated(), symbol.count(), symbol.reallocs())
+    })
+    .expect("failed to access symbol table")
+}
+
+#[test]
+fn test_should_keep_block_of_failed_realloc() {
+    leaktracer::init_symbol_table(&["realloc_failure"]);
+
+    let mut buffer = std::hint::black_box(allocate());
+    let ptr = buffer.as_ptr() as usize;
+    let symbol = symbol_stats();
+    let ops = leaktracer::op_breakdown();
+    let stats = leaktracer::quick_stats();
+
+    // no allocator can grow the block to 1 EiB
+    assert!(buffer.try_reserve_exact(1 << 60).is_err());
+
+    // the old block is still live, so it's traced as it was, and no op is recorded
+    assert_eq!(symbol_stats(), symbol);
+    assert_eq!(symbol, (4096, 1, 0));
+    assert_eq!(leaktracer::op_breakdown(), ops);
+    let after = leaktracer::quick_stats();
+    assert_eq!(after.allocated, stats.allocated);
+    assert_eq!(after.total_allocations, stats.total_allocations);
+    assert_eq!(after.total_deallocations, stats.total_deallocations);
+    assert!(
+        leaktracer::live_allocations()
+            .expect("failed to access symbol table")
+            .contains(&(ptr, 4096, SYMBOL))
+    );
+
+    drop(buffer);
+    assert_eq!(symbol_stats(), (0, 0, 0));
+}
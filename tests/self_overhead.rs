@@ -0,0 +1,46 @@
+//! The tracer overhead is only accounted when the tracer is the global allocator,
+//! so this test runs in its own binary.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+macro_rules! allocating_fns {
+    ($($name:ident),*) => {
+        $(
+            #[inline(never)]
+            fn $name() -> Vec<u8> {
+                vec![0; 64]
+            }
+        )*
+
+        /// Allocates once from each function, adding a new symbol per function.
+        fn allocate_from_each_fn() -> Vec<Vec<u8>> {
+            vec![$($name()),*]
+        }
+    };
+}
+
+allocating_fns!(
+    symbol_a, symbol_b, symbol_c, symbol_d, symbol_e, symbol_f, symbol_g, symbol_h
+);
+
+#[test]
+fn test_should_grow_overhead_as_symbols_are_added() {
+    leaktracer::init_symbol_table(&["self_overhead"]);
+
+    // the first traced allocation loads the debug info
+    let first = vec![0u8; 64];
+    let before = leaktracer::self_overhead_bytes();
+    let symbols_before = leaktracer::quick_stats().symbol_count;
+
+    let allocations = allocate_from_each_fn();
+    assert!(leaktracer::quick_stats().symbol_count > symbols_before);
+    assert!(leaktracer::self_overhead_bytes() > before);
+
+    drop(allocations);
+    drop(first);
+}
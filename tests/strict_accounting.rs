@@ -0,0 +1,37 @@
+//! The strict accounting panics are deferred to any later access to the symbol table, so they're tested in their own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init().with_strict_accounting(true);
+
+#[inline(never)]
+fn allocate() -> Vec<u8> {
+    vec![0; 4096]
+}
+
+#[inline(never)]
+fn free(buffer: Vec<u8>) {
+    drop(buffer);
+}
+
+#[test]
+fn test_should_not_underflow_on_free_from_another_function() {
+    leaktracer::init_symbol_table(&["strict_accounting"]);
+
+    let buffer = allocate();
+    let stats =
+        leaktracer::symbol_stats("strict_accounting::allocate").expect("allocate should be traced");
+    assert_eq!(stats.allocated, 4096);
+    free(buffer);
+
+    // the deallocation is attributed to the allocating symbol, not to the freeing one
+    let stats =
+        leaktracer::symbol_stats("strict_accounting::allocate").expect("allocate should be traced");
+    assert_eq!(stats.allocated, 0);
+    assert_eq!(stats.count, 0);
+    assert_eq!(leaktracer::symbol_stats("strict_accounting::free"), None);
+    assert!(leaktracer::accounting_violations().is_empty());
+}
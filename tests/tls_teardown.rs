@@ -0,0 +1,64 @@
+//! The allocations made by the thread-local destructors are tested through the global allocator, in their own process.
+//! There are no threads on WebAssembly.
+#![cfg(not(target_family = "wasm"))]
+
+use std::cell::RefCell;
+use std::thread;
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+const THREADS: usize = 8;
+
+const BUFFER_SIZE: usize = 1 << 20;
+
+/// Allocates and frees a buffer when destroyed, and frees the buffer it holds.
+struct Teardown {
+    held: Vec<u8>,
+}
+
+impl Drop for Teardown {
+    fn drop(&mut self) {
+        let buffer = std::hint::black_box(vec![1u8; BUFFER_SIZE]);
+        assert_eq!(buffer.len(), self.held.len());
+    }
+}
+
+thread_local! {
+    static TEARDOWN: RefCell<Option<Teardown>> = const { RefCell::new(None) };
+}
+
+#[test]
+fn test_should_survive_allocations_in_tls_destructors() {
+    leaktracer::init_symbol_table(&["tls_teardown"]);
+    let allocated = leaktracer::global_allocated();
+    let before = leaktracer::quick_stats();
+
+    let threads: Vec<_> = (0..THREADS)
+        .map(|_| {
+            thread::spawn(|| {
+                TEARDOWN.with_borrow_mut(|teardown| {
+                    *teardown = Some(Teardown {
+                        held: vec![0u8; BUFFER_SIZE],
+                    })
+                });
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().expect("thread panicked during teardown");
+    }
+
+    // the buffers allocated and freed by the destructors were traced, like the held ones
+    let after = leaktracer::quick_stats();
+    assert!(after.total_allocations >= before.total_allocations + 2 * THREADS);
+    assert!(after.total_deallocations >= before.total_deallocations + 2 * THREADS);
+    assert!(
+        leaktracer::global_allocated() < allocated + BUFFER_SIZE,
+        "the buffers freed during the teardown should be released"
+    );
+    // the tracer thread-locals have no destructor, so they outlive the ones of the application
+    assert_eq!(leaktracer::tls_fallbacks(), 0);
+}
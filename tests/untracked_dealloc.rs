@@ -0,0 +1,45 @@
+//! The untracked deallocation hook is process-global, so it's tested in its own process.
+//! The symbols are never created on WebAssembly, where the call stacks can't be captured.
+#![cfg(not(target_family = "wasm"))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use leaktracer::LeaktracerAllocator;
+
+#[global_allocator]
+static ALLOCATOR: LeaktracerAllocator = LeaktracerAllocator::init();
+
+static UNTRACKED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// Frees through the tracer a block of `size` bytes it never saw allocated.
+fn free_untracked(size: usize) {
+    let layout = Layout::from_size_align(size, 8).unwrap();
+    unsafe {
+        let ptr = System.alloc(layout);
+        assert!(!ptr.is_null());
+        ALLOCATOR.dealloc(ptr, layout);
+    }
+}
+
+#[test]
+fn test_should_invoke_untracked_dealloc_hook() {
+    leaktracer::init_symbol_table(&["untracked_dealloc"]);
+    leaktracer::on_untracked_dealloc(|size| {
+        UNTRACKED_BYTES.fetch_add(size, Ordering::Relaxed);
+    });
+
+    free_untracked(4096);
+    assert!(UNTRACKED_BYTES.load(Ordering::Relaxed) >= 4096);
+
+    // a panicking hook must not unwind out of the allocator;
+    // it panics only once, since the panic machinery frees untracked blocks too
+    leaktracer::on_untracked_dealloc(|_| {
+        if !PANICKED.swap(true, Ordering::Relaxed) {
+            panic!("untracked free");
+        }
+    });
+    free_untracked(4096);
+    assert!(PANICKED.load(Ordering::Relaxed));
+}